@@ -0,0 +1,82 @@
+//! Messages that control Nuts itself at runtime.
+//!
+//! Publishing one of the messages in this module with [`nuts::publish`](../fn.publish.html) triggers library-level behavior.
+//! The message is handled by Nuts when it is dispatched, in the same order as all other messages.
+//! Afterwards, it is delivered to subscribers like any other message.
+//!
+//! Embedders can forward commands from a console or a network debug port with [`publish_command`], to get the same operational tooling in all applications built on Nuts.
+//!
+//! ### Example
+//! ```rust
+//! use nuts::control::*;
+//!
+//! nuts::publish(PauseQueue);
+//! nuts::publish(17usize); // held back
+//! nuts::publish(ResumeQueue);
+//!
+//! println!("{}", nuts::control::dump_state());
+//! assert!(nuts::control::publish_command("log off"));
+//! ```
+
+/// Prints an overview of all activities and queued messages to the log, see [`dump_state`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DumpState;
+
+/// Changes how much Nuts prints to the log. Logs are only printed in debug mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SetLogLevel(pub LogLevel);
+
+/// Holds back all published messages until [`ResumeQueue`] is published.
+///
+/// Other control messages are still handled while the queue is paused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PauseQueue;
+
+/// Delivers all messages held back since [`PauseQueue`], in their original order, and continues normally afterwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResumeQueue;
+
+/// How much Nuts prints to the log
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// Nothing is printed
+    Off,
+    /// Panic information and explicitly requested output, such as [`DumpState`]
+    Info,
+    /// Everything, including debug messages. This is the default.
+    Debug,
+}
+
+/// Returns a human-readable overview of all activities and queued messages.
+///
+/// Type names are only available in debug mode.
+///
+/// # Panics
+/// Panics if called from inside a subscription handler. Publish [`DumpState`] there instead.
+pub fn dump_state() -> String {
+    crate::nut::dump_state()
+}
+
+/// Parses a textual command and publishes the corresponding control message.
+///
+/// Known commands are `dump`, `pause`, `resume` and `log off|info|debug`.
+/// Returns false if the command is not recognized, in which case nothing is published.
+pub fn publish_command(command: &str) -> bool {
+    let mut words = command.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("dump"), None, None) => crate::publish(DumpState),
+        (Some("pause"), None, None) => crate::publish(PauseQueue),
+        (Some("resume"), None, None) => crate::publish(ResumeQueue),
+        (Some("log"), Some(level), None) => {
+            let level = match level {
+                "off" => LogLevel::Off,
+                "info" => LogLevel::Info,
+                "debug" => LogLevel::Debug,
+                _ => return false,
+            };
+            crate::publish(SetLogLevel(level))
+        }
+        _ => return false,
+    }
+    true
+}
@@ -1,11 +1,15 @@
 #![allow(unused_macros)]
+use crate::control::LogLevel;
+
 /* log_print, to println! or web console log (nothing in release mode) */
 
 #[cfg(debug_assertions)]
 #[cfg(all(feature = "web-debug", target_arch = "wasm32"))]
 macro_rules! log_print {
     ( $( $t:tt )* ) => {
-        web_sys::console::log_1(&format!( $( $t )* ).into());
+        if crate::debug::log_enabled(crate::control::LogLevel::Info) {
+            web_sys::console::log_1(&format!( $( $t )* ).into());
+        }
     }
 }
 
@@ -13,7 +17,9 @@ macro_rules! log_print {
 #[cfg(not(all(feature = "web-debug", target_arch = "wasm32")))]
 macro_rules! log_print {
     ( $( $t:tt )* ) => {
-        println!( $( $t )* );
+        if crate::debug::log_enabled(crate::control::LogLevel::Info) {
+            println!( $( $t )* );
+        }
     }
 }
 
@@ -28,7 +34,9 @@ macro_rules! log_print {
 #[cfg(all(feature = "web-debug", target_arch = "wasm32"))]
 macro_rules! debug_print {
     ( $( $t:tt )* ) => {
-        web_sys::console::debug_1(&format!( $( $t )* ).into());
+        if crate::debug::log_enabled(crate::control::LogLevel::Debug) {
+            web_sys::console::debug_1(&format!( $( $t )* ).into());
+        }
     }
 }
 
@@ -36,7 +44,9 @@ macro_rules! debug_print {
 #[cfg(not(all(feature = "web-debug", target_arch = "wasm32")))]
 macro_rules! debug_print {
     ( $( $t:tt )* ) => {
-        println!( $( $t )* );
+        if crate::debug::log_enabled(crate::control::LogLevel::Debug) {
+            println!( $( $t )* );
+        }
     }
 }
 
@@ -45,6 +55,18 @@ macro_rules! debug_print {
     ( $( $t:tt )* ) => {};
 }
 
+thread_local!(static LOG_LEVEL: core::cell::Cell<LogLevel> = const { core::cell::Cell::new(LogLevel::Debug) });
+
+/// Changes which messages are printed by `log_print` and `debug_print`
+pub(crate) fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.with(|l| l.set(level));
+}
+
+#[allow(dead_code)]
+pub(crate) fn log_enabled(level: LogLevel) -> bool {
+    LOG_LEVEL.with(|l| l.get() >= level)
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct DebugTypeName(
     #[cfg(debug_assertions)] pub(crate) &'static str,
@@ -52,6 +74,7 @@ pub(crate) struct DebugTypeName(
 );
 
 impl DebugTypeName {
+    #[cfg_attr(not(debug_assertions), allow(clippy::extra_unused_type_parameters))]
     pub fn new<MSG: std::any::Any>() -> Self {
         Self(
             #[cfg(debug_assertions)]
@@ -60,6 +83,13 @@ impl DebugTypeName {
             (),
         )
     }
+    /// The type name in debug mode, a placeholder in release mode
+    pub fn name(&self) -> &'static str {
+        #[cfg(debug_assertions)]
+        return self.0;
+        #[cfg(not(debug_assertions))]
+        return "<unknown type>";
+    }
 }
 
 #[cfg(debug_assertions)]
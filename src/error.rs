@@ -0,0 +1,70 @@
+//! Errors returned by the fallible variants of the Nuts API.
+
+/// Error returned by the `try_` methods on activity IDs and by functions that check the setup of Nuts, such as [`validate_domains`](fn.validate_domains.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The activity has been deleted, or its deletion is already queued.
+    ActivityDeleted,
+    /// A domained subscription has been registered on an activity without a domain, the type name of the activity is included.
+    MissingDomain(&'static str),
+    /// The configuration has been changed after the first message has been published.
+    ConfiguredAfterPublish,
+    /// The configuration is inconsistent, the reason is included.
+    InvalidConfig(&'static str),
+    /// Nuts has already been claimed by another thread.
+    WrongThread,
+    /// The token to publish a restricted message type has already been handed out.
+    PublishTokenTaken,
+    /// The attached future has been dropped because its activity has been deleted.
+    TaskCancelled,
+    /// No codec has been registered for the message type.
+    NoCodec,
+    /// The codec could not decode the bytes of a message.
+    MalformedMessage,
+    /// The message type has been protected with `protect_message`, the type name is included.
+    ImmutableMessage(&'static str),
+    /// The thread that should receive the message has exited.
+    Disconnected,
+    /// The request has been handled without reply, because the activity has no request handler for it or because it has been deleted or replaced as responder before.
+    NoReply,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ActivityDeleted => write!(f, "the activity has been deleted"),
+            Self::MissingDomain(activity) => {
+                write!(
+                    f,
+                    "activity {} has domained subscriptions but no domain",
+                    activity
+                )
+            }
+            Self::ConfiguredAfterPublish => {
+                write!(f, "nuts must be configured before publishing messages")
+            }
+            Self::InvalidConfig(reason) => write!(f, "invalid configuration: {}", reason),
+            Self::WrongThread => write!(f, "nuts has been claimed by another thread"),
+            Self::PublishTokenTaken => {
+                write!(
+                    f,
+                    "the publish token of this message type has already been taken"
+                )
+            }
+            Self::TaskCancelled => write!(f, "the task has been cancelled"),
+            Self::NoCodec => write!(f, "no codec has been registered for the message type"),
+            Self::MalformedMessage => write!(f, "the message could not be decoded"),
+            Self::ImmutableMessage(msg) => {
+                write!(
+                    f,
+                    "messages of type {} are immutable and cannot be modified or owned by handlers",
+                    msg
+                )
+            }
+            Self::Disconnected => write!(f, "the receiving thread has exited"),
+            Self::NoReply => write!(f, "the request has been handled without reply"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -41,17 +41,66 @@
 #[macro_use]
 pub(crate) mod debug;
 
+pub mod control;
+mod error;
 mod nut;
 
-#[cfg(test)]
-mod test;
+pub mod sim;
+pub mod test;
 
-pub use crate::nut::iac::managed_state::{DefaultDomain, DomainEnumeration, DomainState};
+#[cfg(feature = "ecs")]
+pub use crate::nut::iac::managed_state::{
+    ComponentInserted, ComponentRemoved, Entities, Entity, EntityDespawned, EntityMut,
+};
+pub use crate::nut::iac::managed_state::{
+    ConflictPolicy, DefaultDomain, DomainEnumeration, DomainInfo, DomainState, DomainView,
+};
 use core::any::Any;
+use core::hash::Hash;
+pub use error::Error;
 pub use nut::activity::*;
+#[cfg(feature = "alloc-audit")]
+pub use nut::audit::AllocationReport;
+#[cfg(not(target_arch = "wasm32"))]
+pub use nut::clock::StdClock;
+#[cfg(feature = "web-clock")]
+pub use nut::clock::WebClock;
+pub use nut::clock::{Clock, MockClock};
+pub use nut::compact::CompactionReport;
+pub use nut::config::{ConfigBuilder, DispatchOrder, PanicPolicy, QueueOrder};
+pub use nut::exec::frame::Phase;
+pub use nut::exec::scratch::Scratch;
+pub use nut::exec::tasks::{TaskHandle, TaskPolicy};
+pub use nut::exec::teardown::ExitRequested;
+pub use nut::iac::codec::{EncodedMessage, MessageCodec};
+pub use nut::iac::digest::{DigestSubscription, DigestWindow};
+pub use nut::iac::double_buffer::{BufferReader, BufferWriter};
 pub use nut::iac::filter::*;
+pub use nut::iac::immutable::ImmutableMessage;
+pub use nut::iac::params::{HandlerContext, HandlerFn, HandlerParam};
+#[cfg(feature = "perf")]
+pub use nut::iac::profile::HandlerReport;
+#[cfg(feature = "message-arena")]
+pub use nut::iac::publish::ArenaUsage;
+pub use nut::iac::publish::{
+    AliasReport, AwaitingPublish, KeyedMessage, MessageControl, PublishGroup, PublishToken, Reply,
+    ResponseConfig, ResponseStats, ScopeToken, SharedPayload, SlotReuse,
+};
+pub use nut::iac::subscription::{
+    CaptureWarning, SubscriptionEntry, SubscriptionId, SubscriptionPriority, SubscriptionStats,
+    SubscriptionTable, UnusedSubscription,
+};
+pub use nut::remote::RemotePublisher;
+pub use nut::stats::NutsStats;
+pub use nut::timer::TimerHandle;
+pub use nut::trace::{TraceId, TraceNode};
+#[cfg(feature = "log")]
+pub use nut::usage_log::LogThresholds;
+use std::ops::Deref;
+pub use test::{verify_activity, LifecycleConformance};
 
 use nut::iac::managed_state::*;
+use nut::iac::publish::ResponseMode;
 use nut::iac::topic::*;
 
 /// Consumes a struct and registers it as an Activity.
@@ -160,107 +209,1902 @@ where
     a
 }
 
+/// Same as [`new_activity`](fn.new_activity.html) but the activity also receives an `on_enter` for its initial activation.
+///
+/// Activities created with `new_activity` start as active and thus never receive an `on_enter` for that.
+/// Activities created with this function do, which allows putting initialization logic into `on_enter` handlers.
+///
+/// The initial `on_enter` is delivered right before the next message is published (or when Nuts finishes handling all queued messages).
+/// Therefore, handlers registered right after creating the activity are called, too.
+/// If the activity is not active anymore at that time, it does not receive the initial `on_enter`.
+///
+/// ### Example
+/// ```rust
+/// struct Screen { initialized: bool }
+/// struct Draw;
+///
+/// let id = nuts::new_activity_with_enter(Screen { initialized: false });
+/// id.on_enter(|screen| screen.initialized = true);
+/// id.subscribe(|screen, _: &Draw| assert!(screen.initialized));
+/// nuts::publish(Draw);
+/// ```
+pub fn new_activity_with_enter<A>(activity: A) -> ActivityId<A>
+where
+    A: Activity,
+{
+    nut::new_activity_with_enter(activity, DomainId::default())
+}
+
+/// Same as [`new_activity_with_enter`](fn.new_activity_with_enter.html) but the activity has access to the specified domain.
+pub fn new_domained_activity_with_enter<A, D>(activity: A, domain: &D) -> ActivityId<A>
+where
+    A: Activity,
+    D: DomainEnumeration,
+{
+    nut::new_activity_with_enter(activity, DomainId::new(domain))
+}
+
+/// Creates an activity from the output of an async factory, for activities whose initial state must be loaded first.
+///
+/// The activity is created once `factory` resolves, the returned future then resolves to its ID.
+/// Until then, private messages sent to the activity type with [`send_to`](fn.send_to.html) are held back.
+/// They are delivered right before the next message is published (or when Nuts finishes handling all queued messages).
+/// Therefore, private channels registered right after awaiting the returned future receive them.
+///
+/// Messages are held from the call of this function on, not only once the future is polled.
+/// If the future is dropped before the factory resolves, the held messages are discarded.
+///
+/// ### Example
+/// ```rust
+/// struct Level { tiles: Vec<u8> }
+/// struct LoadTile(usize);
+///
+/// async fn load_level() -> Level {
+///     // e.g. a fetch or a file load
+///     Level { tiles: vec![1, 2, 3] }
+/// }
+///
+/// struct Game;
+/// nuts::new_activity(Game).attach_future(async {
+///     let level = nuts::new_activity_async(load_level()).await;
+///     level.private_channel(|level, msg: LoadTile| assert_eq!(3, level.tiles[msg.0]));
+/// });
+/// // Sent before the level exists, delivered once it does
+/// nuts::send_to::<Level, _>(LoadTile(2));
+/// ```
+pub fn new_activity_async<A, F>(factory: F) -> impl core::future::Future<Output = ActivityId<A>>
+where
+    A: Activity,
+    F: core::future::Future<Output = A>,
+{
+    let pending = nut::activity::pending::PendingActivity::new::<A>();
+    async move {
+        let activity = factory.await;
+        let id = new_activity(activity);
+        drop(pending);
+        id
+    }
+}
+
 /// Puts the data object to the domain, which can be accessed by all associated activities.
 ///
 /// This function stores the data to the domain immediately if called outside of activities.
 /// Inside activities, it will be delayed. However, any messages published after calling this function can
 /// rely on the store to the domain to have completed when the corresponding subscribers are executed.
+///
+/// Other subscribers to the message that is currently being handled may still see the old data, as well as messages published before calling this function.
+/// Use [`store_to_domain_ordered`](fn.store_to_domain_ordered.html) if this is a problem.
 pub fn store_to_domain<D, T>(domain: &D, data: T)
 where
     D: DomainEnumeration,
     T: core::any::Any,
 {
-    nut::write_domain(domain, data)
+    nut::write_domain(domain, data);
 }
 
-/// Registers a callback closure with a specific topic to listen to.
+/// Same as [`store_to_domain`](fn.store_to_domain.html) but returns a future that resolves once the data has been stored.
 ///
-/// This variant of subscription has no activity. See [`ActivityId::subscribe`](struct.ActivityId.html#method.subscribe) and friends for other subscription options.
-pub fn subscribe<F, MSG>(f: F)
+/// Outside of activities, the data is stored immediately and the future is ready right away.
+/// Inside activities, the store is queued and the future resolves when it has been applied.
+/// Async setup code can await it to sequence the initialization of domains reliably.
+///
+/// ### Example
+/// ```rust
+/// use nuts::DefaultDomain;
+/// struct Loader;
+/// struct Config { volume: u8 }
+/// struct Start;
+///
+/// let loader = nuts::new_activity(Loader);
+/// loader.subscribe(move |_, _: &Start| {
+///     let stored = nuts::store_to_domain_awaitable(&DefaultDomain, Config { volume: 7 });
+///     loader.attach_future(async move {
+///         stored.await;
+///         // every handler called from now on sees the config
+///     });
+/// });
+/// nuts::publish(Start);
+/// ```
+pub fn store_to_domain_awaitable<D, T>(
+    domain: &D,
+    data: T,
+) -> impl core::future::Future<Output = ()>
 where
-    F: Fn(&MSG) + 'static,
-    MSG: Any,
+    D: DomainEnumeration,
+    T: core::any::Any,
 {
-    crate::nut::register_no_activity(f)
+    nut::write_domain_awaitable(domain, data)
 }
 
-/// Send the message to all subscribed activities
+/// Same as [`store_to_domain`](fn.store_to_domain.html) but with read-your-writes ordering.
 ///
-// @ START-DOC PUBLISH
-/// Any instance of a struct or primitive can be published, as long as its type is known at compile-time. (The same constraint as for Activities.)
-/// Upon calling `nuts::publish`, all active subscriptions for the same type are executed and the published object will be shared with all of them.
+/// Outside of activities, the data is stored immediately.
+/// Inside activities, the store is applied as soon as the current subscription handler returns.
+/// Thus, all handlers called afterwards will see the new data, including other subscribers to the same message.
 ///
 /// ### Example
 /// ```rust
-/// struct ChangeUser { user_name: String }
-/// pub fn main() {
-///     let msg = ChangeUser { user_name: "Donald Duck".to_owned() };
-///     nuts::publish(msg);
-///     // Subscribers to messages of type `ChangeUser` will be notified
+/// use nuts::DefaultDomain;
+/// struct MyActivity;
+/// struct Update;
+///
+/// nuts::store_to_domain(&DefaultDomain, 0u32);
+/// let id = nuts::new_domained_activity(MyActivity, &DefaultDomain);
+/// id.subscribe(|_, _: &Update| {
+///     nuts::store_to_domain_ordered(&DefaultDomain, 1u32);
+/// });
+/// id.subscribe_domained(|_, domain, _: &Update| {
+///     assert_eq!(*domain.get::<u32>(), 1);
+/// });
+/// nuts::publish(Update);
+/// ```
+pub fn store_to_domain_ordered<D, T>(domain: &D, data: T)
+where
+    D: DomainEnumeration,
+    T: core::any::Any,
+{
+    nut::write_domain_ordered(domain, data)
+}
+
+/// Registers a conversion of domain objects from type `OLD` to type `NEW`.
+///
+/// The conversion is applied lazily, when a domain that contains an `OLD` but no `NEW` object is accessed by a handler.
+/// The `OLD` object is removed, thus code reading `NEW` never sees both types at the same time.
+/// Migrations can be chained, from `A` to `B` and from `B` to `C`.
+///
+/// This supports refactoring the types of domain objects step by step, and upgrading state that has been stored by an older version of the code.
+///
+/// # Panics
+/// Panics if called from inside a subscription handler.
+///
+/// ### Example
+/// ```rust
+/// use nuts::DefaultDomain;
+/// struct ScoreV1(u32);
+/// struct ScoreV2 { points: u64, bonus: u64 }
+/// struct Check;
+///
+/// nuts::store_to_domain(&DefaultDomain, ScoreV1(10));
+/// nuts::register_domain_migration(|old: ScoreV1| ScoreV2 { points: old.0 as u64, bonus: 0 });
+///
+/// let id = nuts::new_domained_activity((), &DefaultDomain);
+/// id.subscribe_domained(|_, domain, _: &Check| {
+///     assert_eq!(domain.get::<ScoreV2>().points, 10);
+///     assert!(domain.try_get::<ScoreV1>().is_none());
+/// });
+/// nuts::publish(Check);
+/// ```
+pub fn register_domain_migration<OLD, NEW>(f: impl Fn(OLD) -> NEW + 'static)
+where
+    OLD: Any,
+    NEW: Any,
+{
+    nut::register_domain_migration(f)
+}
+
+/// Returns the number of subscriptions for messages of type `MSG`.
+///
+/// Publishers can use this to skip constructing expensive messages, such as diagnostics, when nobody is listening.
+/// Subscriptions of inactive activities are counted, too.
+/// Subscriptions that are registered while a message is being handled are only counted once the message has been handled.
+///
+/// This is cheap and can be called from anywhere, including from inside subscription handlers.
+///
+/// ### Example
+/// ```rust
+/// struct FrameStats(String);
+///
+/// if nuts::subscriber_count::<FrameStats>() > 0 {
+///     nuts::publish(FrameStats(format!("{:?}", std::time::Instant::now())));
 /// }
 /// ```
-// @ END-DOC PUBLISH
-/// ### Advanced: Understanding the Execution Order
-// @ START-DOC PUBLISH_ADVANCED
-/// When calling `nuts::publish(...)`, the message may not always be published immediately. While executing a subscription handler from previous `publish`, all new messages are queued up until the previous one is completed.
+pub fn subscriber_count<MSG: Any>() -> usize {
+    nut::subscriber_count::<MSG>()
+}
+
+/// Keeps copies of the last `n` published messages of type `MSG`.
+///
+/// Subscriptions registered with [`subscribe_and_replay`](struct.ActivityId.html#method.subscribe_and_replay) receive the retained messages right away.
+/// This is useful for activities that are created later but need to know the latest state, similar to sticky events.
+///
+/// Only messages published with `publish` and its variants are retained, private messages are not.
+/// Calling this again changes the number of retained messages, `n = 0` stops retaining messages of that type.
+pub fn retain_last<MSG: Any + Clone>(n: usize) {
+    nut::retain_last::<MSG>(n)
+}
+
+/// Holds back published messages that have no subscriber, until [`nuts::start`](fn.start.html) is called.
+///
+/// Messages published during startup are usually lost if the subscribing activity is created a bit later.
+/// For example, a `Resize` event fired by the browser while the application is still loading.
+/// With startup buffering, such messages are kept and delivered as soon as the first subscription for their type is registered.
+///
+/// At most `capacity` messages are kept, older messages are dropped first.
+/// Only messages published with `publish` and its variants are buffered, private messages are not.
+///
+/// ### Example
 /// ```rust
-/// struct MyActivity;
-/// let activity = nuts::new_activity(MyActivity);
-/// activity.subscribe(
-///     |_, msg: &usize| {
-///         println!("Start of {}", msg);
-///         if *msg < 3 {
-///             nuts::publish( msg + 1 );
-///         }
-///         println!("End of {}", msg);
+/// struct Resize(u32, u32);
+///
+/// nuts::buffer_startup_messages(16);
+/// nuts::publish(Resize(800, 600));
+///
+/// // Subscribing delivers the buffered message
+/// nuts::subscribe(|msg: &Resize| assert_eq!(800, msg.0));
+/// nuts::start();
+/// ```
+pub fn buffer_startup_messages(capacity: usize) {
+    nut::buffer_startup_messages(capacity)
+}
+
+/// Ends the startup phase started with [`buffer_startup_messages`](fn.buffer_startup_messages.html).
+///
+/// Buffered messages that still have no subscriber are dropped and messages published from now on are not buffered anymore.
+pub fn start() {
+    nut::start()
+}
+
+/// Starts a batch of status changes that are applied atomically.
+///
+/// Changing the status of several activities one by one, other messages may be dispatched in between.
+/// For example, when swapping screens, the old and the new screen could both be active for a moment.
+/// A batch applies all changes together, before the next message is dispatched.
+///
+/// ### Example
+/// ```rust
+/// use nuts::LifecycleStatus;
+/// struct Menu;
+/// struct Game;
+///
+/// let menu = nuts::new_activity(Menu);
+/// let game = nuts::new_activity(Game);
+/// game.set_status(LifecycleStatus::Inactive);
+///
+/// nuts::status_batch()
+///     .set(menu, LifecycleStatus::Inactive)
+///     .set(game, LifecycleStatus::Active)
+///     .commit();
+/// ```
+pub fn status_batch() -> StatusBatch {
+    StatusBatch::new()
+}
+
+/// Dispatches at most `n` queued events and returns how many are still waiting.
+///
+/// Events are waiting in two cases:
+/// - The queue has been paused with [`PauseQueue`](control/struct.PauseQueue.html), which holds back all events.
+///   The queue stays paused, use [`ResumeQueue`](control/struct.ResumeQueue.html) to continue normally.
+/// - The drain limit set with [`ConfigBuilder::drain_limit`](struct.ConfigBuilder.html#method.drain_limit) has stopped dispatching before the queue was empty.
+///   The limit of `n` replaces the drain limit for this call.
+///
+/// Events published by the handlers of processed events are queued behind the waiting events and count towards `n` when they are dispatched.
+/// If no events are waiting, 0 is returned.
+///
+/// This lets the host loop spread the work of a huge burst of messages, such as a replayed recording, over several frames.
+///
+/// # Panics
+/// Panics if called from inside a subscription handler.
+///
+/// ### Example
+/// ```rust
+/// use nuts::control::PauseQueue;
+/// struct Input(u32);
+///
+/// nuts::publish(PauseQueue);
+/// for i in 0..100 {
+///     nuts::publish(Input(i));
+/// }
+/// // in the main loop, once per frame
+/// while nuts::process_at_most(10) > 0 {
+///     // render a frame
+/// }
+/// ```
+pub fn process_at_most(n: usize) -> usize {
+    nut::process_at_most(n)
+}
+
+/// Declares the current thread as the only thread that uses Nuts.
+///
+/// Nuts keeps its state in thread-local storage.
+/// Calling Nuts from another thread does not fail by itself, it operates on a separate and empty state, which is rarely intended.
+/// After claiming, the first call to Nuts on any other thread panics with an explicit message instead.
+/// Threads that have used Nuts before the claim are not affected, so claim the main thread early.
+///
+/// # Errors
+/// Returns `Error::WrongThread` if another thread has already claimed Nuts. Claiming twice on the same thread is fine.
+///
+/// ### Example
+/// ```rust
+/// struct Job;
+/// nuts::claim_main_thread().expect("first claim");
+/// nuts::subscribe(|_: &Job| {});
+///
+/// let worker = std::thread::spawn(|| nuts::publish(Job));
+/// assert!(worker.join().is_err(), "publishing from a worker thread panics");
+/// assert_eq!(Err(nuts::Error::WrongThread), std::thread::spawn(nuts::claim_main_thread).join().unwrap());
+/// ```
+pub fn claim_main_thread() -> Result<(), Error> {
+    nut::main_thread::claim()
+}
+
+/// Creates a handle for other threads to publish messages on the current thread.
+///
+/// All handles created on the same thread share one queue.
+/// Messages sent through them wait there until this thread calls [`process_remote`](fn.process_remote.html), which publishes them in the order they have been sent.
+/// Call it regularly, for example once per frame of the main loop.
+///
+/// ### Example
+/// ```rust
+/// struct Computed(u64);
+/// struct Results { sum: u64 }
+///
+/// let results = nuts::new_activity(Results { sum: 0 });
+/// results.subscribe(|results, computed: &Computed| results.sum += computed.0);
+///
+/// let publisher = nuts::channel();
+/// let worker = std::thread::spawn(move || {
+///     for i in 1..=3 {
+///         publisher.publish(Computed(i)).expect("main thread is alive");
 ///     }
-/// );
+/// });
+/// worker.join().expect("worker finished");
+/// assert_eq!(3, nuts::process_remote());
+/// ```
+pub fn channel() -> RemotePublisher {
+    nut::channel()
+}
+
+/// Publishes all messages that other threads have sent to the current thread through a [`RemotePublisher`](struct.RemotePublisher.html), returns their number.
 ///
-/// nuts::publish(0usize);
-/// // Output:
-/// // Start of 0
-/// // End of 0
-/// // Start of 1
-/// // End of 1
-/// // Start of 2
-/// // End of 2
-/// // Start of 3
-/// // End of 3
+/// Can be called from inside a handler, the messages are then queued behind the messages that have been published already.
+pub fn process_remote() -> usize {
+    nut::process_remote()
+}
+
+/// Applies a configuration to Nuts on the current thread.
+///
+/// The configuration must be applied before the first message is published, it cannot be changed afterwards.
+/// Besides settings that are only available here, the builder also covers [`configure_responses`](fn.configure_responses.html) and [`record_traces`](fn.record_traces.html).
+///
+/// # Errors
+/// Returns `Error::ConfiguredAfterPublish` if a message has already been published, and `Error::InvalidConfig` if the settings are inconsistent.
+/// Nothing is applied in either case.
+///
+/// ### Example
+/// ```rust
+/// use nuts::{ConfigBuilder, DispatchOrder, PanicPolicy};
+/// nuts::configure(
+///     ConfigBuilder::new()
+///         .queue_limit(10_000)
+///         .panic_policy(PanicPolicy::CatchAndContinue)
+///         .dispatch_order(DispatchOrder::Creation),
+/// )
+/// .expect("configured before the first publish");
 /// ```
-// @ END-DOC PUBLISH_ADVANCED
-pub fn publish<A: Any>(a: A) {
-    nut::publish_custom(a)
+pub fn configure(config: ConfigBuilder) -> Result<(), Error> {
+    nut::configure(config)
 }
 
-/// Returns a future of type `NutsResponse` which will resolve after the
-/// message has been published and all subscribers have finished processing it.
-pub async fn publish_awaiting_response<A: Any>(a: A) {
-    nut::publish_custom_and_await(a).await;
+/// Lists all domains with the types stored in them and the number of activities that belong to them.
+///
+/// Intended for debug overlays and health checks. Type names are available in release mode, too.
+/// Domains are listed by their number, including numbers in between that are not in use.
+///
+/// # Panics
+/// Panics if called from inside a subscription handler.
+///
+/// ### Example
+/// ```rust
+/// use nuts::{DefaultDomain, DomainEnumeration};
+/// struct Score(u32);
+/// struct Scoreboard;
+/// nuts::store_to_domain(&DefaultDomain, Score(0));
+/// nuts::new_domained_activity(Scoreboard, &DefaultDomain);
+///
+/// let domains = nuts::domains();
+/// assert_eq!(1, domains.len());
+/// assert_eq!(DefaultDomain.id(), domains[0].id);
+/// assert_eq!(1, domains[0].activity_count);
+/// assert!(domains[0].stored_type_names[0].ends_with("Score"));
+/// ```
+pub fn domains() -> Vec<DomainInfo> {
+    nut::domains()
 }
 
-/// Publish a message to a specific activity. The same as `id.private_message()` but works without an `ActivityId`.
+/// Checks that all activities with domained subscriptions have a domain, to allow skipping the checks at runtime.
 ///
-/// The first type parameter must always be specified.
-/// It determines the receiver of the message.
-/// The message is ignored silently if no such activity has been registered or if it has no private channel for this message.
+/// Handlers registered with `subscribe_domained` and similar methods need the domain of their activity.
+/// If the activity has been created without a domain, calling such a handler panics.
+/// Call `validate_domains` once after setting up all activities, to find such mistakes early.
+///
+/// After a successful validation, domained subscriptions on activities without a domain are rejected immediately when they are registered.
+/// In release builds, handlers that still end up without a domain are skipped instead of panicking, which is counted by [`skipped_domained_calls`](fn.skipped_domained_calls.html).
+/// Debug builds keep panicking.
+///
+/// # Errors
+/// Returns `Error::MissingDomain` with the type name of the first activity that has domained subscriptions but no domain.
+///
+/// # Panics
+/// Panics if called from inside a subscription handler.
 ///
-/// The second type parameter can usually be deferred by the compiler, it is the type of the message to be sent.
 /// ### Example
-// @ START-DOC PUBLISH_PRIVATE
 /// ```rust
-/// struct ExampleActivity {}
-/// let id = nuts::new_activity(ExampleActivity {});
-/// // `private_channel` works similar to `subscribe` but it owns the message.
-/// id.private_channel(|_activity, msg: usize| {
-///     assert_eq!(msg, 7);
+/// use nuts::{DefaultDomain, DomainState};
+/// struct Score;
+/// struct Points(u32);
+/// let score = nuts::new_domained_activity(Score, &DefaultDomain);
+/// score.subscribe_domained(|_, domain: &mut DomainState, points: &Points| {
+///     domain.store(points.0);
 /// });
-/// // `send_to` must be used instead of `publish` when using private channels.
-/// // Which activity receives the message is decide by the first type parameter.
-/// nuts::send_to::<ExampleActivity, _>(7usize);
+/// nuts::validate_domains().expect("all domained activities have a domain");
 /// ```
-// @ END-DOC PUBLISH_PRIVATE
-pub fn send_to<RECEIVER: Any, MSG: Any>(msg: MSG) {
-    nut::send_custom::<RECEIVER, MSG>(msg)
+pub fn validate_domains() -> Result<(), Error> {
+    nut::validate_domains()
+}
+
+/// Number of domained handler calls that have been skipped because the activity had no domain, see [`validate_domains`](fn.validate_domains.html).
+///
+/// # Panics
+/// Panics if called from inside a subscription handler.
+pub fn skipped_domained_calls() -> u64 {
+    nut::skipped_domained_calls()
+}
+
+/// Delivers published messages to the activities assigned to a [`Phase`](enum.Phase.html), one phase after another.
+///
+/// Activities are assigned to a phase with [`set_phase`](struct.ActivityId.html#method.set_phase).
+/// Published messages reach activities without a phase immediately, as usual.
+/// For the activities with a phase, they are kept until the next call of `run_frame`.
+/// Then, each phase is processed in the order defined by [`set_frame_phases`](fn.set_frame_phases.html), which is `Input`, `Update`, `Render` by default.
+/// In each phase, all kept messages are delivered to the subscriptions of activities in that phase.
+///
+/// Messages published during a phase reach the current and all later phases in the same frame, and the earlier phases in the next frame.
+/// This way, each phase sees each message exactly once.
+///
+/// # Panics
+/// Panics if called from inside a subscription handler.
+///
+/// ### Example
+/// ```rust
+/// use nuts::Phase;
+/// struct KeyPressed(char);
+/// struct Player { x: i32 }
+/// struct Renderer;
+///
+/// let player = nuts::new_activity(Player { x: 0 });
+/// player.set_phase(Phase::Update);
+/// player.subscribe(|player, key: &KeyPressed| if key.0 == 'd' { player.x += 1 });
+/// let renderer = nuts::new_activity(Renderer);
+/// renderer.set_phase(Phase::Render);
+/// renderer.subscribe(|_, key: &KeyPressed| println!("drawing after key {}", key.0));
+///
+/// nuts::publish(KeyPressed('d'));
+/// // The game loop, once per frame
+/// nuts::run_frame();
+/// ```
+pub fn run_frame() {
+    nut::run_frame()
+}
+
+/// Defines the phases of a frame and their order, for [`run_frame`](fn.run_frame.html).
+///
+/// Activities assigned to a phase that is not listed do not receive published messages anymore.
+/// At most 64 phases can be used.
+///
+/// # Panics
+/// Panics if more than 64 phases are given.
+pub fn set_frame_phases(phases: &[Phase]) {
+    nut::set_frame_phases(phases.to_vec())
+}
+
+/// Creates a group of messages that are published together.
+///
+/// The messages are queued next to each other and dispatched in order, with no other message in between.
+/// Use this to uphold invariants that span several message types.
+/// For example, subscribers to `TransformSet` can rely on having seen the corresponding `EntityCreated` just before.
+///
+/// ### Example
+/// ```rust
+/// struct EntityCreated(u32);
+/// struct TransformSet(u32, f32, f32);
+///
+/// nuts::publish_group()
+///     .with(EntityCreated(7))
+///     .with(TransformSet(7, 1.0, 2.0))
+///     .publish();
+/// ```
+pub fn publish_group() -> PublishGroup {
+    PublishGroup::new()
+}
+
+/// Calls `f` for every activity that has not been deleted, in order of creation.
+///
+/// Besides some meta information, the visitor receives the activity object as `&dyn Any`, which can be downcast to the concrete type.
+/// This is intended for debugging UIs or for saving the state of all activities.
+///
+/// # Panics
+/// Panics if called from inside a subscription handler.
+///
+/// ### Example
+/// ```rust
+/// struct Player { hp: u32 }
+/// nuts::new_activity(Player { hp: 10 });
+///
+/// nuts::visit_activities(|meta, activity| {
+///     if let Some(player) = activity.downcast_ref::<Player>() {
+///         println!("{} ({:?}) has {} HP", meta.type_name, meta.status, player.hp);
+///     }
+/// });
+/// ```
+pub fn visit_activities<F>(f: F)
+where
+    F: FnMut(ActivityMeta, &dyn Any),
+{
+    nut::visit_activities(f)
+}
+
+/// Registers a closure that is called with the domain object of type `T`, whenever it may have changed.
+///
+/// Every mutable access to `T` in the given domain, including overwriting it with `store_to_domain`, is tracked.
+/// After each broadcast (or other queued event) in which `T` has been accessed, the observer is called once with the current value.
+/// This is less noisy than publishing an event for every write.
+///
+/// Writes that happen while no broadcast is ongoing are noticed after the next broadcast.
+/// Observers may publish messages but they should not write to the observed object, which would cause an endless loop.
+///
+/// ### Example
+/// ```rust
+/// use nuts::{DefaultDomain, DomainState};
+/// struct Score(u32);
+/// struct Points(u32);
+///
+/// struct Game;
+/// nuts::store_to_domain(&DefaultDomain, Score(0));
+/// nuts::observe_domain(&DefaultDomain, |score: &Score| println!("Score is now {}", score.0));
+///
+/// let game = nuts::new_domained_activity(Game, &DefaultDomain);
+/// game.subscribe_domained(|_, domain: &mut DomainState, msg: &Points| {
+///     domain.get_mut::<Score>().0 += msg.0;
+/// });
+/// nuts::publish(Points(10)); // prints "Score is now 10"
+/// ```
+pub fn observe_domain<D, T, F>(domain: &D, f: F)
+where
+    D: DomainEnumeration,
+    T: core::any::Any,
+    F: Fn(&T) + 'static,
+{
+    nut::observe_domain(domain, f)
+}
+
+/// Registers a closure that is called with the domain object of type `T`, whenever its value has changed.
+///
+/// This works like [`observe_domain`](fn.observe_domain.html) but compares the object to a copy of the value seen last time.
+/// Accesses that leave the value as it was do not call the closure.
+/// The first observed access always does.
+///
+/// No activity has to be defined, which makes this handy for small programs that only react to shared state.
+///
+/// ### Example
+/// ```rust
+/// use nuts::{DefaultDomain, DomainState};
+/// #[derive(Clone, PartialEq)]
+/// struct Volume(u8);
+/// struct SetVolume(u8);
+///
+/// struct Settings;
+/// nuts::store_to_domain(&DefaultDomain, Volume(5));
+/// nuts::on_domain_change(&DefaultDomain, |v: &Volume| println!("Volume is now {}", v.0));
+///
+/// let settings = nuts::new_domained_activity(Settings, &DefaultDomain);
+/// settings.subscribe_domained(|_, domain: &mut DomainState, msg: &SetVolume| {
+///     domain.get_mut::<Volume>().0 = msg.0;
+/// });
+/// nuts::publish(SetVolume(7)); // prints "Volume is now 7"
+/// nuts::publish(SetVolume(7)); // prints nothing
+/// ```
+pub fn on_domain_change<D, T, F>(domain: &D, f: F)
+where
+    D: DomainEnumeration,
+    T: core::any::Any + Clone + PartialEq,
+    F: Fn(&T) + 'static,
+{
+    nut::on_domain_change(domain, f)
+}
+
+/// Publishes a copy of the domain object of type `T` as a [`DomainView<T>`](struct.DomainView.html) message.
+///
+/// This lets activities of other domains, or without a domain, consume snapshots of the state of a domain.
+/// Access to the domain itself remains limited to its activities.
+///
+/// Outside of activities, the copy is taken immediately.
+/// Inside activities, it is taken after the current handler, such that stores to the domain issued before the call are included.
+///
+/// # Panics
+/// Panics if no object of type `T` has been stored to the domain.
+///
+/// ### Example
+/// ```rust
+/// use nuts::{DefaultDomain, DomainView};
+/// #[derive(Clone)]
+/// struct Score(u32);
+///
+/// struct Hud;
+/// let hud = nuts::new_activity(Hud);
+/// hud.subscribe(|_, view: &DomainView<Score>| println!("Score is {}", view.data.0));
+///
+/// nuts::store_to_domain(&DefaultDomain, Score(42));
+/// nuts::publish_domain_view::<_, Score>(&DefaultDomain);
+/// ```
+pub fn publish_domain_view<D, T>(domain: &D)
+where
+    D: DomainEnumeration,
+    T: Any + Clone,
+{
+    nut::publish_domain_view::<D, T>(domain)
+}
+
+/// Moves all objects stored in the domain `src` to the domain `dst`, and lets the activities of `src` use `dst` from now on.
+///
+/// This folds a temporary staging domain into the main domain, for example after a part of the world has been loaded.
+/// If both domains store an object of the same type, `policy` decides which one is kept, the other one is dropped.
+///
+/// Afterwards, `src` is an alias of `dst`.
+/// All handlers of activities in `src`, domain stores to `src`, and activities created in `src` later on access `dst`.
+/// [`domains`](fn.domains.html) reports `src` as empty and counts its activities in `dst`.
+///
+/// Outside of activities, the domains are merged immediately.
+/// Inside activities, they are merged after the current handler, together with other queued bookkeeping.
+///
+/// ### Example
+/// ```rust
+/// #[macro_use] extern crate nuts;
+/// use nuts::{ConflictPolicy, DomainEnumeration, DomainState};
+///
+/// #[derive(Clone, Copy)]
+/// enum World { Main, Staging }
+/// domain_enum!(World);
+///
+/// struct Level(u32);
+/// struct Chunk(&'static str);
+/// struct Tick;
+///
+/// struct Loader;
+/// let loader = nuts::new_domained_activity(Loader, &World::Staging);
+/// loader.subscribe_domained(|_, domain: &mut DomainState, _: &Tick| {
+///     // After the merge, this is the main domain
+///     assert_eq!(1, domain.get::<Level>().0);
+///     assert_eq!("forest", domain.get::<Chunk>().0);
+/// });
+///
+/// nuts::store_to_domain(&World::Main, Level(1));
+/// nuts::store_to_domain(&World::Staging, Level(2));
+/// nuts::store_to_domain(&World::Staging, Chunk("forest"));
+/// nuts::merge_domains(&World::Staging, &World::Main, ConflictPolicy::KeepDestination);
+/// nuts::publish(Tick);
+/// ```
+pub fn merge_domains<D: DomainEnumeration>(src: &D, dst: &D, policy: ConflictPolicy) {
+    nut::merge_domains(src, dst, policy)
+}
+
+/// Registers a callback closure with a specific topic to listen to.
+///
+/// This variant of subscription has no activity. See [`ActivityId::subscribe`](struct.ActivityId.html#method.subscribe) and friends for other subscription options.
+pub fn subscribe<F, MSG>(f: F) -> SubscriptionId
+where
+    F: Fn(&MSG) + 'static,
+    MSG: Any,
+{
+    crate::nut::register_no_activity(f)
+}
+
+/// Removes a subscription, such that its handler is not called anymore.
+///
+/// The activity and its other subscriptions are not affected.
+/// Removing a subscription that has already been removed, or whose activity has been deleted, does nothing.
+/// Inside a subscription handler, the subscription is removed after the current message has been handled.
+///
+/// ### Example
+/// ```rust
+/// struct Hud { fps: u32 }
+/// struct Frame;
+///
+/// let hud = nuts::new_activity(Hud { fps: 0 });
+/// let counter = hud.subscribe(|hud, _: &Frame| hud.fps += 1);
+/// nuts::publish(Frame);
+/// // The FPS counter has been turned off
+/// nuts::unsubscribe(counter);
+/// nuts::publish(Frame);
+/// ```
+pub fn unsubscribe(id: SubscriptionId) {
+    nut::unsubscribe(id)
+}
+
+/// Send the message to all subscribed activities
+///
+// @ START-DOC PUBLISH
+/// Any instance of a struct or primitive can be published, as long as its type is known at compile-time. (The same constraint as for Activities.)
+/// Upon calling `nuts::publish`, all active subscriptions for the same type are executed and the published object will be shared with all of them.
+///
+/// ### Example
+/// ```rust
+/// struct ChangeUser { user_name: String }
+/// pub fn main() {
+///     let msg = ChangeUser { user_name: "Donald Duck".to_owned() };
+///     nuts::publish(msg);
+///     // Subscribers to messages of type `ChangeUser` will be notified
+/// }
+/// ```
+// @ END-DOC PUBLISH
+/// ### Advanced: Understanding the Execution Order
+// @ START-DOC PUBLISH_ADVANCED
+/// When calling `nuts::publish(...)`, the message may not always be published immediately. While executing a subscription handler from previous `publish`, all new messages are queued up until the previous one is completed.
+/// ```rust
+/// struct MyActivity;
+/// let activity = nuts::new_activity(MyActivity);
+/// activity.subscribe(
+///     |_, msg: &usize| {
+///         println!("Start of {}", msg);
+///         if *msg < 3 {
+///             nuts::publish( msg + 1 );
+///         }
+///         println!("End of {}", msg);
+///     }
+/// );
+///
+/// nuts::publish(0usize);
+/// // Output:
+/// // Start of 0
+/// // End of 0
+/// // Start of 1
+/// // End of 1
+/// // Start of 2
+/// // End of 2
+/// // Start of 3
+/// // End of 3
+/// ```
+// @ END-DOC PUBLISH_ADVANCED
+/// ### Advanced: Mutable Subscribers
+/// All subscribers receive the same message object.
+/// Subscribers registered with [`subscribe_mut`](struct.ActivityId.html#method.subscribe_mut) can modify it.
+/// They are called after all other subscribers, so only mutable subscribers that are called afterwards see the modified message.
+/// The order in which subscribers of different activities are called is not defined otherwise.
+/// If this aliasing is not wanted, use [`publish_isolated`](fn.publish_isolated.html) instead.
+pub fn publish<A: Any>(a: A) {
+    nut::publish_custom(a)
+}
+
+/// Publishes a message that belongs to the partition identified by `key`, for example the ID of an entity.
+///
+/// Messages with the same key are guaranteed to reach each handler in the order they have been published.
+/// This is the only ordering that handlers registered with [`subscribe_keyed`](struct.ActivityId.html#method.subscribe_keyed) should rely on.
+/// Today, all messages are still processed in order on a single thread.
+/// Future versions may batch or parallelize the processing of different keys, without breaking code that only assumes the per-key order.
+///
+/// ### Example
+/// ```rust
+/// struct Tracker { log: Vec<(u32, i32)> }
+/// struct Moved(i32);
+///
+/// let tracker = nuts::new_activity(Tracker { log: vec![] });
+/// tracker.subscribe_keyed(|tracker, entity: &u32, moved: &Moved| tracker.log.push((*entity, moved.0)));
+///
+/// nuts::publish_keyed(1u32, Moved(5));
+/// nuts::publish_keyed(2u32, Moved(-3));
+/// nuts::publish_keyed(1u32, Moved(2)); // always handled after Moved(5) of entity 1
+/// ```
+pub fn publish_keyed<K, MSG>(key: K, msg: MSG)
+where
+    K: Any + Hash + Eq,
+    MSG: Any,
+{
+    nut::publish_custom(KeyedMessage::new(key, msg))
+}
+
+/// Publishes a message inside the scope `S`, where only handlers registered with [`subscribe_scoped`](struct.ActivityId.html#method.subscribe_scoped) for the same scope receive it.
+///
+/// Use scopes to keep the messages of a plugin or crate apart from others that happen to use the same message type, like `()` or a common struct.
+/// See [`ScopeToken`](trait.ScopeToken.html) for how scopes are shared.
+///
+/// ### Example
+/// ```rust
+/// struct AudioScope;
+/// impl nuts::ScopeToken for AudioScope {}
+/// struct Mixer { loaded: usize }
+/// struct Loaded(String);
+///
+/// let mixer = nuts::new_activity(Mixer { loaded: 0 });
+/// mixer.subscribe_scoped::<AudioScope, _, _>(|mixer, _msg: &Loaded| mixer.loaded += 1);
+/// mixer.subscribe(|_mixer, _msg: &Loaded| panic!("not in the audio scope"));
+///
+/// nuts::publish_scoped::<AudioScope, _>(Loaded("click.ogg".to_owned()));
+/// ```
+pub fn publish_scoped<S: ScopeToken, MSG: Any>(msg: MSG) {
+    nut::publish_custom(nut::iac::publish::ScopedMessage::<S, MSG>::new(msg))
+}
+
+/// Publishes a large payload, such as a buffer of bytes, behind a reference-counted pointer `P` under the tag `T`.
+///
+/// All handlers share the same payload, it is never copied per handler.
+/// Handlers registered with [`subscribe_shared`](struct.ActivityId.html#method.subscribe_shared) read it in place,
+/// those registered with [`subscribe_shared_owned`](struct.ActivityId.html#method.subscribe_shared_owned) receive a clone of the pointer and may keep it after the handler returns.
+/// See [`SharedPayload`](struct.SharedPayload.html) for how tags select the topic.
+///
+/// Nuts never clones a message unless asked to.
+/// Only APIs that require `MSG: Clone` clone messages, such as [`publish_isolated`](fn.publish_isolated.html), [`retain_last`](fn.retain_last.html), or [`audit_mutations`](fn.audit_mutations.html).
+/// For a shared payload, even these only clone the pointer.
+///
+/// ### Example
+/// ```rust
+/// use std::rc::Rc;
+/// struct Frame;
+/// struct Encoder { sent: usize }
+/// struct Recorder { frames: Vec<Rc<[u8]>> }
+///
+/// let encoder = nuts::new_activity(Encoder { sent: 0 });
+/// encoder.subscribe_shared::<Frame, Rc<[u8]>, _>(|encoder, bytes: &[u8]| encoder.sent += bytes.len());
+/// let recorder = nuts::new_activity(Recorder { frames: vec![] });
+/// recorder.subscribe_shared_owned::<Frame, Rc<[u8]>, _>(|recorder, frame| recorder.frames.push(frame));
+///
+/// let frame: Rc<[u8]> = vec![0u8; 1 << 20].into();
+/// nuts::publish_shared::<Frame, _>(frame.clone());
+/// ```
+pub fn publish_shared<T, P>(payload: P)
+where
+    T: Any,
+    P: Deref + Clone + Any,
+{
+    nut::publish_custom(SharedPayload::<T, P>::new(payload))
+}
+
+/// Adds a message type to the family of all messages that implement the trait `T`, for [`subscribe_dyn`](struct.ActivityId.html#method.subscribe_dyn).
+///
+/// The cast converts a message to the trait object, usually it is just `|msg| msg`.
+/// Registering the same message type twice for the same family has no effect.
+pub fn register_dyn<T: ?Sized + 'static, MSG: Any>(cast: fn(&MSG) -> &T) {
+    nut::register_dyn_member(cast)
+}
+
+/// Same as [`publish`](fn.publish.html) but can be called safely at any time, including from initialization and drop code.
+///
+/// Nuts is initialized on the current thread with the first call to any of its functions, so publishing from lazy statics or other setup code is fine.
+/// However, once the thread-local storage of Nuts is being destroyed, at the end of a thread, `publish` panics.
+/// This happens, for example, if activities, domain objects, or other thread-local values publish messages when they are dropped.
+///
+/// `lazy_publish` only calls `f` to create the message if it can be published, and returns whether it has been published.
+/// Messages always go to the Nuts instance of the calling thread, so code that may run on another thread (such as the initialization of a global static) should not rely on them.
+///
+/// ### Example
+/// ```rust
+/// struct Shutdown;
+/// struct Connection;
+/// impl Drop for Connection {
+///     fn drop(&mut self) {
+///         // Safe to call even while the thread is exiting
+///         nuts::lazy_publish(|| Shutdown);
+///     }
+/// }
+/// nuts::new_activity(Connection);
+/// assert!(nuts::lazy_publish(|| Shutdown));
+/// ```
+pub fn lazy_publish<A: Any>(f: impl FnOnce() -> A) -> bool {
+    nut::lazy_publish(f)
+}
+
+/// Same as [`publish`](fn.publish.html) but each subscriber receives its own clone of the message.
+///
+/// Modifications by subscribers registered with `subscribe_mut` are not visible to other subscribers.
+/// This costs one clone per subscriber.
+///
+/// ### Example
+/// ```rust
+/// #[derive(Clone)]
+/// struct Draft { text: String }
+/// struct Editor;
+/// let editor = nuts::new_activity(Editor);
+/// editor.subscribe_mut(|_, msg: &mut Draft| msg.text.push_str(" (edited)"));
+/// editor.subscribe(|_, msg: &Draft| assert_eq!(msg.text, "Hello"));
+///
+/// nuts::publish_isolated(Draft { text: "Hello".to_owned() });
+/// ```
+pub fn publish_isolated<A: Any + Clone>(a: A) {
+    nut::publish_isolated(a)
+}
+
+/// Restricts publishing messages of type `MSG` to the holder of the returned token.
+///
+/// Afterwards, publishing such a message without [`PublishToken::publish`](struct.PublishToken.html#method.publish) panics.
+/// This keeps unrelated code from injecting control messages, like resetting the world, which should only come from the module owning them.
+/// Only published messages are restricted, not messages sent privately to an activity.
+///
+/// # Errors
+/// Returns `Error::PublishTokenTaken` if the token of this message type has been taken already.
+///
+/// ### Example
+/// ```rust
+/// struct ResetWorld;
+///
+/// let token = nuts::restrict_publish::<ResetWorld>().expect("first call");
+/// nuts::subscribe(|_: &ResetWorld| println!("Resetting"));
+/// token.publish(ResetWorld);
+/// assert!(nuts::restrict_publish::<ResetWorld>().is_err());
+/// ```
+pub fn restrict_publish<MSG: Any>() -> Result<PublishToken<MSG>, Error> {
+    nut::restrict_publish()
+}
+
+/// Protects messages of type `MSG` from being altered while they are delivered.
+///
+/// Afterwards, registering a handler that modifies such messages, like [`subscribe_mut`](struct.ActivityId.html#method.subscribe_mut), panics.
+/// So does registering a [private channel](struct.ActivityId.html#method.private_channel) for the type, which would take ownership of the message.
+/// [`try_subscribe_mut`](struct.ActivityId.html#method.try_subscribe_mut) returns `Error::ImmutableMessage` instead.
+///
+/// # Panics
+/// Panics if such a handler has been registered before.
+///
+/// ### Example
+/// ```rust
+/// use nuts::ImmutableMessage;
+///
+/// struct KeyPressed(char);
+/// impl ImmutableMessage for KeyPressed {}
+/// nuts::protect_message::<KeyPressed>();
+///
+/// struct TextInput;
+/// let id = nuts::new_activity(TextInput);
+/// id.subscribe(|_input, _key: &KeyPressed| {});
+/// assert!(id.try_subscribe_mut(|_input, key: &mut KeyPressed| key.0 = 'x').is_err());
+/// ```
+pub fn protect_message<MSG: ImmutableMessage>() {
+    nut::protect_message::<MSG>()
+}
+
+/// Same as [`publish`](fn.publish.html) but without a heap allocation per message, for messages that implement `Copy`.
+///
+/// Published messages are stored on the heap until they have been delivered.
+/// For `Copy` messages, this variant reuses the heap memory of previously published messages of the same type.
+/// Thus, once a few messages have been published, no more heap allocations are necessary.
+/// The first messages of each type are still allocated.
+/// After each drain of the queue, memory for up to 32 messages per type is kept for reuse, [`compact`](fn.compact.html) releases it.
+///
+/// This only saves the allocation, dispatching the message costs the same as with `publish`.
+///
+/// ### Example
+/// ```rust
+/// #[derive(Clone, Copy)]
+/// struct MouseMove { x: i32, y: i32 }
+/// nuts::subscribe(|msg: &MouseMove| println!("Mouse at {}/{}", msg.x, msg.y));
+/// for i in 0..100 {
+///     nuts::publish_copy(MouseMove { x: i, y: i });
+/// }
+/// ```
+pub fn publish_copy<A: Any + Copy>(a: A) {
+    nut::publish_copy(a)
+}
+
+/// Returns a future of type `NutsResponse` which will resolve after the
+/// message has been published and all subscribers have finished processing it.
+///
+/// This is the same as `publish_awaiting(a).await_dispatch()`, except that the message is only published when the future is first polled.
+/// See [`publish_awaiting`](fn.publish_awaiting.html) for other completion semantics.
+pub async fn publish_awaiting_response<A: Any>(a: A) {
+    nut::publish_custom_and_await(a, ResponseMode::Dispatch, false).await;
+}
+
+/// Sends a request to the activity registered with [`respond`](struct.ActivityId.html#method.respond) for `REQ` and `RESP`, returns a future that resolves with its response.
+///
+/// Unlike [`publish_awaiting_response`](fn.publish_awaiting_response.html), which only signals that the message has been handled, the future yields the value returned by the responder.
+/// The request is queued right away as a private message to the responder, it is tracked like all other awaited messages, see [`response_stats`](fn.response_stats.html).
+///
+/// The future resolves with `Error::NoReply` if the request has been handled without reply.
+/// This happens if the responder has been deleted or replaced after the request has been sent, but before it has been handled.
+///
+/// # Panics
+/// Panics if no responder has been registered for `REQ` and `RESP`.
+/// Deleting the activity of a responder unregisters it.
+///
+/// ### Example
+/// ```rust
+/// struct Prices { apple: u32 }
+/// struct Quote(&'static str);
+///
+/// let prices = nuts::new_activity(Prices { apple: 3 });
+/// prices.respond(|prices, _quote: Quote| prices.apple);
+///
+/// let price = futures::executor::block_on(nuts::request::<_, u32>(Quote("apple")));
+/// assert_eq!(Ok(3), price);
+/// ```
+pub fn request<REQ: Any, RESP: Any>(req: REQ) -> Reply<RESP> {
+    nut::request(req)
+}
+
+/// Prepares publishing a message and awaiting its effects.
+///
+/// The returned object defines when the future resolves.
+/// - [`await_dispatch`](struct.AwaitingPublish.html#method.await_dispatch) resolves as soon as all subscribers of this message have run.
+/// - [`await_quiescent`](struct.AwaitingPublish.html#method.await_quiescent) resolves only after all messages published in reaction have been handled as well.
+///
+/// ### Example
+/// ```rust
+/// struct Save;
+/// struct Saved;
+/// nuts::subscribe(|_: &Save| nuts::publish(Saved));
+/// nuts::subscribe(|_: &Saved| println!("Saved"));
+///
+/// // Resolves after "Saved" has been printed
+/// futures::executor::block_on(nuts::publish_awaiting(Save).await_quiescent());
+/// ```
+pub fn publish_awaiting<A: Any>(a: A) -> AwaitingPublish<A> {
+    AwaitingPublish::new(a)
+}
+
+/// Like [`publish_awaiting_response`](fn.publish_awaiting_response.html) but the message skips ahead of all queued messages.
+///
+/// Use this when the response should arrive with bounded latency, even if many other messages are waiting to be delivered.
+/// The elevated priority is inherited by everything the subscribers publish (or otherwise queue) while handling the message.
+/// Thus, if the response is produced through a chain of messages, the entire chain is processed before other queued messages.
+///
+/// Unlike `publish_awaiting_response`, the message is queued immediately when this function is called, not when the future is first polled.
+///
+/// ### Example
+/// ```rust
+/// struct Request;
+/// nuts::subscribe(|_: &Request| println!("Handled before all previously queued messages"));
+/// let response = nuts::publish_awaiting_response_urgent(Request);
+/// futures::executor::block_on(response);
+/// ```
+pub fn publish_awaiting_response_urgent<A: Any>(
+    a: A,
+) -> impl core::future::Future<Output = ()> + Unpin {
+    nut::publish_custom_and_await(a, ResponseMode::Dispatch, true)
+}
+
+/// Tunes the bookkeeping of pending futures returned by [`publish_awaiting_response`](fn.publish_awaiting_response.html) and related functions.
+///
+/// Slots of resolved or dropped futures are reused without searching, the configured policy decides which one comes next.
+/// Use [`response_stats`](fn.response_stats.html) to watch the number of pending futures.
+///
+/// # Panics
+/// With `max_pending` set, creating a future beyond the limit panics.
+///
+/// ### Example
+/// ```rust
+/// nuts::configure_responses(nuts::ResponseConfig {
+///     preallocate: 64,
+///     max_pending: Some(1024),
+///     ..Default::default()
+/// });
+/// assert_eq!(nuts::response_stats().slots, 64);
+/// ```
+pub fn configure_responses(config: ResponseConfig) {
+    nut::configure_responses(config)
+}
+
+/// Returns metrics of the futures of published messages, see [`configure_responses`](fn.configure_responses.html).
+pub fn response_stats() -> ResponseStats {
+    nut::response_stats()
+}
+
+/// Returns a sink that publishes every item sent to it.
+///
+/// This allows to forward streams into Nuts directly, without writing a poll loop by hand.
+/// Only available with the feature `futures`.
+///
+/// ### Example
+/// ```rust
+/// use futures::StreamExt;
+///
+/// struct ChatMessage(String);
+/// nuts::subscribe(|msg: &ChatMessage| println!("Received: {}", msg.0));
+///
+/// let incoming = futures::stream::iter(vec![
+///     ChatMessage("Hello".to_owned()),
+///     ChatMessage("World".to_owned()),
+/// ]);
+/// let forwarding = incoming.map(Ok).forward(nuts::sink());
+/// futures::executor::block_on(forwarding).unwrap();
+/// ```
+#[cfg(feature = "futures")]
+pub fn sink<MSG: Any>() -> impl futures_sink::Sink<MSG, Error = core::convert::Infallible> {
+    nut::iac::publish::PublishSink::new()
+}
+
+/// Publish a message to a specific activity. The same as `id.private_message()` but works without an `ActivityId`.
+///
+/// The first type parameter must always be specified.
+/// It determines the receiver of the message.
+/// The message is ignored silently if no such activity has been registered or if it has no private channel for this message.
+///
+/// The second type parameter can usually be deferred by the compiler, it is the type of the message to be sent.
+/// ### Example
+// @ START-DOC PUBLISH_PRIVATE
+/// ```rust
+/// struct ExampleActivity {}
+/// let id = nuts::new_activity(ExampleActivity {});
+/// // `private_channel` works similar to `subscribe` but it owns the message.
+/// id.private_channel(|_activity, msg: usize| {
+///     assert_eq!(msg, 7);
+/// });
+/// // `send_to` must be used instead of `publish` when using private channels.
+/// // Which activity receives the message is decide by the first type parameter.
+/// nuts::send_to::<ExampleActivity, _>(7usize);
+/// ```
+// @ END-DOC PUBLISH_PRIVATE
+pub fn send_to<RECEIVER: Any, MSG: Any>(msg: MSG) {
+    nut::send_custom::<RECEIVER, MSG>(msg)
+}
+
+/// Creates a double buffer for state that is written by one activity and read by others, such as render state.
+///
+/// The writer modifies a back buffer while readers see the front buffer, which holds the last completed state.
+/// Writes from subscription handlers become visible to readers once the current broadcast has been handled completely.
+/// This way, a draw activity never sees a half-updated frame, without copying state manually between update and draw.
+/// Both buffers start with the default value of `T`.
+///
+/// ### Example
+/// ```rust
+/// #[derive(Clone, Default)]
+/// struct RenderState { player_x: f32 }
+/// struct Update;
+/// struct Draw;
+///
+/// let (writer, reader) = nuts::double_buffer::<RenderState>();
+/// nuts::subscribe(move |_: &Update| {
+///     writer.write(|state| state.player_x += 1.0);
+/// });
+/// nuts::subscribe(move |_: &Draw| {
+///     println!("Player at {}", reader.read().player_x);
+/// });
+/// nuts::publish(Update);
+/// nuts::publish(Draw);
+/// ```
+pub fn double_buffer<T: Clone + Default + 'static>() -> (BufferWriter<T>, BufferReader<T>) {
+    nut::iac::double_buffer::new(T::default())
+}
+
+/// Sets a closure that is called when the thread exits, right before all activities are torn down.
+///
+/// When the thread exits, the thread-local state of Nuts is destroyed in a defined order:
+/// 1. The teardown hook is called.
+/// 2. `on_leave` handlers are called for all active activities, in reverse creation order.
+/// 3. All activities are deleted in reverse creation order, calling their `on_delete` handlers.
+/// 4. Domain objects are dropped, in reverse creation order.
+///
+/// Queued messages that have not been delivered, yet, are dropped.
+/// The same teardown happens in [`request_exit`](fn.request_exit.html), in which case it is not repeated at thread exit.
+///
+/// The thread-local storage of Nuts is not accessible anymore at that point.
+/// The hook and all handlers that are called during teardown must not call any functions of Nuts, such as `nuts::publish`, otherwise they panic.
+///
+/// Note that thread-local state of the main thread is not always destroyed, depending on the platform.
+pub fn set_teardown_hook(f: impl FnOnce() + 'static) {
+    nut::set_teardown_hook(Box::new(f))
+}
+
+/// Requests an orderly shutdown of the application with the given exit code.
+///
+/// First, [`ExitRequested`](struct.ExitRequested.html) is published and any subscriber may veto the exit.
+/// Unless vetoed, the same teardown as at thread exit follows (see [`set_teardown_hook`](fn.set_teardown_hook.html)),
+/// and finally the closure registered with [`set_exit_handler`](fn.set_exit_handler.html) is called with the exit code.
+///
+/// Messages queued before the request are delivered first.
+/// Messages that are still queued after the teardown reach no activity.
+/// Inside a subscription handler, the exit happens after the current message has been handled.
+///
+/// ### Example
+/// ```rust
+/// struct Editor { unsaved: bool }
+/// let editor = nuts::new_activity(Editor { unsaved: true });
+/// editor.subscribe(|editor, exit: &nuts::ExitRequested| {
+///     if editor.unsaved {
+///         exit.veto();
+///     }
+/// });
+/// editor.on_delete(|_| println!("Editor closed"));
+/// nuts::set_exit_handler(|code| println!("Exiting with {}", code));
+///
+/// nuts::request_exit(0); // Vetoed, the editor has unsaved changes
+/// ```
+pub fn request_exit(code: i32) {
+    nut::request_exit(code)
+}
+
+/// Sets the closure that is called at the end of [`request_exit`](fn.request_exit.html), usually to terminate the process.
+///
+/// ### Example
+/// ```rust
+/// nuts::set_exit_handler(|code| std::process::exit(code));
+/// ```
+pub fn set_exit_handler(f: impl FnOnce(i32) + 'static) {
+    nut::set_exit_handler(Box::new(f))
+}
+
+/// Runs the closure once the current message has been handled, when Nuts holds no borrows of activities or domains.
+///
+/// Some functions cannot be called inside subscription handlers, like [`ActivityId::is_valid`](struct.ActivityId.html#method.is_valid), because the activities are borrowed while a handler runs.
+/// Deferred closures are a sanctioned way to call them anyway.
+/// They are queued like other internal operations, see [`QueueOrder`](enum.QueueOrder.html).
+/// Outside of subscription handlers, the closure is called immediately.
+///
+/// The closure may publish messages and create activities, those are processed as usual.
+///
+/// ### Example
+/// ```rust
+/// struct Spawner;
+/// struct Spawn;
+/// struct Enemy;
+///
+/// let spawner = nuts::new_activity(Spawner);
+/// spawner.subscribe(|_, _: &Spawn| {
+///     let enemy = nuts::new_activity(Enemy);
+///     // Panics if called directly inside the handler
+///     nuts::defer(move || assert!(enemy.is_valid()));
+/// });
+/// nuts::publish(Spawn);
+/// ```
+pub fn defer(f: impl FnOnce() + 'static) {
+    nut::defer(Box::new(f))
+}
+
+/// Returns the scratch space, which holds temporary values shared by all handlers of one cascade of messages.
+///
+/// Handlers can store intermediate results there for handlers called later, without adding transient data to a domain.
+/// The scratch space holds at most one value per type.
+/// It is cleared as soon as no more messages are queued, which is also the case right after a top-level `publish` returns.
+/// Values stored outside of subscription handlers are kept until the next cascade has been processed.
+///
+/// ### Example
+/// ```rust
+/// struct Tick;
+/// struct Collision;
+/// struct EndOfTick;
+/// struct Physics;
+/// struct Audio;
+///
+/// #[derive(Default)]
+/// struct Impacts(u32);
+///
+/// let physics = nuts::new_activity(Physics);
+/// physics.subscribe(|_, _: &Collision| {
+///     nuts::scratch().update(|impacts: &mut Impacts| impacts.0 += 1);
+/// });
+/// let audio = nuts::new_activity(Audio);
+/// audio.subscribe(|_, _: &EndOfTick| {
+///     let impacts = nuts::scratch().take::<Impacts>().unwrap_or_default();
+///     assert_eq!(2, impacts.0);
+/// });
+/// physics.subscribe(|_, _: &Tick| {
+///     nuts::publish(Collision);
+///     nuts::publish(Collision);
+///     nuts::publish(EndOfTick);
+/// });
+/// nuts::publish(Tick);
+/// assert!(!nuts::scratch().contains::<Impacts>());
+/// ```
+pub fn scratch() -> Scratch {
+    Scratch::new()
+}
+
+/// Polls all futures attached with [`ActivityId::attach_future`](struct.ActivityId.html#method.attach_future) that have been woken.
+///
+/// Nuts polls woken futures by itself whenever all queued messages have been handled.
+/// Call this if futures may be woken by something else than Nuts, for example by another thread or by a timer of the platform.
+pub fn poll_tasks() {
+    nut::poll_tasks()
+}
+
+/// Polls each future attached with [`ActivityId::attach_future`](struct.ActivityId.html#method.attach_future) that has been woken, once.
+///
+/// Unlike [`poll_tasks`](fn.poll_tasks.html), futures woken while polling are not polled again in the same call.
+/// This bounds the work per call, for hosts that drive Nuts from their own loop, such as `requestAnimationFrame` in a browser.
+/// No executor like `wasm-bindgen-futures` is needed.
+/// Futures returned by [`publish_awaiting_response`](fn.publish_awaiting_response.html) and similar functions are driven this way, too, when they are awaited inside an attached future.
+///
+/// Returns true if woken futures are left, which should be polled in the next step.
+/// Returns false without polling anything if called while futures are being polled already.
+///
+/// ### Example
+/// ```rust
+/// struct Loader;
+/// struct Loaded;
+///
+/// let loader = nuts::new_activity(Loader);
+/// loader.attach_future(async {
+///     nuts::publish_awaiting_response(Loaded).await;
+///     println!("Everyone knows now");
+/// });
+///
+/// // Called once per animation frame by the host
+/// let more_work = nuts::poll_futures();
+/// ```
+pub fn poll_futures() -> bool {
+    nut::poll_futures()
+}
+
+/// Publishes the message returned by `f` periodically, until the returned handle is cancelled.
+///
+/// Nuts has no clock of its own, time is driven by the application calling [`advance_time`](fn.advance_time.html), for example once per frame.
+/// The first message is published after one full period.
+/// This covers heartbeats, autosaves, polling and similar use cases without managing timers manually.
+///
+/// # Panics
+/// Panics if `period` is zero.
+///
+/// ### Example
+/// ```rust
+/// use std::time::Duration;
+/// struct Autosave;
+///
+/// let handle = nuts::every(Duration::from_secs(60), || Autosave);
+/// nuts::subscribe(|_: &Autosave| println!("Saving..."));
+///
+/// // In the main loop of the application
+/// nuts::advance_time(Duration::from_millis(16));
+///
+/// // Stop saving
+/// handle.cancel();
+/// ```
+pub fn every<MSG: Any>(period: std::time::Duration, f: impl Fn() -> MSG + 'static) -> TimerHandle {
+    nut::every(period, f)
+}
+
+/// Moves the time of Nuts forward by `dt`, publishing the messages of all periodic publishers that are due.
+///
+/// If a period elapsed several times, the message is published several times.
+/// Messages are published in the order of their due times.
+pub fn advance_time(dt: std::time::Duration) {
+    nut::advance_time(dt)
+}
+
+/// Calls `f` once Nuts has been idle for at least `after`, to schedule housekeeping like cache eviction or autosaves only when the application is not busy.
+///
+/// Like periodic publishers, idle callbacks are driven by [`advance_time`](fn.advance_time.html).
+/// Nuts counts as busy during a call to `advance_time` if any message has been dispatched since the previous call.
+/// Messages published by periodic publishers and idle callbacks themselves do not count, unless they are only dispatched after `advance_time` returned.
+/// The callback is called once per idle period, it is called again only after Nuts has been busy and then idle for `after` again.
+///
+/// ### Example
+/// ```rust
+/// use std::time::Duration;
+/// struct Edit;
+/// struct Autosave;
+///
+/// nuts::subscribe(|_: &Autosave| println!("Saving..."));
+/// let handle = nuts::on_idle(Duration::from_secs(5), || nuts::publish(Autosave));
+///
+/// // In the main loop of the application
+/// nuts::publish(Edit);
+/// nuts::advance_time(Duration::from_secs(3)); // busy
+/// nuts::advance_time(Duration::from_secs(3)); // idle for only 3s
+/// nuts::advance_time(Duration::from_secs(3)); // saves
+///
+/// handle.cancel();
+/// ```
+pub fn on_idle(after: std::time::Duration, f: impl Fn() + 'static) -> TimerHandle {
+    nut::on_idle(after, f)
+}
+
+/// Converts published messages of type `OLD` to `NEW`, for renaming message types incrementally.
+///
+/// After this call, messages of type `OLD` are converted with `f` when they are published and delivered to subscribers of `NEW`.
+/// Subscribers of `OLD` do not receive them anymore.
+/// This also applies to messages published with `publish_copy` and the other variants, but not to private messages.
+///
+/// The number of converted messages is reported by [`alias_report`](fn.alias_report.html).
+/// Once it stays at zero, all publishers have been migrated and the alias can be removed.
+///
+/// ### Example
+/// ```rust
+/// struct OldResize(u32, u32);
+/// struct Resize { width: u32, height: u32 }
+///
+/// nuts::alias(|old: OldResize| Resize { width: old.0, height: old.1 });
+/// nuts::subscribe(|msg: &Resize| assert_eq!(msg.width, 800));
+///
+/// // Somewhere in code that has not been migrated, yet
+/// nuts::publish(OldResize(800, 600));
+/// assert_eq!(nuts::alias_report()[0].publishes, 1);
+/// ```
+pub fn alias<OLD: Any, NEW: Any>(f: impl Fn(OLD) -> NEW + 'static) {
+    nut::alias(f)
+}
+
+/// Lists all aliases registered with [`alias`](fn.alias.html), with the number of messages that still use the old type.
+pub fn alias_report() -> Vec<AliasReport> {
+    nut::alias_report()
+}
+
+/// Modifies every published message of type `MSG` with `f` before it is delivered, for example to normalize coordinates or to apply DPI scaling in one place instead of in every subscriber.
+///
+/// There is at most one mapper per message type, registering another one replaces it.
+/// The mapper only changes the message in place, it can neither drop it nor change its type, use [`alias`](fn.alias.html) for that.
+/// It is applied once per message when it is published, after aliases have been resolved, hence messages published as an aliased type are mapped as the new type.
+/// Like aliases, it does not apply to private messages.
+///
+/// ### Example
+/// ```rust
+/// struct Click { x: f32, y: f32 }
+///
+/// let scale = 2.0;
+/// nuts::map_messages(move |click: &mut Click| {
+///     click.x /= scale;
+///     click.y /= scale;
+/// });
+/// nuts::subscribe(|click: &Click| assert_eq!((click.x, click.y), (50.0, 20.0)));
+///
+/// // Physical pixels reported by the platform
+/// nuts::publish(Click { x: 100.0, y: 40.0 });
+/// ```
+pub fn map_messages<MSG: Any>(f: impl Fn(&mut MSG) + 'static) {
+    nut::map_messages(f)
+}
+
+/// Registers an activity under a global name, for use with [`send_to_named`](fn.send_to_named.html).
+///
+/// Names decouple the sender from the type of the receiving activity.
+/// This is useful when modules cannot share `ActivityId` types, for example across crate boundaries.
+/// Registering a name again replaces the previous activity.
+pub fn register_name(name: impl Into<String>, id: impl Into<UncheckedActivityId>) {
+    nut::register_name(name.into(), id.into())
+}
+
+/// Sends a private message to the activity registered under the given name.
+///
+/// Works like [`send_to`](fn.send_to.html), except that the receiver is looked up by its name.
+/// The message is ignored silently if no activity has been registered under that name, or if it has no private channel for this message.
+///
+/// ### Example
+/// ```rust
+/// struct Audio;
+/// struct PlaySound(&'static str);
+///
+/// let id = nuts::new_activity(Audio);
+/// id.private_channel(|_audio, msg: PlaySound| assert_eq!(msg.0, "click.ogg"));
+/// nuts::register_name("audio", id);
+///
+/// // Somewhere else, without access to the `Audio` type
+/// nuts::send_to_named("audio", PlaySound("click.ogg"));
+/// ```
+pub fn send_to_named<MSG: Any>(name: &str, msg: MSG) {
+    nut::send_custom_by_name(name, msg)
+}
+
+/// Returns the number of heap allocations Nuts has performed since the last call, and resets the counters.
+///
+/// Only available with the feature `alloc-audit`.
+/// Use this to verify that no allocations happen per frame in a steady state, for example in a test.
+/// Allocations inside subscription handlers are not counted.
+///
+/// ### Example
+/// ```rust
+/// # #[cfg(feature = "alloc-audit")]
+/// # {
+/// #[derive(Clone, Copy)]
+/// struct Tick(u32);
+/// nuts::subscribe(|_: &Tick| {});
+/// // Warm up: The first messages fill the message pool and grow the queues
+/// nuts::publish_copy(Tick(0));
+/// nuts::take_allocation_report();
+///
+/// for i in 0..100 {
+///     nuts::publish_copy(Tick(i));
+/// }
+/// let report = nuts::take_allocation_report();
+/// assert_eq!(report.broadcasts, 100);
+/// assert_eq!(report.total(), 0);
+/// # }
+/// ```
+#[cfg(feature = "alloc-audit")]
+pub fn take_allocation_report() -> AllocationReport {
+    nut::audit::take_report()
+}
+
+/// Returns how much memory the message arena has used, to tune bursts of messages.
+///
+/// With the feature `message-arena`, [`publish`](fn.publish.html) boxes messages in memory of previously delivered messages of the same type, just like [`publish_copy`](fn.publish_copy.html).
+/// This applies to all message types without drop glue, i.e. types that neither implement `Drop` nor contain fields that do, such as `String` or `Vec`.
+/// Other messages are boxed as usual.
+///
+/// Only available with the feature `message-arena`.
+///
+/// ### Example
+/// ```rust
+/// # #[cfg(feature = "message-arena")]
+/// # {
+/// struct Moved { x: f32, y: f32 }
+/// nuts::subscribe(|_: &Moved| {});
+/// nuts::publish(Moved { x: 1.0, y: 2.0 });
+/// let usage = nuts::arena_usage();
+/// assert_eq!(8, usage.last_drain_bytes);
+/// assert_eq!(8, usage.pooled_bytes);
+/// # }
+/// ```
+#[cfg(feature = "message-arena")]
+pub fn arena_usage() -> ArenaUsage {
+    nut::arena_usage()
+}
+
+/// Releases memory that internal storage keeps after activities have been deleted and queues have been drained.
+///
+/// Storage of Nuts grows with bursts of activities, subscriptions and messages but never shrinks on its own.
+/// This drops the subscription handlers of deleted activities, frees the boxes of delivered messages kept for reuse, and shrinks activity storage, subscription tables, and the event queue to fit.
+/// Long-lived applications can call it after a burst is over, for example when switching scenes.
+///
+/// Deleted activities keep a small slot each, which is needed to detect stale IDs.
+///
+/// # Panics
+/// Panics if called from inside a subscription handler.
+///
+/// ### Example
+/// ```rust
+/// struct Enemy;
+/// struct Tick;
+///
+/// for _ in 0..100 {
+///     let id = nuts::new_activity(Enemy);
+///     id.subscribe(|_, _: &Tick| {});
+///     id.set_status(nuts::LifecycleStatus::Deleted);
+/// }
+/// let report = nuts::compact();
+/// assert_eq!(100, report.dropped_handlers);
+/// ```
+pub fn compact() -> CompactionReport {
+    nut::compact()
+}
+
+/// Changes the limits above which warnings about suspicious usage are emitted.
+///
+/// Only available with the feature `log`, which emits warnings through the [`log`](https://docs.rs/log) crate for
+/// - messages that are published without any subscriber (once per message type),
+/// - cascades of messages published by handlers that are deeper than `max_cascade_depth`,
+/// - queues that grow beyond `max_queue_len` events,
+/// - handlers that take longer than `handler_budget`, measured with the [clock](fn.set_clock.html) of Nuts.
+///
+/// The limits apply to the current thread.
+///
+/// ### Example
+/// ```rust
+/// # #[cfg(feature = "log")]
+/// # {
+/// use std::time::Duration;
+/// nuts::set_log_thresholds(nuts::LogThresholds {
+///     handler_budget: Duration::from_millis(4),
+///     ..Default::default()
+/// });
+/// # }
+/// ```
+#[cfg(feature = "log")]
+pub fn set_log_thresholds(thresholds: LogThresholds) {
+    nut::set_log_thresholds(thresholds)
+}
+
+/// Replaces the clock used to measure how long subscription handlers take, and the time between calls of [timed subscriptions](struct.ActivityId.html#method.subscribe_timed).
+///
+/// By default, the clock is a [`StdClock`](struct.StdClock.html) on native targets.
+/// On `wasm32`, where `std::time::Instant` is not available, it is a [`WebClock`](struct.WebClock.html) if the feature `web-clock` is enabled.
+/// Otherwise, time does not pass on `wasm32` and all measured durations are zero.
+///
+/// Tests can set a [`MockClock`](struct.MockClock.html) to control the measured durations.
+pub fn set_clock(clock: impl Clock + 'static) {
+    nut::set_clock(Box::new(clock))
+}
+
+/// Registers `C` as the codec of messages of type `MSG`, replacing any codec registered before.
+///
+/// The registry is the single place where messages are turned into bytes and back.
+/// Everything that stores or forwards messages outside of the current thread, like recordings, snapshots, or a bridge to another process, should go through [`encode_message`](fn.encode_message.html) and [`publish_encoded`](fn.publish_encoded.html) instead of serializing messages itself.
+///
+/// Codecs are registered per thread.
+///
+/// ### Example
+/// ```rust
+/// use nuts::{EncodedMessage, MessageCodec};
+/// use std::convert::TryInto;
+///
+/// struct Score(u32);
+///
+/// #[derive(Default)]
+/// struct ScoreCodec;
+/// impl MessageCodec<Score> for ScoreCodec {
+///     fn encode(&self, msg: &Score) -> Vec<u8> {
+///         msg.0.to_le_bytes().to_vec()
+///     }
+///     fn decode(&self, bytes: &[u8]) -> Option<Score> {
+///         Some(Score(u32::from_le_bytes(bytes.try_into().ok()?)))
+///     }
+/// }
+///
+/// nuts::register_codec::<Score, ScoreCodec>();
+/// let encoded = nuts::encode_message(&Score(7)).unwrap();
+///
+/// // e.g. sent over the network as name and bytes
+/// let received = EncodedMessage::from_parts(encoded.type_name, encoded.bytes).unwrap();
+/// nuts::publish_encoded(&received).unwrap();
+/// ```
+pub fn register_codec<MSG: Any, C: MessageCodec<MSG> + Default + 'static>() {
+    nut::register_codec::<MSG>(C::default())
+}
+
+/// Encodes a message with the codec registered for its type, see [`register_codec`](fn.register_codec.html).
+///
+/// # Errors
+/// Returns `Error::NoCodec` if no codec has been registered for `MSG`.
+pub fn encode_message<MSG: Any>(msg: &MSG) -> Result<EncodedMessage, Error> {
+    nut::encode_message(msg)
+}
+
+/// Decodes a message with the codec registered for its type and publishes it, like [`publish`](fn.publish.html).
+///
+/// # Errors
+/// Returns `Error::NoCodec` if no codec has been registered for the message type.
+/// Returns `Error::MalformedMessage` if the codec cannot decode the bytes, nothing is published in that case.
+pub fn publish_encoded(msg: &EncodedMessage) -> Result<(), Error> {
+    nut::publish_encoded(msg)
+}
+
+/// Returns the names of all published message types that share ownership between publisher and subscribers.
+///
+/// Only available with the feature `shared-audit`.
+/// Messages are supposed to be moved from the publisher to the subscribers.
+/// Publishing an `Rc`, or a reference to a `RefCell`, lets activities share state behind the back of Nuts, which often leads to hidden aliasing bugs.
+/// Each such message type is reported once, with a warning printed to the log in debug mode.
+///
+/// Detection is based on the type name of the message, so only shared pointers that appear in it are found.
+/// For example, `Rc<RefCell<State>>` is detected but a struct with a private `Rc` field is not.
+///
+/// ### Example
+/// ```rust
+/// # #[cfg(feature = "shared-audit")]
+/// # {
+/// use std::{cell::RefCell, rc::Rc};
+/// nuts::publish(Rc::new(RefCell::new(0u32)));
+/// assert_eq!(nuts::shared_payload_warnings().len(), 1);
+/// # }
+/// ```
+#[cfg(feature = "shared-audit")]
+pub fn shared_payload_warnings() -> Vec<&'static str> {
+    nut::shared_audit::warnings()
+}
+
+/// Marks a message type as intentionally sharing ownership, which suppresses warnings of the feature `shared-audit`.
+#[cfg(feature = "shared-audit")]
+pub fn allow_shared<MSG: Any>() {
+    nut::shared_audit::allow::<MSG>()
+}
+
+/// Compares messages of the given type before and after each handler with mutable access, to record which activities modified them.
+///
+/// Only available with the feature `mutation-audit`.
+/// The recorded activities are returned by [`mutation_trail`](fn.mutation_trail.html).
+/// This costs one clone and one comparison per call of a handler registered with [`subscribe_mut`](struct.ActivityId.html#method.subscribe_mut).
+/// For message types that cannot be compared, handlers can call [`mark_changed`](fn.mark_changed.html) instead.
+///
+/// ### Example
+/// ```rust
+/// # #[cfg(feature = "mutation-audit")]
+/// # {
+/// #[derive(Clone, PartialEq)]
+/// struct Damage(u32);
+/// struct Armor;
+/// struct Shield;
+///
+/// nuts::audit_mutations::<Damage>();
+/// nuts::new_activity(Armor).subscribe_mut(|_, dmg: &mut Damage| dmg.0 /= 2);
+/// nuts::new_activity(Shield).subscribe_mut(|_, dmg: &mut Damage| dmg.0 = dmg.0.min(100));
+///
+/// nuts::publish(Damage(50));
+/// // Only the armor changed the damage
+/// assert_eq!(nuts::mutation_trail::<Damage>().len(), 1);
+/// # }
+/// ```
+#[cfg(feature = "mutation-audit")]
+pub fn audit_mutations<MSG: Any + Clone + PartialEq>() {
+    nut::mutation_audit::register::<MSG>()
+}
+
+/// Records that the currently executing handler modified the message it received with mutable access.
+///
+/// Only available with the feature `mutation-audit`.
+/// Calls outside of handlers registered with [`subscribe_mut`](struct.ActivityId.html#method.subscribe_mut) have no effect.
+#[cfg(feature = "mutation-audit")]
+pub fn mark_changed() {
+    nut::mutation_audit::mark_changed()
+}
+
+/// Returns the type names of all activities that modified the most recent message of the given type, in the order they modified it.
+///
+/// Only available with the feature `mutation-audit`.
+/// Called from inside a handler, it lists the activities that modified the message before the handler has seen it.
+/// Modifications are detected for message types registered with [`audit_mutations`](fn.audit_mutations.html) and for handlers that call [`mark_changed`](fn.mark_changed.html).
+/// Type names are only available in debug mode.
+#[cfg(feature = "mutation-audit")]
+pub fn mutation_trail<MSG: Any>() -> Vec<&'static str> {
+    nut::mutation_audit::trail::<MSG>()
+}
+
+/// Returns the accumulated time measurements of all subscription handlers.
+///
+/// Only available with the feature `perf`.
+/// Both wall time and the CPU time of the thread are measured, which helps to distinguish handlers that block from those that are busy computing.
+/// Wall time is measured with the clock set by [`set_clock`](fn.set_clock.html).
+/// CPU time is not measured on `wasm32` targets and is always zero there.
+///
+/// # Panics
+/// Panics if called from inside a subscription handler.
+#[cfg(feature = "perf")]
+pub fn profiling_report() -> Vec<HandlerReport> {
+    nut::profiling_report()
+}
+
+/// Lists subscriptions that have never been called, although at least `min_broadcasts` messages have been dispatched since they were registered.
+///
+/// This helps to find and prune dead wiring, such as subscriptions to messages that nobody publishes anymore.
+/// A handler counts as called even if its activity was inactive and the handler has not been executed because of that.
+///
+/// The necessary bookkeeping is only done in debug mode, in release builds the list is always empty.
+///
+/// ### Example
+/// ```rust
+/// struct Tick;
+/// struct Renamed;
+/// nuts::subscribe(|_: &Tick| {});
+/// nuts::subscribe(|_: &Renamed| {});
+/// for _ in 0..10 {
+///     nuts::publish(Tick);
+/// }
+/// // In debug mode, this prints the subscription to `Renamed`
+/// for unused in nuts::unused_subscriptions(10) {
+///     println!("{} never received {}", unused.activity, unused.topic);
+/// }
+/// ```
+pub fn unused_subscriptions(min_broadcasts: u64) -> Vec<UnusedSubscription> {
+    nut::unused_subscriptions(min_broadcasts)
+}
+
+/// Lists handlers registered with [`subscribe_domained_capturing`](struct.ActivityId.html#method.subscribe_domained_capturing) that use the ID of an activity in another domain.
+///
+/// Such handlers compile and register fine, but they only see the domain of their own activity.
+/// If they expect the objects of the other activity's domain, they panic once they are called.
+/// Checking this list at the end of the setup turns these panics into setup-time diagnostics.
+/// Each warning is also printed when the handler is registered.
+///
+/// Domains that are merged later with [`merge_domains`](fn.merge_domains.html) still count as different domains.
+/// The check is only done in debug mode, in release builds the list is always empty.
+///
+/// ### Example
+/// ```rust
+/// use nuts::{domain_enum, DomainEnumeration};
+/// #[derive(Clone, Copy)]
+/// enum World { Ui, Game }
+/// domain_enum!(World);
+/// struct Hud;
+/// struct Player;
+/// struct Redraw;
+///
+/// let player = nuts::new_domained_activity(Player, &World::Game);
+/// let hud = nuts::new_domained_activity(Hud, &World::Ui);
+/// hud.subscribe_domained_capturing(player, |_hud, _ui, _player, _: &Redraw| {});
+/// // In debug mode, this reports the handler of `Hud`
+/// for warning in nuts::capture_warnings() {
+///     eprintln!("{} uses the ID of {} for {}", warning.activity, warning.captured, warning.topic);
+/// }
+/// ```
+pub fn capture_warnings() -> Vec<CaptureWarning> {
+    nut::capture_warnings()
+}
+
+/// Exports all registered subscriptions as a table of topics, subscribing activities, and handler counts.
+///
+/// Store the table, for example with [`SubscriptionTable::to_json`](struct.SubscriptionTable.html#method.to_json), and compare it between builds to catch subscriptions that have been dropped accidentally in a refactoring.
+/// Call it once all activities have been created, usually at the end of the setup.
+///
+/// Type names are only available in debug mode, in release builds all types are listed as `<unknown type>`.
+///
+/// ### Example
+/// ```rust
+/// struct Player;
+/// struct Jump;
+/// let player = nuts::new_activity(Player);
+/// player.subscribe(|_, _: &Jump| {});
+/// player.subscribe(|_, _: &Jump| {});
+/// nuts::subscribe(|_: &Jump| {});
+///
+/// let table = nuts::subscription_table();
+/// assert_eq!(2, table.entries.len());
+/// assert_eq!(3, table.entries.iter().map(|entry| entry.handlers).sum::<usize>());
+/// println!("{}", table.to_json());
+/// ```
+pub fn subscription_table() -> SubscriptionTable {
+    nut::subscription_table()
+}
+
+/// Returns the trace of the message that is currently being handled.
+///
+/// Inside a subscription handler, this identifies the message the handler has been called for.
+/// Outside of handlers, `None` is returned.
+/// All messages published while handling a message are linked to it as their cause.
+pub fn current_trace() -> Option<TraceId> {
+    nut::current_trace()
+}
+
+/// Starts recording the causality tree of published messages, keeping at most `capacity` messages.
+///
+/// Older messages are dropped when the capacity is exceeded. Use a capacity of 0 to stop recording.
+/// Type names of recorded messages are only available in debug mode.
+///
+/// ### Example
+/// ```rust
+/// struct Click;
+/// struct OpenMenu;
+/// nuts::record_traces(100);
+/// nuts::subscribe(|_: &Click| nuts::publish(OpenMenu));
+/// nuts::subscribe(|_: &OpenMenu| {
+///     let this = nuts::current_trace().unwrap();
+///     // What chain of events lead to this message?
+///     let chain = nuts::trace_chain(this);
+///     assert_eq!(chain.len(), 2);
+///     assert_eq!(chain[1].cause, Some(chain[0].id));
+/// });
+/// nuts::publish(Click);
+/// ```
+pub fn record_traces(capacity: usize) {
+    nut::record_traces(capacity)
+}
+
+/// Lets Nuts publish a [`NutsStats`](struct.NutsStats.html) message after every `every` broadcasts, with the statistics of that interval.
+///
+/// Use 0 to stop publishing statistics. Changing the interval restarts the counters.
+///
+/// ### Example
+/// ```rust
+/// use nuts::NutsStats;
+/// struct Overlay { last: Option<NutsStats> }
+/// struct Tick;
+///
+/// nuts::publish_stats(60);
+/// let overlay = nuts::new_activity(Overlay { last: None });
+/// overlay.subscribe(|overlay, stats: &NutsStats| overlay.last = Some(stats.clone()));
+/// for _ in 0..60 {
+///     nuts::publish(Tick);
+/// }
+/// ```
+pub fn publish_stats(every: u64) {
+    nut::publish_stats(every)
+}
+
+/// Returns all recorded traces, in the order they have been published.
+///
+/// Each node links to its cause, together they form a forest with one tree per message published outside of handlers.
+/// Recording must be enabled with [`record_traces`](fn.record_traces.html).
+pub fn trace_tree() -> Vec<TraceNode> {
+    nut::trace_tree()
+}
+
+/// Returns the recorded chain of messages that lead to the given message, starting with the root cause.
+///
+/// The chain is cut short if some causes are no longer recorded.
+pub fn trace_chain(id: TraceId) -> Vec<TraceNode> {
+    nut::trace_chain(id)
 }
 
 #[cfg(debug_assertions)]
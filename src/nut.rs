@@ -4,21 +4,40 @@
 //! library developers as well as users if they want to understand more how this library works.
 
 pub(crate) mod activity;
+pub(crate) mod audit;
+pub(crate) mod clock;
+pub(crate) mod compact;
+pub(crate) mod config;
 pub(crate) mod exec;
 pub(crate) mod iac;
+pub(crate) mod main_thread;
+#[cfg(feature = "mutation-audit")]
+pub(crate) mod mutation_audit;
+pub(crate) mod remote;
+pub(crate) mod shared_audit;
+pub(crate) mod stats;
+pub(crate) mod timer;
+pub(crate) mod trace;
+pub(crate) mod usage_log;
 
 use crate::nut::exec::Deferred;
-use crate::nut::iac::subscription::OnDelete;
+use crate::nut::iac::subscription::{OnDelete, SubscriptionId};
 use crate::*;
 use crate::{debug::DebugTypeName, nut::exec::inchoate::InchoateActivityContainer};
 use core::any::Any;
 use core::sync::atomic::AtomicBool;
-use exec::fifo::ThreadLocalFifo;
+use exec::fifo::{PriorityFifo, ThreadLocalFifo};
+use exec::tasks::{TaskArena, TaskFuture};
 use iac::managed_state::*;
 use std::cell::RefCell;
+use timer::{TimerHandle, Timers};
+use trace::{TraceId, TraceNode, Tracer};
 
 use self::iac::{
-    publish::{BroadcastInfo, ResponseTracker},
+    publish::{
+        Aliases, BroadcastInfo, MessageMappers, MessagePool, Replay, ResponseTracker,
+        RetainedMessages, StartupBuffer,
+    },
     subscription::Subscriptions,
 };
 
@@ -46,12 +65,115 @@ struct Nut {
     /// (This restriction might change in the future)
     subscriptions: Subscriptions,
     /// FIFO queue for published messages and other events that cannot be processed immediately.
+    /// Urgent events, and all events they cause, skip ahead of the others.
     /// Atomically accessed mutably between closure dispatches.
-    deferred_events: ThreadLocalFifo<Deferred>,
+    deferred_events: PriorityFifo<Deferred>,
+    /// Activities created with `new_activity_with_enter` that have not received their initial `on_enter`, yet.
+    /// Moved to `deferred_events` right before the next broadcast is queued, or when the queue has been drained.
+    initial_enters: ThreadLocalFifo<UncheckedActivityId>,
+    /// Private messages to activities that are still being created by `new_activity_async`.
+    /// Released messages are moved to `deferred_events` together with `initial_enters`.
+    pending_activities: RefCell<activity::pending::PendingActivities>,
+    /// Copies of recently published messages, for types configured with `retain_last`.
+    /// Atomically accessed when a broadcast is queued and when retained messages are replayed.
+    retained_messages: RefCell<RetainedMessages>,
+    /// Published messages without subscribers, held back until a subscriber appears or `start` is called.
+    /// Atomically accessed when a broadcast is dispatched and when a subscription is added.
+    startup_buffer: RefCell<StartupBuffer>,
+    /// Conversions from old to new message types, registered with `alias`.
+    /// Atomically accessed when a message is published.
+    aliases: RefCell<Aliases>,
+    /// Transformations applied to published messages, registered with `map_messages`.
+    /// Atomically accessed when a message is published.
+    message_mappers: RefCell<MessageMappers>,
+    /// Messages sent from other threads with a `RemotePublisher`, published by `process_remote`.
+    /// Shared with other threads, hence it is synchronized instead of atomically accessed.
+    remote_queue: std::sync::Arc<remote::RemoteQueue>,
+    /// Activities that answer typed requests, registered with `respond`, by the type of the request message.
+    /// Atomically accessed when a responder is registered and when a request is sent.
+    responders: RefCell<std::collections::HashMap<core::any::TypeId, iac::publish::Responder>>,
+    /// Global names of activities, registered with `register_name`.
+    /// Atomically accessed when a name is registered or looked up.
+    names: RefCell<std::collections::HashMap<String, UncheckedActivityId>>,
+    /// Boxes of messages without drop glue, kept for reuse to avoid heap allocations when publishing.
+    /// Atomically accessed when boxing a message and after a broadcast.
+    message_pool: RefCell<MessagePool>,
+    /// Domain stores that must be applied as soon as the current handler returns.
+    /// Atomically accessed mutably between handler calls.
+    ordered_domain_stores: ThreadLocalFifo<DomainStoreData>,
+    /// Closures that are called when a domain object has been accessed mutably.
+    /// Mutable access only between closure dispatches, while executing, or when no broadcast is ongoing.
+    domain_observers: RefCell<Vec<DomainObserver>>,
+    /// Recurring publishers registered with `every`.
+    /// Atomically accessed when timers are added, removed, or when time advances.
+    timers: RefCell<Timers>,
+    /// Futures attached to activities, see `attach_future`.
+    /// Atomically accessed when a task is added, removed, or polled.
+    tasks: RefCell<TaskArena>,
+    /// Activities that should be evicted when idle, registered with `evict_when_idle` and moved to the activities at the next point of quiescence.
+    /// Atomically accessed when a registration is added and at points of quiescence.
+    pending_codecs: RefCell<Vec<(UncheckedActivityId, activity::Codec)>>,
+    /// Number of broadcasts dispatched so far, which measures how long activities have been idle.
+    broadcast_count: std::cell::Cell<u64>,
+    /// Remaining handlers of the message that is currently delivered and whether it has been consumed, see `MessageControl`.
+    /// Only tracked for topics with handlers that may consume the message.
+    delivery: std::cell::Cell<iac::publish::Delivery>,
+    /// Message types that may only be published with a `PublishToken`.
+    /// Atomically accessed when a token is created and when a message is published.
+    publish_restrictions: RefCell<iac::publish::PublishRestrictions>,
+    /// Counters for the statistics published with `nuts::publish_stats`.
+    /// Updated once per broadcast and handler call.
+    stats: stats::StatsCollector,
+    /// Message types that handlers must not modify or own, see `nuts::protect_message`.
+    /// Atomically accessed when a type is protected and when a handler is registered.
+    protected_messages: RefCell<iac::immutable::ProtectedMessages>,
+    /// Source of time for handler measurements, see `nuts::set_clock`.
+    /// Atomically accessed when the clock is set or read.
+    clock: RefCell<clock::SelectedClock>,
+    /// Codecs of message types, see `nuts::register_codec`.
+    /// Atomically accessed when a codec is registered or looked up.
+    message_codecs: RefCell<iac::codec::CodecRegistry>,
+    /// Temporary values shared by handlers, see `nuts::scratch`. Cleared when the queue has been drained.
+    /// Atomically accessed by the methods of `Scratch`.
+    scratch: RefCell<exec::scratch::ScratchMap>,
+    /// Set while tasks are polled, to prevent polling recursively
+    polling_tasks: std::cell::Cell<bool>,
+    /// Broadcasts held back by `PauseQueue`, `None` if the queue is not paused.
+    /// Atomically accessed when a deferred event is executed.
+    paused_events: RefCell<Option<Vec<Deferred>>>,
+    /// Number of events that may still be executed, `None` means unlimited. Set by `process_at_most` and for each drain if a drain limit is configured.
+    processing_budget: std::cell::Cell<Option<usize>>,
+    /// Phases of activities and published messages that still have to be delivered to them, see `run_frame`.
+    /// Atomically accessed when a broadcast is dispatched and between broadcasts in `run_frame`.
+    frames: RefCell<exec::frame::Frames>,
+    /// Families of message types for subscriptions to trait objects, see `subscribe_dyn`.
+    /// Atomically accessed when a family member or handler is registered.
+    dyn_families: RefCell<iac::dynamic::DynFamilies>,
+    /// Domains required by domained subscriptions, checked by `validate_domains`.
+    /// Atomically accessed when a domained subscription is registered and during validation.
+    domained_subscriptions: RefCell<Vec<(DomainId, DebugTypeName)>>,
+    /// Set once `validate_domains` succeeded, afterwards domained subscriptions are checked when they are registered.
+    domains_validated: std::cell::Cell<bool>,
+    /// Double buffers that have been written to during the current broadcast.
+    /// Atomically accessed when a buffer is written and after each broadcast.
+    pending_swaps: RefCell<Vec<std::rc::Rc<dyn iac::double_buffer::Swap>>>,
+    /// Called first when the nut is destroyed at thread exit, see `set_teardown_hook`.
+    teardown_hook: RefCell<Option<Box<dyn FnOnce()>>>,
+    /// Called with the exit code after the teardown triggered by `request_exit`.
+    exit_handler: RefCell<Option<exec::teardown::ExitHandler>>,
+    /// Set once activities and domains have been torn down, which happens only once.
+    torn_down: std::cell::Cell<bool>,
+    /// Settings applied with `configure`, read whenever they are needed.
+    config: std::cell::Cell<config::Config>,
+    /// Set when the first message is published, afterwards the configuration cannot be changed anymore.
+    published: std::cell::Cell<bool>,
     /// Tracks awaited responses, which are pending futures.
-    /// Used when creating new futures (NutsResponse) and when polling the same.
-    /// Atomically accessed in with_response_tracker_mut() only.
+    /// Used when creating new futures (`NutsResponse`) and when polling the same.
+    /// Atomically accessed in `with_response_tracker_mut()` only.
     response_tracker: RefCell<ResponseTracker>,
+    /// Assigns trace IDs to broadcasts and optionally records the causality tree.
+    /// Atomically accessed when a broadcast is queued and while it is dispatched.
+    tracer: RefCell<Tracer>,
     /// A flag that marks if a broadcast is currently on-going
     executing: AtomicBool,
     /// When executing a broadcast, `activities` and `managed_state` is not available.
@@ -60,6 +182,12 @@ struct Nut {
     /// (Note: Adding subscriptions does not require additional structure because they will
     /// be queued and only executed after the activity is available anyway)
     inchoate_activities: RefCell<InchoateActivityContainer>,
+    /// Activities deleted with `ActivityId::delete`, whose slots are released for reuse once the deletion has been processed.
+    /// Can be accessed at any time, it is never borrowed for longer than a single call.
+    slots_to_release: RefCell<Vec<UncheckedActivityId>>,
+    /// Handlers that use IDs of activities in other domains, see `capture_warnings`.
+    #[cfg(debug_assertions)]
+    capture_warnings: RefCell<Vec<iac::subscription::CaptureWarning>>,
     /// For debugging messages
     #[allow(dead_code)]
     active_activity_name: std::cell::Cell<Option<DebugTypeName>>,
@@ -68,14 +196,31 @@ struct Nut {
 /// A method that can be called by the `ActivityManager`.
 /// These handlers are created by the library and not part of the public interface.
 pub(crate) type Handler = Box<dyn Fn(&mut ActivityContainer, &mut ManagedState)>;
+/// A handler that is shared between a subscription and other places that need to call it
+pub(crate) type SharedHandler = std::rc::Rc<dyn Fn(&mut ActivityContainer, &mut ManagedState)>;
 
 impl Nut {
-    fn new() -> Self {
-        Self {
-            activities: RefCell::new(ActivityContainer::new()),
-            ..Default::default()
+    /// Records that a subscription needs the domain, to be checked by `validate_domains`
+    fn require_domain(&self, domain: DomainId, activity: DebugTypeName) {
+        if self.domains_validated.get() {
+            assert!(
+                domain.index().is_some(),
+                "Domained subscription on activity {} without a domain, after domains have been validated.",
+                activity.name()
+            );
+        } else {
+            self.domained_subscriptions
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .push((domain, activity));
         }
     }
+    fn new() -> Self {
+        main_thread::check();
+        let nut = Self::default();
+        *nut.activities.borrow_mut() = ActivityContainer::new();
+        nut
+    }
     fn quiescent(&self) -> bool {
         !self.executing.load(std::sync::atomic::Ordering::Relaxed)
     }
@@ -86,8 +231,7 @@ impl Nut {
                 .expect(IMPOSSIBLE_ERR_MSG)
                 .add_on_delete(id, subscription);
         } else {
-            self.deferred_events
-                .push(Deferred::OnDeleteSubscription(id, subscription))
+            self.defer_bookkeeping(Deferred::OnDeleteSubscription(id, subscription))
         }
     }
     pub(crate) fn with_response_tracker_mut<T>(f: impl FnOnce(&mut ResponseTracker) -> T) -> T {
@@ -96,9 +240,45 @@ impl Nut {
                 .response_tracker
                 .try_borrow_mut()
                 .expect(IMPOSSIBLE_ERR_MSG);
-            f(&mut *response_tracker)
+            f(&mut response_tracker)
         })
     }
+    /// Like `with_response_tracker_mut` but does nothing if the Nut has already been dropped
+    pub(crate) fn try_with_response_tracker_mut(f: impl FnOnce(&mut ResponseTracker)) {
+        let _ = NUT.try_with(|nut| {
+            if let Ok(mut response_tracker) = nut.response_tracker.try_borrow_mut() {
+                f(&mut response_tracker)
+            }
+        });
+    }
+}
+
+pub(crate) fn new_activity_with_enter<A>(activity: A, domain_index: DomainId) -> ActivityId<A>
+where
+    A: Activity,
+{
+    let a = new_activity(activity, domain_index, LifecycleStatus::Active);
+    NUT.with(|nut| nut.initial_enters.push(a.into()));
+    a
+}
+
+pub(crate) fn begin_setup(id: UncheckedActivityId) -> iac::subscription::setup::SetupGuard {
+    NUT.with(|nut| iac::subscription::setup::SetupGuard::new(nut.setup_checkpoint(id)))
+}
+
+pub(crate) fn rollback_setup(checkpoint: &iac::subscription::setup::SetupCheckpoint) {
+    NUT.with(|nut| nut.rollback_setup(checkpoint))
+}
+
+pub(crate) fn with_pending_activities<T>(
+    f: impl FnOnce(&mut activity::pending::PendingActivities) -> T,
+) -> T {
+    NUT.with(|nut| {
+        f(&mut nut
+            .pending_activities
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG))
+    })
 }
 
 pub(crate) fn new_activity<A>(
@@ -123,16 +303,16 @@ where
                 .prepare(domain_index);
             // Make sure that length of activities is available without locking activities.
             // Again, a bit ugly but performance is secondary in this call.
-            nut.inchoate_activities
-                .try_borrow_mut()
-                .expect(IMPOSSIBLE_ERR_MSG)
-                .inc_offset();
-            nut.activities
-                .try_borrow_mut()
-                .expect(IMPOSSIBLE_ERR_MSG)
-                .add(activity, domain_index, status)
+            let mut activities = nut.activities.try_borrow_mut().expect(IMPOSSIBLE_ERR_MSG);
+            if !activities.has_free_slot() {
+                nut.inchoate_activities
+                    .try_borrow_mut()
+                    .expect(IMPOSSIBLE_ERR_MSG)
+                    .inc_offset();
+            }
+            activities.add(activity, domain_index, status)
         } else {
-            nut.deferred_events.push(Deferred::FlushInchoateActivities);
+            nut.defer_bookkeeping(Deferred::FlushInchoateActivities);
             let a = nut
                 .inchoate_activities
                 .try_borrow_mut()
@@ -152,9 +332,103 @@ where
 }
 
 pub(crate) fn publish_custom<MSG: Any>(a: MSG) {
+    #[cfg(feature = "message-arena")]
+    if !core::mem::needs_drop::<MSG>() {
+        return publish_pooled(a);
+    }
     NUT.with(|nut| nut.broadcast(BroadcastInfo::global(a, Topic::public_message::<MSG>())))
 }
 
+pub(crate) fn register_codec<MSG: Any>(codec: impl iac::codec::MessageCodec<MSG> + 'static) {
+    with_message_codecs(|codecs| codecs.register(codec))
+}
+
+pub(crate) fn with_message_codecs<T>(f: impl FnOnce(&mut iac::codec::CodecRegistry) -> T) -> T {
+    NUT.with(|nut| {
+        f(&mut nut
+            .message_codecs
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG))
+    })
+}
+
+pub(crate) fn encode_message<MSG: Any>(msg: &MSG) -> Result<iac::codec::EncodedMessage, Error> {
+    NUT.with(|nut| nut.encode_message(core::any::TypeId::of::<MSG>(), msg))
+}
+
+pub(crate) fn publish_encoded(msg: &iac::codec::EncodedMessage) -> Result<(), Error> {
+    NUT.with(|nut| nut.publish_encoded(msg.type_id, &msg.bytes))
+}
+
+pub(crate) fn publish_authorized<MSG: Any>(a: MSG) {
+    NUT.with(|nut| {
+        nut.broadcast(BroadcastInfo::global(a, Topic::public_message::<MSG>()).authorize())
+    })
+}
+
+pub(crate) fn restrict_publish<MSG: Any>() -> Result<iac::publish::PublishToken<MSG>, crate::Error>
+{
+    NUT.with(|nut| nut.restrict_publish())
+}
+
+/// Publishes the message and panics unless it has been processed completely when this returns.
+pub(crate) fn publish_to_completion<MSG: Any>(a: MSG) {
+    NUT.with(|nut| {
+        assert!(
+            !nut.executing.load(std::sync::atomic::Ordering::Relaxed),
+            "Simulation steps must not be started from inside a subscription handler."
+        );
+        nut.broadcast(BroadcastInfo::global(a, Topic::public_message::<MSG>()));
+        let paused = nut
+            .paused_events
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .is_some();
+        assert!(
+            !paused && nut.deferred_events.len() == 0,
+            "The simulation step could not complete because the queue is paused."
+        );
+    })
+}
+
+/// Publishes the message produced by `f`, unless the thread-local nut is not accessible anymore.
+pub(crate) fn lazy_publish<MSG: Any>(f: impl FnOnce() -> MSG) -> bool {
+    NUT.try_with(|nut| nut.broadcast(BroadcastInfo::global(f(), Topic::public_message::<MSG>())))
+        .is_ok()
+}
+
+pub(crate) fn publish_isolated<MSG: Any + Clone>(msg: MSG) {
+    NUT.with(|nut| {
+        nut.broadcast(BroadcastInfo::global_isolated(
+            msg,
+            Topic::public_message::<MSG>(),
+        ))
+    })
+}
+
+pub(crate) fn publish_group(broadcasts: Vec<iac::publish::BroadcastInfo>) {
+    NUT.with(|nut| nut.broadcast_group(broadcasts))
+}
+
+pub(crate) fn publish_copy<MSG: Any + Copy>(msg: MSG) {
+    publish_pooled(msg)
+}
+
+/// Publishes a message without drop glue in a box taken from the message pool
+fn publish_pooled<MSG: Any>(msg: MSG) {
+    NUT.with(|nut| {
+        let boxed = nut
+            .message_pool
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .boxed(msg);
+        nut.broadcast(BroadcastInfo::global_pooled::<MSG>(
+            boxed,
+            Topic::public_message::<MSG>(),
+        ))
+    })
+}
+
 pub(crate) fn send_custom<RECV: Any, MSG: Any>(a: MSG) {
     NUT.with(|nut| {
         nut.broadcast(BroadcastInfo::local_by_type::<RECV, MSG>(
@@ -174,11 +448,243 @@ pub(crate) fn send_custom_by_id<MSG: Any>(msg: MSG, id: UncheckedActivityId) {
     })
 }
 
-pub(crate) async fn publish_custom_and_await<A: Any>(a: A) {
-    NUT.with(move |nut| nut.publish_and_await(a)).await;
+pub(crate) fn alias<OLD: Any, NEW: Any>(f: impl Fn(OLD) -> NEW + 'static) {
+    NUT.with(|nut| {
+        nut.aliases
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .add(f)
+    });
+}
+
+pub(crate) fn alias_report() -> Vec<iac::publish::AliasReport> {
+    NUT.with(|nut| nut.aliases.try_borrow().expect(IMPOSSIBLE_ERR_MSG).report())
+}
+
+pub(crate) fn map_messages<MSG: Any>(f: impl Fn(&mut MSG) + 'static) {
+    NUT.with(|nut| {
+        nut.message_mappers
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .set(f)
+    });
+}
+
+pub(crate) fn register_name(name: String, id: UncheckedActivityId) {
+    NUT.with(|nut| {
+        nut.names
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .insert(name, id)
+    });
+}
+
+pub(crate) fn send_custom_by_name<MSG: Any>(name: &str, msg: MSG) {
+    let id = NUT.with(|nut| {
+        nut.names
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .get(name)
+            .copied()
+    });
+    if let Some(id) = id {
+        send_custom_by_id(msg, id)
+    } else {
+        #[cfg(debug_assertions)]
+        debug_print!(
+            "No activity registered under the name {:?}, dropping message of type {}",
+            name,
+            std::any::type_name::<MSG>()
+        );
+    }
+}
+
+pub(crate) fn every<MSG: Any>(
+    period: std::time::Duration,
+    f: impl Fn() -> MSG + 'static,
+) -> TimerHandle {
+    add_timer(period, std::rc::Rc::new(move || publish_custom(f())))
+}
+
+pub(crate) fn add_timer(
+    period: std::time::Duration,
+    publish: std::rc::Rc<dyn Fn()>,
+) -> TimerHandle {
+    NUT.with(|nut| {
+        nut.timers
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .add(period, publish)
+    })
+}
+
+pub(crate) fn cancel_timer(id: TimerHandle) {
+    NUT.with(|nut| {
+        nut.timers
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .remove(id)
+    });
+}
+
+pub(crate) fn on_idle(after: std::time::Duration, f: impl Fn() + 'static) -> TimerHandle {
+    NUT.with(|nut| {
+        nut.timers
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .add_idle(after, std::rc::Rc::new(f))
+    })
+}
+
+pub(crate) fn advance_time(dt: std::time::Duration) {
+    let due = NUT.with(|nut| {
+        nut.timers
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .advance(dt, nut.broadcast_count.get())
+    });
+    for publish in due {
+        publish();
+    }
+    NUT.with(|nut| {
+        nut.timers
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .observe(nut.broadcast_count.get())
+    });
+}
+
+pub(crate) fn attach_future(
+    owner: UncheckedActivityId,
+    future: TaskFuture,
+    policy: exec::tasks::TaskPolicy,
+) -> exec::tasks::TaskHandle {
+    NUT.with(|nut| {
+        let handle = nut
+            .tasks
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .add(owner, future, policy);
+        if nut.quiescent() {
+            nut.poll_tasks();
+        }
+        handle
+    })
+}
+
+pub(crate) fn dump_state() -> String {
+    NUT.with(|nut| nut.unchecked_dump_state())
+}
+
+pub(crate) fn swap_after_broadcast(buffers: std::rc::Rc<dyn iac::double_buffer::Swap>) {
+    NUT.with(|nut| {
+        if nut.quiescent() {
+            buffers.swap();
+        } else {
+            nut.pending_swaps
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .push(buffers);
+        }
+    });
+}
+
+pub(crate) fn set_teardown_hook(f: Box<dyn FnOnce()>) {
+    NUT.with(|nut| {
+        *nut.teardown_hook
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG) = Some(f)
+    });
+}
+
+pub(crate) fn set_exit_handler(f: exec::teardown::ExitHandler) {
+    NUT.with(|nut| *nut.exit_handler.try_borrow_mut().expect(IMPOSSIBLE_ERR_MSG) = Some(f));
+}
+
+pub(crate) fn request_exit(code: i32) {
+    NUT.with(|nut| nut.request_exit(code));
+}
+
+pub(crate) fn poll_tasks() {
+    NUT.with(|nut| nut.poll_tasks());
+}
+
+pub(crate) fn defer(f: Box<dyn FnOnce()>) {
+    NUT.with(|nut| {
+        if nut.executing.load(std::sync::atomic::Ordering::Relaxed) {
+            nut.defer_bookkeeping(Deferred::Closure(f));
+        } else {
+            f();
+        }
+    })
+}
+
+pub(crate) fn set_dispatch_weight(topic: Topic, id: UncheckedActivityId, weight: i64) {
+    defer(Box::new(move || {
+        NUT.with(|nut| nut.subscriptions.set_weight(topic, id, weight))
+    }))
+}
+
+pub(crate) fn unsubscribe(sub: SubscriptionId) {
+    defer(Box::new(move || {
+        NUT.with(|nut| {
+            nut.subscriptions.retain(|_, id| id != sub);
+            nut.dyn_families
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .retain(|_, id| id != sub);
+        })
+    }))
+}
+
+/// Registers all subscriptions added by `f` under one ID, see `Subscriptions::grouped`
+pub(crate) fn group_subscriptions(f: impl FnOnce()) -> SubscriptionId {
+    NUT.with(|nut| nut.subscriptions.grouped(f))
+}
+
+pub(crate) fn with_scratch<T>(f: impl FnOnce(&mut exec::scratch::ScratchMap) -> T) -> T {
+    NUT.with(|nut| f(&mut nut.scratch.try_borrow_mut().expect(IMPOSSIBLE_ERR_MSG)))
 }
 
-pub(crate) fn register_no_activity<F, MSG>(f: F)
+pub(crate) fn delivery() -> iac::publish::Delivery {
+    NUT.with(|nut| nut.delivery.get())
+}
+
+pub(crate) fn consume_message() {
+    NUT.with(|nut| nut.consume_message())
+}
+
+pub(crate) fn poll_futures() -> bool {
+    NUT.with(|nut| nut.poll_tasks_once())
+}
+
+pub(crate) fn publish_custom_and_await<A: Any>(
+    a: A,
+    mode: iac::publish::ResponseMode,
+    urgent: bool,
+) -> impl core::future::Future<Output = ()> + Unpin {
+    NUT.with(move |nut| nut.publish_and_await(a, mode, urgent))
+}
+
+pub(crate) fn ask<REQ: Any, RESP: Any>(
+    id: UncheckedActivityId,
+    req: REQ,
+) -> iac::publish::Reply<RESP> {
+    NUT.with(move |nut| nut.ask(id, req))
+}
+
+pub(crate) fn request<REQ: Any, RESP: Any>(req: REQ) -> iac::publish::Reply<RESP> {
+    NUT.with(move |nut| nut.request(req))
+}
+
+/// Registers the subscription as the responder for `REQ` and `RESP`, the previous responder is unsubscribed.
+pub(crate) fn set_responder<REQ: Any, RESP: Any>(id: UncheckedActivityId, sub: SubscriptionId) {
+    if let Some(previous) = NUT.with(|nut| nut.set_responder::<REQ, RESP>(id, sub)) {
+        unsubscribe(previous);
+    }
+}
+
+pub(crate) fn register_no_activity<F, MSG>(f: F) -> SubscriptionId
 where
     F: Fn(&MSG) + 'static,
     MSG: Any,
@@ -187,10 +693,14 @@ where
         let closure = ManagedState::pack_closure_no_activity::<_, MSG>(f);
         let topic = Topic::public_message::<MSG>();
         let id = NotAnActivity::id();
-        nut.push_closure(topic, id, closure);
-    });
+        nut.push_closure(topic, id, closure)
+    })
 }
-pub(crate) fn register<A, F, MSG>(id: ActivityId<A>, f: F, filter: SubscriptionFilter)
+pub(crate) fn register<A, F, MSG>(
+    id: ActivityId<A>,
+    f: F,
+    filter: SubscriptionFilter,
+) -> SubscriptionId
 where
     A: Activity,
     F: Fn(&mut A, &MSG) + 'static,
@@ -199,10 +709,262 @@ where
     NUT.with(|nut| {
         let closure = ManagedState::pack_closure::<_, _, MSG>(f, id, filter);
         let topic = Topic::public_message::<MSG>();
-        nut.push_closure(topic, id, closure);
+        nut.push_closure(topic, id, closure)
+    })
+}
+pub(crate) fn register_with_params<A, F, MSG, P>(
+    id: ActivityId<A>,
+    f: F,
+    filter: SubscriptionFilter,
+) -> SubscriptionId
+where
+    A: Activity,
+    F: iac::params::HandlerFn<A, MSG, P> + 'static,
+    MSG: Any,
+{
+    NUT.with(|nut| {
+        let closure = ManagedState::pack_closure_with_params::<_, _, MSG, P>(f, id, filter);
+        let topic = Topic::public_message::<MSG>();
+        if F::CLAIMS_MESSAGE {
+            nut.push_handler(
+                topic,
+                id.into(),
+                closure,
+                DebugTypeName::new::<A>(),
+                iac::subscription::Access::Claim,
+                nut.subscriptions.next_id(),
+            )
+        } else if F::MUTATES_MESSAGE {
+            nut.push_mutating_closure(topic, id, closure)
+        } else {
+            nut.push_closure(topic, id, closure)
+        }
+    })
+}
+pub(crate) fn register_and_replay<A, F, MSG>(
+    id: ActivityId<A>,
+    f: F,
+    filter: SubscriptionFilter,
+) -> SubscriptionId
+where
+    A: Activity,
+    F: Fn(&mut A, &MSG) + 'static,
+    MSG: Any,
+{
+    NUT.with(|nut| {
+        let shared: SharedHandler = ManagedState::pack_closure::<_, _, MSG>(f, id, filter).into();
+        let handler = shared.clone();
+        let closure = Box::new(
+            move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
+                handler(activities, managed_state)
+            },
+        );
+        let sub = nut.push_closure(Topic::public_message::<MSG>(), id, closure);
+        let replay = Replay::new::<A>(core::any::TypeId::of::<MSG>(), shared, sub);
+        nut.deferred_events.push(Deferred::Replay(replay));
+        nut.catch_up_deferred_to_quiescence();
+        sub
+    })
+}
+/// True iff a broadcast of `MSG` is queued but has not been dispatched, yet.
+pub(crate) fn is_queued<MSG: Any>() -> bool {
+    let topic = Topic::public_message::<MSG>();
+    NUT.with(|nut| {
+        nut.deferred_events.any(|event| match event {
+            Deferred::Broadcast(b) | Deferred::BroadcastAwaitingResponse(b, _) => {
+                b.topic() == topic
+            }
+            _ => false,
+        })
+    })
+}
+#[cfg(feature = "log")]
+pub(crate) fn set_log_thresholds(thresholds: usage_log::LogThresholds) {
+    usage_log::set_thresholds(thresholds)
+}
+pub(crate) fn process_at_most(n: usize) -> usize {
+    NUT.with(|nut| nut.process_at_most(n))
+}
+pub(crate) fn channel() -> remote::RemotePublisher {
+    NUT.with(|nut| remote::RemotePublisher::new(&nut.remote_queue))
+}
+pub(crate) fn process_remote() -> usize {
+    let messages = NUT.with(|nut| nut.remote_queue.drain());
+    let count = messages.len();
+    for publish in messages {
+        publish();
+    }
+    count
+}
+pub(crate) fn domains() -> Vec<iac::managed_state::DomainInfo> {
+    NUT.with(|nut| {
+        let activity_counts = nut
+            .activities
+            .try_borrow()
+            .expect("Domains cannot be inspected while a broadcast is ongoing.")
+            .count_per_domain();
+        nut.managed_state
+            .try_borrow()
+            .expect("Domains cannot be inspected while a broadcast is ongoing.")
+            .domain_infos(&activity_counts)
+    })
+}
+
+pub(crate) fn validate_domains() -> Result<(), Error> {
+    NUT.with(|nut| {
+        assert!(
+            !nut.executing.load(std::sync::atomic::Ordering::Relaxed),
+            "validate_domains must not be called from inside a subscription handler."
+        );
+        let mut managed_state = nut
+            .managed_state
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        let mut required = nut
+            .domained_subscriptions
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        if let Some((_, activity)) = required
+            .iter()
+            .find(|(domain, _)| !managed_state.is_prepared(*domain))
+        {
+            return Err(Error::MissingDomain(activity.name()));
+        }
+        required.clear();
+        managed_state.set_domains_validated();
+        nut.domains_validated.set(true);
+        Ok(())
+    })
+}
+pub(crate) fn register_domain_migration<OLD: Any, NEW: Any>(f: impl Fn(OLD) -> NEW + 'static) {
+    NUT.with(|nut| {
+        nut.managed_state
+            .try_borrow_mut()
+            .expect("Cannot register a domain migration from inside a subscription handler.")
+            .add_migration(DomainMigration::new(f))
+    })
+}
+pub(crate) fn skipped_domained_calls() -> u64 {
+    NUT.with(|nut| {
+        nut.managed_state
+            .try_borrow()
+            .expect("Cannot count skipped calls from inside a subscription handler.")
+            .skipped_domained_calls()
+    })
+}
+pub(crate) fn configure_responses(config: iac::publish::ResponseConfig) {
+    Nut::with_response_tracker_mut(|rt| rt.configure(config))
+}
+pub(crate) fn response_stats() -> iac::publish::ResponseStats {
+    Nut::with_response_tracker_mut(|rt| rt.stats())
+}
+pub(crate) fn configure(builder: config::ConfigBuilder) -> Result<(), Error> {
+    NUT.with(|nut| nut.configure(builder))
+}
+#[cfg(feature = "message-arena")]
+pub(crate) fn arena_usage() -> iac::publish::ArenaUsage {
+    NUT.with(|nut| {
+        nut.message_pool
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .usage()
+    })
+}
+pub(crate) fn compact() -> compact::CompactionReport {
+    NUT.with(|nut| nut.compact())
+}
+pub(crate) fn run_frame() {
+    NUT.with(|nut| nut.run_frame())
+}
+pub(crate) fn set_frame_phases(phases: Vec<exec::frame::Phase>) {
+    NUT.with(|nut| {
+        nut.frames
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .set_phases(phases)
+    })
+}
+pub(crate) fn set_phase(id: UncheckedActivityId, phase: exec::frame::Phase) {
+    NUT.with(|nut| {
+        nut.frames
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .set_affinity(id.index, phase)
+    })
+}
+pub(crate) fn subscriber_count<MSG: Any>() -> usize {
+    NUT.with(|nut| nut.subscriptions.count(&Topic::public_message::<MSG>()))
+}
+pub(crate) fn retain_last<MSG: Any + Clone>(n: usize) {
+    NUT.with(|nut| {
+        nut.retained_messages
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .set_capacity::<MSG>(n)
     });
 }
-pub(crate) fn register_mut<A, F, MSG>(id: ActivityId<A>, f: F, filter: SubscriptionFilter)
+pub(crate) fn buffer_startup_messages(capacity: usize) {
+    NUT.with(|nut| {
+        nut.startup_buffer
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .enable(capacity)
+    });
+}
+pub(crate) fn start() {
+    let _unclaimed = NUT.with(|nut| {
+        nut.startup_buffer
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .finish()
+    });
+    #[cfg(debug_assertions)]
+    for msg in &_unclaimed {
+        debug_print!("Dropping {:?}, nobody subscribed to it during startup", msg);
+    }
+}
+pub(crate) fn register_buffered<A, F, MSG>(
+    id: ActivityId<A>,
+    f: F,
+    policy: InactivePolicy,
+) -> SubscriptionId
+where
+    A: Activity,
+    F: Fn(&mut A, &MSG) + 'static,
+    MSG: Any + Clone,
+{
+    NUT.with(|nut| {
+        let (on_msg, on_enter) = ManagedState::pack_closure_buffered::<_, _, MSG>(f, id, policy);
+        nut.subscriptions.grouped(|| {
+            nut.push_closure(Topic::public_message::<MSG>(), id, on_msg);
+            nut.push_closure(Topic::enter(), id, on_enter);
+        })
+    })
+}
+pub(crate) fn register_parallel<A, F, MSG>(id: ActivityId<A>, f: F) -> SubscriptionId
+where
+    A: ParallelSafe,
+    F: Fn(&mut A, &MSG) + Send + Sync + 'static,
+    MSG: Any + Sync,
+{
+    NUT.with(|nut| {
+        let closure = ManagedState::pack_closure::<_, _, MSG>(f, id, Default::default());
+        let topic = Topic::public_message::<MSG>();
+        nut.push_handler(
+            topic,
+            id.into(),
+            closure,
+            DebugTypeName::new::<A>(),
+            iac::subscription::Access::Parallel,
+            nut.subscriptions.next_id(),
+        )
+    })
+}
+pub(crate) fn register_mut<A, F, MSG>(
+    id: ActivityId<A>,
+    f: F,
+    filter: SubscriptionFilter,
+) -> SubscriptionId
 where
     A: Activity,
     F: Fn(&mut A, &mut MSG) + 'static,
@@ -211,10 +973,75 @@ where
     NUT.with(|nut| {
         let closure = ManagedState::pack_closure_mut::<_, _, MSG>(f, id, filter);
         let topic = Topic::public_message::<MSG>();
-        nut.push_closure(topic, id, closure);
-    });
+        nut.push_mutating_closure(topic, id, closure)
+    })
+}
+pub(crate) fn register_when<A, F, MSG, P>(
+    id: ActivityId<A>,
+    f: F,
+    predicate: P,
+    filter: SubscriptionFilter,
+) -> SubscriptionId
+where
+    A: Activity,
+    F: Fn(&mut A, &MSG) + 'static,
+    MSG: Any,
+    P: Fn(&MSG) -> bool + 'static,
+{
+    NUT.with(|nut| {
+        let closure = ManagedState::pack_closure::<_, _, MSG>(f, id, filter);
+        let closure = ManagedState::guard_closure(closure, predicate);
+        let topic = Topic::public_message::<MSG>();
+        nut.push_closure(topic, id, closure)
+    })
 }
-pub(crate) fn register_owned<A, F, MSG>(id: ActivityId<A>, f: F, filter: SubscriptionFilter)
+pub(crate) fn register_domained_when<A, F, MSG, P>(
+    id: ActivityId<A>,
+    f: F,
+    predicate: P,
+    filter: SubscriptionFilter,
+) -> SubscriptionId
+where
+    A: Activity,
+    F: Fn(&mut A, &mut DomainState, &MSG) + 'static,
+    MSG: Any,
+    P: Fn(&MSG) -> bool + 'static,
+{
+    NUT.with(|nut| {
+        nut.require_domain(id.domain_index, DebugTypeName::new::<A>());
+        let closure = ManagedState::pack_domained_closure::<_, _, MSG>(f, id, filter);
+        let closure = ManagedState::guard_closure(closure, predicate);
+        let topic = Topic::public_message::<MSG>();
+        nut.push_closure(topic, id, closure)
+    })
+}
+pub(crate) fn register_dyn<A, T, F>(
+    id: ActivityId<A>,
+    f: F,
+    filter: SubscriptionFilter,
+) -> SubscriptionId
+where
+    A: Activity,
+    T: ?Sized + 'static,
+    F: Fn(&mut A, &T) + 'static,
+{
+    NUT.with(|nut| {
+        let handler = std::rc::Rc::new(move |activities: &mut ActivityContainer, msg: &T| {
+            if let Some(a) = activities.filtered(id, &filter) {
+                f(a, msg)
+            }
+        });
+        nut.register_dyn_handler::<T>(id.into(), DebugTypeName::new::<A>(), handler)
+    })
+}
+pub(crate) fn register_dyn_member<T: ?Sized + 'static, MSG: Any>(cast: fn(&MSG) -> &T) {
+    NUT.with(|nut| nut.register_dyn_member(cast));
+}
+pub(crate) fn register_owned<A, F, MSG>(
+    id: ActivityId<A>,
+    f: F,
+    filter: SubscriptionFilter,
+) -> SubscriptionId
 where
     A: Activity,
     F: Fn(&mut A, MSG) + 'static,
@@ -223,7 +1050,27 @@ where
     NUT.with(|nut| {
         let closure = ManagedState::pack_closure_owned::<_, _, MSG>(f, id, filter);
         let topic = Topic::private_message::<MSG>();
-        nut.push_closure(topic, id, closure);
+        nut.push_closure(topic, id, closure)
+    })
+}
+
+pub(crate) fn register_owned_once<A, F, MSG>(id: ActivityId<A>, f: F) -> SubscriptionId
+where
+    A: Activity,
+    F: FnOnce(&mut A, MSG) + 'static,
+    MSG: Any,
+{
+    NUT.with(|nut| {
+        let closure = ManagedState::pack_closure_owned_once::<_, _, MSG>(f, id, Default::default());
+        let topic = Topic::private_message::<MSG>();
+        nut.push_closure(topic, id, closure)
+    })
+}
+/// Only called from inside handlers, the channel is removed once the handler returned
+pub(crate) fn remove_private_channel<MSG: Any>(id: UncheckedActivityId) {
+    NUT.with(|nut| {
+        let topic = Topic::private_message::<MSG>();
+        nut.defer_bookkeeping(Deferred::RemovePrivateChannel(topic, id));
     });
 }
 
@@ -233,54 +1080,67 @@ pub(crate) fn register_no_payload<A, F>(
     f: F,
     topic: Topic,
     filter: SubscriptionFilter,
-) where
+) -> SubscriptionId
+where
     A: Activity,
     F: Fn(&mut A) + 'static,
 {
     NUT.with(|nut| {
         let closure = ManagedState::pack_closure_no_payload(f, id, filter);
-        nut.push_closure(topic, id, closure);
-    });
+        nut.push_closure(topic, id, closure)
+    })
 }
 
-pub(crate) fn register_domained<A, F, MSG>(id: ActivityId<A>, f: F, filter: SubscriptionFilter)
+pub(crate) fn register_domained<A, F, MSG>(
+    id: ActivityId<A>,
+    f: F,
+    filter: SubscriptionFilter,
+) -> SubscriptionId
 where
     A: Activity,
     F: Fn(&mut A, &mut DomainState, &MSG) + 'static,
     MSG: Any,
 {
     NUT.with(|nut| {
+        nut.require_domain(id.domain_index, DebugTypeName::new::<A>());
         let closure = ManagedState::pack_domained_closure(f, id, filter);
         let topic = Topic::public_message::<MSG>();
-        nut.push_closure(topic, id, closure);
-    });
+        nut.push_closure(topic, id, closure)
+    })
 }
-pub(crate) fn register_domained_mut<A, F, MSG>(id: ActivityId<A>, f: F, filter: SubscriptionFilter)
+pub(crate) fn register_domained_mut<A, F, MSG>(
+    id: ActivityId<A>,
+    f: F,
+    filter: SubscriptionFilter,
+) -> SubscriptionId
 where
     A: Activity,
     F: Fn(&mut A, &mut DomainState, &mut MSG) + 'static,
     MSG: Any,
 {
     NUT.with(|nut| {
+        nut.require_domain(id.domain_index, DebugTypeName::new::<A>());
         let closure = ManagedState::pack_domained_closure_mut(f, id, filter);
         let topic = Topic::public_message::<MSG>();
-        nut.push_closure(topic, id, closure);
-    });
+        nut.push_mutating_closure(topic, id, closure)
+    })
 }
 pub(crate) fn register_domained_owned<A, F, MSG>(
     id: ActivityId<A>,
     f: F,
     filter: SubscriptionFilter,
-) where
+) -> SubscriptionId
+where
     A: Activity,
     F: Fn(&mut A, &mut DomainState, MSG) + 'static,
     MSG: Any,
 {
     NUT.with(|nut| {
+        nut.require_domain(id.domain_index, DebugTypeName::new::<A>());
         let closure = ManagedState::pack_domained_closure_owned(f, id, filter);
         let topic = Topic::private_message::<MSG>();
-        nut.push_closure(topic, id, closure);
-    });
+        nut.push_closure(topic, id, closure)
+    })
 }
 
 /// For subscriptions without payload but with domain access
@@ -289,14 +1149,16 @@ pub(crate) fn register_domained_no_payload<A, F>(
     f: F,
     topic: Topic,
     filter: SubscriptionFilter,
-) where
+) -> SubscriptionId
+where
     A: Activity,
     F: Fn(&mut A, &mut DomainState) + 'static,
 {
     NUT.with(|nut| {
+        nut.require_domain(id.domain_index, DebugTypeName::new::<A>());
         let closure = ManagedState::pack_closure_domained_no_payload(f, id, filter);
-        nut.push_closure(topic, id, closure);
-    });
+        nut.push_closure(topic, id, closure)
+    })
 }
 
 pub(crate) fn register_on_delete<A, F>(id: ActivityId<A>, f: F)
@@ -313,29 +1175,138 @@ where
     })
 }
 
+pub(crate) fn evict_when_idle<A: Activity>(id: ActivityId<A>, codec: activity::Codec) {
+    NUT.with(|nut| nut.evict_when_idle(id.into(), codec))
+}
+
 pub(crate) fn register_domained_on_delete<A, F>(id: ActivityId<A>, f: F)
 where
     A: Activity,
     F: FnOnce(A, &mut DomainState) + 'static,
 {
     NUT.with(|nut| {
+        nut.require_domain(id.domain_index, DebugTypeName::new::<A>());
         let closure = Box::new(move |a: Box<dyn Any>, managed_state: &mut ManagedState| {
             let activity = a.downcast().expect(IMPOSSIBLE_ERR_MSG);
-            let domain = managed_state
-                .get_mut(id.domain_index)
-                .expect("missing domain");
-            f(*activity, domain);
+            if let Some(domain) = managed_state.domain_for_call(id.domain_index) {
+                f(*activity, domain);
+            }
         });
         let subscription = OnDelete::WithDomain(closure);
         nut.add_on_delete(id.into(), subscription);
     })
 }
 
+/// Gives read access to an activity from outside of subscription handlers.
+///
+/// Returns `None` if the activity has been deleted.
+pub(crate) fn read_activity<A, T>(id: ActivityId<A>, f: impl FnOnce(&A) -> T) -> Option<T>
+where
+    A: Activity,
+{
+    NUT.with(|nut| {
+        let mut activities = nut
+            .activities
+            .try_borrow_mut()
+            .expect("Activities cannot be read while a broadcast is ongoing.");
+        activities.get(id).map(f)
+    })
+}
+
+pub(crate) fn visit_activities(f: impl FnMut(ActivityMeta, &dyn Any)) {
+    NUT.with(|nut| {
+        let activities = nut
+            .activities
+            .try_borrow()
+            .expect("Activities cannot be visited while a broadcast is ongoing.");
+        activities.visit(f)
+    })
+}
+
+pub(crate) fn is_valid<A: Activity>(id: ActivityId<A>) -> bool {
+    NUT.with(|nut| {
+        let activities = nut
+            .activities
+            .try_borrow()
+            .expect("Activities cannot be read while a broadcast is ongoing.");
+        activities.is_valid(id)
+    })
+}
+
+/// True iff the ID points to a living activity of type `A` with the same domain as stored in the ID.
+pub(crate) fn is_token_valid<A: Activity>(id: ActivityId<A>) -> bool {
+    NUT.with(|nut| {
+        let activities = nut
+            .activities
+            .try_borrow()
+            .expect("Activities cannot be read while a broadcast is ongoing.");
+        activities.is_valid(id) && activities.domain(id.id) == Some(id.domain_index)
+    })
+}
+
+/// True once the activity is stored with all other activities, which is not the case while it is inchoate.
+pub(crate) fn is_live(id: UncheckedActivityId) -> bool {
+    NUT.with(|nut| nut.is_live(id))
+}
+
+/// Returns the current lifecycle status of an activity, or `None` if no such activity exists (yet).
+pub(crate) fn activity_status(id: UncheckedActivityId) -> Option<LifecycleStatus> {
+    NUT.with(|nut| {
+        let activities = nut
+            .activities
+            .try_borrow()
+            .expect("Activities cannot be read while a broadcast is ongoing.");
+        (id.index < activities.len()).then(|| activities.status(id))
+    })
+}
+
+/// Fails if the activity has been deleted or its deletion is queued.
+///
+/// Inside subscription handlers, the activities cannot be inspected and the check always succeeds.
+pub(crate) fn check_not_deleted(id: UncheckedActivityId) -> Result<(), crate::Error> {
+    NUT.with(|nut| match nut.activities.try_borrow() {
+        Ok(activities) if activities.status(id) == LifecycleStatus::Deleted => {
+            Err(crate::Error::ActivityDeleted)
+        }
+        _ => Ok(()),
+    })
+}
+
+/// Rejects handlers that could alter messages of type `MSG` from now on, panics if such a handler exists already.
+pub(crate) fn protect_message<MSG: iac::immutable::ImmutableMessage>() {
+    NUT.with(|nut| nut.protect_message::<MSG>())
+}
+
+/// Fails if a handler with this access to the topic could alter a protected message type.
+pub(crate) fn check_access(topic: Topic, access: iac::subscription::Access) -> Result<(), Error> {
+    NUT.with(|nut| nut.check_access(topic, access))
+}
+
 pub(crate) fn set_status(id: UncheckedActivityId, status: LifecycleStatus) {
     NUT.with(|nut| nut.set_status(id, status));
 }
 
-pub(crate) fn write_domain<D, T>(domain: &D, data: T)
+/// Deletes the activity like `set_status` and releases its slot afterwards
+pub(crate) fn delete(id: UncheckedActivityId) {
+    NUT.with(|nut| {
+        nut.slots_to_release
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .push(id);
+        nut.set_status(id, LifecycleStatus::Deleted);
+    });
+}
+
+pub(crate) fn set_status_immediate(id: UncheckedActivityId, status: LifecycleStatus) {
+    NUT.with(|nut| nut.set_status_immediate(id, status));
+}
+
+pub(crate) fn set_status_batch(changes: Vec<activity::LifecycleChange>) {
+    NUT.with(|nut| nut.set_status_batch(changes));
+}
+
+/// Returns true if the data has been stored immediately, false if the store has been queued
+pub(crate) fn write_domain<D, T>(domain: &D, data: T) -> bool
 where
     D: DomainEnumeration,
     T: core::any::Any,
@@ -346,9 +1317,184 @@ where
             managed_state.prepare(id);
             let storage = managed_state.get_mut(id).expect("No domain");
             storage.store(data);
+            true
         } else {
             let event = Deferred::DomainStore(DomainStoreData::new(id, data));
-            nut.deferred_events.push(event);
+            nut.defer_bookkeeping(event);
+            false
+        }
+    })
+}
+
+pub(crate) fn write_domain_awaitable<D, T>(domain: &D, data: T) -> DomainWrite
+where
+    D: DomainEnumeration,
+    T: core::any::Any,
+{
+    if write_domain(domain, data) {
+        return DomainWrite::done();
+    }
+    let (write, signal) = DomainWrite::pending();
+    // Queued right behind the store, hence it runs once the store has been applied
+    NUT.with(|nut| nut.defer_bookkeeping(Deferred::Closure(Box::new(move || signal.complete()))));
+    write
+}
+
+pub(crate) fn publish_domain_view<D, T>(domain: &D)
+where
+    D: DomainEnumeration,
+    T: Any + Clone,
+{
+    NUT.with(|nut| nut.publish_domain_view(DomainViewRequest::new::<T>(DomainId::new(domain))))
+}
+
+pub(crate) fn merge_domains<D: DomainEnumeration>(src: &D, dst: &D, policy: ConflictPolicy) {
+    let merge = DomainMerge::new(DomainId::new(src), DomainId::new(dst), policy);
+    NUT.with(|nut| nut.merge_domains(merge))
+}
+
+pub(crate) fn observe_domain<D, T, F>(domain: &D, f: F)
+where
+    D: DomainEnumeration,
+    T: Any,
+    F: Fn(&T) + 'static,
+{
+    NUT.with(|nut| {
+        let observer = DomainObserver::new(DomainId::new(domain), f);
+        if nut.executing.load(core::sync::atomic::Ordering::Relaxed) {
+            nut.defer_bookkeeping(Deferred::DomainObserver(observer));
+        } else {
+            nut.add_domain_observer(observer);
+        }
+    })
+}
+
+/// Observes a domain object but only calls `f` if the value differs from the one seen last time.
+pub(crate) fn on_domain_change<D, T, F>(domain: &D, f: F)
+where
+    D: DomainEnumeration,
+    T: Any + Clone + PartialEq,
+    F: Fn(&T) + 'static,
+{
+    let last: RefCell<Option<T>> = RefCell::new(None);
+    observe_domain(domain, move |new: &T| {
+        if last.borrow().as_ref() != Some(new) {
+            *last.borrow_mut() = Some(new.clone());
+            f(new);
+        }
+    })
+}
+
+pub(crate) fn set_clock(clock: Box<dyn clock::Clock>) {
+    NUT.with(|nut| {
+        nut.clock
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .set(clock)
+    })
+}
+
+pub(crate) fn now() -> std::time::Duration {
+    NUT.with(|nut| nut.now())
+}
+
+#[cfg(feature = "perf")]
+pub(crate) fn profiling_report() -> Vec<iac::profile::HandlerReport> {
+    NUT.with(|nut| nut.subscriptions.profiling_report())
+}
+
+pub(crate) fn subscription_stats(
+    id: SubscriptionId,
+) -> Option<iac::subscription::SubscriptionStats> {
+    NUT.with(|nut| nut.subscriptions.stats(id))
+}
+
+#[cfg(debug_assertions)]
+pub(crate) fn unused_subscriptions(
+    min_broadcasts: u64,
+) -> Vec<iac::subscription::UnusedSubscription> {
+    NUT.with(|nut| nut.subscriptions.unused(min_broadcasts))
+}
+
+#[cfg(not(debug_assertions))]
+pub(crate) fn unused_subscriptions(
+    _min_broadcasts: u64,
+) -> Vec<iac::subscription::UnusedSubscription> {
+    Vec::new()
+}
+
+#[cfg(debug_assertions)]
+pub(crate) fn lint_capture<A: Activity, B: Activity, MSG: Any>(
+    id: ActivityId<A>,
+    captured: ActivityId<B>,
+) {
+    NUT.with(|nut| {
+        nut.lint_capture(
+            (id.domain_index, DebugTypeName::new::<A>()),
+            (captured.domain_index, DebugTypeName::new::<B>()),
+            Topic::public_message::<MSG>().name(),
+        )
+    })
+}
+
+#[cfg(debug_assertions)]
+pub(crate) fn capture_warnings() -> Vec<iac::subscription::CaptureWarning> {
+    NUT.with(|nut| {
+        nut.capture_warnings
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .clone()
+    })
+}
+
+#[cfg(not(debug_assertions))]
+pub(crate) fn capture_warnings() -> Vec<iac::subscription::CaptureWarning> {
+    Vec::new()
+}
+
+pub(crate) fn subscription_table() -> iac::subscription::SubscriptionTable {
+    NUT.with(|nut| nut.subscriptions.table())
+}
+
+pub(crate) fn current_trace() -> Option<TraceId> {
+    NUT.with(|nut| nut.tracer.try_borrow().expect(IMPOSSIBLE_ERR_MSG).current())
+}
+
+pub(crate) fn record_traces(capacity: usize) {
+    NUT.with(|nut| {
+        nut.tracer
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .set_capacity(capacity)
+    })
+}
+
+pub(crate) fn publish_stats(every: u64) {
+    NUT.with(|nut| nut.stats.set_interval(every))
+}
+
+pub(crate) fn trace_tree() -> Vec<TraceNode> {
+    NUT.with(|nut| nut.tracer.try_borrow().expect(IMPOSSIBLE_ERR_MSG).nodes())
+}
+
+pub(crate) fn trace_chain(id: TraceId) -> Vec<TraceNode> {
+    NUT.with(|nut| nut.tracer.try_borrow().expect(IMPOSSIBLE_ERR_MSG).chain(id))
+}
+
+pub(crate) fn write_domain_ordered<D, T>(domain: &D, data: T)
+where
+    D: DomainEnumeration,
+    T: core::any::Any,
+{
+    NUT.with(|nut| {
+        let id = DomainId::new(domain);
+        if let Ok(mut managed_state) = nut.managed_state.try_borrow_mut() {
+            managed_state.prepare(id);
+            let storage = managed_state.get_mut(id).expect("No domain");
+            storage.store(data);
+        } else {
+            nut.ordered_domain_stores
+                .push(DomainStoreData::new(id, data));
         }
     })
 }
@@ -1,13 +1,23 @@
 mod activity_container;
 mod lifecycle;
+pub(crate) mod pending;
+mod token;
 
 pub(crate) use activity_container::*;
 pub use lifecycle::*;
+pub use token::ActivityToken;
 
-use crate::nut::iac::{filter::SubscriptionFilter, managed_state::DomainId};
+use crate::nut::iac::{
+    filter::SubscriptionFilter,
+    managed_state::DomainId,
+    publish::{KeyedMessage, Request, ScopedMessage},
+};
+use crate::nut::IMPOSSIBLE_ERR_MSG;
 use crate::*;
 use core::any::Any;
-use std::ops::{Index, IndexMut};
+use core::hash::Hash;
+use std::ops::{Deref, Index, IndexMut};
+use std::time::Duration;
 
 // @ START-DOC ACTIVITY
 /// Activities are at the core of Nuts.
@@ -26,6 +36,24 @@ use std::ops::{Index, IndexMut};
 pub trait Activity: Any {}
 impl<T: Any> Activity for T {}
 
+/// Marks activities whose handlers could run in parallel with the handlers of other such activities.
+///
+/// Handlers registered with [`ActivityId::subscribe_parallel`](struct.ActivityId.html#method.subscribe_parallel) only get the activity and a shared reference to the message.
+/// They cannot access a domain or modify the message, hence they do not depend on any state outside of their own activity.
+///
+/// Today, all handlers are still executed one after another on the current thread.
+/// The marker and the separate bookkeeping of these handlers allow running them on multiple threads in the future, without changes to the registration API.
+pub trait ParallelSafe: Activity + Send {}
+
+/// Handler logic for a message, implemented as a normal method on the activity.
+///
+/// Register the implementation with [`ActivityId::subscribe_trait_impl`](struct.ActivityId.html#method.subscribe_trait_impl).
+/// Keeping handlers as methods makes it possible to unit test them without publishing any messages.
+pub trait Subscriber<MSG> {
+    /// Called for every published message of type `MSG`, while the activity is active.
+    fn on_message(&mut self, msg: &MSG);
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 /// Handle to an `Activity` that has been registered, with a type parameter to track the activity's type.
 /// Can be used to add type-checked closures to the activity, which will be used as event listeners.
@@ -37,6 +65,23 @@ pub struct ActivityId<A> {
     phantom: std::marker::PhantomData<A>,
 }
 
+/// Description of an activity, passed to the visitor of [`visit_activities`](fn.visit_activities.html).
+#[derive(Clone, Debug)]
+pub struct ActivityMeta {
+    /// The activity's ID, which can be used to change its status
+    pub id: UncheckedActivityId,
+    /// Type name of the activity, only available in debug mode.
+    pub type_name: &'static str,
+    /// The domain the activity has been registered with, as returned by `DomainEnumeration::id()`
+    pub domain: Option<usize>,
+    /// The current lifecycle status
+    pub status: LifecycleStatus,
+    /// Set if the activity has been evicted, see [`ActivityId::evict_when_idle`](struct.ActivityId.html#method.evict_when_idle).
+    ///
+    /// The visitor receives the serialized form of evicted activities, which cannot be downcast to the activity's type.
+    pub evicted: bool,
+}
+
 /// This type is used for subscriptions without activity. It is zero sized, hence should be a zero-cost abstraction.
 pub(crate) struct NotAnActivity;
 
@@ -46,28 +91,36 @@ pub(crate) struct NotAnActivity;
 ///
 /// The information about the activity's type is lost at this point.
 /// Therefore, this id cannot be used to register closures.
+///
+/// The ID also stores the generation of the activity's slot.
+/// Once the activity has been deleted, the ID is stale and never refers to another activity.
 pub struct UncheckedActivityId {
     pub(crate) index: usize,
+    pub(crate) generation: u32,
 }
 
 impl<A: Activity> ActivityId<A> {
     pub(crate) fn new(index: usize, domain_index: DomainId) -> Self {
         Self {
-            id: UncheckedActivityId { index },
+            id: UncheckedActivityId::new(index, 0),
             domain_index,
             phantom: Default::default(),
         }
     }
+    pub(crate) fn with_generation(mut self, generation: u32) -> Self {
+        self.id.generation = generation;
+        self
+    }
     /// Registers a callback closure that is called when an activity changes from inactive to active.
     /// Multiple handlers can be registered.
-    pub fn on_enter<F>(&self, f: F)
+    pub fn on_enter<F>(&self, f: F) -> SubscriptionId
     where
         F: Fn(&mut A) + 'static,
     {
         crate::nut::register_no_payload(*self, f, Topic::enter(), SubscriptionFilter::no_filter())
     }
     /// Same as `on_enter` but with domain access in closure
-    pub fn on_enter_domained<F>(&self, f: F)
+    pub fn on_enter_domained<F>(&self, f: F) -> SubscriptionId
     where
         F: Fn(&mut A, &mut DomainState) + 'static,
     {
@@ -80,14 +133,14 @@ impl<A: Activity> ActivityId<A> {
     }
     /// Registers a callback closure that is called when an activity changes from active to inactive.
     /// Multiple handlers can be registered.
-    pub fn on_leave<F>(&self, f: F)
+    pub fn on_leave<F>(&self, f: F) -> SubscriptionId
     where
         F: Fn(&mut A) + 'static,
     {
         crate::nut::register_no_payload(*self, f, Topic::leave(), SubscriptionFilter::no_filter())
     }
     /// Same as `on_leave` but with domain access in closure
-    pub fn on_leave_domained<F>(&self, f: F)
+    pub fn on_leave_domained<F>(&self, f: F) -> SubscriptionId
     where
         F: Fn(&mut A, &mut DomainState) + 'static,
     {
@@ -101,12 +154,26 @@ impl<A: Activity> ActivityId<A> {
     /// Registers a callback closure that is called when an activity is deleted.
     /// Only one handler can be registered because it takes ownership of the data.
     /// A second registration will overwrite the first handler.
+    ///
+    /// If the activity has already been deleted, the callback is dropped without being called.
     pub fn on_delete<F>(&self, f: F)
     where
         F: FnOnce(A) + 'static,
     {
         crate::nut::register_on_delete(*self, f);
     }
+    /// Same as [`on_delete`](#method.on_delete) but refuses to register the callback if the activity has been deleted.
+    ///
+    /// # Errors
+    /// Returns `Error::ActivityDeleted` under the same conditions as [`try_subscribe`](#method.try_subscribe).
+    pub fn try_on_delete<F>(&self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(A) + 'static,
+    {
+        crate::nut::check_not_deleted((*self).into())?;
+        self.on_delete(f);
+        Ok(())
+    }
     /// Same as `on_delete` but with domain access in closure
     pub fn on_delete_domained<F>(&self, f: F)
     where
@@ -158,24 +225,548 @@ impl<A: Activity> ActivityId<A> {
     /// }
     /// ```
     // @ END-DOC SUBSCRIBE_EXAMPLE
-    pub fn subscribe<F, MSG>(&self, f: F)
+    pub fn subscribe<F, MSG>(&self, f: F) -> SubscriptionId
     where
         F: Fn(&mut A, &MSG) + 'static,
         MSG: Any,
     {
         crate::nut::register(*self, f, Default::default())
     }
+    /// Same as [subscribe](#method.subscribe) but the handler is removed after it has been called once.
+    ///
+    /// The handler is only required to be `FnOnce`.
+    /// Messages that are skipped because the activity is inactive do not count, the handler then waits for the next message.
+    ///
+    /// ### Example
+    /// ```rust
+    /// struct Client { token: Option<u64> }
+    /// struct LoginReply { token: u64 }
+    ///
+    /// let client = nuts::new_activity(Client { token: None });
+    /// client.subscribe_once(|client, reply: &LoginReply| client.token = Some(reply.token));
+    /// nuts::publish(LoginReply { token: 7 });
+    /// // Later replies are ignored
+    /// nuts::publish(LoginReply { token: 8 });
+    /// ```
+    pub fn subscribe_once<F, MSG>(&self, f: F) -> SubscriptionId
+    where
+        F: FnOnce(&mut A, &MSG) + 'static,
+        MSG: Any,
+    {
+        let own_id = std::rc::Rc::new(std::cell::Cell::new(None));
+        let handle = own_id.clone();
+        let f = std::cell::Cell::new(Some(f));
+        let sub = self.subscribe(move |a, msg: &MSG| {
+            if let Some(f) = f.take() {
+                f(a, msg);
+                crate::unsubscribe(handle.get().expect(IMPOSSIBLE_ERR_MSG));
+            }
+        });
+        own_id.set(Some(sub));
+        sub
+    }
+    /// Same as [`subscribe_once`](#method.subscribe_once) with access to the domain state.
+    ///
+    /// # Panics
+    /// Panics if the activity has not been registered with a domain.
+    pub fn subscribe_once_domained<F, MSG>(&self, f: F) -> SubscriptionId
+    where
+        F: FnOnce(&mut A, &mut DomainState, &MSG) + 'static,
+        MSG: Any,
+    {
+        let own_id = std::rc::Rc::new(std::cell::Cell::new(None));
+        let handle = own_id.clone();
+        let f = std::cell::Cell::new(Some(f));
+        let sub = self.subscribe_domained(move |a, domain, msg: &MSG| {
+            if let Some(f) = f.take() {
+                f(a, domain, msg);
+                crate::unsubscribe(handle.get().expect(IMPOSSIBLE_ERR_MSG));
+            }
+        });
+        own_id.set(Some(sub));
+        sub
+    }
+    /// Same as [subscribe](#method.subscribe) but the handler also gets mutable access to its own state, which starts out as `initial_state`.
+    ///
+    /// The state is private to this subscription, which keeps counters, caches, and the like out of the activity.
+    /// It is dropped together with the subscription.
+    ///
+    /// ### Example
+    /// ```rust
+    /// struct Logger;
+    /// struct Tick;
+    ///
+    /// let id = nuts::new_activity(Logger);
+    /// id.subscribe_with_state(0u32, |_logger, ticks: &mut u32, _: &Tick| {
+    ///     *ticks += 1;
+    ///     if *ticks % 60 == 0 {
+    ///         println!("{} ticks", ticks);
+    ///     }
+    /// });
+    /// nuts::publish(Tick);
+    /// ```
+    pub fn subscribe_with_state<F, S, MSG>(&self, initial_state: S, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, &mut S, &MSG) + 'static,
+        S: 'static,
+        MSG: Any,
+    {
+        let state = std::cell::RefCell::new(initial_state);
+        // Handlers are never called recursively, the state cannot be borrowed already
+        self.subscribe(move |a, msg: &MSG| {
+            f(
+                a,
+                &mut state.try_borrow_mut().expect(IMPOSSIBLE_ERR_MSG),
+                msg,
+            )
+        })
+    }
+    /// Same as [subscribe](#method.subscribe) but the handler also receives the time elapsed since it has last been called.
+    ///
+    /// Time is measured with the clock set by [`nuts::set_clock`](fn.set_clock.html).
+    /// On the first call, the elapsed time is zero.
+    /// Calls that are skipped because the activity is inactive do not count, the next call then receives the time since the last actual call.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use std::time::Duration;
+    /// struct Ball { x: f32, speed: f32 }
+    /// struct Tick;
+    ///
+    /// let id = nuts::new_activity(Ball { x: 0.0, speed: 2.0 });
+    /// id.subscribe_timed(|ball, _: &Tick, dt: Duration| {
+    ///     ball.x += ball.speed * dt.as_secs_f32();
+    /// });
+    /// nuts::publish(Tick);
+    /// ```
+    pub fn subscribe_timed<F, MSG>(&self, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, &MSG, Duration) + 'static,
+        MSG: Any,
+    {
+        let last_call = std::cell::Cell::new(None);
+        self.subscribe(move |a, msg: &MSG| {
+            let now = crate::nut::now();
+            let dt = last_call
+                .replace(Some(now))
+                .map_or(Duration::ZERO, |last: Duration| now.saturating_sub(last));
+            f(a, msg, dt)
+        })
+    }
+    /// Registers a callback closure for messages published with [`nuts::publish_keyed`](fn.publish_keyed.html), with key type `K` and message type `MSG`.
+    ///
+    /// The handler receives key and message separately.
+    /// It may rely on the order of messages with the same key, but not on the order between messages with different keys.
+    pub fn subscribe_keyed<F, K, MSG>(&self, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, &K, &MSG) + 'static,
+        K: Any + Hash + Eq,
+        MSG: Any,
+    {
+        self.subscribe(move |a, keyed: &KeyedMessage<K, MSG>| f(a, &keyed.key, &keyed.msg))
+    }
+    /// Registers a callback closure for payloads published with [`nuts::publish_shared`](fn.publish_shared.html), with tag `T` and pointer type `P`.
+    ///
+    /// The handler reads the payload in place, neither the payload nor the pointer is cloned.
+    pub fn subscribe_shared<T, P, F>(&self, f: F) -> SubscriptionId
+    where
+        T: Any,
+        P: Deref + Clone + Any,
+        F: Fn(&mut A, &P::Target) + 'static,
+    {
+        self.subscribe(move |a, shared: &SharedPayload<T, P>| f(a, shared))
+    }
+    /// Registers a callback closure for payloads published with [`nuts::publish_shared`](fn.publish_shared.html), with tag `T` and pointer type `P`.
+    ///
+    /// The handler receives its own clone of the pointer, which it may keep after returning.
+    /// The payload itself is not copied.
+    pub fn subscribe_shared_owned<T, P, F>(&self, f: F) -> SubscriptionId
+    where
+        T: Any,
+        P: Deref + Clone + Any,
+        F: Fn(&mut A, P) + 'static,
+    {
+        self.subscribe(move |a, shared: &SharedPayload<T, P>| f(a, shared.share()))
+    }
+    /// Registers a callback closure for messages of type `MSG` published with [`nuts::publish_scoped`](fn.publish_scoped.html) inside the scope `S`.
+    ///
+    /// The handler does not receive messages of the same type published with `publish` or inside another scope.
+    pub fn subscribe_scoped<S, F, MSG>(&self, f: F) -> SubscriptionId
+    where
+        S: ScopeToken,
+        F: Fn(&mut A, &MSG) + 'static,
+        MSG: Any,
+    {
+        self.subscribe(move |a, scoped: &ScopedMessage<S, MSG>| f(a, &scoped.msg))
+    }
+    /// Registers a callback closure for a whole family of message types, which all implement the trait `T`.
+    ///
+    /// The handler receives every published message of a type that has been added to the family with [`nuts::register_dyn`](fn.register_dyn.html), as a trait object.
+    /// Types can be added to the family before or after the subscription.
+    ///
+    /// The trait is best specified with the turbofish, as in the example below.
+    /// Annotating the argument of the closure as `&dyn Trait` does not compile, because Rust then assumes a trait object with a shorter lifetime than `'static`.
+    ///
+    /// ### Example
+    /// ```rust
+    /// trait UiEvent { fn describe(&self) -> String; }
+    /// struct Click(i32, i32);
+    /// struct Scroll(i32);
+    /// impl UiEvent for Click { fn describe(&self) -> String { format!("click at {}/{}", self.0, self.1) } }
+    /// impl UiEvent for Scroll { fn describe(&self) -> String { format!("scroll by {}", self.0) } }
+    ///
+    /// nuts::register_dyn::<dyn UiEvent, Click>(|click| click);
+    /// nuts::register_dyn::<dyn UiEvent, Scroll>(|scroll| scroll);
+    ///
+    /// struct EventLog(Vec<String>);
+    /// let log = nuts::new_activity(EventLog(vec![]));
+    /// log.subscribe_dyn::<dyn UiEvent>(|log, event| log.0.push(event.describe()));
+    ///
+    /// nuts::publish(Click(1, 2));
+    /// nuts::publish(Scroll(3));
+    /// ```
+    pub fn subscribe_dyn<T>(&self, f: impl Fn(&mut A, &T) + 'static) -> SubscriptionId
+    where
+        T: ?Sized + 'static,
+    {
+        crate::nut::register_dyn(*self, f, Default::default())
+    }
+    /// Sets the position of the activity among all activities that receive published messages of type `MSG`.
+    ///
+    /// Activities with a lower weight are called first, the default weight is zero.
+    /// Activities with the same weight are called in the order they have been created.
+    /// As soon as one activity has a weight for a message type, this order applies to the message type regardless of the configured [`DispatchOrder`](enum.DispatchOrder.html).
+    ///
+    /// Use it for messages that are handled once per frame by many activities, such as drawing background layers before the foreground.
+    /// Setting the weight from inside a subscription handler takes effect after the current message has been handled.
+    ///
+    /// The weight replaces a [priority](#method.subscribe_with_priority) of the activity for `MSG`, and the other way round.
+    ///
+    /// ### Example
+    /// ```rust
+    /// struct Background;
+    /// struct Foreground;
+    /// struct Draw;
+    ///
+    /// let foreground = nuts::new_activity(Foreground);
+    /// let background = nuts::new_activity(Background);
+    /// background.set_dispatch_weight::<Draw>(-10);
+    /// background.subscribe(|_background, _: &Draw| println!("background"));
+    /// foreground.subscribe(|_foreground, _: &Draw| println!("foreground"));
+    /// nuts::publish(Draw); // prints "background" first, despite the creation order
+    /// ```
+    pub fn set_dispatch_weight<MSG: Any>(&self, weight: i32) {
+        crate::nut::set_dispatch_weight(
+            Topic::public_message::<MSG>(),
+            (*self).into(),
+            i64::from(weight),
+        )
+    }
+    /// Registers a callback closure for published messages of type `MSG` and places the activity before or after all other activities that receive them.
+    ///
+    /// The priority is a [dispatch weight](#method.set_dispatch_weight) below or above all weights that can be set with `set_dispatch_weight`, even `i32::MIN` and `i32::MAX`.
+    /// It applies to all handlers of the activity for `MSG` and replaces a weight set before, just like a weight set later replaces the priority.
+    /// Activities with the same priority are called in the order they have been created.
+    /// The priority orders the activities among handlers of the same kind, handlers that only read the message are still called before those that may modify or consume it.
+    /// Use [`subscribe_mut_with_priority`](#method.subscribe_mut_with_priority) to order handlers that modify the message.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use nuts::SubscriptionPriority;
+    /// struct Game;
+    /// struct InputValidator;
+    /// struct KeyPressed(char);
+    ///
+    /// let game = nuts::new_activity(Game);
+    /// game.subscribe(|_game, key: &KeyPressed| println!("game handles {}", key.0));
+    /// // Created later, but always called before the game logic
+    /// let validator = nuts::new_activity(InputValidator);
+    /// validator.subscribe_with_priority(SubscriptionPriority::High, |_validator, key: &KeyPressed| {
+    ///     assert!(key.0.is_ascii());
+    /// });
+    /// nuts::publish(KeyPressed('w'));
+    /// ```
+    pub fn subscribe_with_priority<F, MSG>(
+        &self,
+        priority: SubscriptionPriority,
+        f: F,
+    ) -> SubscriptionId
+    where
+        F: Fn(&mut A, &MSG) + 'static,
+        MSG: Any,
+    {
+        crate::nut::set_dispatch_weight(
+            Topic::public_message::<MSG>(),
+            (*self).into(),
+            priority.weight(),
+        );
+        self.subscribe(f)
+    }
+    /// Same as [`subscribe_with_priority`](#method.subscribe_with_priority) but the handler may modify the message, see [`subscribe_mut`](#method.subscribe_mut).
+    ///
+    /// Handlers that modify the message are called after all handlers that only read it, the priority orders them among each other.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use nuts::SubscriptionPriority;
+    /// struct Chat;
+    /// struct Filter;
+    /// struct Post(String);
+    ///
+    /// let chat = nuts::new_activity(Chat);
+    /// chat.subscribe_mut(|_chat, post: &mut Post| println!("{}", post.0));
+    /// // Created later, but always modifies the post before the chat shows it
+    /// let filter = nuts::new_activity(Filter);
+    /// filter.subscribe_mut_with_priority(SubscriptionPriority::High, |_filter, post: &mut Post| {
+    ///     post.0 = post.0.replace("darn", "****");
+    /// });
+    /// nuts::publish(Post(String::from("darn")));
+    /// ```
+    pub fn subscribe_mut_with_priority<F, MSG>(
+        &self,
+        priority: SubscriptionPriority,
+        f: F,
+    ) -> SubscriptionId
+    where
+        F: Fn(&mut A, &mut MSG) + 'static,
+        MSG: Any,
+    {
+        crate::nut::set_dispatch_weight(
+            Topic::public_message::<MSG>(),
+            (*self).into(),
+            priority.weight(),
+        );
+        self.subscribe_mut(f)
+    }
+    /// Registers subscriptions of the activity all together or not at all.
+    ///
+    /// The closure gets the activity ID and registers handlers as usual.
+    /// If it returns an error or panics, all subscriptions it added to the activity are removed again, including handlers for lifecycle events and private channels.
+    /// This leaves the activity wired as it was before, instead of only partially.
+    ///
+    /// The rollback does not restore a private channel that has been replaced, nor an `on_delete` handler.
+    /// Messages that have been delivered to the new handlers before the rollback, for example those kept by [`buffer_startup_messages`](fn.buffer_startup_messages.html), are not delivered again.
+    ///
+    /// # Errors
+    /// Returns the error of the closure.
+    ///
+    /// # Panics
+    /// Panics if called from inside a subscription handler.
+    ///
+    /// ### Example
+    /// ```rust
+    /// struct Player;
+    /// struct Jump;
+    /// struct Duck;
+    ///
+    /// let id = nuts::new_activity(Player);
+    /// let result: Result<(), _> = id.try_setup(|id| {
+    ///     id.subscribe(|_player, _: &Jump| {});
+    ///     id.subscribe(|_player, _: &Duck| {});
+    ///     Err("key bindings are missing")
+    /// });
+    /// assert!(result.is_err());
+    /// assert!(nuts::subscription_table().entries.is_empty());
+    /// ```
+    pub fn try_setup<T, E>(&self, f: impl FnOnce(Self) -> Result<T, E>) -> Result<T, E> {
+        let guard = crate::nut::begin_setup((*self).into());
+        let result = f(*self);
+        if result.is_ok() {
+            guard.commit();
+        }
+        result
+    }
+    /// Same as [subscribe](#method.subscribe) but refuses to register the handler if the activity has been deleted.
+    ///
+    /// A handler registered on a deleted activity can never be called.
+    /// This method makes the mistake visible instead of silently registering the handler.
+    ///
+    /// # Errors
+    /// Returns `Error::ActivityDeleted` if the activity has been deleted, or if its deletion is already queued.
+    /// Inside subscription handlers, the status of activities cannot be checked and the handler is always registered.
+    pub fn try_subscribe<F, MSG>(&self, f: F) -> Result<SubscriptionId, Error>
+    where
+        F: Fn(&mut A, &MSG) + 'static,
+        MSG: Any,
+    {
+        crate::nut::check_not_deleted((*self).into())?;
+        Ok(self.subscribe(f))
+    }
+    /// Registers a handler that takes any combination of the supported parameters, in any order.
+    ///
+    /// Supported parameter types:
+    /// - `&mut A`: The activity
+    /// - `&MSG` or `&mut MSG`: The published message
+    /// - `&mut DomainState`: The domain of the activity (panics if the activity has no domain)
+    /// - `ActivityId<A>`: The ID of the activity
+    /// - [`MessageControl`](struct.MessageControl.html): Consumes the message, such that the remaining handlers are skipped
+    ///
+    /// Each parameter can be used at most once and the types of all parameters must be annotated explicitly.
+    /// If the handler does not take the message, its type must be given with a turbofish.
+    ///
+    /// This is an alternative to [subscribe](#method.subscribe), [`subscribe_domained`](#method.subscribe_domained), and their variants.
+    /// By default, the activity will only receive calls when it is active.
+    /// Use `subscribe_with_masked` for more control over this behavior.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use nuts::{ActivityId, DefaultDomain, DomainState};
+    /// struct Player { hp: u32 }
+    /// struct Damage(u32);
+    /// struct Heal(u32);
+    ///
+    /// let id = nuts::new_domained_activity(Player { hp: 10 }, &DefaultDomain);
+    /// id.subscribe_with(|msg: &Damage, player: &mut Player, domain: &mut DomainState| {
+    ///     player.hp = player.hp.saturating_sub(msg.0);
+    ///     domain.store(player.hp);
+    /// });
+    /// id.subscribe_with(|player: &mut Player, msg: &Heal, id: ActivityId<Player>| {
+    ///     player.hp += msg.0;
+    /// });
+    /// id.subscribe_with::<Damage, _, _>(|player: &mut Player| println!("Ouch, {} HP left", player.hp));
+    /// nuts::publish(Damage(3));
+    /// ```
+    pub fn subscribe_with<MSG, P, F>(&self, f: F) -> SubscriptionId
+    where
+        F: HandlerFn<A, MSG, P> + 'static,
+        MSG: Any,
+    {
+        crate::nut::register_with_params(*self, f, Default::default())
+    }
+    /// Same as [`subscribe_with`](#method.subscribe_with) but with filtering options.
+    pub fn subscribe_with_masked<MSG, P, F>(&self, mask: SubscriptionFilter, f: F) -> SubscriptionId
+    where
+        F: HandlerFn<A, MSG, P> + 'static,
+        MSG: Any,
+    {
+        crate::nut::register_with_params(*self, f, mask)
+    }
+    /// Same as [subscribe](#method.subscribe) but the handler is also called with all retained messages right away.
+    ///
+    /// Messages are only retained for types configured with [`nuts::retain_last`](fn.retain_last.html).
+    /// For other types, this is the same as `subscribe`.
+    /// If the activity is inactive during registration, the retained messages are not delivered to it.
+    ///
+    /// ### Example
+    /// ```rust
+    /// #[derive(Clone)]
+    /// struct Volume(u8);
+    /// struct VolumeSlider { volume: u8 }
+    ///
+    /// nuts::retain_last::<Volume>(1);
+    /// nuts::publish(Volume(30));
+    /// nuts::publish(Volume(70));
+    ///
+    /// // The UI is created later but still learns about the current volume
+    /// let slider = nuts::new_activity(VolumeSlider { volume: 0 });
+    /// slider.subscribe_and_replay(|slider, msg: &Volume| slider.volume = msg.0);
+    /// ```
+    pub fn subscribe_and_replay<F, MSG>(&self, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, &MSG) + 'static,
+        MSG: Any,
+    {
+        crate::nut::register_and_replay(*self, f, Default::default())
+    }
+    /// Subscribes the activity's implementation of [`Subscriber<MSG>`](trait.Subscriber.html).
+    ///
+    /// This is equivalent to `subscribe(A::on_message)`.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use nuts::Subscriber;
+    /// struct Counter { n: u32 }
+    /// struct Increment(u32);
+    ///
+    /// impl Subscriber<Increment> for Counter {
+    ///     fn on_message(&mut self, msg: &Increment) {
+    ///         self.n += msg.0;
+    ///     }
+    /// }
+    ///
+    /// let id = nuts::new_activity(Counter { n: 0 });
+    /// id.subscribe_trait_impl::<Increment>();
+    /// nuts::publish(Increment(2));
+    /// ```
+    pub fn subscribe_trait_impl<MSG>(&self) -> SubscriptionId
+    where
+        A: Subscriber<MSG>,
+        MSG: Any,
+    {
+        crate::nut::register(*self, A::on_message, Default::default())
+    }
+    /// Same as [subscribe](#method.subscribe) but messages arriving while the activity is inactive are handled according to `policy`.
+    ///
+    /// Buffered messages are delivered when the activity becomes active again.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use nuts::{InactivePolicy, LifecycleStatus};
+    /// #[derive(Clone)]
+    /// struct Resize { width: u32 }
+    /// #[derive(Clone)]
+    /// struct Chat { text: String }
+    ///
+    /// struct Window;
+    /// let id = nuts::new_activity(Window);
+    /// id.subscribe_buffered(InactivePolicy::BufferLatest, |_, msg: &Resize| {
+    ///     assert_eq!(msg.width, 1024);
+    /// });
+    /// id.subscribe_buffered(InactivePolicy::BufferAll(100), |_, msg: &Chat| {
+    ///     println!("{}", msg.text);
+    /// });
+    ///
+    /// id.set_status(LifecycleStatus::Inactive);
+    /// nuts::publish(Resize { width: 800 });
+    /// nuts::publish(Resize { width: 1024 });
+    /// nuts::publish(Chat { text: "Hi".to_owned() });
+    /// nuts::publish(Chat { text: "Are you there?".to_owned() });
+    /// // Resize is handled once, the two chat messages are both handled
+    /// id.set_status(LifecycleStatus::Active);
+    /// ```
+    pub fn subscribe_buffered<F, MSG>(&self, policy: InactivePolicy, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, &MSG) + 'static,
+        MSG: Any + Clone,
+    {
+        crate::nut::register_buffered(*self, f, policy)
+    }
     /// Same as [subscribe](#method.subscribe) but gives mutable access to the message object.
     ///
     /// Make sure to use the correct signature for the function, the Rust compiler may give strange error messages otherwise.
     /// For example, the message must be borrowed by the subscription handler.
-    pub fn subscribe_mut<F, MSG>(&self, f: F)
+    ///
+    /// Handlers with mutable access are called after all handlers that only read the message, regardless of registration order.
+    /// Readers therefore always see the message as it was published.
+    /// Changes are visible to the mutable handlers that are called afterwards.
+    /// Use [`nuts::publish_isolated`](fn.publish_isolated.html) to give each subscriber its own copy.
+    ///
+    /// Handlers that take ownership of messages can only be registered as [private channels](#method.private_channel).
+    /// They receive messages sent to a specific activity, which never reach `subscribe` and `subscribe_mut` handlers.
+    pub fn subscribe_mut<F, MSG>(&self, f: F) -> SubscriptionId
     where
         F: Fn(&mut A, &mut MSG) + 'static,
         MSG: Any,
     {
         crate::nut::register_mut(*self, f, Default::default())
     }
+    /// Same as [`subscribe_mut`](#method.subscribe_mut) but refuses to register the handler if the activity has been deleted.
+    ///
+    /// # Errors
+    /// Returns `Error::ActivityDeleted` under the same conditions as [`try_subscribe`](#method.try_subscribe).
+    /// Returns `Error::ImmutableMessage` if `MSG` has been protected with [`protect_message`](fn.protect_message.html).
+    pub fn try_subscribe_mut<F, MSG>(&self, f: F) -> Result<SubscriptionId, Error>
+    where
+        F: Fn(&mut A, &mut MSG) + 'static,
+        MSG: Any,
+    {
+        crate::nut::check_not_deleted((*self).into())?;
+        crate::nut::check_access(
+            Topic::public_message::<MSG>(),
+            crate::nut::iac::subscription::Access::Write,
+        )?;
+        Ok(self.subscribe_mut(f))
+    }
 
     /// Registers a callback closure on an activity with a specific topic to listen to.
     /// Has mutable access to the `DomainState` object.
@@ -188,15 +779,51 @@ impl<A: Activity> ActivityId<A> {
     ///
     /// # Panics
     /// Panics if the activity has not been registered with a domain.    
-    pub fn subscribe_domained<F, MSG>(&self, f: F)
+    pub fn subscribe_domained<F, MSG>(&self, f: F) -> SubscriptionId
     where
         F: Fn(&mut A, &mut DomainState, &MSG) + 'static,
         MSG: Any,
     {
         crate::nut::register_domained(*self, f, Default::default())
     }
+    /// Same as [`subscribe_domained`](#method.subscribe_domained) for handlers that use the ID of another activity, which is passed to the handler instead of being captured by the closure.
+    ///
+    /// The handler only has access to the domain of this activity, never to the domain of the other one.
+    /// In debug builds, handlers that use an activity in another domain are reported by [`nuts::capture_warnings`](fn.capture_warnings.html) right when they are registered.
+    ///
+    /// # Panics
+    /// Panics if the activity has not been registered with a domain.
+    pub fn subscribe_domained_capturing<B, F, MSG>(
+        &self,
+        captured: ActivityId<B>,
+        f: F,
+    ) -> SubscriptionId
+    where
+        B: Activity,
+        F: Fn(&mut A, &mut DomainState, ActivityId<B>, &MSG) + 'static,
+        MSG: Any,
+    {
+        #[cfg(debug_assertions)]
+        crate::nut::lint_capture::<A, B, MSG>(*self, captured);
+        self.subscribe_domained(move |a, domain, msg: &MSG| f(a, domain, captured, msg))
+    }
+    /// Same as [`subscribe_domained`](#method.subscribe_domained) but refuses to register the handler if the activity has been deleted.
+    ///
+    /// # Errors
+    /// Returns `Error::ActivityDeleted` under the same conditions as [`try_subscribe`](#method.try_subscribe).
+    ///
+    /// # Panics
+    /// Panics if the activity has not been registered with a domain.
+    pub fn try_subscribe_domained<F, MSG>(&self, f: F) -> Result<SubscriptionId, Error>
+    where
+        F: Fn(&mut A, &mut DomainState, &MSG) + 'static,
+        MSG: Any,
+    {
+        crate::nut::check_not_deleted((*self).into())?;
+        Ok(self.subscribe_domained(f))
+    }
     /// Same as [`subscribe_domained`](#method.subscribe_domained) but gives mutable access to the message object.
-    pub fn subscribe_domained_mut<F, MSG>(&self, f: F)
+    pub fn subscribe_domained_mut<F, MSG>(&self, f: F) -> SubscriptionId
     where
         F: Fn(&mut A, &mut DomainState, &mut MSG) + 'static,
         MSG: Any,
@@ -213,7 +840,7 @@ impl<A: Activity> ActivityId<A> {
     ///
     /// Since the listener takes ownership, it is not possible to have more than one private channel active for the same activity at the same time.
     /// If multiple private channels are added to an activity, only the last listener is retained. (Older ones are replaced and deleted)
-    pub fn private_channel<F, MSG>(&self, f: F)
+    pub fn private_channel<F, MSG>(&self, f: F) -> SubscriptionId
     where
         F: Fn(&mut A, MSG) + 'static,
         MSG: Any,
@@ -221,11 +848,35 @@ impl<A: Activity> ActivityId<A> {
         crate::nut::register_owned(*self, f, Default::default())
     }
 
+    /// Variant of `private_channel` that handles a single message and is removed afterwards.
+    ///
+    /// The handler is only required to be `FnOnce`, thus it can move captured resources that cannot be cloned into the activity.
+    /// Messages of the same type sent to the activity after the first one are dropped, unless a new channel has been registered.
+    ///
+    /// ### Example
+    /// ```rust
+    /// struct Renderer { context: Option<Context> }
+    /// struct Context;
+    /// struct Attach;
+    ///
+    /// let renderer = nuts::new_activity(Renderer { context: None });
+    /// let context = Context;
+    /// renderer.subscribe_owned_once(move |renderer, _: Attach| renderer.context = Some(context));
+    /// nuts::send_to::<Renderer, _>(Attach);
+    /// ```
+    pub fn subscribe_owned_once<F, MSG>(&self, f: F) -> SubscriptionId
+    where
+        F: FnOnce(&mut A, MSG) + 'static,
+        MSG: Any,
+    {
+        crate::nut::register_owned_once(*self, f)
+    }
+
     /// Variant of `private_channel` with access to the domain state.
     ///
     /// # Panics
     /// Panics if the activity has not been registered with a domain.   
-    pub fn private_domained_channel<F, MSG>(&self, f: F)
+    pub fn private_domained_channel<F, MSG>(&self, f: F) -> SubscriptionId
     where
         F: Fn(&mut A, &mut DomainState, MSG) + 'static,
         MSG: Any,
@@ -234,7 +885,7 @@ impl<A: Activity> ActivityId<A> {
     }
 
     /// Variant of `private_channel` with subscription mask.
-    pub fn private_channel_masked<F, MSG>(&self, mask: SubscriptionFilter, f: F)
+    pub fn private_channel_masked<F, MSG>(&self, mask: SubscriptionFilter, f: F) -> SubscriptionId
     where
         F: Fn(&mut A, MSG) + 'static,
         MSG: Any,
@@ -246,7 +897,11 @@ impl<A: Activity> ActivityId<A> {
     ///
     /// # Panics
     /// Panics if the activity has not been registered with a domain.   
-    pub fn private_domained_channel_masked<F, MSG>(&self, mask: SubscriptionFilter, f: F)
+    pub fn private_domained_channel_masked<F, MSG>(
+        &self,
+        mask: SubscriptionFilter,
+        f: F,
+    ) -> SubscriptionId
     where
         F: Fn(&mut A, &mut DomainState, MSG) + 'static,
         MSG: Any,
@@ -254,8 +909,68 @@ impl<A: Activity> ActivityId<A> {
         crate::nut::register_domained_owned(*self, f, mask)
     }
 
+    /// Filters messages by their content, before they reach the subscription handler.
+    ///
+    /// The handler registered on the returned object is only called when `predicate` returns true for the message.
+    /// The predicate is checked before the activity status, so it should be cheap and must not have side-effects.
+    ///
+    /// ### Example
+    /// ```rust
+    /// #[derive(PartialEq)]
+    /// enum Key { Escape, Enter }
+    /// struct KeyDown(Key);
+    /// struct Menu { open: bool }
+    ///
+    /// let id = nuts::new_activity(Menu { open: true });
+    /// id.when(|msg: &KeyDown| msg.0 == Key::Escape)
+    ///     .subscribe(|menu, _msg| menu.open = false);
+    ///
+    /// nuts::publish(KeyDown(Key::Enter));
+    /// nuts::publish(KeyDown(Key::Escape));
+    /// ```
+    pub fn when<MSG, P>(&self, predicate: P) -> ConditionalSubscription<A, MSG, P>
+    where
+        MSG: Any,
+        P: Fn(&MSG) -> bool + 'static,
+    {
+        ConditionalSubscription::new(*self, predicate)
+    }
+
+    /// Collects messages and calls the handler registered on the returned object with batches of them, instead of once per message.
+    ///
+    /// The window is either a number of messages or a `Duration` measured with [`nuts::advance_time`](fn.advance_time.html).
+    /// This suits consumers that only need summaries, like statistics of frequent events.
+    /// Messages that have not been delivered when the activity is deleted are dropped.
+    ///
+    /// # Panics
+    /// Panics if the window is zero messages or zero time.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use std::time::Duration;
+    /// #[derive(Clone)]
+    /// struct Click(i32, i32);
+    /// struct Stats { batches: usize, clicks: usize }
+    ///
+    /// let stats = nuts::new_activity(Stats { batches: 0, clicks: 0 });
+    /// stats.digest(10).subscribe(|stats, _clicks: Vec<Click>| stats.batches += 1);
+    /// stats.digest(Duration::from_secs(1)).subscribe(|stats, clicks: Vec<Click>| stats.clicks += clicks.len());
+    ///
+    /// for i in 0..25 {
+    ///     nuts::publish(Click(i, i));
+    /// }
+    /// // Delivers all 25 clicks to the second handler, the first one has received two batches of 10
+    /// nuts::advance_time(Duration::from_secs(1));
+    /// ```
+    pub fn digest<MSG>(&self, window: impl Into<DigestWindow>) -> DigestSubscription<A, MSG>
+    where
+        MSG: Any + Clone,
+    {
+        DigestSubscription::new(*self, window.into())
+    }
+
     /// Registers a callback closure on an activity with a specific topic to listen to with filtering options.
-    pub fn subscribe_masked<F, MSG>(&self, mask: SubscriptionFilter, f: F)
+    pub fn subscribe_masked<F, MSG>(&self, mask: SubscriptionFilter, f: F) -> SubscriptionId
     where
         F: Fn(&mut A, &MSG) + 'static,
         MSG: Any,
@@ -263,7 +978,7 @@ impl<A: Activity> ActivityId<A> {
         crate::nut::register(*self, f, mask)
     }
     /// Same as [`subscribe_masked`](#method.subscribe_masked) but gives mutable access to the message object.
-    pub fn subscribe_masked_mut<F, MSG>(&self, mask: SubscriptionFilter, f: F)
+    pub fn subscribe_masked_mut<F, MSG>(&self, mask: SubscriptionFilter, f: F) -> SubscriptionId
     where
         F: Fn(&mut A, &mut MSG) + 'static,
         MSG: Any,
@@ -276,7 +991,11 @@ impl<A: Activity> ActivityId<A> {
     ///
     /// # Panics
     /// Panics if the activity has not been registered with a domain.
-    pub fn subscribe_domained_masked<F, MSG>(&self, mask: SubscriptionFilter, f: F)
+    pub fn subscribe_domained_masked<F, MSG>(
+        &self,
+        mask: SubscriptionFilter,
+        f: F,
+    ) -> SubscriptionId
     where
         F: Fn(&mut A, &mut DomainState, &MSG) + 'static,
         MSG: Any,
@@ -284,7 +1003,11 @@ impl<A: Activity> ActivityId<A> {
         crate::nut::register_domained(*self, f, mask)
     }
     /// Same as [`subscribe_domained_masked`](#method.subscribe_domained_masked) but gives mutable access to the message object.
-    pub fn subscribe_domained_masked_mut<F, MSG>(&self, mask: SubscriptionFilter, f: F)
+    pub fn subscribe_domained_masked_mut<F, MSG>(
+        &self,
+        mask: SubscriptionFilter,
+        f: F,
+    ) -> SubscriptionId
     where
         F: Fn(&mut A, &mut DomainState, &mut MSG) + 'static,
         MSG: Any,
@@ -292,14 +1015,220 @@ impl<A: Activity> ActivityId<A> {
         crate::nut::register_domained_mut(*self, f, mask)
     }
 
+    /// Evicts the activity from memory after it has been inactive for `idle_broadcasts` broadcasts.
+    ///
+    /// On eviction, the activity is serialized with `save` and dropped.
+    /// As soon as one of its handlers is called, it is restored with `load`.
+    /// This happens when the activity becomes active again, or when it receives a message for which a handler has been registered with `SubscriptionFilter::no_filter()`.
+    /// This is useful on memory-constrained targets, where many dormant activities hold on to large assets.
+    ///
+    /// Nuts does not depend on a serialization library. Use any format, for example serde with a compact binary encoding, or keep only what is needed to reload the assets.
+    /// With the feature `serde`, [`evict_when_idle_serde`](#method.evict_when_idle_serde) does this for activities that implement serde's traits.
+    /// Idle activities are checked whenever all queued messages have been processed.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use nuts::LifecycleStatus;
+    /// struct Screen { name: String, texture: Vec<u8> }
+    ///
+    /// let screen = nuts::new_activity(Screen { name: "settings".to_owned(), texture: vec![0; 1 << 20] });
+    /// screen.evict_when_idle(
+    ///     10,
+    ///     |screen| screen.name.clone().into_bytes(),
+    ///     |bytes| Screen { name: String::from_utf8_lossy(bytes).into_owned(), texture: vec![0; 1 << 20] },
+    /// );
+    /// screen.on_enter(|screen| println!("Showing {}", screen.name));
+    /// screen.set_status(LifecycleStatus::Inactive);
+    /// // ... after 10 broadcasts, the screen is evicted ...
+    /// screen.set_status(LifecycleStatus::Active); // restores the screen
+    /// ```
+    pub fn evict_when_idle(
+        &self,
+        idle_broadcasts: u64,
+        save: impl Fn(&A) -> Vec<u8> + 'static,
+        load: impl Fn(&[u8]) -> A + 'static,
+    ) {
+        crate::nut::evict_when_idle(*self, Codec::new(idle_broadcasts, save, load));
+    }
+
+    /// Same as [`evict_when_idle`](#method.evict_when_idle) but the activity is saved and restored with its implementations of `Serialize` and `Deserialize`.
+    ///
+    /// The activity is stored as JSON, which is easy to inspect but not compact.
+    /// Use `evict_when_idle` with another format if the serialized activities are large.
+    ///
+    /// Only available with the feature `serde`.
+    ///
+    /// # Panics
+    /// Panics on eviction if the activity cannot be serialized, for example because it contains a map with keys that are not strings.
+    ///
+    /// ### Example
+    /// ```rust
+    /// # #[cfg(feature = "serde")]
+    /// # {
+    /// use nuts::LifecycleStatus;
+    /// type Playlist = Vec<String>;
+    ///
+    /// let playlist = nuts::new_activity::<Playlist>(vec!["intro.ogg".to_owned()]);
+    /// playlist.evict_when_idle_serde(10);
+    /// playlist.set_status(LifecycleStatus::Inactive);
+    /// # }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn evict_when_idle_serde(&self, idle_broadcasts: u64)
+    where
+        A: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.evict_when_idle(
+            idle_broadcasts,
+            |activity| serde_json::to_vec(activity).expect("Failed to serialize evicted activity"),
+            |bytes| serde_json::from_slice(bytes).expect("Failed to restore evicted activity"),
+        );
+    }
+
+    /// Returns true if the ID points to an existing activity of type `A`.
+    ///
+    /// IDs become invalid when the activity has been deleted.
+    /// IDs that have been created by another thread or forged from an integer may be invalid, too.
+    ///
+    /// # Panics
+    /// Panics if called from inside a subscription handler.
+    pub fn is_valid(&self) -> bool {
+        crate::nut::is_valid(*self)
+    }
+
+    /// Returns true if the activity has been fully added to Nuts.
+    ///
+    /// Activities created inside a subscription handler are inchoate at first, they are added after the handler has returned.
+    /// Until then, this returns false.
+    /// Deleting the activity does not change the result, use [`is_valid`](#method.is_valid) for that.
+    ///
+    /// Unlike `is_valid`, this can be called from inside subscription handlers.
+    pub fn is_live(&self) -> bool {
+        crate::nut::is_live(self.id)
+    }
+
     /// Changes the lifecycle status of the activity
     ///
+    /// If the activity is not [live](#method.is_live) yet, the change is queued and applied right after the activity has been added.
+    /// Handlers registered before the status change are then called as usual, e.g. `on_leave` when setting it to `Inactive`.
+    ///
     /// # Panics
     /// If status is set to something other than Deleted after it has been Deleted
     pub fn set_status(&self, status: LifecycleStatus) {
         crate::nut::set_status((*self).into(), status);
     }
 
+    /// Deletes the activity and frees its slot, such that the next activity created takes it over.
+    ///
+    /// Like [`set_status(LifecycleStatus::Deleted)`](#method.set_status), this calls `on_leave` if the activity is active, and then the `on_delete` handler.
+    /// Additionally, all its subscriptions are dropped right away, instead of when calling [`nuts::compact`](fn.compact.html).
+    /// Long-running programs that create and delete many activities therefore do not grow.
+    ///
+    /// The ID and all its copies become stale, they never refer to the activity that takes over the slot.
+    /// Inside a subscription handler, the activity is deleted in queue order, like with `set_status`.
+    ///
+    /// ### Example
+    /// ```rust
+    /// struct Bullet { damage: u32 }
+    /// struct Tick;
+    ///
+    /// let bullet = nuts::new_activity(Bullet { damage: 3 });
+    /// bullet.subscribe(|bullet, _: &Tick| println!("{} damage", bullet.damage));
+    /// bullet.on_delete(|bullet| println!("Bullet with {} damage is gone", bullet.damage));
+    /// bullet.delete();
+    /// assert!(!bullet.is_valid());
+    /// assert_eq!(0, nuts::subscriber_count::<Tick>());
+    /// ```
+    pub fn delete(&self) {
+        crate::nut::delete((*self).into());
+    }
+
+    /// Changes the lifecycle status of the activity before any further message is dispatched.
+    ///
+    /// Inside a subscription handler, [`set_status`](#method.set_status) is applied in queue order.
+    /// Messages published earlier in the same cascade therefore still reach the activity after it has been set to `Inactive`.
+    /// With this method, the change is applied as soon as the current handler has returned, ahead of all queued messages.
+    /// The `on_enter` and `on_leave` handlers are still queued like other messages.
+    ///
+    /// Outside of subscription handlers, this behaves exactly like `set_status`.
+    /// The same holds for activities that are not [live](#method.is_live) yet, the change is applied right after they have been added.
+    ///
+    /// # Panics
+    /// If status is set to something other than Deleted after it has been Deleted
+    pub fn set_status_immediate(&self, status: LifecycleStatus) {
+        crate::nut::set_status_immediate((*self).into(), status);
+    }
+
+    /// Same as [`set_status`](#method.set_status) but returns an error instead of panicking if the activity has been deleted.
+    ///
+    /// # Errors
+    /// Returns `Error::ActivityDeleted` under the same conditions as [`try_subscribe`](#method.try_subscribe).
+    /// Setting the status of a deleted activity to `Deleted` again is an error, too.
+    pub fn try_set_status(&self, status: LifecycleStatus) -> Result<(), Error> {
+        let id: UncheckedActivityId = (*self).into();
+        id.try_set_status(status)
+    }
+
+    /// Assigns the activity to a phase of the frame, see [`nuts::run_frame`](fn.run_frame.html).
+    ///
+    /// Published messages then only reach subscriptions of this activity when `run_frame` processes this phase.
+    /// Messages sent privately to the activity and lifecycle events are not affected.
+    pub fn set_phase(&self, phase: Phase) {
+        crate::nut::set_phase(self.id, phase);
+    }
+
+    /// Lets Nuts drive a future that belongs to this activity.
+    ///
+    /// The future is polled once right away (or after the current message has been handled) and afterwards whenever it has been woken.
+    /// Wakes are processed when all queued messages have been handled, or with [`nuts::poll_tasks`](fn.poll_tasks.html).
+    /// Futures are pinned on the heap, they do not have to implement `Unpin`.
+    ///
+    /// The future is dropped when the activity is deleted, even if it has not completed.
+    /// Futures of published messages that it awaits are dropped with it, which releases their response slots.
+    /// Use [`attach_future_with`](#method.attach_future_with) to choose what happens on deletion and to be notified about it.
+    ///
+    /// ### Example
+    /// ```rust
+    /// struct Loader;
+    /// struct Loaded;
+    ///
+    /// let id = nuts::new_activity(Loader);
+    /// id.attach_future(async {
+    ///     nuts::publish_awaiting(Loaded).await_dispatch().await;
+    ///     println!("Everybody knows it's loaded");
+    /// });
+    /// ```
+    pub fn attach_future(&self, future: impl core::future::Future<Output = ()> + 'static) {
+        self.attach_future_with(TaskPolicy::Cancel, future);
+    }
+
+    /// Same as [`attach_future`](#method.attach_future) but `policy` defines what happens when the activity is deleted.
+    ///
+    /// The returned handle resolves to `Ok(())` when the future has completed.
+    /// If the future is cancelled because the activity has been deleted, the handle resolves to `Err(Error::TaskCancelled)` instead.
+    /// Detached futures keep running until they complete, even after the activity has been deleted.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use nuts::{Error, LifecycleStatus, TaskPolicy};
+    /// struct Download;
+    ///
+    /// let id = nuts::new_activity(Download);
+    /// let handle = id.attach_future_with(TaskPolicy::Cancel, async {
+    ///     // waiting for a response that never arrives
+    ///     core::future::pending::<()>().await;
+    /// });
+    /// id.set_status(LifecycleStatus::Deleted);
+    /// assert_eq!(Err(Error::TaskCancelled), futures::executor::block_on(handle));
+    /// ```
+    pub fn attach_future_with(
+        &self,
+        policy: TaskPolicy,
+        future: impl core::future::Future<Output = ()> + 'static,
+    ) -> TaskHandle {
+        crate::nut::attach_future((*self).into(), Box::pin(future), policy)
+    }
+
     /// Publish a message to a specific activity.
     ///
     /// If you lack access to an `ActivityId`, use `nuts::send_to()` or `UncheckedActivityId::private_message`.
@@ -308,9 +1237,94 @@ impl<A: Activity> ActivityId<A> {
         let id: UncheckedActivityId = (*self).into();
         id.private_message(msg);
     }
+
+    /// Registers a handler that answers requests sent with [`ask`](#method.ask).
+    ///
+    /// Requests are private messages, like with `private_channel`, the handler takes ownership of the request.
+    /// Only one handler per pair of request and response type can be registered on an activity, a second registration replaces the first.
+    pub fn subscribe_request<F, REQ, RESP>(&self, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, REQ) -> RESP + 'static,
+        REQ: Any,
+        RESP: Any,
+    {
+        self.private_channel(move |a, request: Request<REQ, RESP>| request.answer(|req| f(a, req)))
+    }
+
+    /// Sends a request privately to the activity and returns a future that resolves with the reply.
+    ///
+    /// The request is queued right away, like any other private message, and handled by the handler registered with [`subscribe_request`](#method.subscribe_request).
+    /// The future resolves as soon as that handler has returned.
+    ///
+    /// The future resolves with `Error::NoReply` if the request has been handled without reply.
+    /// This happens if the activity has no request handler for `REQ` and `RESP`, or if the activity has been deleted.
+    ///
+    /// ### Example
+    /// ```rust
+    /// struct Inventory { gold: u32 }
+    /// struct Withdraw(u32);
+    ///
+    /// let bank = nuts::new_activity(Inventory { gold: 100 });
+    /// bank.subscribe_request(|inventory, Withdraw(amount)| {
+    ///     let paid = amount.min(inventory.gold);
+    ///     inventory.gold -= paid;
+    ///     paid
+    /// });
+    ///
+    /// let paid = futures::executor::block_on(bank.ask::<_, u32>(Withdraw(150)));
+    /// assert_eq!(Ok(100), paid);
+    /// ```
+    pub fn ask<REQ: Any, RESP: Any>(&self, req: REQ) -> Reply<RESP> {
+        crate::nut::ask((*self).into(), req)
+    }
+
+    /// Registers the activity as the responder for requests of type `REQ` with responses of type `RESP`, sent with [`nuts::request`](fn.request.html).
+    ///
+    /// There is exactly one responder per pair of request and response type.
+    /// Registering another one, on this or another activity, replaces the previous responder, which is unsubscribed.
+    /// The handler can also be called with [`ask`](#method.ask), like a handler registered with [`subscribe_request`](#method.subscribe_request).
+    pub fn respond<REQ: Any, RESP: Any>(
+        &self,
+        f: impl Fn(&mut A, REQ) -> RESP + 'static,
+    ) -> SubscriptionId {
+        let sub = self.subscribe_request(f);
+        crate::nut::set_responder::<REQ, RESP>((*self).into(), sub);
+        sub
+    }
+}
+
+impl<A: ParallelSafe> ActivityId<A> {
+    /// Same as [subscribe](#method.subscribe) but the handler is registered as [parallel-safe](trait.ParallelSafe.html).
+    ///
+    /// Parallel-safe handlers of all activities are called before all other handlers of the same message.
+    /// The handler must be `Send` and `Sync`, such that it can be moved to another thread once handlers are executed in parallel.
+    /// For the same reason, the message must be `Sync`, it will be shared between the threads of all parallel-safe handlers.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use nuts::ParallelSafe;
+    ///
+    /// struct Pathfinder { requests: usize }
+    /// impl ParallelSafe for Pathfinder {}
+    /// struct FindPath { from: u32, to: u32 }
+    ///
+    /// let id = nuts::new_activity(Pathfinder { requests: 0 });
+    /// id.subscribe_parallel(|pathfinder, _msg: &FindPath| pathfinder.requests += 1);
+    /// nuts::publish(FindPath { from: 1, to: 2 });
+    /// ```
+    pub fn subscribe_parallel<F, MSG>(&self, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, &MSG) + Send + Sync + 'static,
+        MSG: Any + Sync,
+    {
+        crate::nut::register_parallel(*self, f)
+    }
 }
 
 impl UncheckedActivityId {
+    pub(crate) fn new(index: usize, generation: u32) -> Self {
+        Self { index, generation }
+    }
     /// Changes the lifecycle status of the activity
     ///
     /// # Panics
@@ -318,6 +1332,23 @@ impl UncheckedActivityId {
     pub fn set_status(&self, status: LifecycleStatus) {
         crate::nut::set_status(*self, status);
     }
+    /// Same as [`ActivityId::set_status_immediate`](struct.ActivityId.html#method.set_status_immediate).
+    ///
+    /// # Panics
+    /// If status is set to something other than Deleted after it has been Deleted
+    pub fn set_status_immediate(&self, status: LifecycleStatus) {
+        crate::nut::set_status_immediate(*self, status);
+    }
+    /// Same as [`set_status`](#method.set_status) but returns an error instead of panicking if the activity has been deleted.
+    ///
+    /// # Errors
+    /// Returns `Error::ActivityDeleted` if the activity has been deleted, or if its deletion is already queued.
+    /// Inside subscription handlers, the status of activities cannot be checked and the call behaves like `set_status`.
+    pub fn try_set_status(&self, status: LifecycleStatus) -> Result<(), Error> {
+        crate::nut::check_not_deleted(*self)?;
+        self.set_status(status);
+        Ok(())
+    }
     /// Publish a message to a specific activity.
     ///
     /// If you lack access to an `UncheckedActivityId`, use `nuts::send_to()`, it is equivalent.
@@ -326,15 +1357,18 @@ impl UncheckedActivityId {
     }
     /// A unique number for the activity.
     /// Can be used for serialization in combination with `forge_from_usize`
+    ///
+    /// The generation is not included, use [`ActivityId::to_token`](struct.ActivityId.html#method.to_token) to keep it.
     pub fn as_usize(&self) -> usize {
         self.index
     }
     /// Can be used for deserialization in combination with `as_usize`
     ///
+    /// The forged ID refers to the first generation of the slot.
     /// If used in any other way, you might experience panics.
     /// Right now, there should still be no UB but that might change in future versions.
     pub fn forge_from_usize(index: usize) -> Self {
-        Self { index }
+        Self::new(index, 0)
     }
 }
 
@@ -351,8 +1385,8 @@ impl<A> Clone for ActivityId<A> {
     }
 }
 
-impl<A> Into<UncheckedActivityId> for ActivityId<A> {
-    fn into(self) -> UncheckedActivityId {
-        self.id
+impl<A> From<ActivityId<A>> for UncheckedActivityId {
+    fn from(id: ActivityId<A>) -> Self {
+        id.id
     }
 }
@@ -1,6 +1,13 @@
 use super::*;
+use crate::debug::DebugTypeName;
 use crate::nut::iac::subscription::OnDelete;
+use crate::nut::IMPOSSIBLE_ERR_MSG;
 use core::any::TypeId;
+use std::collections::HashMap;
+
+mod eviction;
+
+pub(crate) use eviction::Codec;
 
 /// A collection of heterogenous Activities
 ///
@@ -11,6 +18,17 @@ pub(crate) struct ActivityContainer {
     data: Vec<Option<Box<dyn Any>>>,
     active: Vec<LifecycleStatus>,
     on_delete: Vec<OnDelete>,
+    domains: Vec<DomainId>,
+    type_names: Vec<DebugTypeName>,
+    /// Incremented when the activity in a slot is deleted, IDs of older generations are stale
+    generations: Vec<u32>,
+    /// Activities that are evicted when idle, by index
+    codecs: HashMap<usize, Codec>,
+    /// Slots of activities deleted with `ActivityId::delete`, taken by the next activities that are added
+    free: Vec<usize>,
+    /// Used to verify that IDs are used with the correct type, even after the activity has been deleted
+    #[cfg(debug_assertions)]
+    type_ids: Vec<TypeId>,
 }
 
 impl ActivityContainer {
@@ -19,6 +37,13 @@ impl ActivityContainer {
             data: vec![Some(Box::new(NotAnActivity))],
             active: vec![LifecycleStatus::Active],
             on_delete: vec![OnDelete::None],
+            domains: vec![DomainId::default()],
+            type_names: vec![DebugTypeName::new::<NotAnActivity>()],
+            generations: vec![0],
+            codecs: HashMap::new(),
+            free: vec![],
+            #[cfg(debug_assertions)]
+            type_ids: vec![TypeId::of::<NotAnActivity>()],
         }
     }
     pub(crate) fn add<A: Activity>(
@@ -27,23 +52,71 @@ impl ActivityContainer {
         domain: DomainId,
         status: LifecycleStatus,
     ) -> ActivityId<A> {
+        if let Some(i) = self.free.pop() {
+            self.data[i] = Some(Box::new(a));
+            self.active[i] = status;
+            self.on_delete[i] = OnDelete::None;
+            self.domains[i] = domain;
+            self.type_names[i] = DebugTypeName::new::<A>();
+            #[cfg(debug_assertions)]
+            {
+                self.type_ids[i] = TypeId::of::<A>();
+            }
+            return ActivityId::new(i, domain).with_generation(self.generations[i]);
+        }
         let i = self.data.len();
         self.data.push(Some(Box::new(a)));
         self.active.push(status);
         self.on_delete.push(OnDelete::None);
+        self.domains.push(domain);
+        self.type_names.push(DebugTypeName::new::<A>());
+        self.generations.push(0);
+        #[cfg(debug_assertions)]
+        self.type_ids.push(TypeId::of::<A>());
         ActivityId::new(i, domain)
     }
+    /// True iff the ID refers to the current generation of an existing slot.
+    pub(crate) fn is_current(&self, id: UncheckedActivityId) -> bool {
+        self.generations.get(id.index) == Some(&id.generation)
+    }
+    /// Stale IDs always report `Deleted`.
     pub(crate) fn status(&self, id: UncheckedActivityId) -> LifecycleStatus {
-        self.active[id.index]
+        if self.is_current(id) {
+            self.active[id.index]
+        } else {
+            LifecycleStatus::Deleted
+        }
     }
     pub(crate) fn set_status(&mut self, id: UncheckedActivityId, status: LifecycleStatus) {
-        self.active[id.index] = status
+        if self.is_current(id) {
+            self.active[id.index] = status
+        }
+    }
+    /// Stale IDs have no domain.
+    pub(crate) fn domain(&self, id: UncheckedActivityId) -> Option<DomainId> {
+        self.is_current(id).then(|| self.domains[id.index])
     }
+    /// Handlers for stale IDs are dropped, they must not end up with the activity that reuses the slot.
     pub(crate) fn add_on_delete(&mut self, id: UncheckedActivityId, f: OnDelete) {
-        self.on_delete[id.index] = f;
+        if self.is_current(id) {
+            self.on_delete[id.index] = f;
+        }
     }
-    pub(crate) fn delete(&mut self, id: UncheckedActivityId, managed_state: &mut ManagedState) {
+    /// Returns false if the activity had already been deleted
+    pub(crate) fn delete(
+        &mut self,
+        id: UncheckedActivityId,
+        managed_state: &mut ManagedState,
+    ) -> bool {
+        if !self.is_current(id) {
+            return false;
+        }
+        if !matches!(self.on_delete[id.index], OnDelete::None) {
+            self.ensure_loaded(id.index);
+        }
+        self.forget_codec(id.index);
         if let Some(activity) = self.data[id.index].take() {
+            self.generations[id.index] = self.generations[id.index].wrapping_add(1);
             // Taking ownership to call FnOnce
             let mut on_delete = OnDelete::None;
             std::mem::swap(&mut on_delete, &mut self.on_delete[id.index]);
@@ -52,45 +125,196 @@ impl ActivityContainer {
                 OnDelete::Simple(f) => f(activity),
                 OnDelete::WithDomain(f) => f(activity, managed_state),
             }
+            return true;
         }
+        false
+    }
+    /// True if the next activity takes over the slot of a deleted activity
+    pub(crate) fn has_free_slot(&self) -> bool {
+        !self.free.is_empty()
+    }
+    /// Makes the slot of a deleted activity available to the next activity
+    pub(crate) fn release_slot(&mut self, index: usize) {
+        if self.is_deleted_slot(index) && !self.free.contains(&index) {
+            self.free.push(index);
+        }
+    }
+    /// Returns the activity data, unless the activity has been deleted.
+    pub(crate) fn get<A: Activity>(&mut self, id: ActivityId<A>) -> Option<&A> {
+        if !self.is_current(id.id) {
+            return None;
+        }
+        self.ensure_loaded(id.id.index);
+        self.data
+            .get(id.id.index)
+            .and_then(|maybe_activity| maybe_activity.as_ref())
+            .map(|a| a.downcast_ref().expect(IMPOSSIBLE_ERR_MSG))
     }
     pub(crate) fn len(&self) -> usize {
         self.data.len()
     }
+    /// Number of activities that have not been deleted, without `NotAnActivity`
+    pub(crate) fn count_live(&self) -> usize {
+        self.active
+            .iter()
+            .skip(1)
+            .filter(|status| **status != LifecycleStatus::Deleted)
+            .count()
+    }
+    /// True if the activity in the slot has been deleted
+    pub(crate) fn is_deleted_slot(&self, index: usize) -> bool {
+        matches!(self.data.get(index), Some(None))
+    }
+    /// Releases unused capacity and returns the number of slots that have been released.
+    ///
+    /// Slots of deleted activities are kept, their generation is needed to detect stale IDs.
+    pub(crate) fn shrink_to_fit(&mut self) -> usize {
+        let before = self.data.capacity();
+        self.data.shrink_to_fit();
+        self.active.shrink_to_fit();
+        self.on_delete.shrink_to_fit();
+        self.domains.shrink_to_fit();
+        self.type_names.shrink_to_fit();
+        self.generations.shrink_to_fit();
+        self.codecs.shrink_to_fit();
+        self.free.shrink_to_fit();
+        #[cfg(debug_assertions)]
+        self.type_ids.shrink_to_fit();
+        before - self.data.capacity()
+    }
 
     pub(crate) fn append(&mut self, other: &mut Self) {
         self.active.append(&mut other.active);
         self.data.append(&mut other.data);
         self.on_delete.append(&mut other.on_delete);
+        self.domains.append(&mut other.domains);
+        self.type_names.append(&mut other.type_names);
+        self.generations.append(&mut other.generations);
+        #[cfg(debug_assertions)]
+        self.type_ids.append(&mut other.type_ids);
+    }
+    /// True iff the ID points to an activity of the correct type that has not been deleted.
+    pub(crate) fn is_valid<A: Activity>(&self, id: ActivityId<A>) -> bool {
+        self.is_current(id.id)
+            && matches!(
+                self.data.get(id.id.index),
+                Some(Some(activity)) if Self::stored_type(activity.as_ref()) == TypeId::of::<A>()
+            )
+    }
+    /// Panics with a descriptive message if the ID is not valid.
+    #[cfg(debug_assertions)]
+    #[inline]
+    fn verify<A: Activity>(&self, id: ActivityId<A>) {
+        self.verify_slot(id.id, TypeId::of::<A>(), std::any::type_name::<A>())
+    }
+    #[cfg(debug_assertions)]
+    fn verify_slot(&self, id: UncheckedActivityId, type_id: TypeId, expected: &'static str) {
+        let index = id.index;
+        assert!(
+            index < self.data.len(),
+            "ActivityId<{}> used for slot {}, which does not exist. The ID may stem from another thread.",
+            expected,
+            index,
+        );
+        // Checked before the type, the slot may have been taken over by an activity of another type
+        assert!(
+            self.data[index].is_some() && self.generations[index] == id.generation,
+            "ActivityId<{}> used after the activity has been deleted",
+            expected,
+        );
+        assert!(
+            self.type_ids[index] == type_id,
+            "ActivityId<{}> used for slot containing {}",
+            expected,
+            self.type_names[index].name(),
+        );
+    }
+    /// Returns the activity if the filter accepts its current status.
+    #[inline]
+    pub(crate) fn filtered<A: Activity>(
+        &mut self,
+        id: ActivityId<A>,
+        filter: &SubscriptionFilter,
+    ) -> Option<&mut A> {
+        if !filter.accepts(self.status(id.id)) {
+            return None;
+        }
+        #[cfg(debug_assertions)]
+        self.verify(id);
+        Some(
+            self.slot_mut(id.id)
+                .downcast_mut()
+                .expect(IMPOSSIBLE_ERR_MSG),
+        )
+    }
+    fn slot(&self, id: UncheckedActivityId) -> &dyn Any {
+        self.data[id.index]
+            .as_ref()
+            .expect("Missing activity")
+            .as_ref()
+    }
+    fn slot_mut(&mut self, id: UncheckedActivityId) -> &mut dyn Any {
+        self.ensure_loaded(id.index);
+        self.data[id.index]
+            .as_mut()
+            .expect("Missing activity")
+            .as_mut()
+    }
+    /// Calls `f` for every activity that has not been deleted, in order of creation.
+    pub(crate) fn visit(&self, mut f: impl FnMut(ActivityMeta, &dyn Any)) {
+        // Index 0 is `NotAnActivity`, which is internal
+        for index in 1..self.data.len() {
+            if let Some(activity) = &self.data[index] {
+                let meta = ActivityMeta {
+                    id: UncheckedActivityId::new(index, self.generations[index]),
+                    type_name: self.type_names[index].name(),
+                    domain: self.domains[index].index(),
+                    status: self.active[index],
+                    evicted: self.is_evicted(index),
+                };
+                f(meta, activity.as_ref());
+            }
+        }
+    }
+    /// Number of living activities per domain index
+    pub(crate) fn count_per_domain(&self) -> Vec<usize> {
+        let mut counts = vec![];
+        for (data, domain) in self.data.iter().zip(&self.domains).skip(1) {
+            if let (Some(_), Some(d)) = (data, domain.index()) {
+                if counts.len() <= d {
+                    counts.resize(d + 1, 0);
+                }
+                counts[d] += 1;
+            }
+        }
+        counts
     }
     pub(crate) fn id_lookup(&self, t: TypeId) -> Option<UncheckedActivityId> {
         // This is not the most efficient (if there are many activities) but it does the job to get something working.
         // If anyone ever find this to be a performance bottleneck in a real application, this can be fixed with some smarter implementation.
         #[allow(clippy::unwrap_used)]
-        if let Some(index) = self.data.iter().position(|maybe_activity| {
-            maybe_activity.is_some() && (*maybe_activity.as_ref().unwrap().as_ref()).type_id() == t
-        }) {
-            Some(UncheckedActivityId { index })
-        } else {
-            None
-        }
+        self.data
+            .iter()
+            .position(|maybe_activity| {
+                maybe_activity.is_some()
+                    && Self::stored_type(maybe_activity.as_ref().unwrap().as_ref()) == t
+            })
+            .map(|index| UncheckedActivityId::new(index, self.generations[index]))
     }
 }
 
 impl<A: Activity> Index<ActivityId<A>> for ActivityContainer {
     type Output = dyn Any;
     fn index(&self, id: ActivityId<A>) -> &Self::Output {
-        self.data[id.id.index]
-            .as_ref()
-            .expect("Missing activity")
-            .as_ref()
+        #[cfg(debug_assertions)]
+        self.verify(id);
+        self.slot(id.id)
     }
 }
 impl<A: Activity> IndexMut<ActivityId<A>> for ActivityContainer {
     fn index_mut(&mut self, id: ActivityId<A>) -> &mut Self::Output {
-        self.data[id.id.index]
-            .as_mut()
-            .expect("Missing activity")
-            .as_mut()
+        #[cfg(debug_assertions)]
+        self.verify(id);
+        self.slot_mut(id.id)
     }
 }
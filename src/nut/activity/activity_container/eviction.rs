@@ -0,0 +1,145 @@
+//! Eviction of idle activities to a serialized form, see `ActivityId::evict_when_idle`.
+//!
+//! An evicted activity keeps its slot, which then holds a `Dormant` box with the serialized data.
+//! The activity is deserialized as soon as it is accessed mutably, which is the case when any of its handlers is called.
+
+use super::ActivityContainer;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::{LifecycleStatus, UncheckedActivityId};
+use core::any::{Any, TypeId};
+
+type Save = Box<dyn Fn(&dyn Any) -> Vec<u8>>;
+type Load = Box<dyn Fn(&[u8]) -> Box<dyn Any>>;
+
+/// How and when an activity is evicted
+pub(crate) struct Codec {
+    save: Save,
+    load: Load,
+    idle_after: u64,
+    /// Broadcast count when the activity has first been seen inactive
+    inactive_since: Option<u64>,
+}
+
+/// Stored in the slot of an evicted activity
+struct Dormant {
+    bytes: Vec<u8>,
+    type_id: TypeId,
+}
+
+impl Codec {
+    pub(crate) fn new<A: Any>(
+        idle_after: u64,
+        save: impl Fn(&A) -> Vec<u8> + 'static,
+        load: impl Fn(&[u8]) -> A + 'static,
+    ) -> Self {
+        Self {
+            save: Box::new(move |a| save(a.downcast_ref().expect(IMPOSSIBLE_ERR_MSG))),
+            load: Box::new(move |bytes| Box::new(load(bytes))),
+            idle_after,
+            inactive_since: None,
+        }
+    }
+}
+
+impl ActivityContainer {
+    pub(crate) fn set_codec(&mut self, id: UncheckedActivityId, codec: Codec) {
+        if self.is_current(id) {
+            self.codecs.insert(id.index, codec);
+        }
+    }
+    /// Serializes activities that have been inactive for long enough.
+    ///
+    /// Returns the evicted activities, which must be dropped after releasing the container.
+    pub(crate) fn evict_idle(&mut self, now: u64) -> Vec<Box<dyn Any>> {
+        let mut evicted = vec![];
+        let data = &mut self.data;
+        let active = &self.active;
+        self.codecs.retain(|&index, codec| {
+            let slot = match &mut data[index] {
+                Some(slot) => slot,
+                // Deleted
+                None => return false,
+            };
+            match active[index] {
+                LifecycleStatus::Active => {
+                    codec.inactive_since = None;
+                    return true;
+                }
+                // The activity will be removed soon
+                LifecycleStatus::Deleted => return true,
+                LifecycleStatus::Inactive => {}
+            }
+            let since = *codec.inactive_since.get_or_insert(now);
+            if now - since >= codec.idle_after && !slot.is::<Dormant>() {
+                let type_id = slot.as_ref().type_id();
+                let bytes = (codec.save)(slot.as_ref());
+                evicted.push(std::mem::replace(
+                    slot,
+                    Box::new(Dormant { bytes, type_id }),
+                ));
+            }
+            true
+        });
+        evicted
+    }
+    /// Deserializes the activity, if it has been evicted
+    pub(super) fn ensure_loaded(&mut self, index: usize) {
+        let loaded = match self.data.get(index) {
+            Some(Some(slot)) => slot.downcast_ref::<Dormant>().map(|dormant| {
+                let codec = &self.codecs[&index];
+                (codec.load)(&dormant.bytes)
+            }),
+            _ => None,
+        };
+        if let Some(activity) = loaded {
+            self.data[index] = Some(activity);
+        }
+    }
+    pub(super) fn is_evicted(&self, index: usize) -> bool {
+        matches!(&self.data[index], Some(slot) if slot.is::<Dormant>())
+    }
+    /// Type of the activity in the slot, also for evicted activities
+    pub(super) fn stored_type(slot: &dyn Any) -> TypeId {
+        match slot.downcast_ref::<Dormant>() {
+            Some(dormant) => dormant.type_id,
+            None => slot.type_id(),
+        }
+    }
+    /// Drops the serialized data and the codec of a deleted activity
+    pub(super) fn forget_codec(&mut self, index: usize) {
+        self.codecs.remove(&index);
+    }
+}
+
+impl Nut {
+    /// Registers the codec, it takes effect at the next point of quiescence
+    pub(crate) fn evict_when_idle(&self, id: UncheckedActivityId, codec: Codec) {
+        self.pending_codecs
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .push((id, codec));
+    }
+    /// only access after locking with executing flag
+    pub(crate) fn evict_idle_activities(&self) {
+        let pending = std::mem::take(
+            &mut *self
+                .pending_codecs
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG),
+        );
+        let mut activities = self.activities.try_borrow_mut().expect(IMPOSSIBLE_ERR_MSG);
+        for (id, codec) in pending {
+            activities.set_codec(id, codec);
+        }
+        if activities.codecs.is_empty() {
+            return;
+        }
+        let evicted = activities.evict_idle(self.broadcast_count.get());
+        drop(activities);
+        if !evicted.is_empty() {
+            // Dropping activities may publish messages
+            drop(evicted);
+            self.unchecked_catch_up_deferred_to_quiescence();
+        }
+    }
+}
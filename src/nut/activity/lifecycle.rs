@@ -33,6 +33,39 @@ pub(crate) struct LifecycleChange {
     status: LifecycleStatus,
 }
 
+/// A set of status changes that are applied together, created with [`nuts::status_batch`](fn.status_batch.html).
+#[must_use = "Status changes are only applied after calling commit()."]
+#[derive(Default)]
+pub struct StatusBatch {
+    changes: Vec<LifecycleChange>,
+}
+
+impl StatusBatch {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a status change to the batch.
+    ///
+    /// If the same activity is added twice, the changes are applied in order.
+    pub fn set(mut self, id: impl Into<UncheckedActivityId>, status: LifecycleStatus) -> Self {
+        self.changes.push(LifecycleChange {
+            activity: id.into(),
+            status,
+        });
+        self
+    }
+    /// Applies all status changes.
+    ///
+    /// All changes take effect at once, no message is dispatched in between.
+    /// Afterwards, `on_leave` handlers are called first, followed by `on_enter` handlers.
+    ///
+    /// # Panics
+    /// Panics if the status of a deleted activity is changed, just like `set_status`.
+    pub fn commit(self) {
+        crate::nut::set_status_batch(self.changes);
+    }
+}
+
 impl LifecycleStatus {
     /// Returns true iff the status is one that is considered to be active. (Only `LifecycleStatus::Active` at the moment)
     ///
@@ -56,8 +89,63 @@ impl Nut {
         self.deferred_events.push(event.into());
         self.catch_up_deferred_to_quiescence();
     }
+    /// Like `set_status` but the change overtakes all queued messages.
+    ///
+    /// Activities that are not live yet cannot overtake their own flush, their change is queued like with `set_status`.
+    pub(crate) fn set_status_immediate(&self, id: UncheckedActivityId, status: LifecycleStatus) {
+        if !self.is_live(id) {
+            return self.set_status(id, status);
+        }
+        let event = LifecycleChange {
+            activity: id,
+            status,
+        };
+        self.deferred_events.push_internal(event.into());
+        self.catch_up_deferred_to_quiescence();
+    }
+    pub(crate) fn set_status_batch(&self, changes: Vec<LifecycleChange>) {
+        self.deferred_events
+            .push(nut::exec::Deferred::LifecycleBatch(changes));
+        self.catch_up_deferred_to_quiescence();
+    }
     /// only access after locking with executing flag
     pub(crate) fn unchecked_lifecycle_change(&self, lifecycle_change: &LifecycleChange) {
+        if let Some(topic) = self.apply_status(lifecycle_change) {
+            self.broadcast(BroadcastInfo::local((), lifecycle_change.activity, topic));
+        }
+        self.remove_if_deleted(lifecycle_change);
+    }
+    /// only access after locking with executing flag
+    pub(crate) fn unchecked_lifecycle_batch(&self, changes: &[LifecycleChange]) {
+        let mut leaves = vec![];
+        let mut enters = vec![];
+        for change in changes {
+            match self.apply_status(change) {
+                Some(topic) if topic == Topic::leave() => leaves.push(change.activity),
+                Some(_) => enters.push(change.activity),
+                None => {}
+            }
+        }
+        for id in leaves {
+            self.broadcast(BroadcastInfo::local((), id, Topic::leave()));
+        }
+        for id in enters {
+            self.broadcast(BroadcastInfo::local((), id, Topic::enter()));
+        }
+        for change in changes {
+            self.remove_if_deleted(change);
+        }
+    }
+    /// Sets the new status and returns the topic that must be broadcast to the activity, if any.
+    ///
+    /// only access after locking with executing flag
+    fn apply_status(&self, lifecycle_change: &LifecycleChange) -> Option<Topic> {
+        debug_assert!(
+            self.is_live(lifecycle_change.activity),
+            "Status of activity {} changed before it has been flushed from the inchoate activities.",
+            lifecycle_change.activity.index
+        );
+        let mut topic = None;
         let before = self
             .activities
             .try_borrow()
@@ -75,19 +163,14 @@ impl Nut {
                 .expect(IMPOSSIBLE_ERR_MSG)
                 .set_status(lifecycle_change.activity, lifecycle_change.status);
             if !before.is_active() && lifecycle_change.status.is_active() {
-                self.broadcast(BroadcastInfo::local(
-                    (),
-                    lifecycle_change.activity,
-                    Topic::enter(),
-                ));
+                topic = Some(Topic::enter());
             } else if before.is_active() && !lifecycle_change.status.is_active() {
-                self.broadcast(BroadcastInfo::local(
-                    (),
-                    lifecycle_change.activity,
-                    Topic::leave(),
-                ));
+                topic = Some(Topic::leave());
             }
         }
+        topic
+    }
+    fn remove_if_deleted(&self, lifecycle_change: &LifecycleChange) {
         if lifecycle_change.status == LifecycleStatus::Deleted {
             // Delete must be deferred in case the on_leave is hanging.
             self.deferred_events
@@ -96,8 +179,36 @@ impl Nut {
                 ));
         }
     }
+    /// Queues the initial `on_enter` of all activities created with `new_activity_with_enter` since the last call.
+    ///
+    /// Returns false if there were none.
+    pub(crate) fn queue_initial_enters(&self) -> bool {
+        let mut any = false;
+        while let Some(id) = self.initial_enters.pop() {
+            self.deferred_events
+                .push(nut::exec::Deferred::InitialEnter(id));
+            any = true;
+        }
+        // Messages held for activities created by async factories follow their initial `on_enter`
+        self.queue_released_messages() || any
+    }
+    /// only access after locking with executing flag
+    pub(crate) fn unchecked_initial_enter(&self, id: UncheckedActivityId) {
+        let status = self
+            .activities
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .status(id);
+        // The status may have changed since the activity has been created
+        if status.is_active() {
+            let mut broadcast = BroadcastInfo::local((), id, Topic::enter());
+            self.trace_broadcast(&mut broadcast);
+            self.unchecked_broadcast(broadcast);
+        }
+    }
     pub(crate) fn delete_activity(&self, id: UncheckedActivityId) {
-        self.activities
+        let deleted = self
+            .activities
             .try_borrow_mut()
             .expect(IMPOSSIBLE_ERR_MSG)
             .delete(
@@ -107,6 +218,36 @@ impl Nut {
                     .try_borrow_mut()
                     .expect(IMPOSSIBLE_ERR_MSG),
             );
+        self.drop_tasks_of(id);
+        self.forget_responders_of(id);
+        let release = {
+            let mut slots = self
+                .slots_to_release
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG);
+            let before = slots.len();
+            slots.retain(|slot| *slot != id);
+            slots.len() < before
+        };
+        if release && deleted {
+            self.release_slot(id.index);
+        }
+    }
+    /// Drops everything that refers to the slot of a deleted activity, such that the next activity can take it over
+    fn release_slot(&self, index: usize) {
+        self.subscriptions.forget_activity(index);
+        self.dyn_families
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .retain(|activity, _| activity != index);
+        self.frames
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .remove_affinity(index);
+        self.activities
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .release_slot(index);
     }
 }
 
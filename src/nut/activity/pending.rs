@@ -0,0 +1,90 @@
+//! Activities that are still being created by an async factory, see `nuts::new_activity_async`.
+
+use crate::nut::iac::publish::BroadcastInfo;
+use crate::nut::{exec::Deferred, Nut, IMPOSSIBLE_ERR_MSG};
+use core::any::TypeId;
+use std::collections::HashMap;
+
+/// Private messages sent to activity types whose factory has not resolved, yet
+#[derive(Default)]
+pub(crate) struct PendingActivities {
+    /// Number of unresolved factories per activity type
+    factories: HashMap<TypeId, usize>,
+    /// Messages held back until the factory of the receiver resolves
+    held: HashMap<TypeId, Vec<BroadcastInfo>>,
+    /// Messages of resolved factories, to be queued right before the next broadcast
+    released: Vec<BroadcastInfo>,
+}
+
+/// Marks an activity type as pending for as long as it lives
+pub(crate) struct PendingActivity {
+    type_id: TypeId,
+}
+
+impl PendingActivity {
+    pub(crate) fn new<A: 'static>() -> Self {
+        let type_id = TypeId::of::<A>();
+        crate::nut::with_pending_activities(|pending| {
+            *pending.factories.entry(type_id).or_default() += 1
+        });
+        Self { type_id }
+    }
+}
+
+impl Drop for PendingActivity {
+    /// Also called when the factory has been dropped before it resolved, the held messages are then sent to nobody.
+    fn drop(&mut self) {
+        crate::nut::with_pending_activities(|pending| pending.finish(self.type_id));
+    }
+}
+
+impl PendingActivities {
+    fn finish(&mut self, type_id: TypeId) {
+        let factories = self.factories.get_mut(&type_id).expect(IMPOSSIBLE_ERR_MSG);
+        *factories -= 1;
+        if *factories == 0 {
+            self.factories.remove(&type_id);
+            if let Some(held) = self.held.remove(&type_id) {
+                self.released.extend(held);
+            }
+        }
+    }
+}
+
+impl Nut {
+    /// Keeps the broadcast for later if it is a private message to an activity type that is still being created.
+    /// Otherwise, the broadcast is returned for immediate dispatch.
+    pub(crate) fn hold_for_pending_activity(
+        &self,
+        broadcast: BroadcastInfo,
+    ) -> Option<BroadcastInfo> {
+        let mut pending = self
+            .pending_activities
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        match broadcast.receiver_type() {
+            Some(receiver) if pending.factories.contains_key(&receiver) => {
+                pending.held.entry(receiver).or_default().push(broadcast);
+                None
+            }
+            _ => Some(broadcast),
+        }
+    }
+    /// Queues the messages held for activities that have been created in the meantime.
+    ///
+    /// Returns true if any message has been queued.
+    pub(crate) fn queue_released_messages(&self) -> bool {
+        let released = std::mem::take(
+            &mut self
+                .pending_activities
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .released,
+        );
+        let any = !released.is_empty();
+        for broadcast in released {
+            self.deferred_events.push(Deferred::Broadcast(broadcast));
+        }
+        any
+    }
+}
@@ -0,0 +1,68 @@
+use super::*;
+use std::convert::TryFrom;
+
+/// A small, serializable reference to an activity, created with [`ActivityId::to_token`](struct.ActivityId.html#method.to_token).
+///
+/// The token stores the index of the activity, its domain, and the generation of its slot.
+/// Use [`as_u64`](#method.as_u64) and [`from_u64`](#method.from_u64) to store it, for example in a save game.
+///
+/// A token stays valid across save and load as long as the activities are recreated in the same order after loading.
+/// Converting it back with [`ActivityId::from_token`](struct.ActivityId.html#method.from_token) checks that it still refers to an activity of the expected type and domain.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ActivityToken {
+    index: u32,
+    /// Domain index + 1, zero means no domain
+    domain: u16,
+    generation: u16,
+}
+
+impl ActivityToken {
+    /// Packs the token into a single integer.
+    pub fn as_u64(&self) -> u64 {
+        u64::from(self.index) | u64::from(self.domain) << 32 | u64::from(self.generation) << 48
+    }
+    /// Unpacks a token previously packed with `as_u64`.
+    pub fn from_u64(bits: u64) -> Self {
+        Self {
+            index: bits as u32,
+            domain: (bits >> 32) as u16,
+            generation: (bits >> 48) as u16,
+        }
+    }
+}
+
+impl<A: Activity> ActivityId<A> {
+    /// Creates a token that refers to this activity and can be serialized.
+    ///
+    /// # Panics
+    /// Panics if the activity index, domain index, or generation exceeds the range of the token.
+    pub fn to_token(&self) -> ActivityToken {
+        let index = u32::try_from(self.id.index).expect("Activity index too large for a token.");
+        let domain = match self.domain_index.index() {
+            None => 0,
+            Some(d) => u16::try_from(d + 1).expect("Domain index too large for a token."),
+        };
+        let generation =
+            u16::try_from(self.id.generation).expect("Activity generation too large for a token.");
+        ActivityToken {
+            index,
+            domain,
+            generation,
+        }
+    }
+    /// Restores an ID from a token.
+    ///
+    /// Returns `None` if the token does not refer to a living activity of type `A` in the domain stored in the token.
+    ///
+    /// # Panics
+    /// Panics if called from inside a subscription handler.
+    pub fn from_token(token: ActivityToken) -> Option<Self> {
+        let domain = match token.domain {
+            0 => DomainId::default(),
+            d => DomainId::from_index(usize::from(d) - 1),
+        };
+        let id =
+            Self::new(token.index as usize, domain).with_generation(u32::from(token.generation));
+        crate::nut::is_token_valid(id).then_some(id)
+    }
+}
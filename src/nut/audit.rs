@@ -0,0 +1,86 @@
+//! Counting of heap allocations in the dispatch path, enabled with the feature `alloc-audit`.
+//!
+//! Only allocations that Nuts performs itself are counted, at the places where they are known to happen.
+//! Allocations inside of subscription handlers are not counted.
+//! Without the feature, all functions in here compile to nothing.
+
+#[cfg(feature = "alloc-audit")]
+use core::cell::Cell;
+
+#[cfg(feature = "alloc-audit")]
+/// Number of heap allocations performed by Nuts, see [`take_allocation_report`](fn.take_allocation_report.html).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocationReport {
+    /// Number of broadcasts dispatched, including lifecycle events
+    pub broadcasts: u64,
+    /// Boxes for published messages, including copies of retained messages
+    pub messages: u64,
+    /// Boxes for subscription handlers and other closures
+    pub closures: u64,
+    /// Growth of internal queues and boxes for deferred data, such as domain stores
+    pub queues: u64,
+}
+
+#[cfg(feature = "alloc-audit")]
+impl AllocationReport {
+    /// Sum of all allocations
+    pub fn total(&self) -> u64 {
+        self.messages + self.closures + self.queues
+    }
+    /// Average number of allocations per broadcast
+    pub fn per_broadcast(&self) -> f64 {
+        if self.broadcasts == 0 {
+            0.0
+        } else {
+            self.total() as f64 / self.broadcasts as f64
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Allocation {
+    Message,
+    Closure,
+    Queue,
+}
+
+#[cfg(feature = "alloc-audit")]
+thread_local!(static REPORT: Cell<AllocationReport> = Cell::new(AllocationReport::default()));
+
+#[inline]
+#[allow(unused_variables)]
+pub(crate) fn record(kind: Allocation) {
+    #[cfg(feature = "alloc-audit")]
+    REPORT.with(|report| {
+        let mut r = report.get();
+        match kind {
+            Allocation::Message => r.messages += 1,
+            Allocation::Closure => r.closures += 1,
+            Allocation::Queue => r.queues += 1,
+        }
+        report.set(r);
+    });
+}
+
+/// Records the allocation of a `Box<T>`, which only allocates if `T` is not zero-sized.
+#[inline]
+pub(crate) fn record_box<T: ?Sized>(kind: Allocation, value: &T) {
+    if core::mem::size_of_val(value) != 0 {
+        record(kind);
+    }
+}
+
+#[inline]
+pub(crate) fn record_broadcast() {
+    #[cfg(feature = "alloc-audit")]
+    REPORT.with(|report| {
+        let mut r = report.get();
+        r.broadcasts += 1;
+        report.set(r);
+    });
+}
+
+#[cfg(feature = "alloc-audit")]
+pub(crate) fn take_report() -> AllocationReport {
+    REPORT.with(|report| report.take())
+}
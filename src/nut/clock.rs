@@ -0,0 +1,136 @@
+//! Source of time for internal measurements, which works on all targets, see `nuts::set_clock`.
+//!
+//! `std::time::Instant` is not available on `wasm32-unknown-unknown`, hence the clock can be replaced.
+
+use core::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Source of time for measurements inside Nuts, set with [`nuts::set_clock`](fn.set_clock.html).
+///
+/// The clock measures handler durations for [`profiling_report`](fn.profiling_report.html) (feature `perf`) and for the handler budget of [`LogThresholds`](struct.LogThresholds.html) (feature `log`).
+/// It also provides the elapsed time passed to handlers registered with [`subscribe_timed`](struct.ActivityId.html#method.subscribe_timed),
+/// and the time of the last call in [`SubscriptionStats`](struct.SubscriptionStats.html) if enabled with [`ConfigBuilder::record_call_times`](struct.ConfigBuilder.html#method.record_call_times).
+/// The time spent in broadcasts reported by [`publish_stats`](fn.publish_stats.html) is measured with it, too.
+/// Periodic publishers created with [`every`](fn.every.html) do not use it, they are driven by [`advance_time`](fn.advance_time.html).
+pub trait Clock {
+    /// Time elapsed since an arbitrary but fixed point in the past.
+    ///
+    /// Must never decrease. Must not call any function of Nuts.
+    fn now(&self) -> Duration;
+}
+
+/// Clock based on `std::time::Instant`, the default on native targets.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy)]
+pub struct StdClock {
+    origin: std::time::Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for StdClock {
+    fn default() -> Self {
+        Self {
+            origin: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for StdClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// Clock based on `performance.now()` of the browser window, enabled with the feature `web-clock`.
+///
+/// It is the default on `wasm32` when the feature is enabled.
+/// The clock only works inside a browser window, reading it panics elsewhere, e.g. in web workers or on native targets.
+#[cfg(feature = "web-clock")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WebClock;
+
+#[cfg(feature = "web-clock")]
+impl Clock for WebClock {
+    fn now(&self) -> Duration {
+        let millis = web_sys::window()
+            .and_then(|window| window.performance())
+            .expect("performance.now() is only available inside a browser window")
+            .now();
+        Duration::from_secs_f64(millis / 1000.0)
+    }
+}
+
+/// Clock that only moves when told to, for tests.
+///
+/// Clones share the same time, keep one to advance the clock after passing another one to [`set_clock`](fn.set_clock.html).
+/// On `wasm32` without the feature `web-clock`, a mock clock that is never advanced is the default, hence all measured durations are zero.
+///
+/// ### Example
+/// ```rust
+/// use nuts::{Clock, MockClock};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new();
+/// nuts::set_clock(clock.clone());
+/// clock.advance(Duration::from_millis(5));
+/// assert_eq!(Duration::from_millis(5), clock.now());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl MockClock {
+    /// Starts at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Moves the time forward by `dt`.
+    pub fn advance(&self, dt: Duration) {
+        self.now.set(self.now.get() + dt);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+/// The clock used by the Nut, which is the default clock of the target until `set_clock` is called
+pub(crate) struct SelectedClock(Box<dyn Clock>);
+
+impl SelectedClock {
+    pub(crate) fn set(&mut self, clock: Box<dyn Clock>) {
+        self.0 = clock;
+    }
+    pub(crate) fn now(&self) -> Duration {
+        self.0.now()
+    }
+}
+
+impl crate::nut::Nut {
+    pub(crate) fn now(&self) -> Duration {
+        self.clock
+            .try_borrow()
+            .expect(crate::nut::IMPOSSIBLE_ERR_MSG)
+            .now()
+    }
+}
+
+impl Default for SelectedClock {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn default() -> Self {
+        Self(Box::new(StdClock::default()))
+    }
+    #[cfg(all(target_arch = "wasm32", feature = "web-clock"))]
+    fn default() -> Self {
+        Self(Box::new(WebClock))
+    }
+    #[cfg(all(target_arch = "wasm32", not(feature = "web-clock")))]
+    fn default() -> Self {
+        Self(Box::new(MockClock::new()))
+    }
+}
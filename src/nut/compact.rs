@@ -0,0 +1,56 @@
+//! Release of memory that internal storage keeps after many activities and subscriptions are gone, see `nuts::compact`.
+
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use core::sync::atomic::Ordering;
+
+/// Memory released by [`nuts::compact`](fn.compact.html).
+///
+/// Capacities are counted in elements of the respective storage, not in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Unused activity slots that have been released
+    pub activity_slots: usize,
+    /// Subscription handlers of deleted activities that have been dropped
+    pub dropped_handlers: usize,
+    /// Unused entries of subscription tables that have been released
+    pub subscription_entries: usize,
+    /// Unused slots of the event queue that have been released
+    pub queue_slots: usize,
+    /// Boxes of delivered messages that have been kept for reuse and are now released
+    pub pooled_messages: usize,
+}
+
+impl Nut {
+    pub(crate) fn compact(&self) -> CompactionReport {
+        assert!(
+            !self.executing.load(Ordering::Relaxed),
+            "compact must not be called from inside a subscription handler."
+        );
+        let activity_slots = self
+            .activities
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .shrink_to_fit()
+            + self
+                .inchoate_activities
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .shrink_to_fit();
+        let (dropped_handlers, subscription_entries) = {
+            let activities = self.activities.try_borrow().expect(IMPOSSIBLE_ERR_MSG);
+            self.subscriptions
+                .compact(|index| activities.is_deleted_slot(index))
+        };
+        CompactionReport {
+            activity_slots,
+            dropped_handlers,
+            subscription_entries,
+            queue_slots: self.deferred_events.shrink_to_fit(),
+            pooled_messages: self
+                .message_pool
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .clear(),
+        }
+    }
+}
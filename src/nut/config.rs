@@ -0,0 +1,216 @@
+//! Configuration of the thread-local Nut, applied with `nuts::configure` before the first message is published.
+
+use crate::nut::iac::publish::ResponseConfig;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::Error;
+
+/// What happens when a subscription handler panics, see [`ConfigBuilder::panic_policy`](struct.ConfigBuilder.html#method.panic_policy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// The panic unwinds through Nuts into the code that published the message
+    Propagate,
+    /// The panic is caught and the remaining handlers are called as if nothing happened.
+    ///
+    /// This has no effect if the application is compiled with `panic = "abort"`.
+    CatchAndContinue,
+}
+
+/// Order in which the activities subscribed to a published message are called, see [`ConfigBuilder::dispatch_order`](struct.ConfigBuilder.html#method.dispatch_order).
+///
+/// Within one activity, handlers are always called in the order they have been registered, with readers before handlers that modify the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchOrder {
+    /// No particular order, which is the cheapest
+    Unspecified,
+    /// Activities created earlier are called first
+    Creation,
+}
+
+/// Order in which internal bookkeeping is interleaved with messages, see [`ConfigBuilder::queue_order`](struct.ConfigBuilder.html#method.queue_order).
+///
+/// Some operations cannot be applied while a message is being handled, they are queued instead.
+/// These are: new activities and subscriptions, `on_delete` handlers, domain stores and observers, and the removal of one-shot channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueOrder {
+    /// Everything is processed in the order it has been queued, with urgent messages ahead of the others.
+    ///
+    /// An activity created inside a handler receives the messages published after its creation.
+    /// But an urgent message may overtake the creation and never reach the new activity.
+    Fifo,
+    /// Queued bookkeeping is applied as soon as the current event has been processed, ahead of all queued messages.
+    ///
+    /// Activities and subscriptions created inside a handler are ready for every message that is delivered afterwards, even for messages that have been published before.
+    BookkeepingFirst,
+}
+
+/// Settings stored in the Nut
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Config {
+    pub(crate) queue_limit: Option<usize>,
+    pub(crate) panic_policy: PanicPolicy,
+    pub(crate) dispatch_order: DispatchOrder,
+    pub(crate) queue_order: QueueOrder,
+    pub(crate) record_call_times: bool,
+    pub(crate) drain_limit: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            queue_limit: None,
+            panic_policy: PanicPolicy::Propagate,
+            dispatch_order: DispatchOrder::Unspecified,
+            queue_order: QueueOrder::Fifo,
+            record_call_times: false,
+            drain_limit: None,
+        }
+    }
+}
+
+/// Collects settings of Nuts, which are applied together with [`configure`](fn.configure.html).
+///
+/// Queue limit, drain limit, panic policy, dispatch order, queue order, and recording of call times always take the values of the builder, other settings are only changed if they have been set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConfigBuilder {
+    config: Config,
+    responses: Option<ResponseConfig>,
+    trace_capacity: Option<usize>,
+    stats_interval: Option<u64>,
+}
+
+impl ConfigBuilder {
+    /// Starts with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Panics when more than `n` events are queued while messages are processed, which usually indicates an endless cascade of messages.
+    ///
+    /// Must be at least 1. By default, the queue is unlimited.
+    pub fn queue_limit(mut self, n: usize) -> Self {
+        self.config.queue_limit = Some(n);
+        self
+    }
+    /// Limits how many events are dispatched each time Nuts works through the queue, for example after a message has been published from outside of handlers.
+    ///
+    /// Once the limit has been reached, the remaining events stay queued.
+    /// They are dispatched by [`process_at_most`](fn.process_at_most.html), or when the queue is worked through again after the next message has been published.
+    /// While events are waiting, Nuts behaves as if they had been held back by [`PauseQueue`](control/struct.PauseQueue.html):
+    /// activities and subscriptions created outside of handlers are ready before the waiting events are dispatched, and futures waiting for quiescence are not woken.
+    ///
+    /// Must be at least 1. By default, the queue is always worked through until it is empty.
+    ///
+    /// ### Example
+    /// ```rust
+    /// struct Frame(u32);
+    /// nuts::configure(nuts::ConfigBuilder::new().drain_limit(10))
+    ///     .expect("configured before the first publish");
+    /// nuts::subscribe(|frame: &Frame| {
+    ///     if frame.0 > 0 {
+    ///         nuts::publish(Frame(frame.0 - 1));
+    ///     }
+    /// });
+    /// nuts::publish(Frame(100));
+    /// // in the main loop, once per frame
+    /// while nuts::process_at_most(10) > 0 {
+    ///     // render a frame
+    /// }
+    /// ```
+    pub fn drain_limit(mut self, n: usize) -> Self {
+        self.config.drain_limit = Some(n);
+        self
+    }
+    /// Defines how panics in subscription handlers are treated. By default, they are propagated.
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.config.panic_policy = policy;
+        self
+    }
+    /// Defines the order in which subscribed activities are called. By default, the order is unspecified.
+    pub fn dispatch_order(mut self, order: DispatchOrder) -> Self {
+        self.config.dispatch_order = order;
+        self
+    }
+    /// Defines when internal bookkeeping is applied, relative to queued messages. By default, everything is processed in order.
+    pub fn queue_order(mut self, order: QueueOrder) -> Self {
+        self.config.queue_order = order;
+        self
+    }
+    /// Records the time of the last call of each handler, read with [`SubscriptionId::stats`](struct.SubscriptionId.html#method.stats). Off by default.
+    ///
+    /// When enabled, the [clock](fn.set_clock.html) is read for every handler call.
+    pub fn record_call_times(mut self, enabled: bool) -> Self {
+        self.config.record_call_times = enabled;
+        self
+    }
+    /// Same as [`configure_responses`](fn.configure_responses.html).
+    pub fn responses(mut self, responses: ResponseConfig) -> Self {
+        self.responses = Some(responses);
+        self
+    }
+    /// Same as [`record_traces`](fn.record_traces.html).
+    pub fn record_traces(mut self, capacity: usize) -> Self {
+        self.trace_capacity = Some(capacity);
+        self
+    }
+    /// Same as [`publish_stats`](fn.publish_stats.html).
+    pub fn publish_stats(mut self, every: u64) -> Self {
+        self.stats_interval = Some(every);
+        self
+    }
+    fn validate(&self) -> Result<(), Error> {
+        if self.config.queue_limit == Some(0) {
+            return Err(Error::InvalidConfig("the queue limit must be at least 1"));
+        }
+        if self.config.drain_limit == Some(0) {
+            return Err(Error::InvalidConfig("the drain limit must be at least 1"));
+        }
+        if let Some(responses) = &self.responses {
+            match responses.max_pending {
+                Some(0) => {
+                    return Err(Error::InvalidConfig(
+                        "the limit of pending responses must be at least 1",
+                    ))
+                }
+                Some(max) if max < responses.preallocate => {
+                    return Err(Error::InvalidConfig(
+                        "more response slots preallocated than may be pending",
+                    ))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Nut {
+    pub(crate) fn configure(&self, builder: ConfigBuilder) -> Result<(), Error> {
+        if self.published.get() {
+            return Err(Error::ConfiguredAfterPublish);
+        }
+        builder.validate()?;
+        self.config.set(builder.config);
+        if let Some(responses) = builder.responses {
+            Nut::with_response_tracker_mut(|rt| rt.configure(responses));
+        }
+        if let Some(capacity) = builder.trace_capacity {
+            self.tracer
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .set_capacity(capacity);
+        }
+        if let Some(every) = builder.stats_interval {
+            self.stats.set_interval(every);
+        }
+        Ok(())
+    }
+    /// Panics if the configured queue limit is exceeded
+    pub(crate) fn check_queue_limit(&self, len: usize) {
+        if let Some(limit) = self.config.get().queue_limit {
+            assert!(
+                len <= limit,
+                "More than {} events are queued, the configured queue limit has been exceeded.",
+                limit
+            );
+        }
+    }
+}
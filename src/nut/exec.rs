@@ -1,21 +1,41 @@
 use crate::nut::activity::LifecycleChange;
-use crate::nut::iac::publish::{BroadcastInfo, ResponseSlot};
+use crate::nut::iac::managed_state::DomainMerge;
+use crate::nut::iac::managed_state::DomainObserver;
+use crate::nut::iac::managed_state::DomainViewRequest;
+use crate::nut::iac::publish::{BroadcastInfo, Replay, ResponseSlot};
+use crate::nut::iac::topic::Topic;
 use crate::nut::Nut;
 use crate::DomainStoreData;
+use crate::QueueOrder;
 use crate::UncheckedActivityId;
 
+mod control;
 pub(crate) mod fifo;
+pub(crate) mod frame;
 pub(crate) mod inchoate;
+pub(crate) mod scratch;
+pub(crate) mod tasks;
+pub(crate) mod teardown;
 
 pub(crate) enum Deferred {
     Broadcast(BroadcastInfo),
     BroadcastAwaitingResponse(BroadcastInfo, ResponseSlot),
     Subscription(NewSubscription),
+    RemovePrivateChannel(Topic, UncheckedActivityId),
     OnDeleteSubscription(UncheckedActivityId, OnDelete),
     LifecycleChange(LifecycleChange),
+    LifecycleBatch(Vec<LifecycleChange>),
     RemoveActivity(UncheckedActivityId),
+    InitialEnter(UncheckedActivityId),
+    Replay(Replay),
     DomainStore(DomainStoreData),
+    DomainObserver(DomainObserver),
+    DomainView(DomainViewRequest),
+    DomainMerge(DomainMerge),
     FlushInchoateActivities,
+    Exit(teardown::Exit),
+    /// A closure from `nuts::defer`
+    Closure(Box<dyn FnOnce()>),
 }
 use core::sync::atomic::Ordering;
 
@@ -37,16 +57,54 @@ impl Nut {
         if !self.executing.swap(true, Ordering::Relaxed) {
             #[cfg(feature = "verbose-debug-log")]
             debug_print!("Start Executing from quiescent moment");
+            self.processing_budget.set(self.config.get().drain_limit);
             self.unchecked_catch_up_deferred_to_quiescence();
+            self.processing_budget.set(None);
+            self.reach_quiescence();
+        }
+    }
+
+    /// Resets the executing flag after a drain of the queue and, if the queue is empty, completes everything that waits for quiescence.
+    ///
+    /// If a budget has left events in the queue, only the flag is reset.
+    ///
+    /// only access after locking with executing flag
+    fn reach_quiescence(&self) {
+        self.deferred_events.end_drain();
+        if self.deferred_events.len() > 0 {
             self.executing.store(false, Ordering::Relaxed);
-            #[cfg(feature = "verbose-debug-log")]
-            debug_print!("Quiescence Reached");
+            return;
+        }
+        self.message_pool
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .finish_drain();
+        self.evict_idle_activities();
+        let scratch = self.scratch.take();
+        self.executing.store(false, Ordering::Relaxed);
+        // Dropped values may publish messages, which requires the executing flag to be reset
+        drop(scratch);
+        for waker in Nut::with_response_tracker_mut(|rt| rt.quiescent()) {
+            waker.wake();
+        }
+        #[cfg(feature = "verbose-debug-log")]
+        debug_print!("Quiescence Reached");
+        self.poll_tasks();
+    }
+
+    /// Queues an internal event, ahead of all messages if configured with `QueueOrder::BookkeepingFirst`
+    pub(crate) fn defer_bookkeeping(&self, event: Deferred) {
+        match self.config.get().queue_order {
+            QueueOrder::Fifo => self.deferred_events.push(event),
+            QueueOrder::BookkeepingFirst => self.deferred_events.push_internal(event),
         }
     }
 
     /// only access after locking with executing flag
-    fn unchecked_catch_up_deferred_to_quiescence(&self) {
-        while let Some(deferred) = self.deferred_events.pop() {
+    pub(crate) fn unchecked_catch_up_deferred_to_quiescence(&self) {
+        while let Some(deferred) = self.next_deferred() {
+            crate::nut::usage_log::queue_len(self.deferred_events.len() + 1);
+            self.check_queue_limit(self.deferred_events.len() + 1);
             #[cfg(debug_assertions)]
             let debug_message = format!("Executing: {:?}", deferred);
 
@@ -84,17 +142,60 @@ impl Nut {
                 );
                 std::panic::resume_unwind(panic_info);
             }
+
+            // Handlers that do not run inside a broadcast, such as `on_delete`, may also have written to domains
+            self.apply_ordered_domain_stores(
+                &mut self
+                    .managed_state
+                    .try_borrow_mut()
+                    .expect(IMPOSSIBLE_ERR_MSG),
+            );
+            self.notify_domain_observers();
+            self.swap_buffers();
+        }
+    }
+    fn next_deferred(&self) -> Option<Deferred> {
+        if let Some(budget) = self.processing_budget.get() {
+            if budget == 0 {
+                return None;
+            }
+            self.processing_budget.set(Some(budget - 1));
         }
+        self.deferred_events.pop().or_else(|| {
+            if self.queue_initial_enters() {
+                self.deferred_events.pop()
+            } else {
+                None
+            }
+        })
     }
     fn exec_deferred(&self, deferred: Deferred) {
+        let deferred = match self.apply_control(deferred) {
+            Some(deferred) => deferred,
+            None => return,
+        };
         match deferred {
-            Deferred::Broadcast(b) => self.unchecked_broadcast(b),
+            Deferred::Broadcast(b) => {
+                if let Some(b) = self
+                    .hold_for_pending_activity(b)
+                    .and_then(|b| self.buffer_at_startup(b))
+                {
+                    self.unchecked_broadcast(b)
+                }
+            }
             Deferred::BroadcastAwaitingResponse(b, slot) => {
                 self.unchecked_broadcast(b);
-                Nut::with_response_tracker_mut(|rt| rt.done(&slot));
+                if let Some(waker) = Nut::with_response_tracker_mut(|rt| rt.done(&slot)) {
+                    waker.wake();
+                }
             }
             Deferred::Subscription(sub) => {
+                let topic = sub.topic();
                 self.subscriptions.exec_new_subscription(sub);
+                self.release_startup_messages(topic);
+            }
+            Deferred::RemovePrivateChannel(topic, id) => {
+                self.subscriptions.remove_private(topic, id);
             }
             Deferred::OnDeleteSubscription(id, sub) => {
                 self.activities
@@ -103,25 +204,33 @@ impl Nut {
                     .add_on_delete(id, sub);
             }
             Deferred::LifecycleChange(lc) => self.unchecked_lifecycle_change(&lc),
+            Deferred::LifecycleBatch(batch) => self.unchecked_lifecycle_batch(&batch),
             Deferred::RemoveActivity(id) => self.delete_activity(id),
+            Deferred::InitialEnter(id) => self.unchecked_initial_enter(id),
+            Deferred::Replay(replay) => self.unchecked_replay(replay),
             Deferred::DomainStore(d) => self.exec_domain_store(d),
+            Deferred::DomainObserver(o) => self.add_domain_observer(o),
+            Deferred::DomainView(v) => self.exec_domain_view(v),
+            Deferred::DomainMerge(m) => self.exec_domain_merge(&m),
+            Deferred::Exit(exit) => self.unchecked_exit(&exit),
+            Deferred::Closure(f) => f(),
             Deferred::FlushInchoateActivities => self
                 .inchoate_activities
                 .try_borrow_mut()
                 .expect(IMPOSSIBLE_ERR_MSG)
-                .flush(&mut *self.activities.try_borrow_mut().expect(IMPOSSIBLE_ERR_MSG)),
+                .flush(&mut self.activities.try_borrow_mut().expect(IMPOSSIBLE_ERR_MSG)),
         }
     }
 }
-impl Into<Deferred> for BroadcastInfo {
-    fn into(self) -> Deferred {
-        Deferred::Broadcast(self)
+impl From<BroadcastInfo> for Deferred {
+    fn from(b: BroadcastInfo) -> Self {
+        Deferred::Broadcast(b)
     }
 }
 
-impl Into<Deferred> for LifecycleChange {
-    fn into(self) -> Deferred {
-        Deferred::LifecycleChange(self)
+impl From<LifecycleChange> for Deferred {
+    fn from(lc: LifecycleChange) -> Self {
+        Deferred::LifecycleChange(lc)
     }
 }
 
@@ -132,13 +241,24 @@ impl std::fmt::Debug for Deferred {
             Self::Broadcast(b) => write!(f, "Broadcasting {:?}", b),
             Self::BroadcastAwaitingResponse(b, _rs) => write!(f, "Broadcasting {:?}", b),
             Self::Subscription(sub) => write!(f, "{:?}", sub),
+            Self::RemovePrivateChannel(topic, _id) => {
+                write!(f, "Remove {:?} channel of activity {}", topic, _id.index)
+            }
             Self::OnDeleteSubscription(_id, _) => {
                 write!(f, "Adding new on delete listener {}", _id.index)
             }
             Self::LifecycleChange(lc) => write!(f, "{:?}", lc),
+            Self::LifecycleBatch(batch) => write!(f, "Batch of {} status changes", batch.len()),
             Self::RemoveActivity(_id) => write!(f, "Delete activity {}.", _id.index),
+            Self::Replay(replay) => write!(f, "{:?}", replay),
+            Self::InitialEnter(_id) => write!(f, "Initial enter of activity {}.", _id.index),
             Self::DomainStore(ds) => write!(f, "{:?}", ds),
+            Self::DomainObserver(o) => write!(f, "{:?}", o),
+            Self::DomainView(v) => write!(f, "{:?}", v),
+            Self::DomainMerge(m) => write!(f, "{:?}", m),
             Self::FlushInchoateActivities => write!(f, "Adding new activities previously deferred"),
+            Self::Exit(_) => write!(f, "Exit, unless vetoed"),
+            Self::Closure(_) => write!(f, "Deferred closure"),
         }
     }
 }
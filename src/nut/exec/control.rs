@@ -0,0 +1,158 @@
+use super::Deferred;
+use crate::control::{DumpState, PauseQueue, ResumeQueue, SetLogLevel};
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use core::any::TypeId;
+use core::sync::atomic::Ordering;
+use std::fmt::Write;
+
+impl Nut {
+    /// Applies control messages and holds back other broadcasts while the queue is paused.
+    ///
+    /// Returns the event if it should be executed now.
+    ///
+    /// only access after locking with executing flag
+    pub(crate) fn apply_control(&self, deferred: Deferred) -> Option<Deferred> {
+        let broadcast = match &deferred {
+            Deferred::Broadcast(b) | Deferred::BroadcastAwaitingResponse(b, _) => b,
+            _ => return Some(deferred),
+        };
+        let (topic, msg) = match broadcast.public_message() {
+            Some(public) => public,
+            None => return self.hold_if_paused(deferred),
+        };
+        if topic == TypeId::of::<DumpState>() {
+            log_print!("{}", self.unchecked_dump_state());
+        } else if let Some(SetLogLevel(level)) = msg.downcast_ref() {
+            crate::debug::set_log_level(*level);
+        } else if topic == TypeId::of::<PauseQueue>() {
+            self.paused_events
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .get_or_insert_with(Vec::new);
+        } else if topic == TypeId::of::<ResumeQueue>() {
+            let held = self
+                .paused_events
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .take();
+            // Held events have been published before everything that is queued now
+            for event in held.into_iter().flatten() {
+                self.deferred_events.push_urgent(event);
+            }
+        } else {
+            return self.hold_if_paused(deferred);
+        }
+        Some(deferred)
+    }
+    /// Executes up to `n` held events while the queue is paused, including events published by their handlers.
+    /// Without a pause, executes up to `n` events that a drain limit has left in the queue.
+    ///
+    /// Returns the number of events that are still held or queued afterwards.
+    pub(crate) fn process_at_most(&self, n: usize) -> usize {
+        assert!(
+            !self.executing.load(Ordering::Relaxed),
+            "process_at_most must not be called from inside a subscription handler."
+        );
+        let held = self
+            .paused_events
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .take();
+        let held = match held {
+            Some(held) => held,
+            None => return self.process_queued_at_most(n),
+        };
+        for event in held {
+            self.deferred_events.push_urgent(event);
+        }
+        self.executing.store(true, Ordering::Relaxed);
+        self.processing_budget.set(Some(n));
+        self.unchecked_catch_up_deferred_to_quiescence();
+        self.processing_budget.set(None);
+        self.executing.store(false, Ordering::Relaxed);
+
+        let mut paused = self
+            .paused_events
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        // A `PauseQueue` among the processed events may have started holding events again
+        let rest = paused.get_or_insert_with(Vec::new);
+        let mut remaining = vec![];
+        while let Some(event) = self.deferred_events.pop() {
+            remaining.push(event);
+        }
+        remaining.append(rest);
+        *rest = remaining;
+        rest.len()
+    }
+    /// Continues a drain that has been stopped by the drain limit, for at most `n` events.
+    fn process_queued_at_most(&self, n: usize) -> usize {
+        if self.deferred_events.len() == 0 {
+            return 0;
+        }
+        self.executing.store(true, Ordering::Relaxed);
+        self.processing_budget.set(Some(n));
+        self.unchecked_catch_up_deferred_to_quiescence();
+        self.processing_budget.set(None);
+        let held = self
+            .paused_events
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .as_ref()
+            .map_or(0, Vec::len);
+        let remaining = self.deferred_events.len() + held;
+        self.reach_quiescence();
+        remaining
+    }
+    fn hold_if_paused(&self, deferred: Deferred) -> Option<Deferred> {
+        match self
+            .paused_events
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .as_mut()
+        {
+            Some(held) => {
+                held.push(deferred);
+                None
+            }
+            None => Some(deferred),
+        }
+    }
+    /// Must not be called from inside a subscription handler
+    pub(crate) fn unchecked_dump_state(&self) -> String {
+        let activities = self
+            .activities
+            .try_borrow()
+            .expect("Cannot dump state from inside a subscription handler.");
+        let mut out = String::new();
+        let mut n = 0;
+        activities.visit(|meta, _| {
+            n += 1;
+            let _ = writeln!(
+                out,
+                "  #{} {} [{:?}] domain: {:?}",
+                meta.id.as_usize(),
+                meta.type_name,
+                meta.status,
+                meta.domain
+            );
+        });
+        let held = self
+            .paused_events
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .as_ref()
+            .map(Vec::len);
+        format!(
+            "Nuts state\n{} activities:\n{}queued events: {}\nsubscribed topics: {}\nqueue paused: {}",
+            n,
+            out,
+            self.deferred_events.len(),
+            self.subscriptions.get().len(),
+            match held {
+                Some(held) => format!("yes, {} events held back", held),
+                None => "no".to_owned(),
+            }
+        )
+    }
+}
@@ -1,4 +1,5 @@
-use core::cell::RefCell;
+use crate::nut::audit::{self, Allocation};
+use core::cell::{Cell, RefCell};
 use std::collections::VecDeque;
 
 /// FIFO queue that allows thread-local atomic pushing and popping.
@@ -13,15 +14,29 @@ pub(crate) struct ThreadLocalFifo<ITEM> {
 
 impl<ITEM> ThreadLocalFifo<ITEM> {
     pub(crate) fn push(&self, i: ITEM) {
-        self.fifo.borrow_mut().push_back(i);
+        let mut fifo = self.fifo.borrow_mut();
+        if fifo.len() == fifo.capacity() {
+            audit::record(Allocation::Queue);
+        }
+        fifo.push_back(i);
     }
     pub(crate) fn pop(&self) -> Option<ITEM> {
         self.fifo.borrow_mut().pop_front()
     }
-    #[cfg(feature = "verbose-debug-log")]
     pub(crate) fn len(&self) -> usize {
         self.fifo.borrow().len()
     }
+    /// Releases unused capacity and returns the number of items that could have been stored in it
+    pub(crate) fn shrink_to_fit(&self) -> usize {
+        let mut fifo = self.fifo.borrow_mut();
+        let before = fifo.capacity();
+        fifo.shrink_to_fit();
+        before - fifo.capacity()
+    }
+    /// True iff any queued item satisfies the predicate
+    pub(crate) fn any(&self, f: impl Fn(&ITEM) -> bool) -> bool {
+        self.fifo.borrow().iter().any(f)
+    }
 }
 
 impl<ITEM: std::fmt::Debug> ThreadLocalFifo<ITEM> {
@@ -46,3 +61,80 @@ impl<ITEM> Default for ThreadLocalFifo<ITEM> {
         }
     }
 }
+
+/// Two FIFO queues, where items in the urgent queue are always popped first.
+///
+/// Priority is inherited: While an urgent item is being processed (that is, between popping it and popping the next item),
+/// all pushed items are also considered urgent.
+///
+/// A third queue for internal bookkeeping is popped before both, it does not affect the inherited priority.
+pub(crate) struct PriorityFifo<ITEM> {
+    internal: ThreadLocalFifo<ITEM>,
+    urgent: ThreadLocalFifo<ITEM>,
+    normal: ThreadLocalFifo<ITEM>,
+    elevated: Cell<bool>,
+}
+
+impl<ITEM> PriorityFifo<ITEM> {
+    /// Pushes to the back of the normal queue, unless an urgent item is currently processed.
+    pub(crate) fn push(&self, i: ITEM) {
+        if self.elevated.get() {
+            self.urgent.push(i);
+        } else {
+            self.normal.push(i);
+        }
+    }
+    pub(crate) fn push_urgent(&self, i: ITEM) {
+        self.urgent.push(i);
+    }
+    pub(crate) fn push_internal(&self, i: ITEM) {
+        self.internal.push(i);
+    }
+    pub(crate) fn pop(&self) -> Option<ITEM> {
+        if let Some(i) = self.internal.pop() {
+            return Some(i);
+        }
+        if let Some(i) = self.urgent.pop() {
+            self.elevated.set(true);
+            return Some(i);
+        }
+        self.elevated.set(false);
+        self.normal.pop()
+    }
+    pub(crate) fn len(&self) -> usize {
+        self.internal.len() + self.urgent.len() + self.normal.len()
+    }
+    /// Ends the inherited priority when items are no longer processed, also if urgent items are left in the queue
+    pub(crate) fn end_drain(&self) {
+        self.elevated.set(false);
+    }
+    pub(crate) fn shrink_to_fit(&self) -> usize {
+        self.internal.shrink_to_fit() + self.urgent.shrink_to_fit() + self.normal.shrink_to_fit()
+    }
+    /// True iff any queued item satisfies the predicate
+    pub(crate) fn any(&self, f: impl Fn(&ITEM) -> bool) -> bool {
+        self.internal.any(&f) || self.urgent.any(&f) || self.normal.any(&f)
+    }
+}
+
+impl<ITEM: std::fmt::Debug> PriorityFifo<ITEM> {
+    #[cfg(feature = "verbose-debug-log")]
+    pub(crate) fn events_debug_list(&self) -> String {
+        format!(
+            "internal: {}, urgent: {}, normal: {}",
+            self.internal.events_debug_list(),
+            self.urgent.events_debug_list(),
+            self.normal.events_debug_list()
+        )
+    }
+}
+impl<ITEM> Default for PriorityFifo<ITEM> {
+    fn default() -> Self {
+        PriorityFifo {
+            internal: Default::default(),
+            urgent: Default::default(),
+            normal: Default::default(),
+            elevated: Cell::new(false),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Frame structure for game loops: published messages are delivered to activities one phase after another.
+
+use crate::nut::iac::publish::BroadcastInfo;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use core::any::Any;
+use core::sync::atomic::Ordering;
+use std::collections::HashMap;
+
+/// A step in the processing of a frame, see [`run_frame`](fn.run_frame.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Handling user input
+    Input,
+    /// Updating the state of the world
+    Update,
+    /// Drawing the world
+    Render,
+    /// A phase defined by the application, it must be listed in [`set_frame_phases`](fn.set_frame_phases.html) to be processed
+    Custom(&'static str),
+}
+
+/// Phases of activities and the messages that have not been delivered to all phases, yet
+pub(crate) struct Frames {
+    phases: Vec<Phase>,
+    affinity: HashMap<usize, Phase>,
+    queue: Vec<Queued>,
+    /// Position in `queue` of the message that is currently dispatched to a phase
+    in_flight: Option<usize>,
+}
+
+struct Queued {
+    /// `None` while the message is dispatched
+    broadcast: Option<BroadcastInfo>,
+    /// One bit per phase, set if the message has not been delivered to that phase, yet
+    pending: u64,
+}
+
+impl Default for Frames {
+    fn default() -> Self {
+        Self {
+            phases: vec![Phase::Input, Phase::Update, Phase::Render],
+            affinity: HashMap::new(),
+            queue: vec![],
+            in_flight: None,
+        }
+    }
+}
+
+impl Frames {
+    pub(crate) fn set_phases(&mut self, phases: Vec<Phase>) {
+        assert!(phases.len() <= 64, "At most 64 phases are supported.");
+        let all = all_phases(phases.len());
+        for queued in &mut self.queue {
+            queued.pending = all;
+        }
+        self.phases = phases;
+    }
+    pub(crate) fn set_affinity(&mut self, activity: usize, phase: Phase) {
+        self.affinity.insert(activity, phase);
+    }
+    pub(crate) fn remove_affinity(&mut self, activity: usize) {
+        self.affinity.remove(&activity);
+    }
+    /// Whether any activity has a phase, otherwise all messages are delivered immediately to all subscribers
+    pub(crate) fn in_use(&self) -> bool {
+        !self.affinity.is_empty()
+    }
+    pub(crate) fn phase_of(&self, activity: usize) -> Option<Phase> {
+        self.affinity.get(&activity).copied()
+    }
+    /// Keeps a published message for delivery in the next phases
+    pub(crate) fn enqueue(&mut self, broadcast: BroadcastInfo) {
+        self.queue.push(Queued {
+            broadcast: Some(broadcast),
+            pending: all_phases(self.phases.len()),
+        });
+    }
+    /// Returns the message after it has been dispatched to a phase
+    pub(crate) fn restore(&mut self, msg: Option<Box<dyn Any>>, broadcast: BroadcastInfo) {
+        let i = self.in_flight.take().expect(IMPOSSIBLE_ERR_MSG);
+        match msg {
+            Some(msg) => self.queue[i].broadcast = Some(broadcast.with_msg(msg)),
+            // Nobody can take ownership of published messages, but stay consistent if it happens
+            None => self.queue[i].pending = 0,
+        }
+    }
+    /// Takes the next message at or after `cursor` that is pending for the given phase
+    fn next_pending(&mut self, phase: usize, cursor: &mut usize) -> Option<BroadcastInfo> {
+        let bit = 1 << phase;
+        while *cursor < self.queue.len() {
+            let i = *cursor;
+            *cursor += 1;
+            let queued = &mut self.queue[i];
+            if queued.pending & bit != 0 {
+                queued.pending &= !bit;
+                self.in_flight = Some(i);
+                let broadcast = queued.broadcast.take().expect(IMPOSSIBLE_ERR_MSG);
+                return Some(broadcast.for_phase(self.phases[phase]));
+            }
+        }
+        None
+    }
+    fn end_frame(&mut self) {
+        self.queue.retain(|queued| queued.pending != 0);
+    }
+}
+
+fn all_phases(n: usize) -> u64 {
+    if n == 64 {
+        u64::MAX
+    } else {
+        (1 << n) - 1
+    }
+}
+
+impl Nut {
+    /// Delivers the queued messages to the activities of each phase, one phase after another.
+    pub(crate) fn run_frame(&self) {
+        assert!(
+            !self.executing.load(Ordering::Relaxed),
+            "run_frame must not be called from inside a subscription handler."
+        );
+        // Messages queued before the frame must reach activities without phase first
+        self.catch_up_deferred_to_quiescence();
+        self.executing.store(true, Ordering::Relaxed);
+        let phases = self
+            .frames
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .phases
+            .len();
+        for phase in 0..phases {
+            let mut cursor = 0;
+            loop {
+                let next = self
+                    .frames
+                    .try_borrow_mut()
+                    .expect(IMPOSSIBLE_ERR_MSG)
+                    .next_pending(phase, &mut cursor);
+                match next {
+                    Some(broadcast) => {
+                        self.unchecked_broadcast(broadcast);
+                        self.unchecked_catch_up_deferred_to_quiescence();
+                    }
+                    None => break,
+                }
+            }
+        }
+        self.frames
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .end_frame();
+        self.executing.store(false, Ordering::Relaxed);
+        self.catch_up_deferred_to_quiescence();
+    }
+}
@@ -2,7 +2,10 @@
 //! To still be able to add new activities and subscriptions during that time, temporary
 //! structures are used to buffer additions. Theses are then merged in a deferred event.
 
-use crate::{Activity, ActivityContainer, ActivityId, DomainId, LifecycleStatus};
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::{
+    Activity, ActivityContainer, ActivityId, DomainId, LifecycleStatus, UncheckedActivityId,
+};
 
 pub(crate) struct InchoateActivityContainer {
     activities: ActivityContainer,
@@ -30,6 +33,15 @@ impl InchoateActivityContainer {
     pub(crate) fn len(&self) -> usize {
         self.activities.len()
     }
+    /// True if the activity with this index has been moved to the final container.
+    ///
+    /// Indices of inchoate activities start at the offset.
+    pub(crate) fn is_flushed(&self, index: usize) -> bool {
+        index < self.offset
+    }
+    pub(crate) fn shrink_to_fit(&mut self) -> usize {
+        self.activities.shrink_to_fit()
+    }
     pub(crate) fn flush(&mut self, final_activities: &mut ActivityContainer) {
         self.offset += self.len();
         final_activities.append(&mut self.activities);
@@ -49,3 +61,13 @@ impl InchoateActivityContainer {
         aid
     }
 }
+
+impl Nut {
+    /// Activities are live once they have been flushed from the inchoate container, only then messages and status changes can reach them.
+    pub(crate) fn is_live(&self, id: UncheckedActivityId) -> bool {
+        self.inchoate_activities
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .is_flushed(id.index)
+    }
+}
@@ -0,0 +1,72 @@
+//! Temporary data shared by the handlers of one queue drain, see `nuts::scratch`.
+
+use crate::nut::IMPOSSIBLE_ERR_MSG;
+use core::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// Values stored in the scratch space, at most one per type
+#[derive(Default)]
+pub(crate) struct ScratchMap {
+    values: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl ScratchMap {
+    pub(crate) fn insert<T: Any>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .map(|old| *old.downcast().expect(IMPOSSIBLE_ERR_MSG))
+    }
+    pub(crate) fn take<T: Any>(&mut self) -> Option<T> {
+        self.values
+            .remove(&TypeId::of::<T>())
+            .map(|old| *old.downcast().expect(IMPOSSIBLE_ERR_MSG))
+    }
+    pub(crate) fn get<T: Any + Clone>(&self) -> Option<T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .map(|value| value.downcast_ref::<T>().expect(IMPOSSIBLE_ERR_MSG).clone())
+    }
+    pub(crate) fn contains<T: Any>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+}
+
+/// Handle to the scratch space of the current queue drain, returned by [`nuts::scratch`](fn.scratch.html).
+///
+/// The scratch space stores at most one value per type.
+/// All values are dropped when no more messages are queued.
+#[derive(Debug, Clone, Copy)]
+pub struct Scratch {
+    _private: (),
+}
+
+impl Scratch {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+    /// Stores the value, returns the value of the same type that has been stored before, if any.
+    pub fn insert<T: Any>(&self, value: T) -> Option<T> {
+        crate::nut::with_scratch(|scratch| scratch.insert(value))
+    }
+    /// Returns a copy of the stored value of type `T`.
+    pub fn get<T: Any + Clone>(&self) -> Option<T> {
+        crate::nut::with_scratch(|scratch| scratch.get())
+    }
+    /// Removes the stored value of type `T` and returns it.
+    pub fn take<T: Any>(&self) -> Option<T> {
+        crate::nut::with_scratch(|scratch| scratch.take())
+    }
+    /// Returns true if a value of type `T` is stored.
+    pub fn contains<T: Any>(&self) -> bool {
+        crate::nut::with_scratch(|scratch| scratch.contains::<T>())
+    }
+    /// Calls the closure with the stored value of type `T`, which is created with `Default` first if necessary.
+    ///
+    /// The value is taken out of the scratch space while the closure runs, the closure may therefore use the scratch space, too.
+    pub fn update<T: Any + Default, R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut value = self.take::<T>().unwrap_or_default();
+        let result = f(&mut value);
+        self.insert(value);
+        result
+    }
+}
@@ -0,0 +1,263 @@
+//! Futures owned by activities, polled by Nuts whenever they have been woken.
+
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::{Error, UncheckedActivityId};
+use core::cell::{Cell, RefCell};
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::Wake;
+
+pub(crate) type TaskFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+/// What happens to an attached future when its activity is deleted, see [`ActivityId::attach_future_with`](struct.ActivityId.html#method.attach_future_with).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskPolicy {
+    /// The future is dropped, its handle resolves to `Err(Error::TaskCancelled)`
+    Cancel,
+    /// The future keeps running until it completes, as if it had been attached to no activity
+    Detach,
+}
+
+/// Resolves when an attached future has completed or has been cancelled, returned by [`ActivityId::attach_future_with`](struct.ActivityId.html#method.attach_future_with).
+///
+/// Dropping the handle does not affect the attached future.
+#[derive(Debug)]
+pub struct TaskHandle {
+    completion: Rc<Completion>,
+}
+
+/// Shared between a task and its handle
+#[derive(Debug, Default)]
+struct Completion {
+    outcome: Cell<Option<Result<(), Error>>>,
+    waker: RefCell<Option<Waker>>,
+}
+
+impl Completion {
+    fn complete(&self, outcome: Result<(), Error>) {
+        self.outcome.set(Some(outcome));
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Future for TaskHandle {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.completion.outcome.get() {
+            Some(outcome) => Poll::Ready(outcome),
+            None => {
+                *self.completion.waker.borrow_mut() = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Storage for futures attached to activities.
+///
+/// Futures are pinned on the heap when they are attached and never move afterwards.
+/// Therefore, they do not have to implement `Unpin`.
+#[derive(Default)]
+pub(crate) struct TaskArena {
+    next_id: u64,
+    tasks: Vec<Task>,
+}
+
+struct Task {
+    id: u64,
+    /// `None` for detached tasks, which are not removed with the activity
+    owner: Option<UncheckedActivityId>,
+    /// `None` while the future is being polled
+    future: Option<TaskFuture>,
+    waker: Arc<TaskWaker>,
+    /// Shared with the `TaskHandle`, if any
+    completion: Option<Rc<Completion>>,
+}
+
+/// Marks a task for polling, may be sent to other threads
+struct TaskWaker {
+    woken: AtomicBool,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.woken.store(true, Ordering::Relaxed);
+    }
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Task {
+    fn take_if_woken(&mut self) -> Option<(u64, TaskFuture, Waker)> {
+        if self.future.is_some() && self.waker.woken.swap(false, Ordering::Relaxed) {
+            let future = self.future.take().expect(IMPOSSIBLE_ERR_MSG);
+            Some((self.id, future, self.waker.clone().into()))
+        } else {
+            None
+        }
+    }
+}
+
+impl TaskArena {
+    pub(crate) fn add(
+        &mut self,
+        owner: UncheckedActivityId,
+        future: TaskFuture,
+        policy: TaskPolicy,
+    ) -> TaskHandle {
+        self.next_id += 1;
+        let completion = Rc::new(Completion::default());
+        self.tasks.push(Task {
+            id: self.next_id,
+            owner: match policy {
+                TaskPolicy::Cancel => Some(owner),
+                TaskPolicy::Detach => None,
+            },
+            future: Some(future),
+            // Poll once right away
+            waker: Arc::new(TaskWaker {
+                woken: AtomicBool::new(true),
+            }),
+            completion: Some(completion.clone()),
+        });
+        TaskHandle { completion }
+    }
+    /// Takes out the future of a task that has been woken, to poll it without borrowing the arena.
+    fn take_woken(&mut self) -> Option<(u64, TaskFuture, Waker)> {
+        self.tasks.iter_mut().find_map(Task::take_if_woken)
+    }
+    /// Same as `take_woken` but only considers the task with the given ID.
+    fn take_woken_by_id(&mut self, id: u64) -> Option<(u64, TaskFuture, Waker)> {
+        self.tasks
+            .iter_mut()
+            .find(|task| task.id == id)
+            .and_then(Task::take_if_woken)
+    }
+    fn woken_ids(&self) -> Vec<u64> {
+        self.tasks
+            .iter()
+            .filter(|task| task.future.is_some() && task.waker.woken.load(Ordering::Relaxed))
+            .map(|task| task.id)
+            .collect()
+    }
+    /// Returns a polled future to the arena.
+    /// If the task has been removed in the meantime, the future is returned to the caller to be dropped.
+    fn put_back(&mut self, id: u64, future: TaskFuture) -> Option<TaskFuture> {
+        match self.tasks.iter_mut().find(|task| task.id == id) {
+            Some(task) => {
+                task.future = Some(future);
+                None
+            }
+            None => Some(future),
+        }
+    }
+    /// Removes a completed task and returns its completion, to be completed by the caller
+    fn finish(&mut self, id: u64) -> Option<Rc<Completion>> {
+        let index = self.tasks.iter().position(|task| task.id == id)?;
+        self.tasks.remove(index).completion
+    }
+    /// Removes all tasks of an activity and returns their futures and completions, to be dropped and completed by the caller
+    fn remove_owned_by(
+        &mut self,
+        owner: UncheckedActivityId,
+    ) -> (Vec<TaskFuture>, Vec<Rc<Completion>>) {
+        let mut futures = vec![];
+        let mut completions = vec![];
+        self.tasks.retain_mut(|task| {
+            if task.owner == Some(owner) {
+                futures.extend(task.future.take());
+                completions.extend(task.completion.take());
+                false
+            } else {
+                true
+            }
+        });
+        (futures, completions)
+    }
+}
+
+impl Nut {
+    /// Polls all tasks that have been woken, until none is left.
+    ///
+    /// Returns immediately if tasks are already being polled further up the stack.
+    pub(crate) fn poll_tasks(&self) {
+        if self.polling_tasks.replace(true) {
+            return;
+        }
+        loop {
+            let next = self
+                .tasks
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .take_woken();
+            match next {
+                Some(next) => self.poll_task(next),
+                None => break,
+            }
+        }
+        self.polling_tasks.set(false);
+    }
+    /// Polls each task that has been woken once, tasks woken in the meantime are left for the next call.
+    ///
+    /// Returns true if tasks are left that have been woken.
+    pub(crate) fn poll_tasks_once(&self) -> bool {
+        if self.polling_tasks.replace(true) {
+            return false;
+        }
+        let woken = self
+            .tasks
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .woken_ids();
+        for id in woken {
+            let next = self
+                .tasks
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .take_woken_by_id(id);
+            if let Some(next) = next {
+                self.poll_task(next);
+            }
+        }
+        self.polling_tasks.set(false);
+        !self
+            .tasks
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .woken_ids()
+            .is_empty()
+    }
+    fn poll_task(&self, (id, mut future, waker): (u64, TaskFuture, Waker)) {
+        let mut cx = Context::from_waker(&waker);
+        let poll = future.as_mut().poll(&mut cx);
+        let mut tasks = self.tasks.try_borrow_mut().expect(IMPOSSIBLE_ERR_MSG);
+        let (orphan, completion) = match poll {
+            Poll::Ready(()) => (Some(future), tasks.finish(id)),
+            Poll::Pending => (tasks.put_back(id, future), None),
+        };
+        drop(tasks);
+        drop(orphan);
+        if let Some(completion) = completion {
+            completion.complete(Ok(()));
+        }
+    }
+    pub(crate) fn drop_tasks_of(&self, owner: UncheckedActivityId) {
+        let (futures, completions) = self
+            .tasks
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .remove_owned_by(owner);
+        drop(futures);
+        for completion in completions {
+            completion.complete(Err(Error::TaskCancelled));
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Orderly teardown of all activities and domains, when the thread-local nut is destroyed or the application exits.
+
+use super::Deferred;
+use crate::nut::iac::publish::BroadcastInfo;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::*;
+use core::cell::Cell;
+use core::sync::atomic::Ordering;
+use std::rc::Rc;
+
+/// Published by [`request_exit`](fn.request_exit.html), before the application is torn down.
+///
+/// Any handler can prevent the exit by calling [`veto`](#method.veto).
+#[derive(Debug)]
+pub struct ExitRequested {
+    /// The exit code passed to `request_exit`
+    pub code: i32,
+    vetoed: Rc<Cell<bool>>,
+}
+
+impl ExitRequested {
+    /// Cancels the exit, nothing is torn down.
+    pub fn veto(&self) {
+        self.vetoed.set(true);
+    }
+    /// Whether a handler has already vetoed the exit.
+    pub fn is_vetoed(&self) -> bool {
+        self.vetoed.get()
+    }
+}
+
+/// Called with the exit code at the end of `request_exit`
+pub(crate) type ExitHandler = Box<dyn FnOnce(i32)>;
+
+/// Queued after `ExitRequested`, exits unless a handler vetoed.
+pub(crate) struct Exit {
+    code: i32,
+    vetoed: Rc<Cell<bool>>,
+}
+
+impl Nut {
+    pub(crate) fn request_exit(&self, code: i32) {
+        let vetoed = Rc::new(Cell::new(false));
+        let request = ExitRequested {
+            code,
+            vetoed: vetoed.clone(),
+        };
+        let mut broadcast =
+            BroadcastInfo::global(request, Topic::public_message::<ExitRequested>());
+        self.trace_broadcast(&mut broadcast);
+        self.deferred_events.push(broadcast.into());
+        self.deferred_events
+            .push(Deferred::Exit(Exit { code, vetoed }));
+        self.catch_up_deferred_to_quiescence();
+    }
+    /// only access after locking with executing flag
+    pub(crate) fn unchecked_exit(&self, exit: &Exit) {
+        if exit.vetoed.get() || self.torn_down.get() {
+            return;
+        }
+        self.tear_down();
+        let handler = self
+            .exit_handler
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .take();
+        if let Some(handler) = handler {
+            handler(exit.code);
+        }
+    }
+    /// Tears down in this order:
+    /// 1. The teardown hook is called.
+    /// 2. `on_leave` is called for all active activities, in reverse creation order.
+    /// 3. All activities are deleted in reverse creation order, calling their `on_delete`.
+    /// 4. Domain objects are dropped in reverse creation order.
+    ///
+    /// Only access after locking with executing flag.
+    fn tear_down(&self) {
+        self.torn_down.set(true);
+        let hook = self
+            .teardown_hook
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .take();
+        if let Some(hook) = hook {
+            hook();
+        }
+        self.exec_deferred(Deferred::FlushInchoateActivities);
+
+        let mut ids = vec![];
+        self.activities
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .visit(|meta, _| ids.push((meta.id, meta.status)));
+        ids.reverse();
+
+        for (id, status) in &ids {
+            if status.is_active() {
+                self.unchecked_broadcast(BroadcastInfo::local((), *id, Topic::leave()));
+            }
+        }
+        for (id, _) in ids {
+            self.delete_activity(id);
+        }
+        self.managed_state
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .drop_domains();
+    }
+}
+
+impl Drop for Nut {
+    /// Tears down everything, unless that already happened on exit.
+    ///
+    /// Queued messages that have not been delivered, yet, are dropped without delivering them.
+    fn drop(&mut self) {
+        // Subscription handlers cannot access the nut anymore, nothing new can be queued
+        self.executing.store(true, Ordering::Relaxed);
+        if !self.torn_down.get() {
+            self.tear_down();
+        }
+    }
+}
@@ -6,8 +6,16 @@
 //!
 //! TODO: model for shared memory is planned for higher bandwidth communication.
 
+pub(crate) mod codec;
+pub(crate) mod digest;
+pub(crate) mod double_buffer;
+pub(crate) mod dynamic;
 pub(crate) mod filter;
+pub(crate) mod immutable;
 pub(crate) mod managed_state;
+pub(crate) mod params;
+#[cfg(feature = "perf")]
+pub(crate) mod profile;
 pub(crate) mod publish;
 pub(crate) mod subscription;
 pub(crate) mod topic;
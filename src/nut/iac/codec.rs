@@ -0,0 +1,132 @@
+//! Binary encoding of messages, registered once per message type and shared by everything that serializes messages.
+
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::Error;
+use core::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Converts messages of type `MSG` to bytes and back, registered with [`register_codec`](fn.register_codec.html).
+///
+/// Nuts does not depend on a serialization library, implement it with any format.
+pub trait MessageCodec<MSG> {
+    /// Serializes the message.
+    fn encode(&self, msg: &MSG) -> Vec<u8>;
+    /// Deserializes a message, `None` if the bytes are malformed.
+    fn decode(&self, bytes: &[u8]) -> Option<MSG>;
+}
+
+/// A message in binary form, created with [`encode_message`](fn.encode_message.html).
+///
+/// The type ID is only meaningful inside the same build of the application.
+/// Use `type_name` to identify the message type across processes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodedMessage {
+    /// Type of the encoded message
+    pub type_id: TypeId,
+    /// Type name of the encoded message, as returned by `std::any::type_name`
+    pub type_name: &'static str,
+    /// Output of [`MessageCodec::encode`](trait.MessageCodec.html#tymethod.encode)
+    pub bytes: Vec<u8>,
+}
+
+type EncodeFn = Box<dyn Fn(&dyn Any) -> Vec<u8>>;
+type PublishFn = Box<dyn Fn(&[u8]) -> bool>;
+
+/// Type-erased codec of one message type
+struct ErasedCodec {
+    type_name: &'static str,
+    encode: EncodeFn,
+    /// Decodes and publishes the message, false if the bytes are malformed
+    publish: PublishFn,
+}
+
+/// All registered codecs, by the type ID of the message
+#[derive(Default)]
+pub(crate) struct CodecRegistry {
+    codecs: HashMap<TypeId, Rc<ErasedCodec>>,
+    by_name: HashMap<&'static str, TypeId>,
+}
+
+impl CodecRegistry {
+    pub(crate) fn register<MSG, C>(&mut self, codec: C)
+    where
+        MSG: Any,
+        C: MessageCodec<MSG> + 'static,
+    {
+        let codec = Rc::new(codec);
+        let decoder = codec.clone();
+        let type_name = std::any::type_name::<MSG>();
+        let erased = ErasedCodec {
+            type_name,
+            encode: Box::new(move |msg| {
+                codec.encode(msg.downcast_ref().expect(IMPOSSIBLE_ERR_MSG))
+            }),
+            publish: Box::new(move |bytes| match decoder.decode(bytes) {
+                Some(msg) => {
+                    crate::nut::publish_custom::<MSG>(msg);
+                    true
+                }
+                None => false,
+            }),
+        };
+        self.codecs.insert(TypeId::of::<MSG>(), Rc::new(erased));
+        self.by_name.insert(type_name, TypeId::of::<MSG>());
+    }
+    fn get(&self, type_id: TypeId) -> Option<Rc<ErasedCodec>> {
+        self.codecs.get(&type_id).cloned()
+    }
+    fn type_by_name(&self, type_name: &str) -> Option<(TypeId, &'static str)> {
+        self.by_name
+            .get_key_value(type_name)
+            .map(|(name, id)| (*id, *name))
+    }
+}
+
+impl EncodedMessage {
+    /// Reassembles a message that has been transferred as type name and bytes, for example over the network.
+    ///
+    /// # Errors
+    /// Returns `Error::NoCodec` if no codec has been registered for a message type with that name.
+    pub fn from_parts(type_name: &str, bytes: Vec<u8>) -> Result<Self, Error> {
+        let (type_id, type_name) = crate::nut::with_message_codecs(|codecs| {
+            codecs.type_by_name(type_name).ok_or(Error::NoCodec)
+        })?;
+        Ok(Self {
+            type_id,
+            type_name,
+            bytes,
+        })
+    }
+}
+
+impl Nut {
+    // Codecs are cloned out of the registry before they are called, such that they may use Nuts themselves.
+    fn codec(&self, type_id: TypeId) -> Option<Rc<ErasedCodec>> {
+        self.message_codecs
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .get(type_id)
+    }
+    /// Encodes a message of any type that has a registered codec
+    pub(crate) fn encode_message(
+        &self,
+        type_id: TypeId,
+        msg: &dyn Any,
+    ) -> Result<EncodedMessage, Error> {
+        let codec = self.codec(type_id).ok_or(Error::NoCodec)?;
+        Ok(EncodedMessage {
+            type_id,
+            type_name: codec.type_name,
+            bytes: (codec.encode)(msg),
+        })
+    }
+    pub(crate) fn publish_encoded(&self, type_id: TypeId, bytes: &[u8]) -> Result<(), Error> {
+        let codec = self.codec(type_id).ok_or(Error::NoCodec)?;
+        if (codec.publish)(bytes) {
+            Ok(())
+        } else {
+            Err(Error::MalformedMessage)
+        }
+    }
+}
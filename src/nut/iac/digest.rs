@@ -0,0 +1,138 @@
+//! Subscriptions that receive batches of messages instead of every single message.
+
+use crate::nut::iac::filter::SubscriptionFilter;
+use crate::{Activity, ActivityId, SubscriptionId, TimerHandle};
+use core::any::Any;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// How messages are grouped into digests, see [`ActivityId::digest`](struct.ActivityId.html#method.digest).
+///
+/// Converts from `usize` for a number of messages and from `Duration` for a time window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestWindow {
+    /// A digest is delivered as soon as this many messages have been collected
+    Count(usize),
+    /// All messages collected are delivered whenever this period has elapsed, according to [`advance_time`](fn.advance_time.html).
+    ///
+    /// No digest is delivered for a period without messages.
+    Time(Duration),
+}
+
+impl From<usize> for DigestWindow {
+    fn from(n: usize) -> Self {
+        DigestWindow::Count(n)
+    }
+}
+
+impl From<Duration> for DigestWindow {
+    fn from(period: Duration) -> Self {
+        DigestWindow::Time(period)
+    }
+}
+
+/// A subscription that is called with batches of messages.
+///
+/// Created with [`ActivityId::digest`](struct.ActivityId.html#method.digest).
+#[must_use = "The handler is only registered when calling subscribe() or another method of this struct."]
+pub struct DigestSubscription<A, MSG> {
+    id: ActivityId<A>,
+    window: DigestWindow,
+    _msg: core::marker::PhantomData<fn(&MSG)>,
+}
+
+/// Published by the timer of a digest with a time window when messages have been collected
+struct DigestDue(u64);
+
+thread_local!(static NEXT_DIGEST: Cell<u64> = const { Cell::new(0) });
+
+impl<A, MSG> DigestSubscription<A, MSG>
+where
+    A: Activity,
+    MSG: Any + Clone,
+{
+    pub(crate) fn new(id: ActivityId<A>, window: DigestWindow) -> Self {
+        assert_ne!(
+            window,
+            DigestWindow::Count(0),
+            "A digest must collect at least one message."
+        );
+        Self {
+            id,
+            window,
+            _msg: core::marker::PhantomData,
+        }
+    }
+    /// Registers the handler, which receives the collected messages in the order they have been published.
+    pub fn subscribe<F>(self, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, Vec<MSG>) + 'static,
+    {
+        self.subscribe_masked(Default::default(), f)
+    }
+    /// Registers the handler, see [`ActivityId::subscribe_masked`](struct.ActivityId.html#method.subscribe_masked).
+    ///
+    /// The mask applies to collecting messages as well as to delivering digests.
+    /// Digests that are due while the activity is filtered out are delivered later, together with the messages collected in the meantime.
+    pub fn subscribe_masked<F>(self, mask: SubscriptionFilter, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, Vec<MSG>) + 'static,
+    {
+        let collected: Rc<RefCell<Vec<MSG>>> = Default::default();
+        match self.window {
+            DigestWindow::Count(n) => self.id.subscribe_masked(mask, move |a, msg: &MSG| {
+                let full = {
+                    let mut collected = collected.borrow_mut();
+                    collected.push(msg.clone());
+                    collected.len() >= n
+                };
+                if full {
+                    f(a, collected.take());
+                }
+            }),
+            DigestWindow::Time(period) => {
+                let digest = NEXT_DIGEST.with(|next| {
+                    next.set(next.get() + 1);
+                    next.get()
+                });
+                let pending = Rc::downgrade(&collected);
+                let collector = collected.clone();
+                let id = self.id;
+                let sub = crate::nut::group_subscriptions(move || {
+                    id.subscribe_masked(mask.clone(), move |_, msg: &MSG| {
+                        collector.borrow_mut().push(msg.clone())
+                    });
+                    id.subscribe_masked(mask, move |a, due: &DigestDue| {
+                        if due.0 == digest {
+                            let batch = collected.take();
+                            if !batch.is_empty() {
+                                f(a, batch);
+                            }
+                        }
+                    });
+                });
+                // The timer stops once the subscriptions have been dropped, together with the activity or when unsubscribed
+                let handle: Rc<Cell<Option<TimerHandle>>> = Default::default();
+                let own_handle = handle.clone();
+                let timer = crate::nut::add_timer(
+                    period,
+                    Rc::new(move || match pending.upgrade() {
+                        Some(collected) => {
+                            if !collected.borrow().is_empty() {
+                                crate::nut::publish_custom(DigestDue(digest));
+                            }
+                        }
+                        None => {
+                            if let Some(timer) = own_handle.get() {
+                                timer.cancel();
+                            }
+                        }
+                    }),
+                );
+                handle.set(Some(timer));
+                sub
+            }
+        }
+    }
+}
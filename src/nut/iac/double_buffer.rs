@@ -0,0 +1,110 @@
+//! Double-buffered state, swapped between broadcasts.
+
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use core::cell::{Cell, Ref, RefCell};
+use std::rc::Rc;
+
+/// Write access to a double buffer, created with [`double_buffer`](fn.double_buffer.html).
+pub struct BufferWriter<T> {
+    buffers: Rc<Buffers<T>>,
+}
+
+/// Read access to a double buffer, created with [`double_buffer`](fn.double_buffer.html).
+///
+/// Readers can be cloned and shared between activities.
+pub struct BufferReader<T> {
+    buffers: Rc<Buffers<T>>,
+}
+
+pub(crate) struct Buffers<T> {
+    /// The last completed state, visible to readers
+    front: RefCell<T>,
+    /// The state currently written to
+    back: RefCell<T>,
+    /// Set when the back buffer has been written to since the last swap
+    dirty: Cell<bool>,
+}
+
+/// A double buffer with pending writes
+pub(crate) trait Swap {
+    fn swap(&self);
+}
+
+pub(crate) fn new<T: Clone + 'static>(initial: T) -> (BufferWriter<T>, BufferReader<T>) {
+    let buffers = Rc::new(Buffers {
+        front: RefCell::new(initial.clone()),
+        back: RefCell::new(initial),
+        dirty: Cell::new(false),
+    });
+    (
+        BufferWriter {
+            buffers: buffers.clone(),
+        },
+        BufferReader { buffers },
+    )
+}
+
+impl<T: Clone + 'static> BufferWriter<T> {
+    /// Modifies the state that is currently being written.
+    ///
+    /// Inside subscription handlers, readers see the change once the current broadcast has been handled completely.
+    /// Outside of handlers, the change is visible immediately.
+    /// The buffer starts out as a copy of the last completed state, so it can be updated partially.
+    pub fn write<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let result = f(&mut self
+            .buffers
+            .back
+            .try_borrow_mut()
+            .expect("Double buffer is already being written to."));
+        if !self.buffers.dirty.replace(true) {
+            crate::nut::swap_after_broadcast(self.buffers.clone());
+        }
+        result
+    }
+}
+
+impl<T> BufferReader<T> {
+    /// Returns the last completed state.
+    ///
+    /// # Panics
+    /// Panics if the returned reference is still held when the buffers are swapped at the end of a broadcast.
+    pub fn read(&self) -> Ref<'_, T> {
+        self.buffers.front.borrow()
+    }
+}
+
+impl<T> Clone for BufferReader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            buffers: self.buffers.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Swap for Buffers<T> {
+    fn swap(&self) {
+        self.dirty.set(false);
+        let mut front = self
+            .front
+            .try_borrow_mut()
+            .expect("Double buffer cannot be swapped while it is being read.");
+        std::mem::swap(&mut *front, &mut *self.back.borrow_mut());
+        // Continue writing where the last state left off
+        self.back.borrow_mut().clone_from(&front);
+    }
+}
+
+impl Nut {
+    /// Makes pending writes to double buffers visible to readers
+    pub(crate) fn swap_buffers(&self) {
+        let pending = std::mem::take(
+            &mut *self
+                .pending_swaps
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG),
+        );
+        for buffers in pending {
+            buffers.swap();
+        }
+    }
+}
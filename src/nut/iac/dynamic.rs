@@ -0,0 +1,160 @@
+//! Subscriptions to families of message types, through trait objects.
+//!
+//! Each concrete message type of a family is registered together with a cast to the trait object.
+//! A handler of the family is then subscribed to every registered concrete type, also to those registered later.
+
+use crate::debug::DebugTypeName;
+use crate::nut::iac::subscription::{Access, SubscriptionId};
+use crate::nut::{Handler, Nut, IMPOSSIBLE_ERR_MSG};
+use crate::{ActivityContainer, ManagedState, UncheckedActivityId};
+use core::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::topic::Topic;
+
+/// A handler of a family, called with the message already cast to the trait object
+pub(crate) type DynHandler<T> = Rc<dyn Fn(&mut ActivityContainer, &T)>;
+/// Creates the subscription of a family handler to one concrete message type
+type Binder<T> = Rc<dyn Fn(DynHandler<T>) -> (Topic, Handler)>;
+
+/// All families, by the type id of the trait object
+#[derive(Default)]
+pub(crate) struct DynFamilies {
+    families: HashMap<TypeId, Box<dyn ErasedFamily>>,
+}
+
+struct DynFamily<T: ?Sized> {
+    members: Vec<TypeId>,
+    binders: Vec<Binder<T>>,
+    handlers: Vec<(
+        UncheckedActivityId,
+        DebugTypeName,
+        DynHandler<T>,
+        SubscriptionId,
+    )>,
+}
+
+/// Part of a family that does not depend on the trait object
+trait ErasedFamily {
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// Stops subscribing handlers to members that are added later, unless `keep` returns true for the activity index and the subscription ID
+    fn retain_handlers(&mut self, keep: &dyn Fn(usize, SubscriptionId) -> bool);
+}
+
+impl<T: ?Sized + 'static> ErasedFamily for DynFamily<T> {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn retain_handlers(&mut self, keep: &dyn Fn(usize, SubscriptionId) -> bool) {
+        self.handlers
+            .retain(|handler| keep(handler.0.index, handler.3));
+    }
+}
+
+impl<T: ?Sized> Default for DynFamily<T> {
+    fn default() -> Self {
+        Self {
+            members: vec![],
+            binders: vec![],
+            handlers: vec![],
+        }
+    }
+}
+
+impl DynFamilies {
+    fn family<T: ?Sized + 'static>(&mut self) -> &mut DynFamily<T> {
+        self.families
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(DynFamily::<T>::default()))
+            .as_any_mut()
+            .downcast_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+    }
+    /// Same as `Subscriptions::retain`, for the handlers that are subscribed to members added later
+    pub(crate) fn retain(&mut self, keep: impl Fn(usize, SubscriptionId) -> bool) {
+        for family in self.families.values_mut() {
+            family.retain_handlers(&keep);
+        }
+    }
+}
+
+type NewSubscriptions = Vec<(
+    Topic,
+    UncheckedActivityId,
+    DebugTypeName,
+    Handler,
+    SubscriptionId,
+)>;
+
+impl Nut {
+    /// Adds a concrete message type to the family of `T` and subscribes the existing handlers of the family to it
+    pub(crate) fn register_dyn_member<T, MSG>(&self, cast: fn(&MSG) -> &T)
+    where
+        T: ?Sized + 'static,
+        MSG: Any,
+    {
+        let subs: NewSubscriptions = {
+            let mut families = self
+                .dyn_families
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG);
+            let family = families.family::<T>();
+            if family.members.contains(&TypeId::of::<MSG>()) {
+                return;
+            }
+            family.members.push(TypeId::of::<MSG>());
+            let binder: Binder<T> = Rc::new(move |handler| {
+                (
+                    Topic::public_message::<MSG>(),
+                    ManagedState::pack_dyn_closure(handler, cast),
+                )
+            });
+            let subs = family
+                .handlers
+                .iter()
+                .map(|(id, name, handler, sub)| {
+                    let (topic, closure) = binder(handler.clone());
+                    (topic, *id, *name, closure, *sub)
+                })
+                .collect();
+            family.binders.push(binder);
+            subs
+        };
+        self.push_dyn_subscriptions(subs);
+    }
+    /// Subscribes the handler to all current and future members of the family of `T`
+    pub(crate) fn register_dyn_handler<T: ?Sized + 'static>(
+        &self,
+        id: UncheckedActivityId,
+        type_name: DebugTypeName,
+        handler: DynHandler<T>,
+    ) -> SubscriptionId {
+        let sub = self.subscriptions.next_id();
+        let subs: NewSubscriptions = {
+            let mut families = self
+                .dyn_families
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG);
+            let family = families.family::<T>();
+            let subs = family
+                .binders
+                .iter()
+                .map(|binder| {
+                    let (topic, closure) = binder(handler.clone());
+                    (topic, id, type_name, closure, sub)
+                })
+                .collect();
+            family.handlers.push((id, type_name, handler, sub));
+            subs
+        };
+        self.push_dyn_subscriptions(subs);
+        sub
+    }
+    // Pushing may execute queued events, which could register more members or handlers, hence the families must not be borrowed here.
+    fn push_dyn_subscriptions(&self, subs: NewSubscriptions) {
+        for (topic, id, type_name, closure, sub) in subs {
+            self.push_handler(topic, id, closure, type_name, Access::Read, sub);
+        }
+    }
+}
@@ -23,13 +23,82 @@ impl SubscriptionFilter {
     }
 }
 
-impl ActivityContainer {
+/// Defines what happens to messages that arrive while the subscribing activity is inactive.
+///
+/// Used with [`subscribe_buffered`](struct.ActivityId.html#method.subscribe_buffered).
+/// Buffered messages are delivered right after the activity becomes active again, before its `on_enter` handlers registered afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InactivePolicy {
+    /// Ignore messages while inactive. This is what subscriptions without a policy do.
+    #[default]
+    Drop,
+    /// Keep only the most recent message.
+    BufferLatest,
+    /// Keep up to the given number of messages. If more arrive, the oldest are dropped.
+    BufferAll(usize),
+}
+
+impl InactivePolicy {
+    /// Maximum number of messages buffered under this policy
+    pub(crate) fn capacity(&self) -> usize {
+        match self {
+            Self::Drop => 0,
+            Self::BufferLatest => 1,
+            Self::BufferAll(n) => *n,
+        }
+    }
+}
+
+/// A subscription that is only called for messages accepted by a predicate.
+///
+/// Created with [`ActivityId::when`](struct.ActivityId.html#method.when).
+/// Use one of the methods to register the handler, which works just like the corresponding method on `ActivityId`.
+#[must_use = "The handler is only registered when calling subscribe() or another method of this struct."]
+pub struct ConditionalSubscription<A, MSG, P> {
+    id: ActivityId<A>,
+    predicate: P,
+    _msg: core::marker::PhantomData<fn(&MSG)>,
+}
+
+impl<A, MSG, P> ConditionalSubscription<A, MSG, P>
+where
+    A: Activity,
+    MSG: Any,
+    P: Fn(&MSG) -> bool + 'static,
+{
+    pub(crate) fn new(id: ActivityId<A>, predicate: P) -> Self {
+        Self {
+            id,
+            predicate,
+            _msg: core::marker::PhantomData,
+        }
+    }
+    /// Registers the handler, see [`ActivityId::subscribe`](struct.ActivityId.html#method.subscribe).
+    pub fn subscribe<F>(self, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, &MSG) + 'static,
+    {
+        crate::nut::register_when(self.id, f, self.predicate, Default::default())
+    }
+    /// Registers the handler, see [`ActivityId::subscribe_masked`](struct.ActivityId.html#method.subscribe_masked).
+    pub fn subscribe_masked<F>(self, mask: SubscriptionFilter, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, &MSG) + 'static,
+    {
+        crate::nut::register_when(self.id, f, self.predicate, mask)
+    }
+    /// Registers the handler, see [`ActivityId::subscribe_domained`](struct.ActivityId.html#method.subscribe_domained).
+    pub fn subscribe_domained<F>(self, f: F) -> SubscriptionId
+    where
+        F: Fn(&mut A, &mut DomainState, &MSG) + 'static,
+    {
+        crate::nut::register_domained_when(self.id, f, self.predicate, Default::default())
+    }
+}
+
+impl SubscriptionFilter {
     /// Returns true if the call should go through (false if it should be filtered out)
-    pub(crate) fn filter<A: Activity>(
-        &self,
-        id: ActivityId<A>,
-        filter: &SubscriptionFilter,
-    ) -> bool {
-        !filter.active_only || self.status(id.id).is_active()
+    pub(crate) fn accepts(&self, status: LifecycleStatus) -> bool {
+        !self.active_only || status.is_active()
     }
 }
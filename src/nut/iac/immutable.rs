@@ -0,0 +1,59 @@
+//! Message types that handlers must neither modify nor take ownership of, see `nuts::protect_message`.
+
+use crate::nut::iac::subscription::Access;
+use crate::nut::iac::topic::Topic;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::Error;
+use core::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// Marks message types that must reach every handler exactly as they have been published, such as input reports.
+///
+/// Implementing the trait documents the intent, the protection is enabled with [`protect_message`](fn.protect_message.html).
+/// Afterwards, registering a handler that could alter the message fails.
+pub trait ImmutableMessage: Any {}
+
+/// Protected message types with their names, empty unless `protect_message` has been used
+#[derive(Default)]
+pub(crate) struct ProtectedMessages {
+    types: HashMap<TypeId, &'static str>,
+}
+
+impl Nut {
+    pub(crate) fn protect_message<MSG: ImmutableMessage>(&self) {
+        let name = std::any::type_name::<MSG>();
+        assert!(
+            !self.subscriptions.has_mutable_access(
+                &Topic::public_message::<MSG>(),
+                &Topic::private_message::<MSG>()
+            ),
+            "Messages of type {} are already handled mutably.",
+            name
+        );
+        self.protected_messages
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .types
+            .insert(TypeId::of::<MSG>(), name);
+    }
+    /// Fails if a handler with this access to the topic could alter a protected message type.
+    ///
+    /// Handlers of private messages own the message, hence they are rejected regardless of their access.
+    pub(crate) fn check_access(&self, topic: Topic, access: Access) -> Result<(), Error> {
+        let id = match (topic, access) {
+            (Topic::PublicMessage(id, _), Access::Write | Access::Claim)
+            | (Topic::PrivateMessage(id, _), _) => id,
+            _ => return Ok(()),
+        };
+        match self
+            .protected_messages
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .types
+            .get(&id)
+        {
+            Some(name) => Err(Error::ImmutableMessage(name)),
+            None => Ok(()),
+        }
+    }
+}
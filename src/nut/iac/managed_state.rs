@@ -3,33 +3,137 @@
 //! Objects to which multiple activities have access
 
 mod domain_id;
+mod domain_merge;
+mod domain_observer;
 mod domain_state;
 mod domain_store;
+mod domain_view;
+#[cfg(feature = "ecs")]
+mod entities;
+mod migration;
 
 use crate::nut::activity::Activity;
 use crate::nut::activity::ActivityContainer;
 use crate::nut::activity::ActivityId;
-use crate::nut::iac::filter::SubscriptionFilter;
+use crate::nut::iac::filter::{InactivePolicy, SubscriptionFilter};
+use crate::nut::iac::params::{HandlerContext, HandlerFn};
 use crate::nut::Handler;
 use crate::nut::IMPOSSIBLE_ERR_MSG;
+use crate::LifecycleStatus;
 use core::any::Any;
 pub use domain_id::*;
+pub use domain_merge::ConflictPolicy;
+pub(crate) use domain_merge::DomainMerge;
+pub(crate) use domain_observer::*;
 pub use domain_state::*;
 pub(crate) use domain_store::*;
+pub use domain_view::DomainView;
+pub(crate) use domain_view::DomainViewRequest;
+#[cfg(feature = "ecs")]
+pub use entities::*;
+pub(crate) use migration::DomainMigration;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+/// Summary of a domain, returned by [`domains`](fn.domains.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DomainInfo {
+    /// The domain's number, as returned by `DomainEnumeration::id()`
+    pub id: usize,
+    /// Type names of the objects stored in the domain, in the order they have been stored first.
+    ///
+    /// The names are taken from `std::any::type_name`, their exact format is not guaranteed.
+    pub stored_type_names: Vec<&'static str>,
+    /// Number of activities in the domain that have not been deleted
+    pub activity_count: usize,
+}
 
 #[derive(Default)]
 pub(crate) struct ManagedState {
     domains: Vec<DomainState>,
     broadcast: Option<Box<dyn Any>>,
+    /// Set by `validate_domains`, allows skipping domained handlers without a domain in release mode
+    domains_validated: bool,
+    /// Number of handler calls skipped because the domain was missing
+    skipped_domained_calls: u64,
+    /// Applied to a domain before it is handed out
+    migrations: Vec<DomainMigration>,
+    /// Domains that have been merged into another domain, by index, see `merge_domains`
+    aliases: HashMap<usize, usize>,
 }
 
 impl ManagedState {
+    /// Index of the domain that stores the objects of `id`, which differs from the ID's index if the domain has been merged
+    fn resolve(&self, id: DomainId) -> Option<usize> {
+        id.index()
+            .map(|i| self.aliases.get(&i).copied().unwrap_or(i))
+    }
     pub(crate) fn get_mut(&mut self, id: DomainId) -> Option<&mut DomainState> {
-        id.index().map(move |i| &mut self.domains[i])
+        let index = self.resolve(id);
+        let domains = &mut self.domains;
+        let migrations = &self.migrations;
+        index.map(move |i| {
+            let domain = &mut domains[i];
+            domain.migrate(migrations);
+            domain
+        })
+    }
+    /// Whether the domain has been prepared
+    pub(crate) fn is_prepared(&self, id: DomainId) -> bool {
+        self.resolve(id).is_some_and(|i| i < self.domains.len())
+    }
+    pub(crate) fn set_domains_validated(&mut self) {
+        self.domains_validated = true;
+    }
+    pub(crate) fn skipped_domained_calls(&self) -> u64 {
+        self.skipped_domained_calls
+    }
+    /// The domain for a handler that requires one, or `None` if the call must be skipped.
+    ///
+    /// A missing domain is a bug in the application and panics, unless all domained subscriptions have been validated and this is a release build.
+    pub(crate) fn domain_for_call(&mut self, id: DomainId) -> Option<&mut DomainState> {
+        if self.is_prepared(id) {
+            return self.get_mut(id);
+        }
+        if !self.domains_validated || cfg!(debug_assertions) {
+            panic!("Activity has no domain");
+        }
+        self.skipped_domained_calls += 1;
+        None
+    }
+    /// Describes all domains that have been prepared, `activity_counts` is indexed by domain
+    ///
+    /// Activities of merged domains are counted in the domain they have been merged into.
+    pub(crate) fn domain_infos(&self, activity_counts: &[usize]) -> Vec<DomainInfo> {
+        let n = self.domains.len().max(activity_counts.len());
+        let mut counts = vec![0; n];
+        for (i, count) in activity_counts.iter().enumerate() {
+            counts[self
+                .resolve(DomainId::from_index(i))
+                .expect(IMPOSSIBLE_ERR_MSG)] += count;
+        }
+        (0..n)
+            .map(|id| DomainInfo {
+                id,
+                stored_type_names: self
+                    .domains
+                    .get(id)
+                    .map(DomainState::type_names)
+                    .unwrap_or_default(),
+                activity_count: counts[id],
+            })
+            .collect()
+    }
+    /// Drops all domains and their objects, in reverse creation order
+    pub(crate) fn drop_domains(&mut self) {
+        while let Some(mut domain) = self.domains.pop() {
+            domain.drop_objects();
+        }
     }
     /// Fills all domains with default values. Must be called once or will panic when used.
     pub(crate) fn prepare(&mut self, id: DomainId) {
-        if let Some(n) = id.index() {
+        if let Some(n) = self.resolve(id) {
             while self.domains.len() <= n {
                 self.domains.push(Default::default());
             }
@@ -38,53 +142,78 @@ impl ManagedState {
     pub(crate) fn set_broadcast(&mut self, msg: Box<dyn Any>) {
         self.broadcast = Some(msg);
     }
-    pub(crate) fn clear_broadcast(&mut self) {
-        self.broadcast = None;
+    #[cfg(feature = "mutation-audit")]
+    pub(crate) fn broadcast_ref(&self) -> Option<&dyn Any> {
+        self.broadcast.as_deref()
+    }
+    /// Removes the current broadcast, returning it unless it has been taken by a handler
+    pub(crate) fn clear_broadcast(&mut self) -> Option<Box<dyn Any>> {
+        self.broadcast.take()
     }
+    // The accessors below are split into a non-generic part, shared by all handlers, and a thin generic downcast.
+    // This keeps the code generated for each combination of activity, message, and closure small.
+
     /// panics if runtime broadcast is not of static type A
+    #[inline]
     fn current_broadcast<A: Any>(&mut self) -> &mut A {
-        let msg = self
-            .broadcast
-            .as_mut()
-            .expect(IMPOSSIBLE_ERR_MSG)
+        self.broadcast_mut()
             .downcast_mut()
-            .expect(IMPOSSIBLE_ERR_MSG);
-        msg
-    }
-    fn current_broadcast_and_domain<A: Any>(&mut self, id: DomainId) -> (&mut A, &mut DomainState) {
-        let msg: &mut A = self
-            .broadcast
-            .as_mut()
             .expect(IMPOSSIBLE_ERR_MSG)
-            .downcast_mut()
-            .expect(IMPOSSIBLE_ERR_MSG);
-        let i = id.index().expect(IMPOSSIBLE_ERR_MSG);
-        let domain = &mut self.domains[i];
-        (msg, domain)
     }
+    /// `None` if the call must be skipped because the domain is missing
+    #[inline]
+    fn current_broadcast_and_domain<A: Any>(
+        &mut self,
+        id: DomainId,
+    ) -> Option<(&mut A, &mut DomainState)> {
+        self.domain_for_call(id)?;
+        let (msg, domain) = self.broadcast_and_optional_domain(id);
+        let msg = msg.downcast_mut().expect(IMPOSSIBLE_ERR_MSG);
+        Some((msg, domain.expect(IMPOSSIBLE_ERR_MSG)))
+    }
+    #[inline]
+    fn current_broadcast_and_optional_domain<A: Any>(
+        &mut self,
+        id: DomainId,
+    ) -> (&mut A, Option<&mut DomainState>) {
+        let (msg, domain) = self.broadcast_and_optional_domain(id);
+        (msg.downcast_mut().expect(IMPOSSIBLE_ERR_MSG), domain)
+    }
+    #[inline]
     fn take_current_broadcast<A: Any>(&mut self) -> Box<A> {
-        let msg = self
-            .broadcast
-            .take()
-            .expect(IMPOSSIBLE_ERR_MSG)
-            .downcast()
-            .expect(IMPOSSIBLE_ERR_MSG);
-        msg
+        self.take_broadcast().downcast().expect(IMPOSSIBLE_ERR_MSG)
     }
+    /// `None` if the call must be skipped because the domain is missing, the message is not taken in that case
+    #[inline]
     fn take_current_broadcast_and_borrow_domain<A: Any>(
         &mut self,
         id: DomainId,
-    ) -> (Box<A>, &mut DomainState) {
-        let msg = self
-            .broadcast
-            .take()
-            .expect(IMPOSSIBLE_ERR_MSG)
-            .downcast()
-            .expect(IMPOSSIBLE_ERR_MSG);
-        let i = id.index().expect("Activity has no domain");
-        let domain = &mut self.domains[i];
+    ) -> Option<(Box<A>, &mut DomainState)> {
+        self.domain_for_call(id)?;
+        let msg = self.take_broadcast().downcast().expect(IMPOSSIBLE_ERR_MSG);
+        Some((msg, self.get_mut(id).expect(IMPOSSIBLE_ERR_MSG)))
+    }
+    fn broadcast_mut(&mut self) -> &mut dyn Any {
+        self.broadcast.as_mut().expect(IMPOSSIBLE_ERR_MSG).as_mut()
+    }
+    fn broadcast_and_optional_domain(
+        &mut self,
+        id: DomainId,
+    ) -> (&mut dyn Any, Option<&mut DomainState>) {
+        let index = self.resolve(id);
+        let msg = self.broadcast.as_mut().expect(IMPOSSIBLE_ERR_MSG).as_mut();
+        let domains = &mut self.domains;
+        let migrations = &self.migrations;
+        let domain = index.map(move |i| {
+            let domain = &mut domains[i];
+            domain.migrate(migrations);
+            domain
+        });
         (msg, domain)
     }
+    fn take_broadcast(&mut self) -> Box<dyn Any> {
+        self.broadcast.take().expect(IMPOSSIBLE_ERR_MSG)
+    }
 
     pub(crate) fn pack_closure_no_payload<A, F>(
         f: F,
@@ -97,10 +226,7 @@ impl ManagedState {
     {
         Box::new(
             move |activities: &mut ActivityContainer, _: &mut ManagedState| {
-                if activities.filter(index, &filter) {
-                    let a = activities[index]
-                        .downcast_mut::<A>()
-                        .expect(IMPOSSIBLE_ERR_MSG);
+                if let Some(a) = activities.filtered(index, &filter) {
                     f(a)
                 }
             },
@@ -118,13 +244,10 @@ impl ManagedState {
     {
         Box::new(
             move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
-                if activities.filter(index, &filter) {
-                    let a = activities[index]
-                        .downcast_mut::<A>()
-                        .expect(IMPOSSIBLE_ERR_MSG);
-                    let domain = &mut managed_state.domains
-                        [index.domain_index.index().expect(IMPOSSIBLE_ERR_MSG)];
-                    f(a, domain)
+                if let Some(a) = activities.filtered(index, &filter) {
+                    if let Some(domain) = managed_state.domain_for_call(index.domain_index) {
+                        f(a, domain)
+                    }
                 }
             },
         )
@@ -154,16 +277,93 @@ impl ManagedState {
     {
         Box::new(
             move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
-                if activities.filter(index, &filter) {
-                    let a = activities[index]
-                        .downcast_mut::<A>()
-                        .expect(IMPOSSIBLE_ERR_MSG);
+                if let Some(a) = activities.filtered(index, &filter) {
                     let msg = managed_state.current_broadcast();
                     f(a, msg)
                 }
             },
         )
     }
+    /// Packs a handler of a message family, see `subscribe_dyn`, for one concrete message type
+    pub(crate) fn pack_dyn_closure<MSG, T>(
+        handler: crate::nut::iac::dynamic::DynHandler<T>,
+        cast: fn(&MSG) -> &T,
+    ) -> Handler
+    where
+        MSG: Any,
+        T: ?Sized + 'static,
+    {
+        Box::new(
+            move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
+                let msg = managed_state.current_broadcast::<MSG>();
+                handler(activities, cast(msg))
+            },
+        )
+    }
+    /// Wraps a packed closure, such that it is only called when the predicate accepts the current message
+    pub(crate) fn guard_closure<MSG, P>(handler: Handler, predicate: P) -> Handler
+    where
+        MSG: Any,
+        P: Fn(&MSG) -> bool + 'static,
+    {
+        Box::new(
+            move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
+                if predicate(managed_state.current_broadcast::<MSG>()) {
+                    handler(activities, managed_state)
+                }
+            },
+        )
+    }
+    /// Packs a closure that buffers messages while the activity is inactive.
+    ///
+    /// Returns the handler for the message topic and the handler for the enter topic, which delivers the buffered messages.
+    pub(crate) fn pack_closure_buffered<A, F, MSG>(
+        f: F,
+        index: ActivityId<A>,
+        policy: InactivePolicy,
+    ) -> (Handler, Handler)
+    where
+        A: Activity,
+        F: Fn(&mut A, &MSG) + 'static,
+        MSG: Any + Clone,
+    {
+        let f = Rc::new(f);
+        let buffer: Rc<RefCell<VecDeque<MSG>>> = Default::default();
+        let on_msg = {
+            let f = f.clone();
+            let buffer = buffer.clone();
+            Box::new(
+                move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
+                    let status = activities.status(index.id);
+                    let msg: &MSG = managed_state.current_broadcast();
+                    if status.is_active() {
+                        let a = activities[index]
+                            .downcast_mut::<A>()
+                            .expect(IMPOSSIBLE_ERR_MSG);
+                        f(a, msg)
+                    } else if status != LifecycleStatus::Deleted && policy.capacity() > 0 {
+                        let mut buffer = buffer.borrow_mut();
+                        if buffer.len() >= policy.capacity() {
+                            buffer.pop_front();
+                        }
+                        buffer.push_back(msg.clone());
+                    }
+                },
+            )
+        };
+        let on_enter = Box::new(
+            move |activities: &mut ActivityContainer, _: &mut ManagedState| {
+                let a = activities[index]
+                    .downcast_mut::<A>()
+                    .expect(IMPOSSIBLE_ERR_MSG);
+                let buffered = std::mem::take(&mut *buffer.borrow_mut());
+                for msg in buffered.iter() {
+                    f(a, msg)
+                }
+            },
+        );
+        (on_msg, on_enter)
+    }
     pub(crate) fn pack_closure_mut<A, F, MSG>(
         f: F,
         index: ActivityId<A>,
@@ -176,10 +376,7 @@ impl ManagedState {
     {
         Box::new(
             move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
-                if activities.filter(index, &filter) {
-                    let a = activities[index]
-                        .downcast_mut::<A>()
-                        .expect(IMPOSSIBLE_ERR_MSG);
+                if let Some(a) = activities.filtered(index, &filter) {
                     let msg = managed_state.current_broadcast();
                     f(a, msg)
                 }
@@ -198,16 +395,65 @@ impl ManagedState {
     {
         Box::new(
             move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
-                if activities.filter(index, &filter) {
-                    let a = activities[index]
-                        .downcast_mut::<A>()
-                        .expect(IMPOSSIBLE_ERR_MSG);
+                if let Some(a) = activities.filtered(index, &filter) {
                     let msg = managed_state.take_current_broadcast();
                     f(a, *msg)
                 }
             },
         )
     }
+    /// Packs a closure that is called for the first message only, the subscription is removed afterwards.
+    pub(crate) fn pack_closure_owned_once<A, F, MSG>(
+        f: F,
+        index: ActivityId<A>,
+        filter: SubscriptionFilter,
+    ) -> Handler
+    where
+        A: Activity,
+        F: FnOnce(&mut A, MSG) + 'static,
+        MSG: Any,
+    {
+        let f = core::cell::Cell::new(Some(f));
+        Box::new(
+            move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
+                if let Some(a) = activities.filtered(index, &filter) {
+                    if let Some(f) = f.take() {
+                        // Removed before the call, the handler may register a new channel for the same message type
+                        crate::nut::remove_private_channel::<MSG>(index.into());
+                        let msg = managed_state.take_current_broadcast();
+                        f(a, *msg)
+                    }
+                }
+            },
+        )
+    }
+    /// Packs a closure with parameters that are extracted through `HandlerParam`.
+    pub(crate) fn pack_closure_with_params<A, F, MSG, P>(
+        f: F,
+        index: ActivityId<A>,
+        filter: SubscriptionFilter,
+    ) -> Handler
+    where
+        A: Activity,
+        F: HandlerFn<A, MSG, P> + 'static,
+        MSG: Any,
+    {
+        Box::new(
+            move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
+                if let Some(a) = activities.filtered(index, &filter) {
+                    let (msg, domain) =
+                        managed_state.current_broadcast_and_optional_domain(index.domain_index);
+                    let mut ctx = HandlerContext {
+                        activity: Some(a),
+                        domain: domain.ok_or("Activity has no domain"),
+                        msg: Some(msg),
+                        id: index,
+                    };
+                    f.call(&mut ctx)
+                }
+            },
+        )
+    }
     pub(crate) fn pack_domained_closure<A, F, MSG>(
         f: F,
         index: ActivityId<A>,
@@ -220,13 +466,12 @@ impl ManagedState {
     {
         Box::new(
             move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
-                if activities.filter(index, &filter) {
-                    let a = activities[index]
-                        .downcast_mut::<A>()
-                        .expect(IMPOSSIBLE_ERR_MSG);
-                    let (msg, domain) =
-                        managed_state.current_broadcast_and_domain(index.domain_index);
-                    f(a, domain, msg)
+                if let Some(a) = activities.filtered(index, &filter) {
+                    if let Some((msg, domain)) =
+                        managed_state.current_broadcast_and_domain(index.domain_index)
+                    {
+                        f(a, domain, msg)
+                    }
                 }
             },
         )
@@ -243,13 +488,12 @@ impl ManagedState {
     {
         Box::new(
             move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
-                if activities.filter(index, &filter) {
-                    let a = activities[index]
-                        .downcast_mut::<A>()
-                        .expect(IMPOSSIBLE_ERR_MSG);
-                    let (msg, domain) =
-                        managed_state.current_broadcast_and_domain(index.domain_index);
-                    f(a, domain, msg)
+                if let Some(a) = activities.filtered(index, &filter) {
+                    if let Some((msg, domain)) =
+                        managed_state.current_broadcast_and_domain(index.domain_index)
+                    {
+                        f(a, domain, msg)
+                    }
                 }
             },
         )
@@ -266,13 +510,12 @@ impl ManagedState {
     {
         Box::new(
             move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
-                if activities.filter(index, &filter) {
-                    let a = activities[index]
-                        .downcast_mut::<A>()
-                        .expect(IMPOSSIBLE_ERR_MSG);
-                    let (msg, domain) =
-                        managed_state.take_current_broadcast_and_borrow_domain(index.domain_index);
-                    f(a, domain, *msg)
+                if let Some(a) = activities.filtered(index, &filter) {
+                    if let Some((msg, domain)) =
+                        managed_state.take_current_broadcast_and_borrow_domain(index.domain_index)
+                    {
+                        f(a, domain, *msg)
+                    }
                 }
             },
         )
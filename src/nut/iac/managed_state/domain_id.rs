@@ -23,13 +23,16 @@ impl DomainId {
     pub(crate) fn new(d: &impl DomainEnumeration) -> DomainId {
         DomainId(Some(d.id()))
     }
+    pub(crate) fn from_index(index: usize) -> DomainId {
+        DomainId(Some(index))
+    }
     pub(crate) fn index(&self) -> Option<usize> {
         self.0
     }
 }
 
 #[macro_export]
-/// Implements DomainEnumeration for an enum.
+/// Implements `DomainEnumeration` for an enum.
 ///
 /// This macro can only be used on primitive enums that implement Copy.
 /// The current implementation of the macro unfortunately also requires
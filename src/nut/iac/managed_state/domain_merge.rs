@@ -0,0 +1,88 @@
+use crate::nut::iac::managed_state::ManagedState;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::DomainId;
+use core::any::Any;
+
+/// Which object is kept when both domains store an object of the same type, see [`merge_domains`](fn.merge_domains.html).
+///
+/// Keyed objects of the same type and key type count as a single object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// The object already stored in the destination is kept, the object of the source is dropped
+    KeepDestination,
+    /// The object of the source replaces the object in the destination
+    KeepSource,
+}
+
+/// A merge of two domains, queued while domains cannot be accessed
+pub(crate) struct DomainMerge {
+    src: DomainId,
+    dst: DomainId,
+    policy: ConflictPolicy,
+}
+
+impl DomainMerge {
+    pub(crate) fn new(src: DomainId, dst: DomainId, policy: ConflictPolicy) -> Self {
+        Self { src, dst, policy }
+    }
+}
+
+impl ManagedState {
+    /// Moves all objects from `src` to `dst` and makes `src` an alias of `dst`.
+    ///
+    /// Returns the objects that have been dropped due to conflicts, which must be dropped after releasing the managed state.
+    fn merge(&mut self, merge: &DomainMerge) -> Vec<Box<dyn Any>> {
+        self.prepare(merge.src);
+        self.prepare(merge.dst);
+        let src = self.resolve(merge.src).expect("Domain ID invalid");
+        let dst = self.resolve(merge.dst).expect("Domain ID invalid");
+        if src == dst {
+            return vec![];
+        }
+        let migrations = &self.migrations;
+        self.domains[src].migrate(migrations);
+        self.domains[dst].migrate(migrations);
+        let source = std::mem::take(&mut self.domains[src]);
+        let dropped = self.domains[dst].absorb(source, merge.policy);
+        for target in self.aliases.values_mut() {
+            if *target == src {
+                *target = dst;
+            }
+        }
+        self.aliases.insert(src, dst);
+        dropped
+    }
+}
+
+impl Nut {
+    /// Merges the domains right away, or queues the merge if the domains cannot be accessed
+    pub(crate) fn merge_domains(&self, merge: DomainMerge) {
+        match self.managed_state.try_borrow_mut() {
+            Ok(managed_state) => {
+                drop(managed_state);
+                self.exec_domain_merge(&merge);
+            }
+            Err(_) => self.defer_bookkeeping(crate::nut::exec::Deferred::DomainMerge(merge)),
+        }
+    }
+    pub(crate) fn exec_domain_merge(&self, merge: &DomainMerge) {
+        let dropped = self
+            .managed_state
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .merge(merge);
+        drop(dropped);
+    }
+}
+
+#[cfg(debug_assertions)]
+impl std::fmt::Debug for DomainMerge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Merging domain {} into {}",
+            self.src.index().unwrap_or_default(),
+            self.dst.index().unwrap_or_default()
+        )
+    }
+}
@@ -0,0 +1,77 @@
+use crate::debug::DebugTypeName;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::{DomainId, DomainState};
+use core::any::{Any, TypeId};
+
+/// A closure that is called with an object from a domain, whenever it has been accessed mutably.
+pub(crate) struct DomainObserver {
+    domain: DomainId,
+    id: TypeId,
+    /// Version of the object when the observer has last been called, `None` before the object has been stored
+    seen: Option<u64>,
+    f: Box<dyn Fn(&DomainState)>,
+    #[allow(dead_code)]
+    type_name: DebugTypeName,
+}
+
+impl Nut {
+    pub(crate) fn add_domain_observer(&self, mut observer: DomainObserver) {
+        let mut managed_state = self
+            .managed_state
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        managed_state.prepare(observer.domain);
+        observer.seen = managed_state
+            .get_mut(observer.domain)
+            .expect("Domain ID invalid")
+            .version(observer.id);
+        self.domain_observers
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .push(observer);
+    }
+    /// Calls all observers of objects that have been accessed mutably since the observers have last been called.
+    ///
+    /// Only call while executing, such that observers cannot be added concurrently.
+    pub(crate) fn notify_domain_observers(&self) {
+        let mut observers = self
+            .domain_observers
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        if observers.is_empty() {
+            return;
+        }
+        let mut managed_state = self
+            .managed_state
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        for observer in observers.iter_mut() {
+            if let Some(domain) = managed_state.get_mut(observer.domain) {
+                let version = domain.version(observer.id);
+                if version != observer.seen {
+                    observer.seen = version;
+                    (observer.f)(domain);
+                }
+            }
+        }
+    }
+}
+
+impl DomainObserver {
+    pub(crate) fn new<T: Any>(domain: DomainId, f: impl Fn(&T) + 'static) -> Self {
+        Self {
+            domain,
+            id: TypeId::of::<T>(),
+            seen: None,
+            f: Box::new(move |domain: &DomainState| f(domain.get::<T>())),
+            type_name: DebugTypeName::new::<T>(),
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+impl std::fmt::Debug for DomainObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Adding observer for {:?} in the domain", self.type_name)
+    }
+}
@@ -1,6 +1,8 @@
 use core::any::{Any, TypeId};
 use std::collections::{hash_map::Entry, HashMap};
+use std::hash::Hash;
 
+use super::ConflictPolicy;
 use crate::nut::IMPOSSIBLE_ERR_MSG;
 
 /// Stores passive data that can be accessed in event handlers of multiple activities.
@@ -22,9 +24,16 @@ pub struct DomainState {
     // Indirection to Vec is used here to allow for safe internal mutability without falling back to RefCells.
     // (RefCells are uneasy to use from outside AND the runtime hit is larger)
     objects: Vec<Box<dyn Any>>,
+    /// Counts mutable accesses to each object, same indices as `objects`
+    versions: Vec<u64>,
+    /// Type names of the objects, same indices as `objects`, kept in release mode for `nuts::domains`
+    type_names: Vec<&'static str>,
     index_map: HashMap<TypeId, usize>,
 }
 
+/// Container for all keyed instances of type `T`, stored as a single object in the domain
+struct Keyed<K, T>(HashMap<K, T>);
+
 impl DomainState {
     /// Stores a value in the domain.
     // @ START-DOC DOMAIN_STORE
@@ -35,13 +44,17 @@ impl DomainState {
         let id = TypeId::of::<T>();
         match self.index_map.entry(id) {
             Entry::Occupied(entry) => {
-                *self.objects[*entry.get()]
+                let index = *entry.get();
+                *self.objects[index]
                     .downcast_mut()
                     .expect(IMPOSSIBLE_ERR_MSG) = obj;
+                self.versions[index] += 1;
             }
             Entry::Vacant(entry) => {
                 entry.insert(self.objects.len());
                 self.objects.push(Box::new(obj));
+                self.versions.push(0);
+                self.type_names.push(std::any::type_name::<T>());
             }
         }
     }
@@ -51,14 +64,23 @@ impl DomainState {
     /// Used for delayed stores to domains.
     ///
     /// This variant is slightly less efficient as it will allocate another Box if the value was already in the domain.
-    pub(crate) fn store_unchecked(&mut self, id: TypeId, obj: Box<dyn Any>) {
+    pub(crate) fn store_unchecked(
+        &mut self,
+        id: TypeId,
+        type_name: &'static str,
+        obj: Box<dyn Any>,
+    ) {
         match self.index_map.entry(id) {
             Entry::Occupied(entry) => {
-                self.objects[*entry.get()] = obj;
+                let index = *entry.get();
+                self.objects[index] = obj;
+                self.versions[index] += 1;
             }
             Entry::Vacant(entry) => {
                 entry.insert(self.objects.len());
                 self.objects.push(obj);
+                self.versions.push(0);
+                self.type_names.push(type_name);
             }
         }
     }
@@ -73,6 +95,7 @@ impl DomainState {
     #[allow(clippy::unwrap_used)]
     pub fn try_get_mut<T: Any>(&mut self) -> Option<&mut T> {
         if let Some(index) = self.index_map.get(&TypeId::of::<T>()) {
+            self.versions[*index] += 1;
             Some(self.objects[*index].as_mut().downcast_mut().unwrap())
         } else {
             None
@@ -95,6 +118,8 @@ impl DomainState {
 
         let i1 = i1.unwrap();
         let i2 = i2.unwrap();
+        self.versions[*i1] += 1;
+        self.versions[*i2] += 1;
 
         let split = i1.min(i2) + 1;
         let (left, right) = self.objects.split_at_mut(split);
@@ -109,6 +134,142 @@ impl DomainState {
             Some(t2.as_mut().downcast_mut().unwrap()),
         )
     }
+    /// Stores a value in the domain under the given key.
+    ///
+    /// Unlike [`store`](#method.store), many instances of the same type can be stored, one per key.
+    /// Keyed values are independent of a value stored with `store`, even if they have the same type.
+    /// If a value of the same type is already stored under the same key, it is replaced and returned.
+    /// The type of the key is part of its identity, `1u32` and `1usize` are different keys.
+    ///
+    /// ### Example
+    /// ```rust
+    /// # use nuts::DomainState;
+    /// struct PlayerInventory { coins: u32 }
+    ///
+    /// let mut domain = DomainState::default();
+    /// domain.store_keyed(1u32, PlayerInventory { coins: 5 });
+    /// domain.store_keyed(2u32, PlayerInventory { coins: 0 });
+    ///
+    /// domain.keyed_entry::<PlayerInventory, _>(2u32)
+    ///     .or_insert(PlayerInventory { coins: 0 })
+    ///     .coins += 3;
+    /// assert_eq!(5, domain.get_keyed::<PlayerInventory, _>(&1u32).unwrap().coins);
+    /// assert_eq!(3, domain.get_keyed::<PlayerInventory, _>(&2u32).unwrap().coins);
+    /// ```
+    pub fn store_keyed<K: Hash + Eq + Any, T: Any>(&mut self, key: K, obj: T) -> Option<T> {
+        self.keyed_map_mut::<K, T>().insert(key, obj)
+    }
+    /// Returns a reference to the value of the specified type stored under the given key, if any.
+    pub fn get_keyed<T: Any, K: Hash + Eq + Any>(&self, key: &K) -> Option<&T> {
+        self.try_get::<Keyed<K, T>>()
+            .and_then(|keyed| keyed.0.get(key))
+    }
+    /// Same as [`get_keyed`](#method.get_keyed) but grants mutable access to the object.
+    pub fn get_keyed_mut<T: Any, K: Hash + Eq + Any>(&mut self, key: &K) -> Option<&mut T> {
+        self.try_get_mut::<Keyed<K, T>>()
+            .and_then(|keyed| keyed.0.get_mut(key))
+    }
+    /// Removes the value of the specified type stored under the given key and returns it.
+    pub fn remove_keyed<T: Any, K: Hash + Eq + Any>(&mut self, key: &K) -> Option<T> {
+        self.try_get_mut::<Keyed<K, T>>()
+            .and_then(|keyed| keyed.0.remove(key))
+    }
+    /// Gets the entry of the specified type for the given key, for in-place manipulation.
+    ///
+    /// This is the same entry API as provided by `HashMap`.
+    pub fn keyed_entry<T: Any, K: Hash + Eq + Any>(&mut self, key: K) -> Entry<'_, K, T> {
+        self.keyed_map_mut::<K, T>().entry(key)
+    }
+    /// Iterates over all keyed values of the specified type, in no particular order.
+    pub fn iter_keyed<T: Any, K: Hash + Eq + Any>(&self) -> impl Iterator<Item = (&K, &T)> {
+        self.try_get::<Keyed<K, T>>()
+            .into_iter()
+            .flat_map(|keyed| keyed.0.iter())
+    }
+    /// Returns the entities stored in the domain, creating an empty store on first use.
+    ///
+    /// Only available with the feature `ecs`.
+    #[cfg(feature = "ecs")]
+    pub fn entities(&mut self) -> &mut super::Entities {
+        if self.try_get::<super::Entities>().is_none() {
+            self.store(super::Entities::default());
+        }
+        self.get_mut::<super::Entities>()
+    }
+    fn keyed_map_mut<K: Hash + Eq + Any, T: Any>(&mut self) -> &mut HashMap<K, T> {
+        if self.try_get::<Keyed<K, T>>().is_none() {
+            self.store(Keyed::<K, T>(HashMap::new()));
+        }
+        &mut self.get_mut::<Keyed<K, T>>().0
+    }
+    /// Replaces the object of type `from` with a converted object of type `to`, unless `to` is already stored.
+    ///
+    /// The new object takes the place of the old one, to keep the drop order.
+    pub(crate) fn replace_object(
+        &mut self,
+        from: TypeId,
+        to: (TypeId, &'static str),
+        convert: &dyn Fn(Box<dyn Any>) -> Box<dyn Any>,
+    ) -> bool {
+        let (to, to_name) = to;
+        if self.index_map.contains_key(&to) {
+            return false;
+        }
+        let index = match self.index_map.remove(&from) {
+            Some(index) => index,
+            None => return false,
+        };
+        let old = std::mem::replace(&mut self.objects[index], Box::new(()));
+        self.objects[index] = convert(old);
+        self.versions[index] += 1;
+        self.type_names[index] = to_name;
+        self.index_map.insert(to, index);
+        true
+    }
+    /// Moves all objects of `other` to this domain, in the order they have been stored first.
+    ///
+    /// Returns the objects that have been dropped due to conflicts, which must be dropped by the caller.
+    pub(crate) fn absorb(
+        &mut self,
+        other: DomainState,
+        policy: ConflictPolicy,
+    ) -> Vec<Box<dyn Any>> {
+        let mut type_ids: Vec<Option<TypeId>> = vec![None; other.objects.len()];
+        for (id, index) in other.index_map {
+            type_ids[index] = Some(id);
+        }
+        let mut dropped = vec![];
+        let objects = other.objects.into_iter().zip(other.type_names);
+        for ((obj, type_name), id) in objects.zip(type_ids) {
+            let id = id.expect(IMPOSSIBLE_ERR_MSG);
+            match (self.index_map.get(&id), policy) {
+                (Some(_), ConflictPolicy::KeepDestination) => dropped.push(obj),
+                (Some(&index), ConflictPolicy::KeepSource) => {
+                    dropped.push(std::mem::replace(&mut self.objects[index], obj));
+                    self.versions[index] += 1;
+                }
+                (None, _) => self.store_unchecked(id, type_name, obj),
+            }
+        }
+        dropped
+    }
+    /// Drops all objects in reverse creation order
+    pub(crate) fn drop_objects(&mut self) {
+        self.index_map.clear();
+        self.versions.clear();
+        self.type_names.clear();
+        while let Some(obj) = self.objects.pop() {
+            drop(obj);
+        }
+    }
+    /// Type names of all stored objects, in the order they have been stored first
+    pub(crate) fn type_names(&self) -> Vec<&'static str> {
+        self.type_names.clone()
+    }
+    /// Number of times the object with the given type has been accessed mutably (or overwritten), or `None` if it has not been stored, yet.
+    pub(crate) fn version(&self, id: TypeId) -> Option<u64> {
+        self.index_map.get(&id).map(|index| self.versions[*index])
+    }
     /// Returns a reference to a value of the specified type, taken from the domain.
     /// # Panics
     /// Panics if object of that type has not been stored previously.
@@ -125,6 +286,96 @@ impl DomainState {
     pub fn get_mut<T: Any>(&mut self) -> &mut T {
         self.try_get_mut().expect("Not in domain")
     }
+    /// Used by `domain_state!`, returns mutable references to the objects of the given types, in the same order.
+    ///
+    /// # Panics
+    /// Panics if a type is missing in the domain or if it is requested twice.
+    #[doc(hidden)]
+    pub fn borrow_all_mut(&mut self, types: &[(TypeId, &'static str)]) -> Vec<&mut dyn Any> {
+        let mut slots: Vec<Option<&mut dyn Any>> = types.iter().map(|_| None).collect();
+        let mut positions = HashMap::new();
+        for (position, (id, name)) in types.iter().enumerate() {
+            let index = *self
+                .index_map
+                .get(id)
+                .unwrap_or_else(|| panic!("{} not in domain", name));
+            assert!(
+                positions.insert(index, position).is_none(),
+                "Cannot get two mutable references of same type from domain"
+            );
+            self.versions[index] += 1;
+        }
+        for (index, object) in self.objects.iter_mut().enumerate() {
+            if let Some(position) = positions.get(&index) {
+                slots[*position] = Some(object.as_mut());
+            }
+        }
+        slots
+            .into_iter()
+            .map(|slot| slot.expect(IMPOSSIBLE_ERR_MSG))
+            .collect()
+    }
+}
+
+#[macro_export]
+/// Defines a strongly typed view on a domain, with one field per domain object.
+///
+/// The generated struct borrows all listed objects at once, mutably, with `from_domain`.
+/// Instead of `domain.get_mut::<TileMap>()`, fields are accessed directly, and several of them can be used at the same time.
+/// All listed types must be distinct and stored in the domain before the view is created.
+///
+/// # Example
+/// ```
+/// use nuts::{DefaultDomain, DomainState};
+/// struct PhysicsWorld { gravity: f32 }
+/// struct TileMap { tiles: Vec<u8> }
+///
+/// nuts::domain_state!(WorldDomain { physics: PhysicsWorld, map: TileMap });
+///
+/// struct Game;
+/// struct Tick;
+/// nuts::store_to_domain(&DefaultDomain, PhysicsWorld { gravity: 9.81 });
+/// nuts::store_to_domain(&DefaultDomain, TileMap { tiles: vec![0; 16] });
+///
+/// let game = nuts::new_domained_activity(Game, &DefaultDomain);
+/// game.subscribe_domained(|_, domain: &mut DomainState, _: &Tick| {
+///     let world = WorldDomain::from_domain(domain);
+///     world.map.tiles[0] = world.physics.gravity as u8;
+/// });
+/// nuts::publish(Tick);
+/// ```
+macro_rules! domain_state {
+    ( $(#[$meta:meta])* $vis:vis $name:ident { $( $field:ident : $ty:ty ),* $(,)? } ) => {
+        $(#[$meta])*
+        $vis struct $name<'a> {
+            $(
+                #[doc = concat!("The `", stringify!($ty), "` object of the domain")]
+                pub $field: &'a mut $ty,
+            )*
+        }
+        impl<'a> $name<'a> {
+            /// Borrows all objects from the domain.
+            ///
+            /// # Panics
+            /// Panics if an object has not been stored in the domain.
+            #[allow(dead_code)]
+            $vis fn from_domain(domain: &'a mut $crate::DomainState) -> Self {
+                let mut objects = domain
+                    .borrow_all_mut(&[
+                        $( (::core::any::TypeId::of::<$ty>(), ::core::any::type_name::<$ty>()), )*
+                    ])
+                    .into_iter();
+                Self {
+                    $(
+                        $field: objects
+                            .next()
+                            .and_then(|object| object.downcast_mut::<$ty>())
+                            .expect("domain_state! object has wrong type"),
+                    )*
+                }
+            }
+        }
+    };
 }
 // This should really be a const fn so that we get compile-time panic instead of run-time checks.
 // But unfortunately, that is currently not possible.
@@ -1,33 +1,106 @@
-use crate::debug::DebugTypeName;
+use crate::nut::audit::{self, Allocation};
 use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
 use crate::DomainId;
+use crate::ManagedState;
 use core::any::{Any, TypeId};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 
 pub(crate) struct DomainStoreData {
     domain: DomainId,
     id: TypeId,
     data: Box<dyn Any>,
-    #[allow(dead_code)]
-    type_name: DebugTypeName,
+    type_name: &'static str,
 }
 impl Nut {
     pub fn exec_domain_store(&self, d: DomainStoreData) {
-        self.managed_state
-            .try_borrow_mut()
-            .expect(IMPOSSIBLE_ERR_MSG)
-            .get_mut(d.domain)
-            .expect("Domain ID invalid")
-            .store_unchecked(d.id, d.data);
+        d.apply(
+            &mut self
+                .managed_state
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG),
+        );
+    }
+    /// Applies all writes from `store_to_domain_ordered` that were issued while the managed state was borrowed.
+    pub(crate) fn apply_ordered_domain_stores(&self, managed_state: &mut ManagedState) {
+        while let Some(d) = self.ordered_domain_stores.pop() {
+            d.apply(managed_state);
+        }
     }
 }
 
 impl DomainStoreData {
+    pub(crate) fn apply(self, managed_state: &mut ManagedState) {
+        managed_state.prepare(self.domain);
+        managed_state
+            .get_mut(self.domain)
+            .expect("Domain ID invalid")
+            .store_unchecked(self.id, self.type_name, self.data);
+    }
     pub fn new<DATA: Any>(domain: DomainId, data: DATA) -> Self {
+        audit::record_box(Allocation::Queue, &data);
         Self {
             domain,
             id: TypeId::of::<DATA>(),
             data: Box::new(data),
-            type_name: DebugTypeName::new::<DATA>(),
+            type_name: std::any::type_name::<DATA>(),
+        }
+    }
+}
+
+/// Future of a domain store, resolves once the data has been stored, see `nuts::store_to_domain_awaitable`
+pub(crate) struct DomainWrite {
+    signal: Rc<WriteSignal>,
+}
+
+/// Shared between the queued store and its future
+#[derive(Default)]
+pub(crate) struct WriteSignal {
+    done: Cell<bool>,
+    waker: RefCell<Option<Waker>>,
+}
+
+impl DomainWrite {
+    pub(crate) fn done() -> Self {
+        let signal = WriteSignal::default();
+        signal.done.set(true);
+        Self {
+            signal: Rc::new(signal),
+        }
+    }
+    /// A write that is not applied, yet, together with the signal to complete it
+    pub(crate) fn pending() -> (Self, Rc<WriteSignal>) {
+        let signal = Rc::new(WriteSignal::default());
+        (
+            Self {
+                signal: signal.clone(),
+            },
+            signal,
+        )
+    }
+}
+
+impl WriteSignal {
+    pub(crate) fn complete(&self) {
+        self.done.set(true);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Future for DomainWrite {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.signal.done.get() {
+            Poll::Ready(())
+        } else {
+            *self.signal.waker.borrow_mut() = Some(cx.waker().clone());
+            Poll::Pending
         }
     }
 }
@@ -35,6 +108,6 @@ impl DomainStoreData {
 #[cfg(debug_assertions)]
 impl std::fmt::Debug for DomainStoreData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Storing {:?} to the domain", self.type_name)
+        write!(f, "Storing {} to the domain", self.type_name)
     }
 }
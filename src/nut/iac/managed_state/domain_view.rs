@@ -0,0 +1,76 @@
+use crate::debug::DebugTypeName;
+use crate::nut::iac::publish::BroadcastInfo;
+use crate::nut::iac::topic::Topic;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::{DomainId, DomainState};
+use core::any::Any;
+
+/// A copy of an object stored in a domain, published with [`publish_domain_view`](fn.publish_domain_view.html).
+///
+/// Changing the copy does not change the object in the domain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DomainView<T> {
+    /// The domain's number, as returned by `DomainEnumeration::id()`
+    pub domain: usize,
+    /// The copied object
+    pub data: T,
+}
+
+/// A view to publish, queued while domains cannot be accessed
+pub(crate) struct DomainViewRequest {
+    domain: DomainId,
+    snapshot: Box<dyn FnOnce(&DomainState) -> BroadcastInfo>,
+    #[allow(dead_code)]
+    type_name: DebugTypeName,
+}
+
+impl DomainViewRequest {
+    pub(crate) fn new<T: Any + Clone>(domain: DomainId) -> Self {
+        let index = domain.index().expect(IMPOSSIBLE_ERR_MSG);
+        Self {
+            domain,
+            snapshot: Box::new(move |state: &DomainState| {
+                let view = DomainView {
+                    domain: index,
+                    data: state.get::<T>().clone(),
+                };
+                BroadcastInfo::global(view, Topic::public_message::<DomainView<T>>())
+            }),
+            type_name: DebugTypeName::new::<T>(),
+        }
+    }
+}
+
+impl Nut {
+    /// Publishes the view right away, or queues the request if the domain cannot be accessed
+    pub(crate) fn publish_domain_view(&self, request: DomainViewRequest) {
+        match self.managed_state.try_borrow_mut() {
+            Ok(managed_state) => {
+                drop(managed_state);
+                self.exec_domain_view(request);
+            }
+            Err(_) => self.defer_bookkeeping(crate::nut::exec::Deferred::DomainView(request)),
+        }
+    }
+    pub(crate) fn exec_domain_view(&self, request: DomainViewRequest) {
+        let broadcast = {
+            let mut managed_state = self
+                .managed_state
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG);
+            managed_state.prepare(request.domain);
+            let state = managed_state
+                .get_mut(request.domain)
+                .expect("Domain ID invalid");
+            (request.snapshot)(state)
+        };
+        self.broadcast(broadcast);
+    }
+}
+
+#[cfg(debug_assertions)]
+impl std::fmt::Debug for DomainViewRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Publishing a view of {:?} in the domain", self.type_name)
+    }
+}
@@ -0,0 +1,318 @@
+//! Minimal entity-component storage inside a domain, available with the feature `ecs`.
+
+use core::any::{Any, TypeId};
+use core::fmt;
+use core::marker::PhantomData;
+use std::collections::{HashMap, HashSet};
+
+use crate::nut::IMPOSSIBLE_ERR_MSG;
+
+/// Handle to an entity stored in [`Entities`](struct.Entities.html).
+///
+/// Entity handles are never reused, a despawned entity stays invalid forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity(u32);
+
+/// Entities and their components, stored in a domain.
+///
+/// Obtained with [`DomainState::entities`](struct.DomainState.html#method.entities).
+/// Components of the same type are stored next to each other, which makes iterating over all of them cheap.
+///
+/// Structural changes are published as Nuts messages:
+/// [`ComponentInserted<C>`](struct.ComponentInserted.html), [`ComponentRemoved<C>`](struct.ComponentRemoved.html), and [`EntityDespawned`](struct.EntityDespawned.html).
+/// Mutating a component in place does not publish anything.
+///
+/// ### Example
+/// ```rust
+/// use nuts::{ComponentInserted, DefaultDomain, DomainState};
+/// #[derive(Debug, PartialEq)]
+/// struct Pos(i32, i32);
+/// struct Vel(i32, i32);
+/// struct Spawn;
+/// struct Tick;
+///
+/// struct World;
+/// let world = nuts::new_domained_activity(World, &DefaultDomain);
+/// world.subscribe_domained(|_, domain: &mut DomainState, _: &Spawn| {
+///     domain.entities().spawn().insert(Pos(0, 0)).insert(Vel(1, 2));
+/// });
+/// world.subscribe_domained(|_, domain: &mut DomainState, _: &Tick| {
+///     let entities = domain.entities();
+///     for entity in entities.with::<Vel>() {
+///         let vel = entities.get::<Vel>(entity).map(|v| (v.0, v.1)).unwrap();
+///         let pos = entities.get_mut::<Pos>(entity).unwrap();
+///         pos.0 += vel.0;
+///         pos.1 += vel.1;
+///     }
+/// });
+/// nuts::subscribe(|msg: &ComponentInserted<Pos>| println!("{:?} has a position", msg.entity));
+///
+/// nuts::publish(Spawn);
+/// nuts::publish(Tick);
+/// ```
+#[derive(Default)]
+pub struct Entities {
+    next: u32,
+    alive: HashSet<Entity>,
+    columns: HashMap<TypeId, Box<dyn Column>>,
+}
+
+/// Mutable access to a single entity, for adding and removing components.
+pub struct EntityMut<'a> {
+    entity: Entity,
+    entities: &'a mut Entities,
+}
+
+/// Published when a component has been inserted to an entity, or replaced.
+pub struct ComponentInserted<C> {
+    /// The entity that received the component
+    pub entity: Entity,
+    _component: PhantomData<fn() -> C>,
+}
+
+/// Published when a component has been removed from an entity, including removals due to despawning the entity.
+pub struct ComponentRemoved<C> {
+    /// The entity that lost the component
+    pub entity: Entity,
+    _component: PhantomData<fn() -> C>,
+}
+
+/// Published after an entity has been despawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntityDespawned {
+    /// The despawned entity, which is no longer valid
+    pub entity: Entity,
+}
+
+/// Dense storage of all components of one type
+struct Components<C> {
+    entities: Vec<Entity>,
+    values: Vec<C>,
+    index: HashMap<Entity, usize>,
+}
+
+/// Type-erased operations on component storage, needed to despawn entities
+trait Column {
+    /// Removes the component of the entity, if it has one, and publishes the removal
+    fn remove_entity(&mut self, entity: Entity);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl Entities {
+    /// Creates a new entity without any components.
+    pub fn spawn(&mut self) -> EntityMut<'_> {
+        let entity = Entity(self.next);
+        self.next = self.next.checked_add(1).expect("Too many entities spawned");
+        self.alive.insert(entity);
+        EntityMut {
+            entity,
+            entities: self,
+        }
+    }
+    /// Grants access to an existing entity, or `None` if it has been despawned.
+    pub fn entity(&mut self, entity: Entity) -> Option<EntityMut<'_>> {
+        if self.contains(entity) {
+            Some(EntityMut {
+                entity,
+                entities: self,
+            })
+        } else {
+            None
+        }
+    }
+    /// Removes the entity and all its components.
+    ///
+    /// Returns `false` if the entity had already been despawned.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.alive.remove(&entity) {
+            return false;
+        }
+        for column in self.columns.values_mut() {
+            column.remove_entity(entity);
+        }
+        crate::publish(EntityDespawned { entity });
+        true
+    }
+    /// Whether the entity exists and has not been despawned.
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.alive.contains(&entity)
+    }
+    /// Number of entities that have not been despawned.
+    pub fn len(&self) -> usize {
+        self.alive.len()
+    }
+    /// Whether there are no entities.
+    pub fn is_empty(&self) -> bool {
+        self.alive.is_empty()
+    }
+    /// Returns the component of the entity, if it has one.
+    pub fn get<C: Any>(&self, entity: Entity) -> Option<&C> {
+        let components = self.components::<C>()?;
+        components
+            .index
+            .get(&entity)
+            .map(|i| &components.values[*i])
+    }
+    /// Returns the component of the entity mutably, if it has one.
+    pub fn get_mut<C: Any>(&mut self, entity: Entity) -> Option<&mut C> {
+        let components = self.components_mut::<C>()?;
+        let i = *components.index.get(&entity)?;
+        Some(&mut components.values[i])
+    }
+    /// Iterates over all components of a type, together with the entity they belong to, in no particular order.
+    pub fn iter<C: Any>(&self) -> impl Iterator<Item = (Entity, &C)> {
+        self.components::<C>()
+            .into_iter()
+            .flat_map(|c| c.entities.iter().copied().zip(c.values.iter()))
+    }
+    /// Same as [`iter`](#method.iter) but grants mutable access to the components.
+    pub fn iter_mut<C: Any>(&mut self) -> impl Iterator<Item = (Entity, &mut C)> {
+        self.components_mut::<C>()
+            .into_iter()
+            .flat_map(|c| c.entities.iter().copied().zip(c.values.iter_mut()))
+    }
+    /// Returns all entities that have a component of the given type.
+    ///
+    /// The entities are collected, so that components can be modified while going through the list.
+    pub fn with<C: Any>(&self) -> Vec<Entity> {
+        self.components::<C>()
+            .map(|c| c.entities.clone())
+            .unwrap_or_default()
+    }
+    fn components<C: Any>(&self) -> Option<&Components<C>> {
+        self.columns.get(&TypeId::of::<C>()).map(|column| {
+            column
+                .as_any()
+                .downcast_ref::<Components<C>>()
+                .expect(IMPOSSIBLE_ERR_MSG)
+        })
+    }
+    fn components_mut<C: Any>(&mut self) -> Option<&mut Components<C>> {
+        self.columns.get_mut(&TypeId::of::<C>()).map(|column| {
+            column
+                .as_any_mut()
+                .downcast_mut::<Components<C>>()
+                .expect(IMPOSSIBLE_ERR_MSG)
+        })
+    }
+}
+
+impl<'a> EntityMut<'a> {
+    /// The handle of this entity, to access it again later.
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+    /// Adds a component to the entity, replacing a previous component of the same type.
+    ///
+    /// Publishes [`ComponentInserted<C>`](struct.ComponentInserted.html).
+    pub fn insert<C: Any>(self, component: C) -> Self {
+        let components = self
+            .entities
+            .columns
+            .entry(TypeId::of::<C>())
+            .or_insert_with(|| Box::new(Components::<C>::default()))
+            .as_any_mut()
+            .downcast_mut::<Components<C>>()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        components.insert(self.entity, component);
+        crate::publish(ComponentInserted::<C>::new(self.entity));
+        self
+    }
+    /// Removes a component from the entity and returns it.
+    ///
+    /// Publishes [`ComponentRemoved<C>`](struct.ComponentRemoved.html) if there was such a component.
+    pub fn remove<C: Any>(&mut self) -> Option<C> {
+        let removed = self.entities.components_mut::<C>()?.remove(self.entity);
+        if removed.is_some() {
+            crate::publish(ComponentRemoved::<C>::new(self.entity));
+        }
+        removed
+    }
+    /// Returns the component of the entity, if it has one.
+    pub fn get<C: Any>(&self) -> Option<&C> {
+        self.entities.get(self.entity)
+    }
+    /// Returns the component of the entity mutably, if it has one.
+    pub fn get_mut<C: Any>(&mut self) -> Option<&mut C> {
+        self.entities.get_mut(self.entity)
+    }
+}
+
+impl<C> Default for Components<C> {
+    fn default() -> Self {
+        Self {
+            entities: Vec::new(),
+            values: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+}
+
+impl<C> Components<C> {
+    fn insert(&mut self, entity: Entity, component: C) {
+        if let Some(i) = self.index.get(&entity) {
+            self.values[*i] = component;
+        } else {
+            self.index.insert(entity, self.values.len());
+            self.entities.push(entity);
+            self.values.push(component);
+        }
+    }
+    fn remove(&mut self, entity: Entity) -> Option<C> {
+        let i = self.index.remove(&entity)?;
+        self.entities.swap_remove(i);
+        let removed = self.values.swap_remove(i);
+        if let Some(moved) = self.entities.get(i) {
+            self.index.insert(*moved, i);
+        }
+        Some(removed)
+    }
+}
+
+impl<C: Any> Column for Components<C> {
+    fn remove_entity(&mut self, entity: Entity) {
+        if self.remove(entity).is_some() {
+            crate::publish(ComponentRemoved::<C>::new(entity));
+        }
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// Manual implementations, to avoid requiring the same traits on the component type
+macro_rules! component_event {
+    ($name:ident) => {
+        impl<C> $name<C> {
+            fn new(entity: Entity) -> Self {
+                Self {
+                    entity,
+                    _component: PhantomData,
+                }
+            }
+        }
+        impl<C> Clone for $name<C> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+        impl<C> Copy for $name<C> {}
+        impl<C> fmt::Debug for $name<C> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(
+                    f,
+                    "{}<{}>({:?})",
+                    stringify!($name),
+                    core::any::type_name::<C>(),
+                    self.entity
+                )
+            }
+        }
+    };
+}
+component_event!(ComponentInserted);
+component_event!(ComponentRemoved);
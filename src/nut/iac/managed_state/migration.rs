@@ -0,0 +1,50 @@
+//! Upgrades of domain objects to new types, registered with `nuts::register_domain_migration`.
+//!
+//! Migrations are applied lazily, whenever a domain is handed to a handler and contains an old type but not the new one.
+
+use super::{DomainState, ManagedState};
+use crate::nut::IMPOSSIBLE_ERR_MSG;
+use core::any::{Any, TypeId};
+
+type Convert = Box<dyn Fn(Box<dyn Any>) -> Box<dyn Any>>;
+
+pub(crate) struct DomainMigration {
+    from: TypeId,
+    to: (TypeId, &'static str),
+    convert: Convert,
+}
+
+impl DomainMigration {
+    pub(crate) fn new<OLD: Any, NEW: Any>(f: impl Fn(OLD) -> NEW + 'static) -> Self {
+        Self {
+            from: TypeId::of::<OLD>(),
+            to: (TypeId::of::<NEW>(), std::any::type_name::<NEW>()),
+            convert: Box::new(move |old| {
+                let old = old.downcast::<OLD>().expect(IMPOSSIBLE_ERR_MSG);
+                Box::new(f(*old))
+            }),
+        }
+    }
+}
+
+impl ManagedState {
+    pub(crate) fn add_migration(&mut self, migration: DomainMigration) {
+        self.migrations.push(migration);
+    }
+}
+
+impl DomainState {
+    /// Applies all migrations that match objects in the domain, including chains of migrations.
+    pub(crate) fn migrate(&mut self, migrations: &[DomainMigration]) {
+        // Bounded by the number of migrations, in case they form a cycle
+        for _ in 0..migrations.len() {
+            let mut migrated = false;
+            for m in migrations {
+                migrated |= self.replace_object(m.from, m.to, &m.convert);
+            }
+            if !migrated {
+                break;
+            }
+        }
+    }
+}
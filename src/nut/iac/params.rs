@@ -0,0 +1,165 @@
+//! Handler parameters that are extracted from the current broadcast.
+//!
+//! A handler registered with [`ActivityId::subscribe_with`](../struct.ActivityId.html#method.subscribe_with)
+//! can take any combination of the supported parameters, in any order.
+//! Each supported parameter type implements [`HandlerParam`](trait.HandlerParam.html).
+//! New parameter types only need a new implementation of that trait, the registration API stays the same.
+
+use crate::{ActivityId, DomainState, MessageControl};
+
+/// Everything a handler can ask for, while a message is dispatched to it.
+///
+/// Each part can be handed out only once per call, the context is consumed while the parameters are extracted.
+pub struct HandlerContext<'a, A, MSG> {
+    pub(crate) activity: Option<&'a mut A>,
+    pub(crate) domain: Result<&'a mut DomainState, &'static str>,
+    pub(crate) msg: Option<&'a mut MSG>,
+    pub(crate) id: ActivityId<A>,
+}
+
+/// A type that can be used as parameter of a subscription handler.
+///
+/// The `Marker` type only exists to distinguish implementations that would overlap otherwise, it is always inferred.
+pub trait HandlerParam<A, MSG, Marker> {
+    /// The parameter type with the lifetime of a single handler call
+    type Item<'a>
+    where
+        A: 'a,
+        MSG: 'a;
+    /// Extracts the parameter from the context.
+    ///
+    /// # Panics
+    /// Panics if the same part of the context has already been extracted for another parameter.
+    fn fetch<'a>(ctx: &mut HandlerContext<'a, A, MSG>) -> Self::Item<'a>;
+    /// True if the parameter gives mutable access to the message.
+    /// Handlers with such a parameter are called after all handlers that only read the message.
+    const MUTATES_MESSAGE: bool = false;
+    /// True if the parameter allows consuming the message, which is ordered like mutable access.
+    const CLAIMS_MESSAGE: bool = false;
+}
+
+/// Markers for implementations of `HandlerParam`.
+///
+/// They are public to satisfy the type checker but they cannot be named by users.
+pub mod markers {
+    /// `&mut A`
+    pub struct Activity;
+    /// `&mut DomainState`
+    pub struct Domain;
+    /// `&MSG`
+    pub struct Message;
+    /// `&mut MSG`
+    pub struct MessageMut;
+    /// `ActivityId<A>`
+    pub struct Id;
+    /// `MessageControl`
+    pub struct Control;
+}
+
+const TAKEN_TWICE: &str = "A handler cannot take the same parameter twice.";
+
+impl<A, MSG> HandlerParam<A, MSG, markers::Activity> for &mut A {
+    type Item<'a>
+        = &'a mut A
+    where
+        A: 'a,
+        MSG: 'a;
+    fn fetch<'a>(ctx: &mut HandlerContext<'a, A, MSG>) -> &'a mut A {
+        ctx.activity.take().expect(TAKEN_TWICE)
+    }
+}
+
+impl<A, MSG> HandlerParam<A, MSG, markers::Domain> for &mut DomainState {
+    type Item<'a>
+        = &'a mut DomainState
+    where
+        A: 'a,
+        MSG: 'a;
+    fn fetch<'a>(ctx: &mut HandlerContext<'a, A, MSG>) -> &'a mut DomainState {
+        match std::mem::replace(&mut ctx.domain, Err(TAKEN_TWICE)) {
+            Ok(domain) => domain,
+            Err(msg) => panic!("{}", msg),
+        }
+    }
+}
+
+impl<A, MSG> HandlerParam<A, MSG, markers::Message> for &MSG {
+    type Item<'a>
+        = &'a MSG
+    where
+        A: 'a,
+        MSG: 'a;
+    fn fetch<'a>(ctx: &mut HandlerContext<'a, A, MSG>) -> &'a MSG {
+        ctx.msg.take().expect(TAKEN_TWICE)
+    }
+}
+
+impl<A, MSG> HandlerParam<A, MSG, markers::MessageMut> for &mut MSG {
+    type Item<'a>
+        = &'a mut MSG
+    where
+        A: 'a,
+        MSG: 'a;
+    fn fetch<'a>(ctx: &mut HandlerContext<'a, A, MSG>) -> &'a mut MSG {
+        ctx.msg.take().expect(TAKEN_TWICE)
+    }
+    const MUTATES_MESSAGE: bool = true;
+}
+
+impl<A, MSG> HandlerParam<A, MSG, markers::Id> for ActivityId<A> {
+    type Item<'a>
+        = ActivityId<A>
+    where
+        A: 'a,
+        MSG: 'a;
+    fn fetch<'a>(ctx: &mut HandlerContext<'a, A, MSG>) -> ActivityId<A> {
+        ctx.id
+    }
+}
+
+impl<A, MSG> HandlerParam<A, MSG, markers::Control> for MessageControl {
+    type Item<'a>
+        = MessageControl
+    where
+        A: 'a,
+        MSG: 'a;
+    fn fetch<'a>(_ctx: &mut HandlerContext<'a, A, MSG>) -> MessageControl {
+        MessageControl::new()
+    }
+    const CLAIMS_MESSAGE: bool = true;
+}
+
+/// A function or closure that can be used as subscription handler with [`ActivityId::subscribe_with`](struct.ActivityId.html#method.subscribe_with).
+///
+/// Implemented for all functions that take between one and five parameters that implement [`HandlerParam`](trait.HandlerParam.html).
+/// `Params` is inferred from the function signature.
+pub trait HandlerFn<A, MSG, Params> {
+    /// Extracts all parameters from the context and calls the function
+    fn call(&self, ctx: &mut HandlerContext<'_, A, MSG>);
+    /// True if any parameter gives mutable access to the message
+    const MUTATES_MESSAGE: bool = false;
+    /// True if any parameter allows consuming the message
+    const CLAIMS_MESSAGE: bool = false;
+}
+
+macro_rules! impl_handler_fn {
+    ( $( $param:ident $marker:ident ),+ ) => {
+        impl<F, A, MSG, $( $param, $marker ),+> HandlerFn<A, MSG, ( $( $param, $marker ),+ ,)> for F
+        where
+            $( $param: HandlerParam<A, MSG, $marker>, )+
+            F: Fn( $( $param ),+ ) + for<'a> Fn( $( $param::Item<'a> ),+ ),
+        {
+            fn call(&self, ctx: &mut HandlerContext<'_, A, MSG>) {
+                self( $( $param::fetch(ctx) ),+ )
+            }
+            const MUTATES_MESSAGE: bool = false $( || $param::MUTATES_MESSAGE )+;
+            const CLAIMS_MESSAGE: bool = false $( || $param::CLAIMS_MESSAGE )+;
+        }
+    };
+}
+
+impl_handler_fn!(P1 M1);
+impl_handler_fn!(P1 M1, P2 M2);
+impl_handler_fn!(P1 M1, P2 M2, P3 M3);
+impl_handler_fn!(P1 M1, P2 M2, P3 M3, P4 M4);
+impl_handler_fn!(P1 M1, P2 M2, P3 M3, P4 M4, P5 M5);
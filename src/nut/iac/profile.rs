@@ -0,0 +1,64 @@
+//! Time measurements of subscription handlers, enabled with the feature `perf`.
+//!
+//! Wall time is measured with the clock of the Nut.
+//! CPU time of the executing thread is only measured on native targets.
+
+use core::cell::Cell;
+use std::time::Duration;
+
+/// Accumulated measurements of a single subscription handler.
+#[derive(Clone, Debug)]
+pub struct HandlerReport {
+    /// Type name of the subscribing activity (only available in debug mode)
+    pub activity: &'static str,
+    /// Type name of the message or name of the lifecycle event (only available in debug mode)
+    pub topic: &'static str,
+    /// Number of times the handler has been called
+    pub calls: u64,
+    /// Total time spent in the handler, including time spent blocking
+    pub wall_time: Duration,
+    /// Total CPU time the thread spent in the handler
+    ///
+    /// If this is much lower than `wall_time`, the handler is probably blocking, e.g. on IO.
+    pub cpu_time: Duration,
+}
+
+#[derive(Default)]
+pub(crate) struct HandlerProfile {
+    calls: Cell<u64>,
+    wall_time: Cell<Duration>,
+    cpu_time: Cell<Duration>,
+}
+
+pub(crate) struct Measurement {
+    wall: Duration,
+    #[cfg(not(target_arch = "wasm32"))]
+    cpu: cpu_time::ThreadTime,
+}
+
+impl HandlerProfile {
+    pub(crate) fn start(&self, now: Duration) -> Measurement {
+        Measurement {
+            wall: now,
+            #[cfg(not(target_arch = "wasm32"))]
+            cpu: cpu_time::ThreadTime::now(),
+        }
+    }
+    pub(crate) fn stop(&self, measurement: &Measurement, now: Duration) {
+        self.calls.set(self.calls.get() + 1);
+        self.wall_time
+            .set(self.wall_time.get() + (now - measurement.wall));
+        #[cfg(not(target_arch = "wasm32"))]
+        self.cpu_time
+            .set(self.cpu_time.get() + measurement.cpu.elapsed());
+    }
+    pub(crate) fn report(&self, activity: &'static str, topic: &'static str) -> HandlerReport {
+        HandlerReport {
+            activity,
+            topic,
+            calls: self.calls.get(),
+            wall_time: self.wall_time.get(),
+            cpu_time: self.cpu_time.get(),
+        }
+    }
+}
@@ -1,9 +1,46 @@
+pub use alias::AliasReport;
+pub(crate) use alias::Aliases;
+pub use ask::Reply;
+pub(crate) use ask::{Request, Responder};
 pub(crate) use broadcast::BroadcastInfo;
+pub(crate) use capability::PublishRestrictions;
+pub use capability::PublishToken;
+pub(crate) use claim::Delivery;
+pub use claim::MessageControl;
+pub use group::PublishGroup;
+pub use keyed::KeyedMessage;
+pub use scope::ScopeToken;
+pub(crate) use scope::ScopedMessage;
+pub use shared::SharedPayload;
 
+mod alias;
+mod ask;
 mod broadcast;
+mod capability;
+mod claim;
+mod group;
+mod keyed;
+mod mapper;
+mod recycle;
 mod response;
+mod retain;
+mod scope;
+mod shared;
+#[cfg(feature = "futures")]
+mod sink;
+mod startup;
+pub(crate) use mapper::MessageMappers;
+#[cfg(feature = "message-arena")]
+pub use recycle::ArenaUsage;
+pub(crate) use recycle::MessagePool;
+pub(crate) use response::ResponseMode;
 pub(crate) use response::ResponseTracker;
 pub(crate) use response::Slot as ResponseSlot;
+pub use response::{ResponseConfig, ResponseStats, SlotReuse};
+pub(crate) use retain::{Replay, RetainedMessages};
+#[cfg(feature = "futures")]
+pub(crate) use sink::PublishSink;
+pub(crate) use startup::StartupBuffer;
 
 use crate::nut::Nut;
 use crate::*;
@@ -13,18 +50,78 @@ use self::response::NutsResponse;
 
 impl Nut {
     pub(crate) fn broadcast(&self, broadcast: BroadcastInfo) {
+        self.check_publish_permission(&broadcast);
+        self.published.set(true);
+        let mut broadcast = self.resolve_alias(broadcast);
+        self.map_message(&mut broadcast);
+        self.trace_broadcast(&mut broadcast);
+        self.retain_broadcast(&broadcast);
+        self.queue_initial_enters();
         self.deferred_events.push(broadcast.into());
         self.catch_up_deferred_to_quiescence();
     }
-    pub(crate) fn publish_and_await<MSG: Any>(&self, msg: MSG) -> NutsResponse {
+    pub(crate) fn publish_and_await<MSG: Any>(
+        &self,
+        msg: MSG,
+        mode: ResponseMode,
+        urgent: bool,
+    ) -> NutsResponse {
         let broadcast = BroadcastInfo::global(msg, Topic::public_message::<MSG>());
-        let ticket = Nut::with_response_tracker_mut(|rt| rt.allocate());
+        self.broadcast_awaiting(broadcast, mode, urgent)
+    }
+    /// Queues the broadcast and returns a future that resolves once it has been handled, as defined by `mode`
+    pub(crate) fn broadcast_awaiting(
+        &self,
+        broadcast: BroadcastInfo,
+        mode: ResponseMode,
+        urgent: bool,
+    ) -> NutsResponse {
+        self.check_publish_permission(&broadcast);
+        self.published.set(true);
+        let mut broadcast = self.resolve_alias(broadcast);
+        self.map_message(&mut broadcast);
+        self.trace_broadcast(&mut broadcast);
+        self.retain_broadcast(&broadcast);
+        let ticket = Nut::with_response_tracker_mut(|rt| rt.allocate(mode));
         let future = NutsResponse::new(&ticket);
-        self.deferred_events
-            .push(nut::exec::Deferred::BroadcastAwaitingResponse(
-                broadcast, ticket,
-            ));
+        let event = nut::exec::Deferred::BroadcastAwaitingResponse(broadcast, ticket);
+        self.queue_initial_enters();
+        if urgent {
+            self.deferred_events.push_urgent(event);
+        } else {
+            self.deferred_events.push(event);
+        }
         self.catch_up_deferred_to_quiescence();
         future
     }
 }
+
+/// A message that will be published once it is decided when the returned future should resolve.
+///
+/// Created with [`nuts::publish_awaiting`](fn.publish_awaiting.html).
+/// The message is published as soon as one of the methods is called, not when the future is first polled.
+/// The returned futures are `Unpin`, they can be polled without pinning them first.
+/// They wake their task once they are ready, so they can be awaited inside futures attached to activities.
+#[must_use = "The message is only published after calling await_dispatch() or await_quiescent()."]
+pub struct AwaitingPublish<A> {
+    msg: A,
+}
+
+impl<A: Any> AwaitingPublish<A> {
+    pub(crate) fn new(msg: A) -> Self {
+        Self { msg }
+    }
+    /// Publishes the message and returns a future that resolves once all subscribers to this message have handled it.
+    ///
+    /// Messages published by those subscribers may still be queued when the future resolves.
+    pub fn await_dispatch(self) -> impl core::future::Future<Output = ()> + Unpin {
+        nut::publish_custom_and_await(self.msg, ResponseMode::Dispatch, false)
+    }
+    /// Publishes the message and returns a future that resolves once no more messages are queued.
+    ///
+    /// At that point, the entire cascade of messages published in reaction to this message has been handled.
+    /// Unrelated messages that are published in the meantime delay the resolution as well.
+    pub fn await_quiescent(self) -> impl core::future::Future<Output = ()> + Unpin {
+        nut::publish_custom_and_await(self.msg, ResponseMode::Quiescent, false)
+    }
+}
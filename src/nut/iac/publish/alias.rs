@@ -0,0 +1,90 @@
+use super::BroadcastInfo;
+use crate::debug::DebugTypeName;
+use crate::nut::iac::topic::Topic;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use core::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Number of messages that have been published with an aliased type, see [`alias`](fn.alias.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AliasReport {
+    /// Name of the old message type, only available in debug mode
+    pub old: &'static str,
+    /// Name of the new message type, only available in debug mode
+    pub new: &'static str,
+    /// How many messages of the old type have been published and converted
+    pub publishes: u64,
+}
+
+/// Conversions of published messages from old to new types
+#[derive(Default)]
+pub(crate) struct Aliases {
+    aliases: HashMap<TypeId, Alias>,
+}
+
+type Conversion = Rc<dyn Fn(Box<dyn Any>) -> Box<dyn Any>>;
+
+struct Alias {
+    convert: Conversion,
+    topic: Topic,
+    old: DebugTypeName,
+    publishes: u64,
+}
+
+impl Aliases {
+    pub(crate) fn add<OLD: Any, NEW: Any>(&mut self, f: impl Fn(OLD) -> NEW + 'static) {
+        let convert = move |old: Box<dyn Any>| -> Box<dyn Any> {
+            Box::new(f(*old.downcast().expect(IMPOSSIBLE_ERR_MSG)))
+        };
+        self.aliases.insert(
+            TypeId::of::<OLD>(),
+            Alias {
+                convert: Rc::new(convert),
+                topic: Topic::public_message::<NEW>(),
+                old: DebugTypeName::new::<OLD>(),
+                publishes: 0,
+            },
+        );
+    }
+    /// Looks up the conversion for a published message and counts the publish
+    fn lookup(&mut self, topic: TypeId) -> Option<(Conversion, Topic)> {
+        self.aliases.get_mut(&topic).map(|alias| {
+            alias.publishes += 1;
+            (alias.convert.clone(), alias.topic)
+        })
+    }
+    pub(crate) fn report(&self) -> Vec<AliasReport> {
+        self.aliases
+            .values()
+            .map(|alias| AliasReport {
+                old: alias.old.name(),
+                new: alias.topic.name(),
+                publishes: alias.publishes,
+            })
+            .collect()
+    }
+}
+
+impl Nut {
+    /// Converts a published message of an aliased type to the new type, repeatedly if the new type is aliased, too.
+    pub(crate) fn resolve_alias(&self, mut broadcast: BroadcastInfo) -> BroadcastInfo {
+        // Bounded to avoid an endless loop in case of cyclic aliases
+        for _ in 0..8 {
+            let topic = match broadcast.public_message() {
+                Some((topic, _)) => topic,
+                None => return broadcast,
+            };
+            let alias = self
+                .aliases
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .lookup(topic);
+            match alias {
+                Some((convert, new_topic)) => broadcast = broadcast.convert(&*convert, new_topic),
+                None => return broadcast,
+            }
+        }
+        broadcast
+    }
+}
@@ -0,0 +1,109 @@
+//! Request-response between activities, see `ActivityId::ask` and `nuts::request`.
+
+use super::response::NutsResponse;
+use super::{BroadcastInfo, ResponseMode};
+use crate::nut::iac::subscription::SubscriptionId;
+use crate::nut::iac::topic::Topic;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::{Error, UncheckedActivityId};
+use core::any::{Any, TypeId};
+use core::cell::Cell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::rc::Rc;
+
+/// Private message carrying a request together with the place for its reply
+pub(crate) struct Request<REQ, RESP> {
+    req: REQ,
+    reply: Rc<Cell<Option<RESP>>>,
+}
+
+impl<REQ, RESP> Request<REQ, RESP> {
+    pub(crate) fn answer(self, f: impl FnOnce(REQ) -> RESP) {
+        self.reply.set(Some(f(self.req)));
+    }
+}
+
+/// The only activity that answers requests of a pair of request and response type sent with `nuts::request`
+pub(crate) struct Responder {
+    activity: UncheckedActivityId,
+    sub: SubscriptionId,
+}
+
+/// Future returned by [`ActivityId::ask`](struct.ActivityId.html#method.ask) and [`nuts::request`](fn.request.html), resolves with the reply of the activity.
+///
+/// The future is `Unpin` and wakes its task once the request has been handled.
+/// It resolves with `Error::NoReply` if the request has been handled without reply.
+pub struct Reply<RESP> {
+    response: NutsResponse,
+    reply: Rc<Cell<Option<RESP>>>,
+}
+
+impl<RESP> Future for Reply<RESP> {
+    type Output = Result<RESP, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.response).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(()) => Poll::Ready(self.reply.take().ok_or(Error::NoReply)),
+        }
+    }
+}
+
+impl Nut {
+    pub(crate) fn ask<REQ: Any, RESP: Any>(
+        &self,
+        id: UncheckedActivityId,
+        req: REQ,
+    ) -> Reply<RESP> {
+        let reply = Rc::new(Cell::new(None));
+        let request = Request {
+            req,
+            reply: reply.clone(),
+        };
+        let broadcast =
+            BroadcastInfo::local(request, id, Topic::private_message::<Request<REQ, RESP>>());
+        let response = self.broadcast_awaiting(broadcast, ResponseMode::Dispatch, false);
+        Reply { response, reply }
+    }
+    /// Sends the request to the registered responder
+    pub(crate) fn request<REQ: Any, RESP: Any>(&self, req: REQ) -> Reply<RESP> {
+        let responder = self
+            .responders
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .get(&TypeId::of::<Request<REQ, RESP>>())
+            .map(|responder| responder.activity)
+            .unwrap_or_else(|| {
+                panic!(
+                    "No responder for requests of type {} with responses of type {}.",
+                    std::any::type_name::<REQ>(),
+                    std::any::type_name::<RESP>()
+                )
+            });
+        self.ask(responder, req)
+    }
+    /// Makes the subscription the only responder for `REQ` and `RESP`, returns the subscription of the previous responder
+    pub(crate) fn set_responder<REQ: Any, RESP: Any>(
+        &self,
+        activity: UncheckedActivityId,
+        sub: SubscriptionId,
+    ) -> Option<SubscriptionId> {
+        self.responders
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .insert(
+                TypeId::of::<Request<REQ, RESP>>(),
+                Responder { activity, sub },
+            )
+            .map(|previous| previous.sub)
+    }
+    /// Unregisters all responders of a deleted activity
+    pub(crate) fn forget_responders_of(&self, id: UncheckedActivityId) {
+        self.responders
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .retain(|_, responder| responder.activity != id);
+    }
+}
@@ -1,5 +1,8 @@
+use super::retain::{clone_any, CloneFn};
 use crate::debug::DebugTypeName;
-use crate::nut::{iac::subscription::Subscription, Nut};
+use crate::nut::audit::{self, Allocation};
+use crate::nut::{iac::subscription::Subscription, trace::TraceId, Nut, IMPOSSIBLE_ERR_MSG};
+use crate::nut::{shared_audit, usage_log};
 use crate::*;
 use core::any::{Any, TypeId};
 use std::cell::RefMut;
@@ -7,7 +10,17 @@ use std::cell::RefMut;
 pub(crate) struct BroadcastInfo {
     address: BroadcastAddress,
     msg: Box<dyn Any>,
-    topic: Topic,
+    pub(super) topic: Topic,
+    /// Assigned when the broadcast is queued, together with the depth in the cascade of messages
+    trace: Option<(TraceId, u32)>,
+    /// Return the message box to the message pool after the broadcast
+    recycle: bool,
+    /// Give each subscriber its own copy of the message, created with this function
+    isolate: Option<CloneFn>,
+    /// Set while the message is delivered to the activities of a phase in `run_frame`, `None` for the initial delivery
+    phase: Option<Phase>,
+    /// Published with a `PublishToken`, which allows restricted message types
+    authorized: bool,
     #[allow(dead_code)]
     type_name: DebugTypeName,
 }
@@ -20,65 +33,336 @@ enum BroadcastAddress {
 
 impl BroadcastInfo {
     pub(crate) fn global<MSG: Any>(msg: MSG, topic: Topic) -> Self {
+        audit::record_box(Allocation::Message, &msg);
+        shared_audit::check::<MSG>();
         BroadcastInfo {
             address: BroadcastAddress::Global,
             msg: Box::new(msg),
             topic,
+            trace: None,
+            recycle: false,
+            isolate: None,
+            phase: None,
+            authorized: false,
+            type_name: DebugTypeName::new::<MSG>(),
+        }
+    }
+    /// A global broadcast where each subscriber receives a fresh clone of the message
+    pub(crate) fn global_isolated<MSG: Any + Clone>(msg: MSG, topic: Topic) -> Self {
+        let mut broadcast = Self::global(msg, topic);
+        broadcast.isolate = Some(clone_any::<MSG>);
+        broadcast
+    }
+    /// A global broadcast of a message that has been boxed by the `MessagePool`
+    pub(crate) fn global_pooled<MSG: Any>(msg: Box<dyn Any>, topic: Topic) -> Self {
+        shared_audit::check::<MSG>();
+        BroadcastInfo {
+            address: BroadcastAddress::Global,
+            msg,
+            topic,
+            trace: None,
+            recycle: true,
+            isolate: None,
+            phase: None,
+            authorized: false,
             type_name: DebugTypeName::new::<MSG>(),
         }
     }
     pub(crate) fn local<MSG: Any>(msg: MSG, id: UncheckedActivityId, topic: Topic) -> Self {
+        audit::record_box(Allocation::Message, &msg);
+        shared_audit::check::<MSG>();
         BroadcastInfo {
             address: BroadcastAddress::Local(id),
             msg: Box::new(msg),
             topic,
+            trace: None,
+            recycle: false,
+            isolate: None,
+            phase: None,
+            authorized: false,
             type_name: DebugTypeName::new::<MSG>(),
         }
     }
     pub(crate) fn local_by_type<RECV: Any, MSG: Any>(msg: MSG, topic: Topic) -> Self {
+        audit::record_box(Allocation::Message, &msg);
+        shared_audit::check::<MSG>();
         BroadcastInfo {
             address: BroadcastAddress::LocalByType(TypeId::of::<RECV>()),
             msg: Box::new(msg),
             topic,
+            trace: None,
+            recycle: false,
+            isolate: None,
+            phase: None,
+            authorized: false,
             type_name: DebugTypeName::new::<MSG>(),
         }
     }
 }
 
+impl BroadcastInfo {
+    pub(crate) fn topic(&self) -> Topic {
+        self.topic
+    }
+    pub(crate) fn is_authorized(&self) -> bool {
+        self.authorized
+    }
+    /// Type and content of a globally published message, `None` for private messages and events
+    pub(crate) fn public_message(&self) -> Option<(TypeId, &dyn Any)> {
+        match (&self.address, self.topic) {
+            (BroadcastAddress::Global, Topic::PublicMessage(topic, _)) => {
+                Some((topic, self.msg.as_ref()))
+            }
+            _ => None,
+        }
+    }
+    /// Mutable content of a globally published message, `None` for private messages and events
+    pub(crate) fn public_message_mut(&mut self) -> Option<&mut dyn Any> {
+        match (&self.address, self.topic) {
+            (BroadcastAddress::Global, Topic::PublicMessage(..)) => Some(self.msg.as_mut()),
+            _ => None,
+        }
+    }
+    /// Type of the receiving activity for private messages sent with `send_to`
+    pub(crate) fn receiver_type(&self) -> Option<TypeId> {
+        match self.address {
+            BroadcastAddress::LocalByType(receiver) => Some(receiver),
+            _ => None,
+        }
+    }
+}
+
+impl BroadcastInfo {
+    pub(crate) fn authorize(self) -> Self {
+        Self {
+            authorized: true,
+            ..self
+        }
+    }
+    pub(crate) fn with_msg(self, msg: Box<dyn Any>) -> Self {
+        Self { msg, ..self }
+    }
+    pub(crate) fn for_phase(self, phase: Phase) -> Self {
+        Self {
+            phase: Some(phase),
+            trace: None,
+            ..self
+        }
+    }
+    /// Replaces the message with a converted one of a different type.
+    pub(super) fn convert(self, f: &dyn Fn(Box<dyn Any>) -> Box<dyn Any>, topic: Topic) -> Self {
+        Self {
+            msg: f(self.msg),
+            topic,
+            recycle: false,
+            type_name: match topic {
+                Topic::PublicMessage(_, name) => name,
+                _ => self.type_name,
+            },
+            ..self
+        }
+    }
+}
+
 impl Nut {
+    /// Assigns a new trace to the broadcast, caused by the currently handled broadcast (if any)
+    pub(crate) fn trace_broadcast(&self, broadcast: &mut BroadcastInfo) {
+        let mut tracer = self.tracer.try_borrow_mut().expect(IMPOSSIBLE_ERR_MSG);
+        let trace = tracer.new_trace(broadcast.type_name);
+        broadcast.trace = Some((trace, tracer.depth() + 1));
+    }
     /// only access after locking with executing flag
-    pub(crate) fn unchecked_broadcast(&self, broadcast: BroadcastInfo) {
+    pub(crate) fn unchecked_broadcast(&self, mut broadcast: BroadcastInfo) {
+        audit::record_broadcast();
+        self.broadcast_count.set(self.broadcast_count.get() + 1);
+        #[cfg(debug_assertions)]
+        self.subscriptions.count_broadcast();
+        let topic = broadcast.topic;
+        let stats_start = self.stats_begin(topic);
+        let previous_trace = broadcast.trace.map(|(trace, depth)| {
+            usage_log::cascade_depth(depth, topic);
+            self.tracer
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .enter(trace, depth)
+        });
+        #[cfg(feature = "log")]
+        if matches!(
+            (&broadcast.address, topic),
+            (BroadcastAddress::Global, Topic::PublicMessage(..))
+        ) && self.subscriptions.count(&topic) == 0
+        {
+            usage_log::no_subscribers(topic);
+        }
+        #[cfg(feature = "mutation-audit")]
+        crate::nut::mutation_audit::begin(broadcast.public_message().map(|(t, _)| t));
         let mut managed_state = self.managed_state.borrow_mut();
-        managed_state.set_broadcast(broadcast.msg);
-        if let Some(handlers) = self.subscriptions.get().get(&broadcast.topic) {
+        // With isolation, the original is kept here and each subscriber receives a clone of it
+        let isolate = broadcast.isolate;
+        let msg = std::mem::replace(&mut broadcast.msg, Box::new(()));
+        let original = match isolate {
+            Some(_) => Some(msg),
+            None => {
+                managed_state.set_broadcast(msg);
+                None
+            }
+        };
+        let frames = self.frames.try_borrow().expect(IMPOSSIBLE_ERR_MSG);
+        let phased = frames.in_use();
+        drop(frames);
+        let phase = broadcast.phase;
+        let in_phase = |activity: usize| {
+            !phased
+                || self
+                    .frames
+                    .try_borrow()
+                    .expect(IMPOSSIBLE_ERR_MSG)
+                    .phase_of(activity)
+                    == phase
+        };
+        let mut has_phased_subscribers = false;
+        let mut call = |sub: &Subscription| {
+            if let (Some(clone), Some(original)) = (isolate, &original) {
+                managed_state.set_broadcast(clone(original.as_ref()));
+            }
+            self.call_subscriber(sub, &mut managed_state);
+        };
+        let receiver_current = self.is_receiver_current(&broadcast.address);
+        if let Some(handlers) = self
+            .subscriptions
+            .get()
+            .get(&broadcast.topic)
+            .filter(|_| receiver_current)
+        {
             match self.receiver_id(&broadcast.address) {
                 None => {
-                    for sub in handlers.shared_subscriptions() {
-                        self.call_subscriber(sub, &mut managed_state);
-                    }
+                    let count = handlers
+                        .is_claimable()
+                        .then(|| handlers.shared_subscriptions_where(in_phase).count());
+                    let consumed = match self.config.get().dispatch_order {
+                        DispatchOrder::Unspecified if !handlers.is_weighted() => self.deliver(
+                            handlers.shared_subscriptions_where(in_phase),
+                            count,
+                            &mut call,
+                        ),
+                        DispatchOrder::Unspecified | DispatchOrder::Creation => self.deliver(
+                            handlers.shared_subscriptions_in_creation_order(in_phase),
+                            count,
+                            &mut call,
+                        ),
+                    };
+                    has_phased_subscribers = !consumed
+                        && phased
+                        && phase.is_none()
+                        && handlers.activities().any(|activity| !in_phase(activity));
                 }
                 Some(id) => {
                     if broadcast.topic.unqiue_per_activity() {
                         if let Some(sub) = handlers.private_subscription(id) {
-                            self.call_subscriber(sub, &mut managed_state);
+                            call(sub);
                         }
                     } else {
-                        for sub in handlers.shared_subscriptions_of_single_activity(id) {
-                            self.call_subscriber(sub, &mut managed_state);
-                        }
+                        let count = handlers
+                            .is_claimable()
+                            .then(|| handlers.shared_subscriptions_of_single_activity(id).count());
+                        self.deliver(
+                            handlers.shared_subscriptions_of_single_activity(id),
+                            count,
+                            &mut call,
+                        );
                     }
                 }
             }
             #[cfg(debug_assertions)]
             self.active_activity_name.set(None);
         }
-        managed_state.clear_broadcast();
+        #[cfg(feature = "mutation-audit")]
+        crate::nut::mutation_audit::end();
+        let last = managed_state.clear_broadcast();
+        let msg = original.or(last);
+        drop(managed_state);
+        if phase.is_some() {
+            self.frames
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .restore(msg, broadcast);
+        } else if has_phased_subscribers {
+            if let Some(msg) = msg {
+                self.frames
+                    .try_borrow_mut()
+                    .expect(IMPOSSIBLE_ERR_MSG)
+                    .enqueue(broadcast.with_msg(msg));
+            }
+        } else if broadcast.recycle {
+            if let Some(msg) = msg {
+                self.message_pool
+                    .try_borrow_mut()
+                    .expect(IMPOSSIBLE_ERR_MSG)
+                    .recycle(msg);
+            }
+        }
+        if let Some(previous) = previous_trace {
+            self.tracer
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .leave(previous);
+        }
+        self.stats_end(stats_start);
     }
-    fn call_subscriber(&self, sub: &Subscription, managed_state: &mut RefMut<ManagedState>) {
+    pub(super) fn call_subscriber(
+        &self,
+        sub: &Subscription,
+        managed_state: &mut RefMut<ManagedState>,
+    ) {
         #[cfg(debug_assertions)]
         self.active_activity_name.set(Some(sub.type_name));
+        let now = self.config.get().record_call_times.then(|| self.now());
+        sub.mark_used(now);
+        self.stats.count_handler();
         let f = &sub.handler;
-        f(&mut self.activities.borrow_mut(), managed_state);
+        #[cfg(feature = "perf")]
+        let measurement = sub.profile.start(self.now());
+        #[cfg(feature = "log")]
+        let start = self.now();
+        #[cfg(feature = "mutation-audit")]
+        let before =
+            crate::nut::mutation_audit::before_handler(sub.mutates, managed_state.broadcast_ref());
+        match self.config.get().panic_policy {
+            PanicPolicy::Propagate => f(&mut self.activities.borrow_mut(), managed_state),
+            PanicPolicy::CatchAndContinue => {
+                let call = std::panic::AssertUnwindSafe(|| {
+                    f(&mut self.activities.borrow_mut(), managed_state)
+                });
+                if std::panic::catch_unwind(call).is_err() {
+                    usage_log::caught_panic(sub.type_name.name());
+                }
+            }
+        }
+        #[cfg(feature = "mutation-audit")]
+        crate::nut::mutation_audit::after_handler(
+            before,
+            managed_state.broadcast_ref(),
+            sub.type_name.name(),
+        );
+        #[cfg(feature = "perf")]
+        sub.profile.stop(&measurement, self.now());
+        #[cfg(feature = "log")]
+        usage_log::handler_time(self.now() - start, sub.type_name.name());
+        self.apply_ordered_domain_stores(managed_state);
+    }
+    /// False if the message is addressed to an activity ID that has become stale
+    fn is_receiver_current(&self, address: &BroadcastAddress) -> bool {
+        match address {
+            BroadcastAddress::Local(id) => {
+                debug_assert!(
+                    self.is_live(*id),
+                    "Message sent to activity {} before it has been flushed from the inchoate activities.",
+                    id.index
+                );
+                self.activities.borrow().is_current(*id)
+            }
+            BroadcastAddress::LocalByType(_) | BroadcastAddress::Global => true,
+        }
     }
     fn receiver_id(&self, address: &BroadcastAddress) -> Option<UncheckedActivityId> {
         match address {
@@ -0,0 +1,62 @@
+//! Message types that may only be published by the holder of a `PublishToken`.
+
+use super::BroadcastInfo;
+use crate::nut::iac::topic::Topic;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use crate::Error;
+use core::any::{Any, TypeId};
+use core::marker::PhantomData;
+use std::collections::HashSet;
+
+/// Permission to publish messages of type `MSG`, obtained with [`restrict_publish`](fn.restrict_publish.html).
+///
+/// Once the token exists, messages of this type can only be published through it.
+/// Keep it private to the module that owns the message type.
+#[derive(Debug)]
+pub struct PublishToken<MSG> {
+    _msg: PhantomData<fn(MSG)>,
+}
+
+impl<MSG: Any> PublishToken<MSG> {
+    /// Same as [`nuts::publish`](fn.publish.html), for the restricted message type.
+    pub fn publish(&self, msg: MSG) {
+        crate::nut::publish_authorized(msg)
+    }
+}
+
+/// Message types that require a token, empty unless `restrict_publish` has been used
+#[derive(Default)]
+pub(crate) struct PublishRestrictions {
+    restricted: HashSet<TypeId>,
+}
+
+impl Nut {
+    pub(crate) fn restrict_publish<MSG: Any>(&self) -> Result<PublishToken<MSG>, Error> {
+        let mut restrictions = self
+            .publish_restrictions
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        if restrictions.restricted.insert(TypeId::of::<MSG>()) {
+            Ok(PublishToken { _msg: PhantomData })
+        } else {
+            Err(Error::PublishTokenTaken)
+        }
+    }
+    /// Panics if the message type is restricted and the broadcast has not been published with the token
+    pub(crate) fn check_publish_permission(&self, broadcast: &BroadcastInfo) {
+        let restrictions = self
+            .publish_restrictions
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        if restrictions.restricted.is_empty() || broadcast.is_authorized() {
+            return;
+        }
+        if let Topic::PublicMessage(id, _name) = broadcast.topic() {
+            assert!(
+                !restrictions.restricted.contains(&id),
+                "Messages of type {} can only be published with its PublishToken.",
+                _name.name()
+            );
+        }
+    }
+}
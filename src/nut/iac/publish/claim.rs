@@ -0,0 +1,98 @@
+//! Handlers that take the message away from the handlers after them, see `MessageControl`.
+
+use crate::nut::iac::subscription::Subscription;
+use crate::nut::Nut;
+
+/// Controls the delivery of the message that is currently handled.
+///
+/// Request it as parameter of a handler registered with [`ActivityId::subscribe_with`](struct.ActivityId.html#method.subscribe_with).
+/// Like handlers with mutable access to the message, such handlers are called after all handlers that only read it.
+/// Thus, readers always see the message, while the handlers that may consume it take turns.
+///
+/// ### Example
+/// ```rust
+/// use nuts::MessageControl;
+/// struct Click { x: i32, y: i32 }
+/// struct Button { left: i32, right: i32, clicked: bool }
+///
+/// for (left, right) in [(0, 10), (5, 20)] {
+///     let button = nuts::new_activity(Button { left, right, clicked: false });
+///     button.subscribe_with(|button: &mut Button, click: &Click, control: MessageControl| {
+///         if (button.left..button.right).contains(&click.x) {
+///             button.clicked = true;
+///             // Overlapping buttons further down are not clicked
+///             control.consume();
+///         }
+///     });
+/// }
+/// nuts::publish(Click { x: 7, y: 0 });
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MessageControl {
+    _private: (),
+}
+
+/// State of the current delivery, only tracked for topics with handlers that take `MessageControl`
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Delivery {
+    pub(crate) remaining: usize,
+    consumed: bool,
+}
+
+impl MessageControl {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+    /// Number of handlers that are still going to receive the message after the current one, unless it is consumed.
+    ///
+    /// Handlers of inactive activities are included, although they will skip the call.
+    pub fn remaining_subscribers(&self) -> usize {
+        crate::nut::delivery().remaining
+    }
+    /// Stops the delivery of the message, the remaining handlers are not called.
+    pub fn consume(&self) {
+        crate::nut::consume_message()
+    }
+}
+
+impl Nut {
+    /// Calls the handlers in order, until one of them consumes the message.
+    ///
+    /// `count` must be the number of handlers if one of them may consume the message, `None` skips the bookkeeping.
+    /// Returns true if the message has been consumed.
+    pub(super) fn deliver<'a>(
+        &self,
+        subs: impl Iterator<Item = &'a Subscription>,
+        count: Option<usize>,
+        call: &mut impl FnMut(&Subscription),
+    ) -> bool {
+        let mut remaining = match count {
+            Some(count) => count,
+            None => {
+                subs.for_each(call);
+                return false;
+            }
+        };
+        let mut consumed = false;
+        for sub in subs {
+            remaining = remaining.saturating_sub(1);
+            self.delivery.set(Delivery {
+                remaining,
+                consumed: false,
+            });
+            call(sub);
+            consumed = self.delivery.get().consumed;
+            if consumed {
+                break;
+            }
+        }
+        self.delivery.set(Delivery::default());
+        consumed
+    }
+    pub(crate) fn consume_message(&self) {
+        let mut delivery = self.delivery.get();
+        delivery.consumed = true;
+        delivery.remaining = 0;
+        self.delivery.set(delivery);
+    }
+}
@@ -0,0 +1,50 @@
+use super::BroadcastInfo;
+use crate::nut::iac::topic::Topic;
+use crate::nut::Nut;
+use core::any::Any;
+
+/// A set of messages that are published together, created with [`nuts::publish_group`](fn.publish_group.html).
+#[must_use = "Messages are only published after calling publish()."]
+#[derive(Default)]
+pub struct PublishGroup {
+    broadcasts: Vec<BroadcastInfo>,
+}
+
+impl PublishGroup {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a message to the group.
+    ///
+    /// Messages are dispatched in the order they have been added.
+    pub fn with<MSG: Any>(mut self, msg: MSG) -> Self {
+        self.broadcasts
+            .push(BroadcastInfo::global(msg, Topic::public_message::<MSG>()));
+        self
+    }
+    /// Publishes all messages of the group.
+    ///
+    /// The messages are queued next to each other, no other message is dispatched in between.
+    /// Messages published by subscribers of the group are queued after the last message of the group.
+    pub fn publish(self) {
+        crate::nut::publish_group(self.broadcasts);
+    }
+}
+
+impl Nut {
+    pub(crate) fn broadcast_group(&self, broadcasts: Vec<BroadcastInfo>) {
+        for broadcast in &broadcasts {
+            self.check_publish_permission(broadcast);
+        }
+        self.published.set(true);
+        self.queue_initial_enters();
+        for broadcast in broadcasts {
+            let mut broadcast = self.resolve_alias(broadcast);
+            self.map_message(&mut broadcast);
+            self.trace_broadcast(&mut broadcast);
+            self.retain_broadcast(&broadcast);
+            self.deferred_events.push(broadcast.into());
+        }
+        self.catch_up_deferred_to_quiescence();
+    }
+}
@@ -0,0 +1,22 @@
+//! Messages partitioned by a key, see `nuts::publish_keyed`.
+
+use core::any::Any;
+use core::hash::Hash;
+
+/// A message published with [`publish_keyed`](fn.publish_keyed.html), together with its key.
+///
+/// Handlers registered with [`subscribe_keyed`](struct.ActivityId.html#method.subscribe_keyed) receive key and message separately.
+/// It can also be subscribed to as a normal message.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyedMessage<K, MSG> {
+    /// Messages with the same key are delivered in the order they have been published
+    pub key: K,
+    /// The message itself
+    pub msg: MSG,
+}
+
+impl<K: Any + Hash + Eq, MSG: Any> KeyedMessage<K, MSG> {
+    pub(crate) fn new(key: K, msg: MSG) -> Self {
+        Self { key, msg }
+    }
+}
@@ -0,0 +1,46 @@
+use super::BroadcastInfo;
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+use core::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+type MapFn = Rc<dyn Fn(&mut dyn Any)>;
+
+/// In-place transformations of published messages, at most one per message type
+#[derive(Default)]
+pub(crate) struct MessageMappers {
+    mappers: HashMap<TypeId, MapFn>,
+}
+
+impl MessageMappers {
+    /// Sets the mapper of `MSG`, replacing the previous one
+    pub(crate) fn set<MSG: Any>(&mut self, f: impl Fn(&mut MSG) + 'static) {
+        let map = move |msg: &mut dyn Any| f(msg.downcast_mut().expect(IMPOSSIBLE_ERR_MSG));
+        self.mappers.insert(TypeId::of::<MSG>(), Rc::new(map));
+    }
+    fn get(&self, topic: TypeId) -> Option<MapFn> {
+        self.mappers.get(&topic).cloned()
+    }
+}
+
+impl Nut {
+    /// Applies the mapper of the message type to a published message, after aliases have been resolved.
+    ///
+    /// The mapper is cloned out of the registry before it is called, such that it may use Nuts itself.
+    pub(crate) fn map_message(&self, broadcast: &mut BroadcastInfo) {
+        let topic = match broadcast.public_message() {
+            Some((topic, _)) => topic,
+            None => return,
+        };
+        let mapper = self
+            .message_mappers
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .get(topic);
+        if let Some(map) = mapper {
+            if let Some(msg) = broadcast.public_message_mut() {
+                map(msg);
+            }
+        }
+    }
+}
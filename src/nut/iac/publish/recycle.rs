@@ -0,0 +1,124 @@
+use crate::nut::audit::{self, Allocation};
+use crate::nut::IMPOSSIBLE_ERR_MSG;
+use core::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+/// Keeps the boxes of messages after they have been broadcast, a typed arena with one free list per message type.
+///
+/// Publishing another message of the same type reuses a box by overwriting its content.
+/// In a steady state, this removes the heap allocation otherwise required for every published message.
+/// Only types without drop glue are pooled, as others could own resources that would be held back by the pool.
+/// These are `Copy` messages published with `publish_copy` and, with the feature `message-arena`, all such messages published with `publish`.
+///
+/// Messages are still boxed, they are not stored inline in the queue entry.
+/// Storing values of arbitrary types in a byte buffer requires unsafe code, which this crate forbids.
+///
+/// After each drain of the queue, at most `MAX_POOLED_PER_TYPE` boxes are kept per type, the rest of a burst is freed.
+/// `nuts::compact` frees all of them.
+#[derive(Default)]
+pub(crate) struct MessagePool {
+    boxes: HashMap<TypeId, Vec<Box<dyn Any>>, BuildHasherDefault<TypeIdHasher>>,
+    /// Bytes handed out since the queue has last been drained
+    #[cfg(feature = "message-arena")]
+    drain_bytes: usize,
+    #[cfg(feature = "message-arena")]
+    usage: ArenaUsage,
+}
+
+/// Memory used by the message arena, returned by [`arena_usage`](fn.arena_usage.html).
+///
+/// Only available with the feature `message-arena`.
+#[cfg(feature = "message-arena")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ArenaUsage {
+    /// Bytes of messages boxed by the arena while the queue has last been drained, covering a published message and all messages it caused
+    pub last_drain_bytes: usize,
+    /// Bytes of boxes that are currently kept for reuse
+    pub pooled_bytes: usize,
+}
+
+/// Boxes kept per message type once the queue has been drained, enough for cascades of messages of the same type
+const MAX_POOLED_PER_TYPE: usize = 32;
+
+/// A `TypeId` is already a hash, hashing it again only costs time.
+#[derive(Default)]
+struct TypeIdHasher(u64);
+
+impl Hasher for TypeIdHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(*b);
+        }
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.0 ^= i;
+    }
+}
+
+impl MessagePool {
+    pub(crate) fn boxed<MSG: Any>(&mut self, msg: MSG) -> Box<dyn Any> {
+        debug_assert!(!core::mem::needs_drop::<MSG>());
+        #[cfg(feature = "message-arena")]
+        {
+            self.drain_bytes += core::mem::size_of::<MSG>();
+        }
+        match self.boxes.get_mut(&TypeId::of::<MSG>()).and_then(Vec::pop) {
+            Some(mut recycled) => {
+                *recycled.downcast_mut::<MSG>().expect(IMPOSSIBLE_ERR_MSG) = msg;
+                #[cfg(feature = "message-arena")]
+                {
+                    self.usage.pooled_bytes -= core::mem::size_of::<MSG>();
+                }
+                recycled
+            }
+            None => {
+                audit::record_box(Allocation::Message, &msg);
+                Box::new(msg)
+            }
+        }
+    }
+    pub(crate) fn recycle(&mut self, msg: Box<dyn Any>) {
+        #[cfg(feature = "message-arena")]
+        {
+            self.usage.pooled_bytes += core::mem::size_of_val(msg.as_ref());
+        }
+        let boxes = self.boxes.entry(msg.as_ref().type_id()).or_default();
+        if boxes.len() == boxes.capacity() {
+            audit::record(Allocation::Queue);
+        }
+        boxes.push(msg);
+    }
+    /// Frees boxes beyond `MAX_POOLED_PER_TYPE` and completes the usage statistics of a queue drain
+    pub(crate) fn finish_drain(&mut self) {
+        for boxes in self.boxes.values_mut() {
+            for _freed in boxes.drain(MAX_POOLED_PER_TYPE.min(boxes.len())..) {
+                #[cfg(feature = "message-arena")]
+                {
+                    self.usage.pooled_bytes -= core::mem::size_of_val(_freed.as_ref());
+                }
+            }
+        }
+        #[cfg(feature = "message-arena")]
+        {
+            self.usage.last_drain_bytes = std::mem::take(&mut self.drain_bytes);
+        }
+    }
+    /// Frees all pooled boxes, returns how many have been freed
+    pub(crate) fn clear(&mut self) -> usize {
+        #[cfg(feature = "message-arena")]
+        {
+            self.usage.pooled_bytes = 0;
+        }
+        let freed = self.boxes.values().map(Vec::len).sum();
+        self.boxes = Default::default();
+        freed
+    }
+    #[cfg(feature = "message-arena")]
+    pub(crate) fn usage(&self) -> ArenaUsage {
+        self.usage
+    }
+}
@@ -1,51 +1,203 @@
-use std::{future::Future, task::Poll};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    task::{Poll, Waker},
+};
 
 use crate::nut::Nut;
 
 #[derive(Default)]
 pub(crate) struct ResponseTracker {
     slots: Vec<SlotState>,
+    /// Wakers of futures that have been polled before they were done, same indices as `slots`
+    wakers: Vec<Option<Waker>>,
+    /// Set for slots whose future has been dropped before it resolved, same indices as `slots`
+    abandoned: Vec<bool>,
+    /// Indices of available slots, most recently freed at the back
+    free: VecDeque<usize>,
+    /// Indices of slots in `AwaitingQuiescence`
+    awaiting: Vec<usize>,
+    config: ResponseConfig,
+    pending: usize,
+    peak_pending: usize,
+    allocations: u64,
 }
 
 enum SlotState {
     Available,
-    Occupied,
+    Occupied(ResponseMode),
+    /// Broadcast has been dispatched but the queue has not been drained, yet
+    AwaitingQuiescence,
     Done,
 }
 
+/// Defines when the future of a published message resolves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ResponseMode {
+    /// As soon as all subscribers have handled the message
+    Dispatch,
+    /// When no more messages are queued, which means all messages published in reaction to the message have also been handled
+    Quiescent,
+}
+
+/// Tuning of the bookkeeping behind futures returned by [`publish_awaiting_response`](fn.publish_awaiting_response.html) and related functions.
+///
+/// Each pending future occupies a slot, which is reused once the future has resolved or has been dropped.
+/// See [`configure_responses`](fn.configure_responses.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseConfig {
+    /// Number of slots that are allocated upfront
+    pub preallocate: usize,
+    /// Which free slot is handed out next
+    pub reuse: SlotReuse,
+    /// Upper limit of futures pending at the same time, `None` for no limit
+    pub max_pending: Option<usize>,
+}
+
+/// Order in which freed response slots are reused, see [`ResponseConfig`](struct.ResponseConfig.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotReuse {
+    /// The slot freed last is reused first, which keeps the set of slots in use small
+    MostRecent,
+    /// The slot freed first is reused first, which spreads reuse evenly over all slots
+    LeastRecent,
+}
+
+/// Metrics of the futures of published messages, see [`response_stats`](fn.response_stats.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseStats {
+    /// Number of slots allocated, each pending future occupies one
+    pub slots: usize,
+    /// Number of futures that have not resolved, yet
+    pub pending: usize,
+    /// Highest number of futures that have been pending at the same time
+    pub peak_pending: usize,
+    /// Number of futures created in total
+    pub allocations: u64,
+}
+
+impl Default for ResponseConfig {
+    fn default() -> Self {
+        Self {
+            preallocate: 0,
+            reuse: SlotReuse::MostRecent,
+            max_pending: None,
+        }
+    }
+}
+
 pub(crate) struct Slot(usize);
 
-#[allow(clippy::single_match)]
 impl ResponseTracker {
-    pub fn allocate(&mut self) -> Slot {
-        for (i, slot) in self.slots.iter_mut().enumerate() {
-            match slot {
-                SlotState::Available => {
-                    *slot = SlotState::Occupied;
-                    return Slot(i);
-                }
-                _ => {}
+    pub fn configure(&mut self, config: ResponseConfig) {
+        while self.slots.len() < config.preallocate {
+            let i = self.push_slot(SlotState::Available);
+            self.free.push_back(i);
+        }
+        self.config = config;
+    }
+    pub fn stats(&self) -> ResponseStats {
+        ResponseStats {
+            slots: self.slots.len(),
+            pending: self.pending,
+            peak_pending: self.peak_pending,
+            allocations: self.allocations,
+        }
+    }
+    pub fn allocate(&mut self, mode: ResponseMode) -> Slot {
+        if let Some(max) = self.config.max_pending {
+            assert!(
+                self.pending < max,
+                "Too many futures of published messages are pending (limit: {}).",
+                max
+            );
+        }
+        self.pending += 1;
+        self.peak_pending = self.peak_pending.max(self.pending);
+        self.allocations += 1;
+        let free = match self.config.reuse {
+            SlotReuse::MostRecent => self.free.pop_back(),
+            SlotReuse::LeastRecent => self.free.pop_front(),
+        };
+        match free {
+            Some(i) => {
+                self.slots[i] = SlotState::Occupied(mode);
+                Slot(i)
+            }
+            None => Slot(self.push_slot(SlotState::Occupied(mode))),
+        }
+    }
+    fn push_slot(&mut self, state: SlotState) -> usize {
+        self.slots.push(state);
+        self.wakers.push(None);
+        self.abandoned.push(false);
+        self.slots.len() - 1
+    }
+    /// Called after the broadcast has been dispatched to all subscribers.
+    ///
+    /// Returns the waker of the future if it is done now, which must be woken after releasing the tracker.
+    #[must_use]
+    pub fn done(&mut self, slot: &Slot) -> Option<Waker> {
+        match self.slots[slot.0] {
+            SlotState::Occupied(ResponseMode::Quiescent) => {
+                self.slots[slot.0] = SlotState::AwaitingQuiescence;
+                self.awaiting.push(slot.0);
+                None
             }
+            _ => self.resolve(slot.0),
+        }
+    }
+    /// Called when the queue of deferred events has been drained.
+    ///
+    /// Returns the wakers of futures that are done now, which must be woken after releasing the tracker.
+    #[must_use]
+    pub fn quiescent(&mut self) -> Vec<Waker> {
+        let awaiting = std::mem::take(&mut self.awaiting);
+        awaiting
+            .into_iter()
+            .filter_map(|i| self.resolve(i))
+            .collect()
+    }
+    fn resolve(&mut self, index: usize) -> Option<Waker> {
+        if self.abandoned[index] {
+            self.free(index);
+            None
+        } else {
+            self.slots[index] = SlotState::Done;
+            self.wakers[index].take()
         }
-        let i = self.slots.len();
-        self.slots.push(SlotState::Occupied);
-        Slot(i)
     }
-    pub fn done(&mut self, slot: &Slot) {
-        self.slots[slot.0] = SlotState::Done;
+    /// Called when a future is dropped, its slot is freed as soon as nothing refers to it anymore
+    fn abandon(&mut self, index: usize) {
+        match self.slots[index] {
+            SlotState::Available => {}
+            SlotState::Done => self.free(index),
+            SlotState::Occupied(_) | SlotState::AwaitingQuiescence => {
+                self.abandoned[index] = true;
+                self.wakers[index] = None;
+            }
+        }
     }
     fn free(&mut self, index: usize) {
         self.slots[index] = SlotState::Available;
+        self.wakers[index] = None;
+        self.abandoned[index] = false;
+        self.free.push_back(index);
+        self.pending -= 1;
     }
 }
 
 pub struct NutsResponse {
     index: usize,
+    resolved: bool,
 }
 
 impl NutsResponse {
     pub(crate) fn new(slot: &Slot) -> Self {
-        Self { index: slot.0 }
+        Self {
+            index: slot.0,
+            resolved: false,
+        }
     }
 }
 
@@ -53,18 +205,34 @@ impl Future for NutsResponse {
     type Output = ();
 
     fn poll(
-        self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
     ) -> Poll<Self::Output> {
-        Nut::with_response_tracker_mut(|response_tracker| {
-            match response_tracker.slots[self.index] {
+        let index = self.index;
+        let poll = Nut::with_response_tracker_mut(|response_tracker| {
+            match response_tracker.slots[index] {
                 SlotState::Available => panic!("Corrupted futures State"),
-                SlotState::Occupied => Poll::Pending,
+                SlotState::Occupied(_) | SlotState::AwaitingQuiescence => {
+                    response_tracker.wakers[index] = Some(cx.waker().clone());
+                    Poll::Pending
+                }
                 SlotState::Done => {
-                    response_tracker.free(self.index);
+                    response_tracker.free(index);
                     Poll::Ready(())
                 }
             }
-        })
+        });
+        self.resolved = poll.is_ready();
+        poll
+    }
+}
+
+impl Drop for NutsResponse {
+    fn drop(&mut self) {
+        if !self.resolved {
+            let index = self.index;
+            // Futures may outlive the Nut when they are dropped during thread teardown
+            Nut::try_with_response_tracker_mut(|rt| rt.abandon(index));
+        }
     }
 }
@@ -0,0 +1,138 @@
+use super::BroadcastInfo;
+use crate::debug::DebugTypeName;
+use crate::nut::audit::{self, Allocation};
+use crate::nut::iac::subscription::{Subscription, SubscriptionId};
+use crate::nut::{Nut, SharedHandler, IMPOSSIBLE_ERR_MSG};
+use crate::ActivityContainer;
+use crate::ManagedState;
+use core::any::{Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+
+/// Copies of the most recently published messages, for types that have been configured with `retain_last`.
+#[derive(Default)]
+pub(crate) struct RetainedMessages {
+    histories: HashMap<TypeId, History>,
+}
+
+struct History {
+    capacity: usize,
+    clone: CloneFn,
+    messages: VecDeque<Box<dyn Any>>,
+}
+
+/// Type-erased `Clone::clone`, for messages that are known to implement `Clone`
+pub(super) type CloneFn = fn(&dyn Any) -> Box<dyn Any>;
+
+/// Delivers the retained messages to a new subscription
+pub(crate) struct Replay {
+    topic: TypeId,
+    handler: SharedHandler,
+    /// ID of the subscription that replays the messages
+    sub: SubscriptionId,
+    #[allow(dead_code)]
+    type_name: DebugTypeName,
+}
+
+impl RetainedMessages {
+    pub(crate) fn set_capacity<MSG: Any + Clone>(&mut self, capacity: usize) {
+        if capacity == 0 {
+            self.histories.remove(&TypeId::of::<MSG>());
+            return;
+        }
+        let history = self
+            .histories
+            .entry(TypeId::of::<MSG>())
+            .or_insert_with(|| History {
+                capacity,
+                clone: clone_any::<MSG>,
+                messages: VecDeque::new(),
+            });
+        history.capacity = capacity;
+        while history.messages.len() > capacity {
+            history.messages.pop_front();
+        }
+    }
+    /// Stores a copy of the message, if its type is retained
+    pub(crate) fn record(&mut self, topic: TypeId, msg: &dyn Any) {
+        if let Some(history) = self.histories.get_mut(&topic) {
+            if history.messages.len() == history.capacity {
+                history.messages.pop_front();
+            }
+            history.messages.push_back((history.clone)(msg));
+        }
+    }
+    fn copies(&self, topic: TypeId) -> Vec<Box<dyn Any>> {
+        self.histories
+            .get(&topic)
+            .map(|history| {
+                history
+                    .messages
+                    .iter()
+                    .map(|msg| (history.clone)(msg.as_ref()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+pub(super) fn clone_any<MSG: Any + Clone>(msg: &dyn Any) -> Box<dyn Any> {
+    let copy = msg.downcast_ref::<MSG>().expect(IMPOSSIBLE_ERR_MSG).clone();
+    audit::record_box(Allocation::Message, &copy);
+    Box::new(copy)
+}
+
+impl Replay {
+    pub(crate) fn new<A: Any>(topic: TypeId, handler: SharedHandler, sub: SubscriptionId) -> Self {
+        Self {
+            topic,
+            handler,
+            sub,
+            type_name: DebugTypeName::new::<A>(),
+        }
+    }
+}
+
+impl Nut {
+    pub(crate) fn retain_broadcast(&self, broadcast: &BroadcastInfo) {
+        if let Some((topic, msg)) = broadcast.public_message() {
+            self.retained_messages
+                .try_borrow_mut()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .record(topic, msg);
+        }
+    }
+    /// only access after locking with executing flag
+    pub(crate) fn unchecked_replay(&self, replay: Replay) {
+        let messages = self
+            .retained_messages
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .copies(replay.topic);
+        let handler = replay.handler;
+        audit::record_box(Allocation::Closure, &handler);
+        let sub = Subscription::new(
+            Box::new(
+                move |activities: &mut ActivityContainer, managed_state: &mut ManagedState| {
+                    handler(activities, managed_state)
+                },
+            ),
+            replay.type_name,
+            replay.sub,
+        );
+        let mut managed_state = self.managed_state.borrow_mut();
+        for msg in messages {
+            managed_state.set_broadcast(msg);
+            self.call_subscriber(&sub, &mut managed_state);
+            managed_state.clear_broadcast();
+        }
+        #[cfg(debug_assertions)]
+        self.active_activity_name.set(None);
+    }
+}
+
+#[cfg(debug_assertions)]
+impl std::fmt::Debug for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Replaying retained messages to {:?}", self.type_name)
+    }
+}
@@ -0,0 +1,37 @@
+//! Messages that are only visible inside a scope, see `nuts::publish_scoped`.
+
+use core::any::Any;
+use core::marker::PhantomData;
+
+/// Key of a message scope, implemented by a type that is defined for this purpose.
+///
+/// Messages published with [`publish_scoped`](fn.publish_scoped.html) only reach handlers registered with [`subscribe_scoped`](struct.ActivityId.html#method.subscribe_scoped) for the same scope.
+/// They never reach handlers of the same message type in another scope or without scope, and vice versa.
+///
+/// Only code that can name the token type can use the scope.
+/// A crate keeps its messages to itself with a private token type, and shares them explicitly by exporting it.
+///
+/// ### Example
+/// ```rust
+/// // Private to the plugin, no other crate can publish or subscribe in this scope
+/// struct PluginScope;
+/// impl nuts::ScopeToken for PluginScope {}
+/// ```
+pub trait ScopeToken: Any {}
+
+/// A message published inside the scope `S`.
+///
+/// Not public, the scope can only be entered through `publish_scoped` and `subscribe_scoped`.
+pub(crate) struct ScopedMessage<S, MSG> {
+    pub(crate) msg: MSG,
+    _scope: PhantomData<fn() -> S>,
+}
+
+impl<S: ScopeToken, MSG: Any> ScopedMessage<S, MSG> {
+    pub(crate) fn new(msg: MSG) -> Self {
+        Self {
+            msg,
+            _scope: PhantomData,
+        }
+    }
+}
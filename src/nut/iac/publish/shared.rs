@@ -0,0 +1,57 @@
+//! Large payloads shared between all handlers behind a reference-counted pointer, see `nuts::publish_shared`.
+
+use core::any::Any;
+use core::marker::PhantomData;
+use std::ops::Deref;
+
+/// A payload behind a reference-counted pointer `P`, such as `Rc<[u8]>` or `Arc<[u8]>`, published with [`publish_shared`](fn.publish_shared.html).
+///
+/// The tag `T` selects the topic, two shared payloads are the same topic only if both tag and pointer type are equal.
+/// Use a tag per kind of content, for example one for image buffers and one for audio buffers, which are then delivered independently although both are `Rc<[u8]>`.
+///
+/// Handlers registered with [`subscribe_shared`](struct.ActivityId.html#method.subscribe_shared) read the payload in place.
+/// Handlers registered with [`subscribe_shared_owned`](struct.ActivityId.html#method.subscribe_shared_owned) receive a clone of the pointer.
+/// Cloning the message itself also only clones the pointer, the payload is never copied.
+pub struct SharedPayload<T, P> {
+    payload: P,
+    _tag: PhantomData<fn() -> T>,
+}
+
+impl<T: Any, P: Deref + Clone + Any> SharedPayload<T, P> {
+    pub(crate) fn new(payload: P) -> Self {
+        Self {
+            payload,
+            _tag: PhantomData,
+        }
+    }
+    /// Another pointer to the same payload.
+    pub fn share(&self) -> P {
+        self.payload.clone()
+    }
+}
+
+impl<T, P: Deref> Deref for SharedPayload<T, P> {
+    type Target = P::Target;
+    fn deref(&self) -> &P::Target {
+        &self.payload
+    }
+}
+
+// Implemented manually, derive would require the tag to be `Clone`.
+impl<T, P: Clone> Clone for SharedPayload<T, P> {
+    fn clone(&self) -> Self {
+        Self {
+            payload: self.payload.clone(),
+            _tag: PhantomData,
+        }
+    }
+}
+
+impl<T, P: std::fmt::Debug> std::fmt::Debug for SharedPayload<T, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedPayload")
+            .field("tag", &std::any::type_name::<T>())
+            .field("payload", &self.payload)
+            .finish()
+    }
+}
@@ -0,0 +1,39 @@
+use core::any::Any;
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_sink::Sink;
+
+/// Publishes every item it receives, as if `nuts::publish` was called on it.
+///
+/// Publishing never blocks and never fails, hence the sink is always ready.
+pub(crate) struct PublishSink<MSG> {
+    phantom: PhantomData<fn(MSG)>,
+}
+
+impl<MSG> PublishSink<MSG> {
+    pub(crate) fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<MSG: Any> Sink<MSG> for PublishSink<MSG> {
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+    fn start_send(self: Pin<&mut Self>, item: MSG) -> Result<(), Self::Error> {
+        crate::nut::publish_custom(item);
+        Ok(())
+    }
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
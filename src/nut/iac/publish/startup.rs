@@ -0,0 +1,91 @@
+use super::BroadcastInfo;
+use crate::nut::iac::topic::Topic;
+use crate::nut::{exec::Deferred, Nut, IMPOSSIBLE_ERR_MSG};
+use std::collections::VecDeque;
+
+/// Published messages that had no subscriber while the application was starting up.
+///
+/// Buffering is enabled with `buffer_startup_messages` and ends with `start`.
+#[derive(Default)]
+pub(crate) struct StartupBuffer {
+    active: bool,
+    capacity: usize,
+    messages: VecDeque<BroadcastInfo>,
+}
+
+impl StartupBuffer {
+    pub(crate) fn enable(&mut self, capacity: usize) {
+        self.active = capacity > 0;
+        self.capacity = capacity;
+        while self.messages.len() > capacity {
+            self.messages.pop_front();
+        }
+    }
+    /// Ends buffering and returns all messages that never found a subscriber
+    pub(crate) fn finish(&mut self) -> VecDeque<BroadcastInfo> {
+        self.active = false;
+        std::mem::take(&mut self.messages)
+    }
+    fn take_topic(&mut self, topic: Topic) -> Vec<BroadcastInfo> {
+        let mut taken = vec![];
+        let mut i = 0;
+        while i < self.messages.len() {
+            if self.messages[i].topic == topic {
+                taken.push(self.messages.remove(i).expect(IMPOSSIBLE_ERR_MSG));
+            } else {
+                i += 1;
+            }
+        }
+        taken
+    }
+}
+
+impl Nut {
+    /// Keeps the broadcast for later if it is a published message that has no subscriber, yet, and startup buffering is active.
+    /// Otherwise, the broadcast is returned for immediate dispatch.
+    ///
+    /// only access after locking with executing flag
+    pub(crate) fn buffer_at_startup(&self, broadcast: BroadcastInfo) -> Option<BroadcastInfo> {
+        let mut buffer = self
+            .startup_buffer
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        if !buffer.active || broadcast.public_message().is_none() {
+            return Some(broadcast);
+        }
+        let has_subscriber = self
+            .subscriptions
+            .get()
+            .get(&broadcast.topic)
+            .is_some_and(|handlers| handlers.shared_subscriptions().next().is_some());
+        if has_subscriber {
+            return Some(broadcast);
+        }
+        if buffer.messages.len() == buffer.capacity {
+            let _dropped = buffer.messages.pop_front();
+            #[cfg(feature = "verbose-debug-log")]
+            #[cfg(debug_assertions)]
+            debug_print!(
+                "Startup buffer is full, dropping {:?}",
+                _dropped.expect(IMPOSSIBLE_ERR_MSG)
+            );
+        }
+        buffer.messages.push_back(broadcast);
+        None
+    }
+    /// Queues all buffered messages of the topic, to be called after the first subscription has been registered.
+    ///
+    /// Returns true if any message has been queued.
+    pub(crate) fn release_startup_messages(&self, topic: Topic) -> bool {
+        let released = self
+            .startup_buffer
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .take_topic(topic);
+        let any = !released.is_empty();
+        for broadcast in released {
+            self.deferred_events.push(Deferred::Broadcast(broadcast));
+        }
+        any
+    }
+}
@@ -1,6 +1,17 @@
+mod lint;
+pub(crate) mod setup;
+mod table;
+
+pub use lint::CaptureWarning;
+
+pub use table::{SubscriptionEntry, SubscriptionTable};
+
+#[cfg(feature = "perf")]
+use super::profile::{HandlerProfile, HandlerReport};
 use super::{managed_state::ManagedState, topic::Topic};
 use crate::{
     debug::DebugTypeName,
+    nut::audit::{self, Allocation},
     nut::{exec::Deferred, Handler, Nut, IMPOSSIBLE_ERR_MSG},
     ActivityId, UncheckedActivityId,
 };
@@ -10,59 +21,332 @@ use std::{
     cell::RefCell,
     collections::HashMap,
     ops::{Index, IndexMut},
+    time::Duration,
 };
 
 #[derive(Default)]
 pub(crate) struct Subscriptions {
     subscriptions: RefCell<HashMap<Topic, SubscriptionContainer>>,
+    /// Last assigned subscription ID, IDs are assigned in increasing order
+    last_id: core::cell::Cell<u64>,
+    /// Set while handlers are registered that are removed together, see `Subscriptions::grouped`
+    group: core::cell::Cell<Option<SubscriptionId>>,
+    /// Number of broadcasts dispatched so far, to find subscriptions that are never called
+    #[cfg(debug_assertions)]
+    broadcasts: core::cell::Cell<u64>,
+}
+
+/// Handle of a subscription, returned by [`ActivityId::subscribe`](struct.ActivityId.html#method.subscribe) and its variants.
+///
+/// Pass it to [`nuts::unsubscribe`](fn.unsubscribe.html) to remove the handler again.
+/// A subscription that consists of several internal handlers, such as a buffered or a digest subscription, is removed as a whole.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SubscriptionId(u64);
+
+/// Runtime statistics of a subscription, returned by [`SubscriptionId::stats`](struct.SubscriptionId.html#method.stats).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SubscriptionStats {
+    /// Number of messages or events delivered to the handler
+    pub calls: u64,
+    /// Time of the last delivery, measured by the [clock](fn.set_clock.html) of Nuts.
+    ///
+    /// Only recorded if enabled with [`ConfigBuilder::record_call_times`](struct.ConfigBuilder.html#method.record_call_times), otherwise always `None`.
+    pub last_call: Option<Duration>,
+}
+
+impl SubscriptionId {
+    /// Statistics of the subscription, to verify in application logic or tests that specific wiring is alive.
+    ///
+    /// Deliveries count even if the handler skips the call, because its activity is inactive or a [subscription filter](struct.SubscriptionFilter.html) rejects it.
+    /// For a subscription that consists of several internal handlers, the calls of all of them are added up.
+    /// Messages replayed with [`retain_last`](fn.retain_last.html) are not counted.
+    ///
+    /// Returns `None` if the subscription has been removed, or if it has been registered inside a handler and is still queued.
+    ///
+    /// ### Example
+    /// ```rust
+    /// struct Ping;
+    /// let id = nuts::subscribe(|_: &Ping| {});
+    /// assert_eq!(0, id.stats().expect("subscribed").calls);
+    /// nuts::publish(Ping);
+    /// assert_eq!(1, id.stats().expect("subscribed").calls);
+    /// nuts::unsubscribe(id);
+    /// assert!(id.stats().is_none());
+    /// ```
+    pub fn stats(self) -> Option<SubscriptionStats> {
+        crate::nut::subscription_stats(self)
+    }
+}
+
+/// Position of an activity among all activities subscribed to the same message type, see [`subscribe_with_priority`](struct.ActivityId.html#method.subscribe_with_priority).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubscriptionPriority {
+    /// Called before all other activities, such as activities that sanitize input for the others
+    High,
+    /// The default, equivalent to a dispatch weight of zero
+    Normal,
+    /// Called after all other activities, such as activities that only observe the outcome
+    Low,
+}
+
+impl SubscriptionPriority {
+    /// The dispatch weight that implements the priority, `High` and `Low` are outside the range of weights set with `set_dispatch_weight`
+    pub(crate) fn weight(self) -> i64 {
+        match self {
+            Self::High => i64::from(i32::MIN) - 1,
+            Self::Normal => 0,
+            Self::Low => i64::from(i32::MAX) + 1,
+        }
+    }
+}
+
+/// A subscription that has not been called once, see [`unused_subscriptions`](fn.unused_subscriptions.html).
+#[derive(Clone, Debug)]
+pub struct UnusedSubscription {
+    /// Type name of the subscribing activity
+    pub activity: &'static str,
+    /// Type name of the message or name of the lifecycle event
+    pub topic: &'static str,
+    /// Number of broadcasts that have been dispatched since the subscription has been registered
+    pub broadcasts: u64,
 }
 
 /// Handlers stored per Activity
 #[derive(Default)]
 pub(crate) struct SubscriptionContainer {
     data: HashMap<usize, ActivityTopicSubscriptions>,
+    /// Set once a handler that may consume the message has been added, see `MessageControl`
+    claimable: bool,
+    /// Activities with a dispatch weight other than zero, see `ActivityId::set_dispatch_weight`
+    weights: HashMap<usize, i64>,
 }
 
 /// Handlers per type per activity
 #[derive(Default)]
 pub(crate) struct ActivityTopicSubscriptions {
+    /// Handlers of `ParallelSafe` activities, called before all other handlers
+    parallel: Vec<Subscription>,
+    /// Handlers that only read the message
     shared: Vec<Subscription>,
+    /// Handlers with mutable access to the message, called after all readers
+    mutating: Vec<Subscription>,
     private: Option<Subscription>,
 }
 
+/// How a handler accesses the message, which determines when it is called
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Access {
+    /// Like `Read`, for handlers that neither access a domain nor depend on the order in which they are called
+    Parallel,
+    Read,
+    Write,
+    /// Like `Write`, the handler may consume the message such that the remaining handlers are skipped
+    Claim,
+}
+
 pub(crate) struct Subscription {
     pub(crate) handler: Handler,
+    pub(crate) id: SubscriptionId,
     #[allow(dead_code)]
     pub(crate) type_name: DebugTypeName,
+    stats: core::cell::Cell<SubscriptionStats>,
+    #[cfg(feature = "perf")]
+    pub(crate) profile: HandlerProfile,
+    /// Whether the handler has mutable access to the message
+    #[cfg(feature = "mutation-audit")]
+    pub(crate) mutates: bool,
+    /// Value of the broadcast counter when the subscription has been registered, `None` once it has been called
+    #[cfg(debug_assertions)]
+    unused_since: core::cell::Cell<Option<u64>>,
 }
 
+type OnDeleteSimple = Box<dyn FnOnce(Box<dyn Any>)>;
+type OnDeleteWithDomain = Box<dyn FnOnce(Box<dyn Any>, &mut ManagedState)>;
+
 pub(crate) enum OnDelete {
     None,
-    Simple(Box<dyn FnOnce(Box<dyn Any>)>),
-    WithDomain(Box<dyn FnOnce(Box<dyn Any>, &mut ManagedState)>),
+    Simple(OnDeleteSimple),
+    WithDomain(OnDeleteWithDomain),
 }
 
 impl Nut {
+    #[inline]
     pub(crate) fn push_closure<A: 'static>(
         &self,
         topic: Topic,
         id: ActivityId<A>,
         closure: Handler,
-    ) {
-        let type_name = DebugTypeName::new::<A>();
+    ) -> SubscriptionId {
+        self.push_handler(
+            topic,
+            id.into(),
+            closure,
+            DebugTypeName::new::<A>(),
+            Access::Read,
+            self.subscriptions.next_id(),
+        )
+    }
+    /// Same as `push_closure` for handlers that modify the message, which are called after all other handlers.
+    #[inline]
+    pub(crate) fn push_mutating_closure<A: 'static>(
+        &self,
+        topic: Topic,
+        id: ActivityId<A>,
+        closure: Handler,
+    ) -> SubscriptionId {
+        self.push_handler(
+            topic,
+            id.into(),
+            closure,
+            DebugTypeName::new::<A>(),
+            Access::Write,
+            self.subscriptions.next_id(),
+        )
+    }
+    /// Non-generic part of `push_closure`, shared by all activity types.
+    pub(crate) fn push_handler(
+        &self,
+        topic: Topic,
+        id: UncheckedActivityId,
+        closure: Handler,
+        type_name: DebugTypeName,
+        access: Access,
+        sub_id: SubscriptionId,
+    ) -> SubscriptionId {
+        if let Err(err) = self.check_access(topic, access) {
+            panic!("Cannot register handler: {}", err);
+        }
+        audit::record_box(Allocation::Closure, closure.as_ref());
         if self.quiescent() {
             self.subscriptions
-                .force_push_closure(topic, id, closure, type_name);
+                .force_push_closure(topic, id, closure, type_name, access, sub_id);
+            if self.release_startup_messages(topic) {
+                self.catch_up_deferred_to_quiescence();
+            }
         } else {
-            let sub = NewSubscription::new(topic, id, closure, type_name);
-            self.deferred_events.push(Deferred::Subscription(sub));
+            let sub = NewSubscription::new(topic, id, closure, type_name, access, sub_id);
+            self.defer_bookkeeping(Deferred::Subscription(sub));
         }
+        sub_id
     }
 }
 
 impl Subscriptions {
     pub(crate) fn exec_new_subscription(&self, sub: NewSubscription) {
-        self.force_push_closure(sub.topic, sub.id, sub.closure, sub.type_name);
+        self.force_push_closure(
+            sub.topic,
+            sub.id,
+            sub.closure,
+            sub.type_name,
+            sub.access,
+            sub.sub_id,
+        );
+    }
+    /// ID for the next subscription, or the ID of the group if handlers are registered as a group
+    pub(crate) fn next_id(&self) -> SubscriptionId {
+        if let Some(group) = self.group.get() {
+            return group;
+        }
+        self.last_id.set(self.last_id.get() + 1);
+        SubscriptionId(self.last_id.get())
+    }
+    /// Registers all handlers added by `f` under the same ID, such that they are removed together.
+    ///
+    /// Inside another group, the handlers join the outer group.
+    pub(crate) fn grouped(&self, f: impl FnOnce()) -> SubscriptionId {
+        if let Some(group) = self.group.get() {
+            f();
+            return group;
+        }
+        let group = self.next_id();
+        self.group.set(Some(group));
+        // Reset even if `f` panics, later subscriptions must not join the group
+        struct Reset<'a>(&'a core::cell::Cell<Option<SubscriptionId>>);
+        impl Drop for Reset<'_> {
+            fn drop(&mut self) {
+                self.0.set(None);
+            }
+        }
+        let _reset = Reset(&self.group);
+        f();
+        group
+    }
+    /// Drops all handlers for which `keep` returns false, called with the activity index and the subscription ID.
+    ///
+    /// Must not be called during a broadcast.
+    pub(crate) fn retain(&self, keep: impl Fn(usize, SubscriptionId) -> bool) {
+        let mut subs = self
+            .subscriptions
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        for container in subs.values_mut() {
+            for (&index, activity) in container.data.iter_mut() {
+                activity.parallel.retain(|sub| keep(index, sub.id));
+                activity.shared.retain(|sub| keep(index, sub.id));
+                activity.mutating.retain(|sub| keep(index, sub.id));
+                if activity
+                    .private
+                    .as_ref()
+                    .is_some_and(|sub| !keep(index, sub.id))
+                {
+                    activity.private = None;
+                }
+            }
+            container.data.retain(|_, activity| activity.len() > 0);
+        }
+    }
+    /// True if any handler modifies messages of the public topic or owns messages of the private topic
+    pub(crate) fn has_mutable_access(&self, public: &Topic, private: &Topic) -> bool {
+        let subs = self.subscriptions.try_borrow().expect(IMPOSSIBLE_ERR_MSG);
+        let modified = subs.get(public).is_some_and(|container| {
+            container
+                .data
+                .values()
+                .any(|activity| !activity.mutating.is_empty())
+        });
+        let owned = subs.get(private).is_some_and(|container| {
+            container
+                .data
+                .values()
+                .any(|activity| activity.private.is_some())
+        });
+        modified || owned
+    }
+    /// Drops all handlers and dispatch weights of the activity in the slot, must not be called during a broadcast
+    pub(crate) fn forget_activity(&self, index: usize) {
+        self.retain(|activity, _| activity != index);
+        let mut subs = self
+            .subscriptions
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        for container in subs.values_mut() {
+            container.weights.remove(&index);
+        }
+    }
+    /// Sets the dispatch weight of the activity for the topic, must not be called during a broadcast
+    pub(crate) fn set_weight(&self, topic: Topic, id: UncheckedActivityId, weight: i64) {
+        let mut subs = self
+            .subscriptions
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        let weights = &mut subs.entry(topic).or_default().weights;
+        if weight == 0 {
+            weights.remove(&id.index);
+        } else {
+            weights.insert(id.index, weight);
+        }
+    }
+    pub(crate) fn remove_private(&self, topic: Topic, id: UncheckedActivityId) {
+        let mut subs = self
+            .subscriptions
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        if let Some(activity) = subs
+            .get_mut(&topic)
+            .and_then(|container| container.data.get_mut(&id.index))
+        {
+            activity.private = None;
+        }
     }
     fn force_push_closure(
         &self,
@@ -70,6 +354,8 @@ impl Subscriptions {
         id: impl Into<UncheckedActivityId>,
         handler: Handler,
         type_name: DebugTypeName,
+        access: Access,
+        sub_id: SubscriptionId,
     ) {
         let id = id.into();
         let private = topic.unqiue_per_activity();
@@ -77,24 +363,211 @@ impl Subscriptions {
             .subscriptions
             .try_borrow_mut()
             .expect(IMPOSSIBLE_ERR_MSG);
-        let subs_per_activity = &mut subs.entry(topic).or_insert_with(Default::default)[id];
+        let container = subs.entry(topic).or_default();
+        container.claimable |= access == Access::Claim;
+        let subs_per_activity = &mut container[id];
 
-        if private {
-            subs_per_activity.private = Some(Subscription { handler, type_name });
-        } else {
-            subs_per_activity
-                .shared
-                .push(Subscription { handler, type_name });
+        #[allow(unused_mut)]
+        let mut sub = Subscription::new(handler, type_name, sub_id);
+        #[cfg(feature = "mutation-audit")]
+        {
+            sub.mutates = !private && !matches!(access, Access::Parallel | Access::Read);
+        }
+        #[cfg(debug_assertions)]
+        sub.unused_since.set(Some(self.broadcasts.get()));
+        match access {
+            _ if private => subs_per_activity.private = Some(sub),
+            Access::Parallel => subs_per_activity.parallel.push(sub),
+            Access::Read => subs_per_activity.shared.push(sub),
+            Access::Write | Access::Claim => subs_per_activity.mutating.push(sub),
         }
     }
-    pub(crate) fn get(&self) -> Ref<HashMap<Topic, SubscriptionContainer>> {
+    #[cfg(feature = "perf")]
+    pub(crate) fn profiling_report(&self) -> Vec<HandlerReport> {
+        let subs = self.subscriptions.try_borrow().expect(IMPOSSIBLE_ERR_MSG);
+        subs.iter()
+            .flat_map(|(topic, container)| {
+                container
+                    .data
+                    .values()
+                    .flat_map(|subs| subs.all())
+                    .map(move |sub| sub.profile.report(sub.type_name.name(), topic.name()))
+            })
+            .collect()
+    }
+    /// Combined statistics of all handlers with the ID, `None` if there are none
+    pub(crate) fn stats(&self, id: SubscriptionId) -> Option<SubscriptionStats> {
+        let subs = self.subscriptions.try_borrow().expect(IMPOSSIBLE_ERR_MSG);
+        subs.values()
+            .flat_map(|container| container.data.values())
+            .flat_map(|subs| subs.all())
+            .filter(|sub| sub.id == id)
+            .map(|sub| sub.stats.get())
+            .reduce(|a, b| SubscriptionStats {
+                calls: a.calls + b.calls,
+                last_call: a.last_call.max(b.last_call),
+            })
+    }
+    #[cfg(debug_assertions)]
+    pub(crate) fn count_broadcast(&self) {
+        self.broadcasts.set(self.broadcasts.get() + 1);
+    }
+    #[cfg(debug_assertions)]
+    pub(crate) fn unused(&self, min_broadcasts: u64) -> Vec<UnusedSubscription> {
+        let now = self.broadcasts.get();
+        let subs = self.subscriptions.try_borrow().expect(IMPOSSIBLE_ERR_MSG);
+        subs.iter()
+            .flat_map(|(topic, container)| {
+                container
+                    .data
+                    .values()
+                    .flat_map(|subs| subs.all())
+                    .filter_map(move |sub| {
+                        let since = sub.unused_since.get()?;
+                        Some(UnusedSubscription {
+                            activity: sub.type_name.name(),
+                            topic: topic.name(),
+                            broadcasts: now - since,
+                        })
+                    })
+            })
+            .filter(|unused| unused.broadcasts >= min_broadcasts)
+            .collect()
+    }
+    /// Number of handlers registered for a topic.
+    ///
+    /// Subscriptions are only borrowed mutably outside of broadcasts, hence this can also be called from inside handlers.
+    pub(crate) fn count(&self, topic: &Topic) -> usize {
+        self.subscriptions
+            .try_borrow()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .get(topic)
+            .map_or(0, |container| container.shared_subscriptions().count())
+    }
+    pub(crate) fn get(&self) -> Ref<'_, HashMap<Topic, SubscriptionContainer>> {
         self.subscriptions.borrow()
     }
+    /// Drops the handlers of deleted activities and topics without handlers, then releases unused capacity.
+    ///
+    /// Returns the number of dropped handlers and the number of released table entries.
+    pub(crate) fn compact(&self, is_deleted: impl Fn(usize) -> bool) -> (usize, usize) {
+        let mut subs = self
+            .subscriptions
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG);
+        let mut dropped = 0;
+        let mut released = 0;
+        for container in subs.values_mut() {
+            container.weights.retain(|&index, _| !is_deleted(index));
+            container.data.retain(|&index, activity| {
+                let keep = !is_deleted(index) && activity.len() > 0;
+                if !keep {
+                    dropped += activity.len();
+                }
+                keep
+            });
+            let before = container.data.capacity();
+            container.data.shrink_to_fit();
+            released += before - container.data.capacity();
+        }
+        subs.retain(|_, container| !container.data.is_empty() || container.is_weighted());
+        let before = subs.capacity();
+        subs.shrink_to_fit();
+        released += before - subs.capacity();
+        (dropped, released)
+    }
+}
+
+impl Subscription {
+    pub(crate) fn new(handler: Handler, type_name: DebugTypeName, id: SubscriptionId) -> Self {
+        Self {
+            handler,
+            id,
+            type_name,
+            stats: Default::default(),
+            #[cfg(feature = "perf")]
+            profile: Default::default(),
+            #[cfg(feature = "mutation-audit")]
+            mutates: false,
+            #[cfg(debug_assertions)]
+            unused_since: Default::default(),
+        }
+    }
+    /// Records that the handler is being called, at time `now` if call times are recorded
+    pub(crate) fn mark_used(&self, now: Option<Duration>) {
+        #[cfg(debug_assertions)]
+        self.unused_since.set(None);
+        let stats = self.stats.get();
+        self.stats.set(SubscriptionStats {
+            calls: stats.calls + 1,
+            last_call: now.or(stats.last_call),
+        });
+    }
 }
 
 impl SubscriptionContainer {
+    /// All handlers that are not private, parallel-safe handlers come first, followed by the other handlers that only read the message.
     pub fn shared_subscriptions(&self) -> impl Iterator<Item = &Subscription> {
-        self.data.values().flat_map(|f| f.shared.iter())
+        self.shared_subscriptions_where(|_| true)
+    }
+    /// Same as `shared_subscriptions` but only of activities accepted by the given filter on their index
+    pub fn shared_subscriptions_where<'a>(
+        &'a self,
+        accept: impl Fn(usize) -> bool + Copy + 'a,
+    ) -> impl Iterator<Item = &'a Subscription> {
+        let parallel = self
+            .data
+            .iter()
+            .filter(move |(activity, _)| accept(**activity))
+            .flat_map(|(_, f)| f.parallel.iter());
+        let readers = self
+            .data
+            .iter()
+            .filter(move |(activity, _)| accept(**activity))
+            .flat_map(|(_, f)| f.shared.iter());
+        let writers = self
+            .data
+            .iter()
+            .filter(move |(activity, _)| accept(**activity))
+            .flat_map(|(_, f)| f.mutating.iter());
+        parallel.chain(readers).chain(writers)
+    }
+    /// Same as `shared_subscriptions_where` but the activities are visited in the order of their weights, and then in the order they have been created
+    pub fn shared_subscriptions_in_creation_order<'a>(
+        &'a self,
+        accept: impl Fn(usize) -> bool + Copy + 'a,
+    ) -> impl Iterator<Item = &'a Subscription> {
+        let mut activities: Vec<_> = self
+            .data
+            .iter()
+            .filter(move |(activity, _)| accept(**activity))
+            .collect();
+        activities.sort_unstable_by_key(|(activity, _)| (self.weight(**activity), **activity));
+        let parallel = activities
+            .clone()
+            .into_iter()
+            .flat_map(|(_, f)| f.parallel.iter());
+        let readers = activities
+            .clone()
+            .into_iter()
+            .flat_map(|(_, f)| f.shared.iter());
+        let writers = activities.into_iter().flat_map(|(_, f)| f.mutating.iter());
+        parallel.chain(readers).chain(writers)
+    }
+    /// True if a handler may consume the message, which requires counting the handlers before the delivery
+    pub fn is_claimable(&self) -> bool {
+        self.claimable
+    }
+    /// True if any activity has a dispatch weight, which requires sorting the activities before the delivery
+    pub fn is_weighted(&self) -> bool {
+        !self.weights.is_empty()
+    }
+    fn weight(&self, activity: usize) -> i64 {
+        self.weights.get(&activity).copied().unwrap_or_default()
+    }
+    /// Indices of all activities with handlers
+    pub fn activities(&self) -> impl Iterator<Item = usize> + '_ {
+        self.data.keys().copied()
     }
     pub fn shared_subscriptions_of_single_activity(
         &self,
@@ -103,13 +576,25 @@ impl SubscriptionContainer {
         self.data
             .get(&id.index)
             .into_iter()
-            .flat_map(|f| f.shared.iter())
+            .flat_map(|f| f.parallel.iter().chain(&f.shared).chain(&f.mutating))
     }
     pub fn private_subscription(&self, id: UncheckedActivityId) -> Option<&Subscription> {
-        self.data
-            .get(&id.index)
-            .map(|f| f.private.as_ref())
-            .flatten()
+        self.data.get(&id.index).and_then(|f| f.private.as_ref())
+    }
+}
+impl ActivityTopicSubscriptions {
+    fn len(&self) -> usize {
+        self.parallel.len()
+            + self.shared.len()
+            + self.mutating.len()
+            + usize::from(self.private.is_some())
+    }
+    fn all(&self) -> impl Iterator<Item = &Subscription> {
+        self.parallel
+            .iter()
+            .chain(self.shared.iter())
+            .chain(self.mutating.iter())
+            .chain(self.private.iter())
     }
 }
 impl Index<UncheckedActivityId> for SubscriptionContainer {
@@ -120,7 +605,7 @@ impl Index<UncheckedActivityId> for SubscriptionContainer {
 }
 impl IndexMut<UncheckedActivityId> for SubscriptionContainer {
     fn index_mut(&mut self, id: UncheckedActivityId) -> &mut Self::Output {
-        self.data.entry(id.index).or_insert_with(Default::default)
+        self.data.entry(id.index).or_default()
     }
 }
 
@@ -129,20 +614,29 @@ pub(crate) struct NewSubscription {
     id: UncheckedActivityId,
     closure: Handler,
     type_name: DebugTypeName,
+    access: Access,
+    sub_id: SubscriptionId,
 }
 
 impl NewSubscription {
+    pub(crate) fn topic(&self) -> Topic {
+        self.topic
+    }
     fn new(
         topic: Topic,
         id: impl Into<UncheckedActivityId>,
         closure: Handler,
         type_name: DebugTypeName,
+        access: Access,
+        sub_id: SubscriptionId,
     ) -> Self {
         Self {
             topic,
             id: id.into(),
             closure,
             type_name,
+            access,
+            sub_id,
         }
     }
 }
@@ -0,0 +1,51 @@
+//! Setup-time diagnostics for handlers that use the IDs of other activities, see `ActivityId::subscribe_domained_capturing`.
+
+#[cfg(debug_assertions)]
+use crate::debug::DebugTypeName;
+#[cfg(debug_assertions)]
+use crate::nut::{Nut, IMPOSSIBLE_ERR_MSG};
+#[cfg(debug_assertions)]
+use crate::DomainId;
+
+/// A handler that uses the ID of an activity in another domain, found by [`capture_warnings`](fn.capture_warnings.html).
+///
+/// The handler receives the domain of its own activity, not the domain of the captured activity.
+/// Objects that are only stored in the domain of the captured activity are missing there, hence `DomainState::get` panics once the handler is called.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaptureWarning {
+    /// Type name of the subscribing activity
+    pub activity: &'static str,
+    /// Type name of the activity whose ID is used by the handler
+    pub captured: &'static str,
+    /// Type name of the message
+    pub topic: &'static str,
+}
+
+#[cfg(debug_assertions)]
+impl Nut {
+    /// Records a warning if the captured activity is not in the domain of the subscribing activity
+    pub(crate) fn lint_capture(
+        &self,
+        activity: (DomainId, DebugTypeName),
+        captured: (DomainId, DebugTypeName),
+        topic: &'static str,
+    ) {
+        if activity.0 == captured.0 {
+            return;
+        }
+        debug_print!(
+            "Handler of {} for {} uses the ID of {}, which is in another domain",
+            activity.1.name(),
+            topic,
+            captured.1.name()
+        );
+        self.capture_warnings
+            .try_borrow_mut()
+            .expect(IMPOSSIBLE_ERR_MSG)
+            .push(CaptureWarning {
+                activity: activity.1.name(),
+                captured: captured.1.name(),
+                topic,
+            });
+    }
+}
@@ -0,0 +1,67 @@
+//! Rollback of subscriptions registered during a failed setup, see `ActivityId::try_setup`.
+
+use super::{SubscriptionId, Subscriptions};
+use crate::nut::Nut;
+use crate::UncheckedActivityId;
+use core::sync::atomic::Ordering;
+
+/// First subscription ID assigned after the setup started
+pub(crate) struct SetupCheckpoint {
+    id: UncheckedActivityId,
+    since: SubscriptionId,
+}
+
+/// Removes all subscriptions of the activity added since its creation, unless the setup is committed.
+pub(crate) struct SetupGuard {
+    checkpoint: Option<SetupCheckpoint>,
+}
+
+impl SetupGuard {
+    pub(crate) fn new(checkpoint: SetupCheckpoint) -> Self {
+        Self {
+            checkpoint: Some(checkpoint),
+        }
+    }
+    pub(crate) fn commit(mut self) {
+        self.checkpoint = None;
+    }
+}
+
+impl Drop for SetupGuard {
+    fn drop(&mut self) {
+        if let Some(checkpoint) = self.checkpoint.take() {
+            crate::nut::rollback_setup(&checkpoint);
+        }
+    }
+}
+
+impl Subscriptions {
+    fn checkpoint(&self, id: UncheckedActivityId) -> SetupCheckpoint {
+        SetupCheckpoint {
+            id,
+            since: SubscriptionId(self.last_id.get() + 1),
+        }
+    }
+    /// Drops the handlers of the activity added after the checkpoint.
+    ///
+    /// Does nothing if the subscriptions are borrowed, which can only happen if the setup panicked inside Nuts.
+    fn rollback(&self, checkpoint: &SetupCheckpoint) {
+        if self.subscriptions.try_borrow_mut().is_err() {
+            return;
+        }
+        self.retain(|index, sub| index != checkpoint.id.index || sub < checkpoint.since);
+    }
+}
+
+impl Nut {
+    pub(crate) fn setup_checkpoint(&self, id: UncheckedActivityId) -> SetupCheckpoint {
+        assert!(
+            !self.executing.load(Ordering::Relaxed),
+            "try_setup must not be called from inside a subscription handler."
+        );
+        self.subscriptions.checkpoint(id)
+    }
+    pub(crate) fn rollback_setup(&self, checkpoint: &SetupCheckpoint) {
+        self.subscriptions.rollback(checkpoint)
+    }
+}
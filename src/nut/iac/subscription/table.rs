@@ -0,0 +1,103 @@
+//! Export of all registered subscriptions, for tools that compare the wiring of different builds.
+
+use super::{ActivityTopicSubscriptions, Subscriptions};
+use crate::nut::iac::topic::Topic;
+use crate::nut::IMPOSSIBLE_ERR_MSG;
+use std::fmt::Write;
+
+/// Snapshot of all subscriptions, created with [`subscription_table`](fn.subscription_table.html).
+///
+/// Entries are sorted by topic and activity, which makes the table stable across runs of the same build.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubscriptionTable {
+    /// One entry per pair of topic and subscribing activity
+    pub entries: Vec<SubscriptionEntry>,
+}
+
+/// Handlers of one activity for one topic, see [`SubscriptionTable`](struct.SubscriptionTable.html).
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SubscriptionEntry {
+    /// Type name of the message or name of the lifecycle event
+    pub topic: &'static str,
+    /// True for private channels and private messages, false for published messages and lifecycle events
+    pub private: bool,
+    /// Type name of the subscribing activity, `None` for subscriptions without activity, such as `nuts::subscribe`
+    pub activity: Option<&'static str>,
+    /// Number of handlers the activity has registered for the topic
+    pub handlers: usize,
+}
+
+impl SubscriptionTable {
+    /// Serializes the table as a JSON array of objects with the same fields as `SubscriptionEntry`.
+    ///
+    /// The output contains one entry per line, which keeps textual diffs readable.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("[");
+        for (i, entry) in self.entries.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(
+                json,
+                "{}\n  {{\"topic\": {}, \"private\": {}, \"activity\": {}, \"handlers\": {}}}",
+                separator,
+                json_string(entry.topic),
+                entry.private,
+                entry
+                    .activity
+                    .map_or_else(|| "null".to_owned(), json_string),
+                entry.handlers
+            )
+            .expect(IMPOSSIBLE_ERR_MSG);
+        }
+        json.push_str("\n]\n");
+        json
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                write!(out, "\\u{:04x}", c as u32).expect(IMPOSSIBLE_ERR_MSG);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl Subscriptions {
+    pub(crate) fn table(&self) -> SubscriptionTable {
+        let subs = self.subscriptions.try_borrow().expect(IMPOSSIBLE_ERR_MSG);
+        let mut entries: Vec<SubscriptionEntry> = subs
+            .iter()
+            .flat_map(|(topic, container)| {
+                container
+                    .data
+                    .iter()
+                    .filter_map(move |(activity, subs)| entry(*topic, *activity, subs))
+            })
+            .collect();
+        entries.sort_unstable();
+        SubscriptionTable { entries }
+    }
+}
+
+fn entry(
+    topic: Topic,
+    activity: usize,
+    subs: &ActivityTopicSubscriptions,
+) -> Option<SubscriptionEntry> {
+    let first = subs.all().next()?;
+    Some(SubscriptionEntry {
+        topic: topic.name(),
+        private: matches!(topic, Topic::PrivateMessage(..)),
+        // Index 0 is `NotAnActivity`, which is internal
+        activity: (activity != 0).then(|| first.type_name.name()),
+        handlers: subs.len(),
+    })
+}
@@ -1,14 +1,19 @@
+use crate::debug::DebugTypeName;
 use core::any::{Any, TypeId};
+use std::hash::{Hash, Hasher};
 
 /// A topic for messages that can be published and subscribed to
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+///
+/// Message topics also store the type name of the message for debugging.
+/// It is ignored for comparisons and hashing.
+#[derive(Clone, Copy)]
 pub(crate) enum Topic {
     /// Topic for a builtin event
     BuiltinEvent(BuiltinEvent),
-    /// Topic for a message type, where type is a Rust type (core::any::TypeId). Many receivers can coexists for each published message.
-    PublicMessage(TypeId),
-    /// Topic for a message type, where type is a Rust type (core::any::TypeId). Only one receiver can exist per activity and each message must be sent to exactly one activity.
-    PrivateMessage(TypeId),
+    /// Topic for a message type, where type is a Rust type (`core::any::TypeId`). Many receivers can coexists for each published message.
+    PublicMessage(TypeId, DebugTypeName),
+    /// Topic for a message type, where type is a Rust type (`core::any::TypeId`). Only one receiver can exist per activity and each message must be sent to exactly one activity.
+    PrivateMessage(TypeId, DebugTypeName),
 }
 
 /// Builtin events are messages without payload that are used internally.
@@ -28,15 +33,55 @@ impl Topic {
         Self::BuiltinEvent(BuiltinEvent::Leave)
     }
     pub(crate) fn public_message<T: Any>() -> Self {
-        Self::PublicMessage(TypeId::of::<T>())
+        Self::PublicMessage(TypeId::of::<T>(), DebugTypeName::new::<T>())
     }
     pub(crate) fn private_message<T: Any>() -> Self {
-        Self::PrivateMessage(TypeId::of::<T>())
+        Self::PrivateMessage(TypeId::of::<T>(), DebugTypeName::new::<T>())
     }
     pub(crate) fn unqiue_per_activity(&self) -> bool {
         match self {
-            Self::BuiltinEvent(_) | Self::PublicMessage(_) => false,
-            Self::PrivateMessage(_) => true,
+            Self::BuiltinEvent(_) | Self::PublicMessage(..) => false,
+            Self::PrivateMessage(..) => true,
+        }
+    }
+    /// Name of the event or message type (only available in debug mode)
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::BuiltinEvent(BuiltinEvent::Enter) => "on_enter",
+            Self::BuiltinEvent(BuiltinEvent::Leave) => "on_leave",
+            Self::PublicMessage(_, name) | Self::PrivateMessage(_, name) => name.name(),
+        }
+    }
+}
+
+impl PartialEq for Topic {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::BuiltinEvent(a), Self::BuiltinEvent(b)) => a == b,
+            (Self::PublicMessage(a, _), Self::PublicMessage(b, _)) => a == b,
+            (Self::PrivateMessage(a, _), Self::PrivateMessage(b, _)) => a == b,
+            _ => false,
+        }
+    }
+}
+impl Eq for Topic {}
+
+impl Hash for Topic {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        core::mem::discriminant(self).hash(state);
+        match self {
+            Self::BuiltinEvent(event) => event.hash(state),
+            Self::PublicMessage(id, _) | Self::PrivateMessage(id, _) => id.hash(state),
+        }
+    }
+}
+
+impl std::fmt::Debug for Topic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BuiltinEvent(event) => write!(f, "{:?}", event),
+            Self::PublicMessage(..) => write!(f, "PublicMessage({})", self.name()),
+            Self::PrivateMessage(..) => write!(f, "PrivateMessage({})", self.name()),
         }
     }
 }
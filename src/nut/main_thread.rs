@@ -0,0 +1,34 @@
+//! Optional restriction of Nuts to a single thread, see `nuts::claim_main_thread`.
+//!
+//! Nuts state is thread-local, thus a call from another thread silently operates on a separate, empty state.
+//! Once a thread has claimed Nuts, creating that separate state panics instead.
+
+use crate::Error;
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once a thread has claimed Nuts
+static CLAIMED: AtomicBool = AtomicBool::new(false);
+
+thread_local!(static IS_MAIN: Cell<bool> = const { Cell::new(false) });
+
+pub(crate) fn claim() -> Result<(), Error> {
+    if IS_MAIN.with(Cell::get) {
+        return Ok(());
+    }
+    if CLAIMED.swap(true, Ordering::AcqRel) {
+        return Err(Error::WrongThread);
+    }
+    IS_MAIN.with(|main| main.set(true));
+    Ok(())
+}
+
+/// Panics if another thread has claimed Nuts
+pub(crate) fn check() {
+    if CLAIMED.load(Ordering::Acquire) && !IS_MAIN.try_with(Cell::get).unwrap_or(false) {
+        panic!(
+            "Nuts has been called from the wrong thread. Another thread has claimed Nuts with `claim_main_thread`, \
+             messages from other threads must be forwarded to it, for example through a channel."
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Records which activities modified a message with mutable access, enabled with the feature `mutation-audit`.
+//!
+//! A handler counts as modifying the message if it calls `nuts::mark_changed()`,
+//! or if the message type has been registered with `nuts::audit_mutations()` and the message differs from a copy taken before the handler.
+
+use core::any::{Any, TypeId};
+use std::{cell::RefCell, collections::HashMap};
+
+type CloneFn = fn(&dyn Any) -> Box<dyn Any>;
+type EqFn = fn(&dyn Any, &dyn Any) -> bool;
+
+#[derive(Default)]
+struct MutationAudit {
+    /// Message types that are compared before and after each mutable handler
+    comparators: HashMap<TypeId, (CloneFn, EqFn)>,
+    /// Type of the message that is currently dispatched, if it is a published message
+    current: Option<TypeId>,
+    /// Set by the currently executing handler
+    marked: bool,
+    /// Activities that modified the message, for the most recent dispatch of each message type
+    trails: HashMap<TypeId, Vec<&'static str>>,
+}
+
+thread_local!(static AUDIT: RefCell<MutationAudit> = RefCell::new(MutationAudit::default()));
+
+/// State captured before a handler with mutable access is called
+pub(crate) struct Before {
+    msg_type: TypeId,
+    snapshot: Option<(Box<dyn Any>, EqFn)>,
+}
+
+pub(crate) fn register<MSG: Any + Clone + PartialEq>() {
+    AUDIT.with(|audit| {
+        audit
+            .borrow_mut()
+            .comparators
+            .insert(TypeId::of::<MSG>(), (clone_any::<MSG>, eq_any::<MSG>));
+    });
+}
+
+/// Starts the trail of a dispatched message, `None` for everything but published messages
+pub(crate) fn begin(msg_type: Option<TypeId>) {
+    let _ = AUDIT.try_with(|audit| {
+        let mut audit = audit.borrow_mut();
+        audit.current = msg_type;
+        if let Some(t) = msg_type {
+            audit.trails.insert(t, vec![]);
+        }
+    });
+}
+
+pub(crate) fn end() {
+    let _ = AUDIT.try_with(|audit| audit.borrow_mut().current = None);
+}
+
+pub(crate) fn before_handler(mutates: bool, msg: Option<&dyn Any>) -> Option<Before> {
+    if !mutates {
+        return None;
+    }
+    AUDIT
+        .try_with(|audit| {
+            let mut audit = audit.borrow_mut();
+            let msg_type = audit.current?;
+            audit.marked = false;
+            let snapshot = match (audit.comparators.get(&msg_type), msg) {
+                (Some((clone, eq)), Some(msg)) => Some((clone(msg), *eq)),
+                _ => None,
+            };
+            Some(Before { msg_type, snapshot })
+        })
+        .ok()
+        .flatten()
+}
+
+pub(crate) fn after_handler(before: Option<Before>, msg: Option<&dyn Any>, activity: &'static str) {
+    if let Some(before) = before {
+        let _ = AUDIT.try_with(|audit| {
+            let mut audit = audit.borrow_mut();
+            let changed = audit.marked
+                || match (&before.snapshot, msg) {
+                    (Some((snapshot, eq)), Some(msg)) => !eq(snapshot.as_ref(), msg),
+                    _ => false,
+                };
+            audit.marked = false;
+            if changed {
+                audit
+                    .trails
+                    .entry(before.msg_type)
+                    .or_default()
+                    .push(activity);
+            }
+        });
+    }
+}
+
+pub(crate) fn mark_changed() {
+    AUDIT.with(|audit| audit.borrow_mut().marked = true);
+}
+
+pub(crate) fn trail<MSG: Any>() -> Vec<&'static str> {
+    AUDIT.with(|audit| {
+        audit
+            .borrow()
+            .trails
+            .get(&TypeId::of::<MSG>())
+            .cloned()
+            .unwrap_or_default()
+    })
+}
+
+fn clone_any<MSG: Any + Clone>(msg: &dyn Any) -> Box<dyn Any> {
+    Box::new(
+        msg.downcast_ref::<MSG>()
+            .expect(crate::nut::IMPOSSIBLE_ERR_MSG)
+            .clone(),
+    )
+}
+
+fn eq_any<MSG: Any + PartialEq>(a: &dyn Any, b: &dyn Any) -> bool {
+    a.downcast_ref::<MSG>() == b.downcast_ref::<MSG>()
+}
@@ -0,0 +1,65 @@
+//! Publishing from other threads into the thread-local Nuts of one thread, see `nuts::channel`.
+//!
+//! Other threads cannot touch the thread-local state, they only push messages to a queue shared with the target thread.
+//! The target thread publishes them when it calls `nuts::process_remote`.
+
+use crate::Error;
+use core::any::Any;
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+
+type RemoteMessage = Box<dyn FnOnce() + Send>;
+
+/// Messages sent from other threads, waiting to be published on the thread that owns the queue
+#[derive(Default)]
+pub(crate) struct RemoteQueue {
+    messages: Mutex<Vec<RemoteMessage>>,
+}
+
+impl RemoteQueue {
+    fn push(&self, msg: RemoteMessage) {
+        self.messages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(msg);
+    }
+    /// Takes all waiting messages, in the order they have been sent
+    pub(crate) fn drain(&self) -> Vec<RemoteMessage> {
+        std::mem::take(&mut *self.messages.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// Handle to publish messages on another thread, created on that thread with [`nuts::channel`](fn.channel.html).
+///
+/// The handle is `Send + Sync` and can be cloned, for example to hand it to several worker threads.
+/// Messages are published on the target thread in the order they have been sent, once it calls [`nuts::process_remote`](fn.process_remote.html).
+#[derive(Clone)]
+pub struct RemotePublisher {
+    queue: Weak<RemoteQueue>,
+}
+
+impl RemotePublisher {
+    pub(crate) fn new(queue: &Arc<RemoteQueue>) -> Self {
+        Self {
+            queue: Arc::downgrade(queue),
+        }
+    }
+    /// Sends the message to the target thread, where it is published like with [`nuts::publish`](fn.publish.html).
+    ///
+    /// Can be called from any thread, including the target thread itself.
+    ///
+    /// # Errors
+    /// Returns `Error::Disconnected` if the target thread has exited, the message is dropped.
+    pub fn publish<MSG: Any + Send>(&self, msg: MSG) -> Result<(), Error> {
+        let queue = self.queue.upgrade().ok_or(Error::Disconnected)?;
+        queue.push(Box::new(move || crate::nut::publish_custom(msg)));
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for RemotePublisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemotePublisher")
+            .field("connected", &(self.queue.strong_count() > 0))
+            .finish()
+    }
+}
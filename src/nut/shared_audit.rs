@@ -0,0 +1,70 @@
+//! Detection of shared ownership smuggled between activities in messages, enabled with the feature `shared-audit`.
+//!
+//! Messages are moved from the publisher to the subscribers.
+//! If a message contains an `Rc` instead, publisher and subscribers share the same data, which defeats the single-writer model of Nuts.
+//! Detection is based on the type name of the message, hence only shared pointers that appear in it are found.
+//! For example, `Rc<RefCell<State>>` and `Handle<Rc<State>>` are detected, a struct with a private `Rc` field is not.
+
+#[cfg(feature = "shared-audit")]
+use core::any::TypeId;
+#[cfg(feature = "shared-audit")]
+use std::{cell::RefCell, collections::HashSet};
+
+/// Parts of type names that indicate shared ownership
+#[cfg(feature = "shared-audit")]
+const SHARED_MARKERS: &[&str] = &[
+    "alloc::rc::Rc<",
+    "alloc::rc::Weak<",
+    "&core::cell::RefCell<",
+    "&core::cell::Cell<",
+    "&mut ",
+];
+
+#[cfg(feature = "shared-audit")]
+#[derive(Default)]
+struct SharedAudit {
+    allowed: HashSet<TypeId>,
+    checked: HashSet<TypeId>,
+    warnings: Vec<&'static str>,
+}
+
+#[cfg(feature = "shared-audit")]
+thread_local!(static AUDIT: RefCell<SharedAudit> = RefCell::new(SharedAudit::default()));
+
+/// Checks the type of a message that is about to be published, each type is checked only once
+///
+/// Messages published during teardown at thread exit are not checked.
+#[inline]
+pub(crate) fn check<MSG: core::any::Any>() {
+    #[cfg(feature = "shared-audit")]
+    let _ = AUDIT.try_with(|audit| {
+        let mut audit = audit.borrow_mut();
+        let id = TypeId::of::<MSG>();
+        if audit.allowed.contains(&id) || !audit.checked.insert(id) {
+            return;
+        }
+        let name = std::any::type_name::<MSG>();
+        if SHARED_MARKERS.iter().any(|marker| name.contains(marker)) {
+            log_print!(
+                "Warning: Message of type {} shares ownership between publisher and subscribers. Use nuts::allow_shared() if this is intended.",
+                name
+            );
+            audit.warnings.push(name);
+        }
+    });
+}
+
+#[cfg(feature = "shared-audit")]
+pub(crate) fn allow<MSG: core::any::Any>() {
+    AUDIT.with(|audit| {
+        let mut audit = audit.borrow_mut();
+        audit.allowed.insert(TypeId::of::<MSG>());
+        let name = std::any::type_name::<MSG>();
+        audit.warnings.retain(|w| *w != name);
+    });
+}
+
+#[cfg(feature = "shared-audit")]
+pub(crate) fn warnings() -> Vec<&'static str> {
+    AUDIT.with(|audit| audit.borrow().warnings.clone())
+}
@@ -0,0 +1,91 @@
+//! Health statistics of the message bus, published by Nuts itself, see `nuts::publish_stats`.
+
+use crate::nut::iac::publish::BroadcastInfo;
+use crate::nut::iac::topic::Topic;
+use crate::nut::{exec::Deferred, Nut, IMPOSSIBLE_ERR_MSG};
+use core::cell::Cell;
+use std::time::Duration;
+
+/// Statistics published every few broadcasts, enabled with [`publish_stats`](fn.publish_stats.html).
+///
+/// Counters cover the interval since the previous statistics were published.
+/// Subscribe to it like to any other message, for example to show the health of the message bus in an overlay.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NutsStats {
+    /// Broadcasts handled, including private messages and lifecycle events, but not the statistics themselves
+    pub broadcasts: u64,
+    /// Subscription handlers called
+    pub handlers_run: u64,
+    /// Time spent handling the broadcasts, measured with the [clock](fn.set_clock.html) of Nuts
+    pub time_spent: Duration,
+    /// Events waiting in the queue when the statistics were published
+    pub queue_depth: usize,
+    /// Activities that have not been deleted
+    pub activities: usize,
+}
+
+/// Counters of the current interval
+#[derive(Default)]
+pub(crate) struct StatsCollector {
+    /// Number of broadcasts per interval, zero if disabled
+    every: Cell<u64>,
+    /// Set while a broadcast is handled that counts towards the statistics
+    recording: Cell<bool>,
+    broadcasts: Cell<u64>,
+    handlers_run: Cell<u64>,
+    time_spent: Cell<Duration>,
+}
+
+impl StatsCollector {
+    pub(crate) fn set_interval(&self, every: u64) {
+        self.every.set(every);
+        self.broadcasts.set(0);
+        self.handlers_run.set(0);
+        self.time_spent.set(Duration::ZERO);
+    }
+    pub(crate) fn count_handler(&self) {
+        if self.recording.get() {
+            self.handlers_run.set(self.handlers_run.get() + 1);
+        }
+    }
+}
+
+impl Nut {
+    /// Starts measuring a broadcast, returns the start time if the broadcast counts towards the statistics
+    pub(crate) fn stats_begin(&self, topic: Topic) -> Option<Duration> {
+        if self.stats.every.get() == 0 || topic == Topic::public_message::<NutsStats>() {
+            return None;
+        }
+        self.stats.recording.set(true);
+        Some(self.now())
+    }
+    /// Completes the measurement of a broadcast and queues the statistics at the end of an interval
+    pub(crate) fn stats_end(&self, start: Option<Duration>) {
+        let start = match start {
+            Some(start) => start,
+            None => return,
+        };
+        let stats = &self.stats;
+        stats.recording.set(false);
+        stats.broadcasts.set(stats.broadcasts.get() + 1);
+        stats
+            .time_spent
+            .set(stats.time_spent.get() + self.now().saturating_sub(start));
+        if stats.broadcasts.get() < stats.every.get() {
+            return;
+        }
+        let report = NutsStats {
+            broadcasts: stats.broadcasts.replace(0),
+            handlers_run: stats.handlers_run.replace(0),
+            time_spent: stats.time_spent.replace(Duration::ZERO),
+            queue_depth: self.deferred_events.len(),
+            activities: self
+                .activities
+                .try_borrow()
+                .expect(IMPOSSIBLE_ERR_MSG)
+                .count_live(),
+        };
+        let broadcast = BroadcastInfo::global(report, Topic::public_message::<NutsStats>());
+        self.deferred_events.push(Deferred::Broadcast(broadcast));
+    }
+}
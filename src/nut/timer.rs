@@ -0,0 +1,115 @@
+//! Recurring publishers and idle callbacks, driven by the application calling `advance_time`.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Handle to a recurring publisher created with [`every`](fn.every.html), or to an idle callback created with [`on_idle`](fn.on_idle.html).
+///
+/// Dropping the handle does not stop the publisher or callback, call [`cancel`](#method.cancel) for that.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TimerHandle(u64);
+
+#[derive(Default)]
+pub(crate) struct Timers {
+    next_id: u64,
+    now: Duration,
+    timers: Vec<Timer>,
+    idle: Vec<IdleCallback>,
+    /// Time at which `advance` has seen broadcasts for the last time
+    last_activity: Duration,
+    /// Number of broadcasts that `advance` has seen, excluding those caused by timers and idle callbacks
+    seen_broadcasts: u64,
+}
+
+struct Timer {
+    id: TimerHandle,
+    period: Duration,
+    due: Duration,
+    publish: Rc<dyn Fn()>,
+}
+
+struct IdleCallback {
+    id: TimerHandle,
+    after: Duration,
+    /// Called during the current idle period already
+    fired: bool,
+    callback: Rc<dyn Fn()>,
+}
+
+impl TimerHandle {
+    /// Stops the publisher. Has no effect if it has been cancelled already.
+    pub fn cancel(self) {
+        crate::nut::cancel_timer(self)
+    }
+}
+
+impl Timers {
+    pub(crate) fn add(&mut self, period: Duration, publish: Rc<dyn Fn()>) -> TimerHandle {
+        assert!(
+            period > Duration::ZERO,
+            "Period of a timer must not be zero"
+        );
+        self.next_id += 1;
+        let id = TimerHandle(self.next_id);
+        self.timers.push(Timer {
+            id,
+            period,
+            due: self.now + period,
+            publish,
+        });
+        id
+    }
+    pub(crate) fn add_idle(&mut self, after: Duration, callback: Rc<dyn Fn()>) -> TimerHandle {
+        self.next_id += 1;
+        let id = TimerHandle(self.next_id);
+        self.idle.push(IdleCallback {
+            id,
+            after,
+            fired: false,
+            callback,
+        });
+        id
+    }
+    pub(crate) fn remove(&mut self, id: TimerHandle) {
+        self.timers.retain(|timer| timer.id != id);
+        self.idle.retain(|idle| idle.id != id);
+    }
+    /// Moves time forward and returns the publishers that are due, in the order of their due times, followed by the idle callbacks that are due.
+    ///
+    /// Timers with a period shorter than `dt` are returned several times.
+    /// `broadcasts` is the number of broadcasts so far, if it changed since the last call, the time in between counts as busy.
+    pub(crate) fn advance(&mut self, dt: Duration, broadcasts: u64) -> Vec<Rc<dyn Fn()>> {
+        self.now += dt;
+        if broadcasts != self.seen_broadcasts {
+            self.last_activity = self.now;
+            for idle in &mut self.idle {
+                idle.fired = false;
+            }
+        }
+        let mut due = vec![];
+        for timer in &mut self.timers {
+            while timer.due <= self.now {
+                due.push((timer.due, timer.id.0, timer.publish.clone()));
+                timer.due += timer.period;
+            }
+        }
+        due.sort_by_key(|(time, id, _)| (*time, *id));
+        let quiet = self.now - self.last_activity;
+        let idle = self
+            .idle
+            .iter_mut()
+            .filter(|idle| !idle.fired && quiet >= idle.after)
+            .map(|idle| {
+                idle.fired = true;
+                idle.callback.clone()
+            });
+        due.into_iter()
+            .map(|(_, _, publish)| publish)
+            .chain(idle)
+            .collect()
+    }
+    /// Marks all broadcasts so far as seen, such that those caused by timers and idle callbacks do not count as activity
+    pub(crate) fn observe(&mut self, broadcasts: u64) {
+        self.seen_broadcasts = broadcasts;
+    }
+}
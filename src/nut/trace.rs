@@ -0,0 +1,92 @@
+//! Correlation of messages that have been published while handling other messages.
+
+use crate::debug::DebugTypeName;
+use std::collections::VecDeque;
+
+/// Identifies a single published message (or lifecycle event) and links it to the message that caused it.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct TraceId(u64);
+
+/// A recorded message in the causality tree. See [`record_traces`](fn.record_traces.html).
+#[derive(Clone, Debug)]
+pub struct TraceNode {
+    /// The traced message
+    pub id: TraceId,
+    /// The message that was being handled when this message was published, `None` if it was published outside of handlers.
+    pub cause: Option<TraceId>,
+    /// Type name of the message, only available in debug mode.
+    pub message: &'static str,
+}
+
+#[derive(Default)]
+pub(crate) struct Tracer {
+    next: u64,
+    capacity: usize,
+    nodes: VecDeque<TraceNode>,
+    current: Option<TraceId>,
+    /// Cascade depth of the current message, zero outside of handlers
+    depth: u32,
+}
+
+impl TraceId {
+    /// A number that uniquely identifies the message within the current thread.
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Tracer {
+    /// Creates the trace for a new message, which is caused by the currently handled message.
+    pub(crate) fn new_trace(&mut self, type_name: DebugTypeName) -> TraceId {
+        self.next += 1;
+        let id = TraceId(self.next);
+        if self.capacity > 0 {
+            if self.nodes.len() >= self.capacity {
+                self.nodes.pop_front();
+            }
+            self.nodes.push_back(TraceNode {
+                id,
+                cause: self.current,
+                message: type_name.name(),
+            });
+        }
+        id
+    }
+    /// Marks the message as being handled, returns the previously handled message and its depth.
+    pub(crate) fn enter(&mut self, id: TraceId, depth: u32) -> (Option<TraceId>, u32) {
+        let previous = (self.current.replace(id), self.depth);
+        self.depth = depth;
+        previous
+    }
+    pub(crate) fn leave(&mut self, previous: (Option<TraceId>, u32)) {
+        self.current = previous.0;
+        self.depth = previous.1;
+    }
+    /// Number of messages in the chain of causes of the currently handled message, including itself
+    pub(crate) fn depth(&self) -> u32 {
+        self.depth
+    }
+    pub(crate) fn current(&self) -> Option<TraceId> {
+        self.current
+    }
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.nodes.len() > capacity {
+            self.nodes.pop_front();
+        }
+    }
+    pub(crate) fn nodes(&self) -> Vec<TraceNode> {
+        self.nodes.iter().cloned().collect()
+    }
+    /// The recorded chain of causes, starting at the root and ending with the given message.
+    pub(crate) fn chain(&self, id: TraceId) -> Vec<TraceNode> {
+        let mut chain = vec![];
+        let mut next = Some(id);
+        while let Some(node) = next.and_then(|id| self.nodes.iter().find(|node| node.id == id)) {
+            next = node.cause;
+            chain.push(node.clone());
+        }
+        chain.reverse();
+        chain
+    }
+}
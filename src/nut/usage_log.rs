@@ -0,0 +1,114 @@
+//! Warnings about suspicious usage, emitted through the `log` crate with the feature `log`.
+//!
+//! Without the feature, the remaining hooks are empty.
+
+#[cfg(feature = "log")]
+use crate::nut::iac::topic::Topic;
+#[cfg(feature = "log")]
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "log")]
+use std::collections::HashSet;
+#[cfg(feature = "log")]
+use std::time::Duration;
+
+/// Limits above which warnings are emitted with the feature `log`, set with [`nuts::set_log_thresholds`](fn.set_log_thresholds.html).
+#[cfg(feature = "log")]
+#[derive(Clone, Copy, Debug)]
+pub struct LogThresholds {
+    /// Number of messages in a chain of messages published by handlers of the previous message
+    pub max_cascade_depth: u32,
+    /// Number of queued events
+    pub max_queue_len: usize,
+    /// Wall-clock time spent in a single handler call, measured with the clock set by `nuts::set_clock`
+    pub handler_budget: Duration,
+}
+
+#[cfg(feature = "log")]
+impl Default for LogThresholds {
+    fn default() -> Self {
+        Self {
+            max_cascade_depth: 32,
+            max_queue_len: 1000,
+            handler_budget: Duration::from_millis(16),
+        }
+    }
+}
+
+#[cfg(feature = "log")]
+thread_local!(
+    static THRESHOLDS: Cell<LogThresholds> = Cell::new(LogThresholds::default());
+    /// Topics that have already been reported as having no subscribers
+    static UNHEARD: RefCell<HashSet<Topic>> = RefCell::new(HashSet::new());
+);
+
+#[cfg(feature = "log")]
+pub(crate) fn set_thresholds(thresholds: LogThresholds) {
+    THRESHOLDS.with(|t| t.set(thresholds));
+}
+
+#[cfg(feature = "log")]
+fn thresholds() -> LogThresholds {
+    THRESHOLDS
+        .try_with(|t| t.get())
+        .unwrap_or_else(|_| LogThresholds::default())
+}
+
+/// Reports a published message without subscribers, once per message type
+#[cfg(feature = "log")]
+pub(crate) fn no_subscribers(topic: Topic) {
+    let first = UNHEARD
+        .try_with(|set| set.borrow_mut().insert(topic))
+        .unwrap_or(false);
+    if first {
+        log::warn!(
+            "Message {} has been published without any subscribers.",
+            topic.name()
+        );
+    }
+}
+
+/// Reports a broadcast that has been reached through a long chain of messages, only where the chain crosses the threshold
+#[inline]
+pub(crate) fn cascade_depth(_depth: u32, _topic: crate::nut::iac::topic::Topic) {
+    #[cfg(feature = "log")]
+    if _depth == thresholds().max_cascade_depth + 1 {
+        log::warn!(
+            "Message cascade deeper than {} messages, reached with {}.",
+            _depth - 1,
+            _topic.name()
+        );
+    }
+}
+
+/// Reports the queue length, only when it crosses the threshold
+#[inline]
+pub(crate) fn queue_len(_len: usize) {
+    #[cfg(feature = "log")]
+    if _len == thresholds().max_queue_len + 1 {
+        log::warn!("More than {} events are queued.", _len - 1);
+    }
+}
+
+/// Reports a panic of a handler that has been caught, see `PanicPolicy::CatchAndContinue`
+#[inline]
+pub(crate) fn caught_panic(_activity: &'static str) {
+    #[cfg(feature = "log")]
+    log::error!(
+        "A handler of {} panicked, the panic has been caught.",
+        _activity
+    );
+}
+
+/// Reports a handler call that took longer than the budget
+#[cfg(feature = "log")]
+pub(crate) fn handler_time(elapsed: Duration, activity: &'static str) {
+    let budget = thresholds().handler_budget;
+    if elapsed > budget {
+        log::warn!(
+            "Handler of activity {} took {:?}, the budget is {:?}.",
+            activity,
+            elapsed,
+            budget
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Deterministic stepping of applications built from activities, for simulation tests.
+//!
+//! Each call to [`step`] publishes one [`SimTick`] and returns once it has been processed completely, including all messages published in reaction to it.
+//! Handlers draw their randomness from [`SimTick::rng`], hence the same seeds always lead to the same outcome.
+//!
+//! ### Example
+//! ```rust
+//! use nuts::sim::SimTick;
+//! struct World { population: u64 }
+//!
+//! let world = nuts::new_activity(World { population: 100 });
+//! world.subscribe(|world, tick: &SimTick| {
+//!     world.population += tick.rng().below(10);
+//! });
+//! nuts::sim::run(1000, 42);
+//! ```
+
+use core::cell::Cell;
+
+/// Published by [`step`] once per simulation step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SimTick {
+    /// Seed of this step
+    pub seed: u64,
+    /// Number of steps before this one, on the current thread
+    pub index: u64,
+}
+
+/// A small, fast random number generator (the `SplitMix64` algorithm) that produces the same sequence on every platform.
+///
+/// Not suitable for cryptography.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimRng {
+    state: u64,
+}
+
+thread_local!(static STEPS: Cell<u64> = const { Cell::new(0) });
+
+impl SimTick {
+    /// A random number generator derived from the seed and index of this step.
+    ///
+    /// Each call returns a generator in the same state, call it once per handler and draw all numbers from it.
+    pub fn rng(&self) -> SimRng {
+        SimRng::new(self.seed ^ self.index.rotate_left(32))
+    }
+}
+
+impl SimRng {
+    /// Creates a generator, the same seed always produces the same sequence.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    /// Returns the next number, uniformly distributed over all `u64` values.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// Returns a number in `0..n`.
+    ///
+    /// # Panics
+    /// Panics if `n` is zero.
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "Cannot draw a number below 0.");
+        // The bias is negligible for the small ranges used in simulations
+        self.next_u64() % n
+    }
+    /// Returns a number in `0.0..1.0`.
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Publishes a [`SimTick`] with the given seed and processes it, and all messages it causes, before returning.
+///
+/// Returns the published tick.
+///
+/// # Panics
+/// Panics if called from inside a subscription handler, or if the queue has been paused with [`PauseQueue`](../control/struct.PauseQueue.html), because the step could not complete.
+pub fn step(seed: u64) -> SimTick {
+    let index = STEPS.with(|steps| steps.replace(steps.get() + 1));
+    let tick = SimTick { seed, index };
+    crate::nut::publish_to_completion(tick);
+    tick
+}
+
+/// Runs `steps` simulation steps, with seeds derived from `seed`.
+///
+/// Running again with the same seed reproduces the same sequence of ticks, except for their index, which keeps counting.
+pub fn run(steps: u64, seed: u64) {
+    let mut seeds = SimRng::new(seed);
+    for _ in 0..steps {
+        step(seeds.next_u64());
+    }
+}
+
+/// Restarts counting steps at index 0.
+pub fn reset_index() {
+    STEPS.with(|steps| steps.set(0));
+}
@@ -1,41 +1,32 @@
+//! Utilities for testing activities.
+//!
+//! These helpers are meant to be used in the test suites of applications built on Nuts.
+//! They must be called from outside of subscription handlers.
+
+mod capture;
+mod conformance;
+mod mock;
+mod order;
+
+pub use capture::*;
+pub use conformance::*;
+pub use mock::*;
+pub use order::*;
+
+#[cfg(test)]
 mod base_tests;
+#[cfg(test)]
 mod domain_tests;
+#[cfg(test)]
+mod fixtures;
+#[cfg(test)]
 mod inchoate_tests;
+#[cfg(test)]
 mod lifecycle_tests;
+#[cfg(test)]
+mod test_utils_tests;
+#[cfg(test)]
+mod trace_tests;
 
-use crate::*;
-use std::cell::Cell;
-use std::rc::Rc;
-
-#[derive(Clone)]
-struct TestActivity {
-    counter: Rc<Cell<u32>>,
-}
-
-impl TestActivity {
-    fn new() -> Self {
-        let shared_counter = Rc::new(Cell::new(0));
-        Self {
-            counter: shared_counter,
-        }
-    }
-    fn shared_counter_ref(&self) -> Rc<Cell<u32>> {
-        self.counter.clone()
-    }
-    fn inc(&self, add: u32) {
-        let i = self.counter.get();
-        self.counter.as_ref().set(i + add)
-    }
-}
-
-#[derive(Clone, Copy)]
-enum TestDomains {
-    DomainA,
-    _DomainB,
-}
-domain_enum!(TestDomains);
-
-struct TestUpdateMsg;
-struct TestForInt(usize);
-struct TestMessage(u32);
-struct TestMessageNoClone;
+#[cfg(test)]
+use fixtures::*;
@@ -162,6 +162,30 @@ fn multi_subscribe_private_message() {
     assert_eq!(1, counter.get()); // Make sure second subscription has been called exactly once
 }
 
+#[test]
+fn owned_once_private_message() {
+    struct Resource;
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    let resource = Resource;
+    id.subscribe_owned_once(move |activity, _msg: TestMessageNoClone| {
+        let _moved = resource;
+        activity.inc(1);
+        // Registered by the one-shot handler itself, this channel takes over
+        id.private_channel(|activity, _msg: TestMessageNoClone| activity.inc(10));
+    });
+    id.private_message(TestMessageNoClone);
+    assert_eq!(1, counter.get());
+    id.private_message(TestMessageNoClone);
+    assert_eq!(11, counter.get());
+
+    id.subscribe_owned_once(|activity, _msg: TestMessageNoClone| activity.inc(100));
+    id.private_message(TestMessageNoClone);
+    id.private_message(TestMessageNoClone);
+    assert_eq!(111, counter.get()); // The second message has been dropped
+}
+
 #[test]
 fn multi_subscriber_private_message() {
     let a = TestActivity::new();
@@ -180,3 +204,1814 @@ fn multi_subscriber_private_message() {
     crate::send_to::<TestActivity, _>(TestMessageNoClone);
     assert_eq!(1, counter.get()); // Make sure subscription of correct type has been called exactly once
 }
+
+#[test]
+#[cfg(feature = "futures")]
+fn forward_stream_into_sink() {
+    use futures::StreamExt;
+
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity, msg: &TestMessage| {
+        activity.inc(msg.0);
+    });
+    let stream = futures::stream::iter(vec![TestMessage(1), TestMessage(10), TestMessage(100)]);
+    futures::executor::block_on(stream.map(Ok).forward(crate::sink())).unwrap();
+    assert_eq!(111, counter.get());
+}
+
+#[derive(Clone, Copy)]
+struct CopyMsg(u32);
+
+#[test]
+fn publish_copy() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity, msg: &CopyMsg| {
+        activity.inc(msg.0);
+        // Nested publishes need more than one pooled box at a time
+        if msg.0 < 100 {
+            crate::publish_copy(CopyMsg(msg.0 * 10));
+            crate::publish_copy(CopyMsg(msg.0 * 10));
+        }
+    });
+    crate::publish_copy(CopyMsg(10));
+    assert_eq!(210, counter.get());
+    crate::publish_copy(CopyMsg(1000));
+    assert_eq!(1210, counter.get());
+}
+
+#[test]
+#[cfg(feature = "perf")]
+fn profiling_report() {
+    let a = TestActivity::new();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity, msg: &TestMessage| {
+        activity.inc(msg.0);
+    });
+    crate::publish(TestMessage(1));
+    crate::publish(TestMessage(1));
+    let report = crate::profiling_report();
+    assert_eq!(1, report.len());
+    assert_eq!(2, report[0].calls);
+}
+
+#[test]
+#[cfg(feature = "perf")]
+fn profiling_with_mock_clock() {
+    use std::time::Duration;
+    let clock = crate::MockClock::new();
+    crate::set_clock(clock.clone());
+    let id = crate::new_activity(TestActivity::new());
+    id.subscribe(move |_activity, msg: &TestMessage| {
+        clock.advance(Duration::from_millis(msg.0.into()));
+    });
+    crate::publish(TestMessage(3));
+    crate::publish(TestMessage(4));
+    let report = crate::profiling_report();
+    assert_eq!(Duration::from_millis(7), report[0].wall_time);
+}
+
+#[test]
+#[cfg(feature = "message-arena")]
+fn message_arena() {
+    struct Plain(u32, u32);
+    let counter = Rc::new(Cell::new(0));
+    let c = counter.clone();
+    crate::subscribe(move |msg: &Plain| c.set(c.get() + msg.0));
+    crate::subscribe(|msg: &Plain| {
+        if msg.1 > 0 {
+            crate::publish(Plain(msg.0, msg.1 - 1));
+        }
+    });
+    crate::publish(Plain(1, 0));
+    let usage = crate::arena_usage();
+    assert_eq!(8, usage.last_drain_bytes);
+    assert_eq!(8, usage.pooled_bytes);
+
+    // A cascade needs a second box while the first one is in use
+    crate::publish(Plain(1, 1));
+    assert_eq!(3, counter.get());
+    let usage = crate::arena_usage();
+    assert_eq!(16, usage.last_drain_bytes);
+    assert_eq!(16, usage.pooled_bytes);
+
+    #[cfg(feature = "alloc-audit")]
+    {
+        crate::take_allocation_report();
+        crate::publish(Plain(1, 1));
+        assert_eq!(0, crate::take_allocation_report().messages);
+    }
+
+    // Messages with drop glue are not pooled
+    crate::subscribe(|_: &String| {});
+    crate::publish(String::from("owned"));
+    let usage = crate::arena_usage();
+    assert_eq!(0, usage.last_drain_bytes);
+    assert_eq!(16, usage.pooled_bytes);
+
+    // Boxes of a burst are freed once the queue has been drained
+    struct Burst;
+    crate::subscribe(|_: &Burst| {
+        for _ in 0..100 {
+            crate::publish(Plain(0, 0));
+        }
+    });
+    crate::publish(Burst);
+    assert_eq!(800, crate::arena_usage().last_drain_bytes);
+    assert_eq!(32 * 8, crate::arena_usage().pooled_bytes);
+    assert_eq!(33, crate::compact().pooled_messages);
+    assert_eq!(0, crate::arena_usage().pooled_bytes);
+}
+
+#[test]
+#[cfg(feature = "alloc-audit")]
+fn allocation_audit() {
+    let counter = Rc::new(Cell::new(0));
+    let c = counter.clone();
+    crate::subscribe(move |msg: &CopyMsg| c.set(c.get() + msg.0));
+    crate::publish_copy(CopyMsg(1));
+    let warmup = crate::take_allocation_report();
+    assert!(warmup.closures > 0);
+
+    for _ in 0..10 {
+        crate::publish_copy(CopyMsg(1));
+    }
+    let report = crate::take_allocation_report();
+    assert_eq!(11, counter.get());
+    assert_eq!(10, report.broadcasts);
+    assert_eq!(0, report.total());
+
+    // Messages published from handlers reuse boxes as well, once the cascade has been seen before
+    #[derive(Clone, Copy)]
+    struct Cascade(u32);
+    crate::subscribe(|msg: &Cascade| {
+        if msg.0 > 0 {
+            crate::publish_copy(Cascade(msg.0 - 1));
+            crate::publish_copy(CopyMsg(0));
+        }
+    });
+    crate::publish_copy(Cascade(5));
+    crate::take_allocation_report();
+    for _ in 0..10 {
+        crate::publish_copy(Cascade(5));
+    }
+    let report = crate::take_allocation_report();
+    assert_eq!(110, report.broadcasts);
+    assert_eq!(0, report.messages);
+    assert_eq!(0, report.total());
+
+    crate::publish(TestMessage(1));
+    let report = crate::take_allocation_report();
+    assert_eq!(1, report.broadcasts);
+    assert_eq!(1, report.messages);
+}
+
+#[test]
+fn urgent_response_skips_queue() {
+    use std::cell::RefCell;
+    struct Low(u32);
+    struct Urgent;
+    struct FollowUp;
+    struct Trigger;
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let pending = Rc::new(RefCell::new(None));
+
+    let l = log.clone();
+    crate::subscribe(move |msg: &Low| l.borrow_mut().push(format!("low {}", msg.0)));
+    let l = log.clone();
+    crate::subscribe(move |_: &Urgent| {
+        l.borrow_mut().push("urgent".to_owned());
+        crate::publish(FollowUp);
+    });
+    let l = log.clone();
+    crate::subscribe(move |_: &FollowUp| l.borrow_mut().push("follow-up".to_owned()));
+    let p = pending.clone();
+    crate::subscribe(move |_: &Trigger| {
+        crate::publish(Low(1));
+        crate::publish(Low(2));
+        *p.borrow_mut() = Some(Box::pin(crate::publish_awaiting_response_urgent(Urgent)));
+    });
+
+    crate::publish(Trigger);
+    assert_eq!(vec!["urgent", "follow-up", "low 1", "low 2"], *log.borrow());
+    let response = pending
+        .borrow_mut()
+        .take()
+        .expect("trigger handler not called");
+    futures::executor::block_on(response);
+}
+
+#[test]
+fn dropped_responses_free_their_slots() {
+    struct Request;
+    struct Trigger;
+    crate::subscribe(|_: &Request| {});
+    crate::subscribe(|_: &Trigger| {
+        // Dropped while the message is still queued
+        drop(crate::publish_awaiting_response_urgent(Request));
+    });
+    crate::configure_responses(crate::ResponseConfig {
+        max_pending: Some(1),
+        ..Default::default()
+    });
+    for _ in 0..3 {
+        crate::publish(Trigger);
+        drop(crate::publish_awaiting_response_urgent(Request));
+    }
+    futures::executor::block_on(crate::publish_awaiting_response_urgent(Request));
+    let stats = crate::response_stats();
+    assert_eq!(stats.slots, 1);
+    assert_eq!(stats.pending, 0);
+    assert_eq!(stats.peak_pending, 1);
+    assert_eq!(stats.allocations, 7);
+}
+
+#[test]
+fn subscribe_trait_impl() {
+    struct Counter(Rc<Cell<u32>>);
+    impl Subscriber<TestMessage> for Counter {
+        fn on_message(&mut self, msg: &TestMessage) {
+            self.0.set(self.0.get() + msg.0);
+        }
+    }
+    let counter = Rc::new(Cell::new(0));
+    let id = crate::new_activity(Counter(counter.clone()));
+    id.subscribe_trait_impl::<TestMessage>();
+    crate::publish(TestMessage(3));
+    crate::publish(TestMessage(4));
+    assert_eq!(counter.get(), 7);
+}
+
+#[test]
+fn await_dispatch_and_quiescent() {
+    use core::future::Future;
+    use core::pin::Pin;
+    use futures::FutureExt;
+    use std::cell::RefCell;
+    type Pending = Rc<RefCell<Option<Pin<Box<dyn Future<Output = ()>>>>>>;
+
+    struct Save;
+    struct Saved;
+    struct Trigger;
+
+    let dispatch: Pending = Default::default();
+    let quiescent: Pending = Default::default();
+    let checked = Rc::new(Cell::new(false));
+
+    crate::subscribe(|_: &Save| crate::publish(Saved));
+    let (d, q) = (dispatch.clone(), quiescent.clone());
+    crate::subscribe(move |_: &Trigger| {
+        *d.borrow_mut() = Some(Box::pin(crate::publish_awaiting(Save).await_dispatch()));
+        *q.borrow_mut() = Some(Box::pin(crate::publish_awaiting(Save).await_quiescent()));
+    });
+    let (d, q, c) = (dispatch.clone(), quiescent.clone(), checked.clone());
+    crate::subscribe(move |_: &Saved| {
+        if c.replace(true) {
+            return;
+        }
+        let mut d = d.borrow_mut();
+        let mut q = q.borrow_mut();
+        assert!(d
+            .as_mut()
+            .expect("dispatch future")
+            .now_or_never()
+            .is_some());
+        assert!(q
+            .as_mut()
+            .expect("quiescent future")
+            .now_or_never()
+            .is_none());
+    });
+
+    crate::publish(Trigger);
+    assert!(checked.get());
+    let quiescent = quiescent.borrow_mut().take().expect("quiescent future");
+    assert!(quiescent.now_or_never().is_some());
+}
+
+#[test]
+fn retained_messages_replay() {
+    #[derive(Clone)]
+    struct Setting(u32);
+    let log = Rc::new(std::cell::RefCell::new(Vec::new()));
+    crate::retain_last::<Setting>(2);
+    for i in 1..=3 {
+        crate::publish(Setting(i));
+    }
+
+    let id = crate::new_activity(TestActivity::new());
+    let l = log.clone();
+    id.subscribe_and_replay(move |_, msg: &Setting| l.borrow_mut().push(msg.0));
+    assert_eq!(vec![2, 3], *log.borrow());
+
+    crate::publish(Setting(4));
+    assert_eq!(vec![2, 3, 4], *log.borrow());
+
+    crate::retain_last::<Setting>(0);
+    let l = log.clone();
+    id.subscribe_and_replay(move |_, msg: &Setting| l.borrow_mut().push(msg.0 * 10));
+    assert_eq!(vec![2, 3, 4], *log.borrow());
+}
+
+#[test]
+fn startup_buffer() {
+    struct Early(u32);
+    struct Late;
+    let counter = Rc::new(Cell::new(0));
+
+    crate::buffer_startup_messages(2);
+    crate::publish(Early(1));
+    crate::publish(Early(10));
+    crate::publish(Early(100));
+
+    let c = counter.clone();
+    crate::subscribe(move |msg: &Early| c.set(c.get() + msg.0));
+    assert_eq!(110, counter.get(), "oldest message should be dropped");
+
+    // Subscribing from within a handler releases buffered messages, too
+    crate::publish(Early(1000));
+    assert_eq!(1110, counter.get());
+    crate::publish(Late);
+    let c = counter.clone();
+    crate::subscribe(move |msg: &Early| {
+        let c = c.clone();
+        let n = msg.0;
+        crate::subscribe(move |_: &Late| c.set(c.get() + n));
+    });
+    crate::publish(Early(1));
+    assert_eq!(1112, counter.get());
+
+    crate::start();
+    struct AfterStart;
+    crate::publish(AfterStart);
+    let c = counter.clone();
+    crate::subscribe(move |_: &AfterStart| c.set(0));
+    assert_eq!(1112, counter.get());
+}
+
+#[test]
+fn mutable_subscribers_alias_message() {
+    #[derive(Clone)]
+    struct Draft(u32);
+    struct Editor;
+    let seen = Rc::new(Cell::new(0));
+    let id = crate::new_activity(Editor);
+    // Mutable subscriptions of the same activity are called in the order they have been registered
+    id.subscribe_mut(|_, msg: &mut Draft| msg.0 += 1);
+    let s = seen.clone();
+    id.subscribe_mut(move |_, msg: &mut Draft| s.set(msg.0));
+
+    crate::publish(Draft(10));
+    assert_eq!(11, seen.get(), "default publish shares the message");
+
+    crate::publish_isolated(Draft(10));
+    assert_eq!(
+        10,
+        seen.get(),
+        "isolated publish gives each subscriber a copy"
+    );
+}
+
+#[test]
+fn readers_are_called_before_mutable_subscribers() {
+    struct Draft(u32);
+    struct Editor;
+    let seen = Rc::new(Cell::new(0));
+    let id = crate::new_activity(Editor);
+    id.subscribe_mut(|_, msg: &mut Draft| msg.0 += 1);
+    let s = seen.clone();
+    id.subscribe(move |_, msg: &Draft| s.set(s.get() + msg.0));
+    let other = crate::new_activity(());
+    let s = seen.clone();
+    other.subscribe(move |_, msg: &Draft| s.set(s.get() + msg.0));
+
+    crate::publish(Draft(10));
+    assert_eq!(20, seen.get(), "readers must see the unmodified message");
+}
+
+#[test]
+fn send_to_named_activity() {
+    struct Audio;
+    struct Mixer;
+    struct Volume(u32);
+    let counter = Rc::new(Cell::new(0));
+    let audio = crate::new_activity(Audio);
+    let c = counter.clone();
+    audio.private_channel(move |_, msg: Volume| c.set(c.get() + msg.0));
+    let mixer = crate::new_activity(Mixer);
+    let c = counter.clone();
+    mixer.private_channel(move |_, msg: Volume| c.set(c.get() + msg.0 * 100));
+
+    crate::send_to_named("audio", Volume(1));
+    assert_eq!(0, counter.get(), "unregistered name is ignored");
+
+    crate::register_name("audio", audio);
+    crate::send_to_named("audio", Volume(1));
+    assert_eq!(1, counter.get());
+
+    crate::register_name("audio", mixer);
+    crate::send_to_named("audio", Volume(1));
+    assert_eq!(101, counter.get());
+}
+
+#[test]
+fn subscribe_when_predicate() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.when(|msg: &TestMessage| msg.0.is_multiple_of(2))
+        .subscribe(|activity, msg| activity.inc(msg.0));
+    for i in 0..5 {
+        crate::publish(TestMessage(i));
+    }
+    assert_eq!(6, counter.get());
+
+    // Predicate is checked independently of the activity status
+    id.when(|msg: &TestMessage| msg.0 == 100)
+        .subscribe_masked(SubscriptionFilter::no_filter(), |activity, msg| {
+            activity.inc(msg.0)
+        });
+    id.set_status(LifecycleStatus::Inactive);
+    crate::publish(TestMessage(2));
+    crate::publish(TestMessage(100));
+    assert_eq!(106, counter.get());
+}
+
+#[test]
+fn periodic_publisher() {
+    use std::cell::RefCell;
+    use std::time::Duration;
+    struct Heartbeat;
+    struct Poll;
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let l = log.clone();
+    crate::subscribe(move |_: &Heartbeat| l.borrow_mut().push("heartbeat"));
+    let l = log.clone();
+    crate::subscribe(move |_: &Poll| l.borrow_mut().push("poll"));
+
+    let heartbeat = crate::every(Duration::from_millis(100), || Heartbeat);
+    crate::every(Duration::from_millis(150), || Poll);
+
+    crate::advance_time(Duration::from_millis(99));
+    assert!(log.borrow().is_empty());
+    crate::advance_time(Duration::from_millis(1));
+    assert_eq!(vec!["heartbeat"], *log.borrow());
+
+    // Missed periods are published in order of their due times
+    log.borrow_mut().clear();
+    crate::advance_time(Duration::from_millis(200));
+    assert_eq!(
+        vec!["poll", "heartbeat", "heartbeat", "poll"],
+        *log.borrow()
+    );
+
+    log.borrow_mut().clear();
+    heartbeat.cancel();
+    crate::advance_time(Duration::from_millis(150));
+    assert_eq!(vec!["poll"], *log.borrow());
+}
+
+#[test]
+fn idle_callbacks() {
+    use std::time::Duration;
+    struct Heartbeat;
+    struct Saved;
+    let saves = Rc::new(Cell::new(0));
+    let s = saves.clone();
+    crate::subscribe(move |_: &Saved| s.set(s.get() + 1));
+    crate::every(Duration::from_secs(1), || Heartbeat);
+    let idle = crate::on_idle(Duration::from_secs(3), || crate::publish(Saved));
+
+    crate::publish(TestUpdateMsg);
+    crate::advance_time(Duration::from_secs(2));
+    crate::advance_time(Duration::from_secs(2));
+    assert_eq!(0, saves.get());
+    // Neither the heartbeat nor the callback itself count as activity, the callback is called once
+    crate::advance_time(Duration::from_secs(1));
+    crate::advance_time(Duration::from_secs(5));
+    assert_eq!(1, saves.get());
+
+    crate::publish(TestUpdateMsg);
+    crate::advance_time(Duration::from_secs(1));
+    crate::advance_time(Duration::from_secs(3));
+    assert_eq!(2, saves.get());
+
+    idle.cancel();
+    crate::publish(TestUpdateMsg);
+    crate::advance_time(Duration::from_secs(10));
+    crate::advance_time(Duration::from_secs(10));
+    assert_eq!(2, saves.get());
+}
+
+#[test]
+fn attached_future() {
+    struct Loader;
+    struct Loaded;
+    struct Ping;
+    struct DropGuard(Rc<Cell<u32>>);
+    impl Drop for DropGuard {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 100);
+        }
+    }
+    let counter = Rc::new(Cell::new(0));
+    let c = counter.clone();
+    crate::subscribe(move |_: &Loaded| c.set(c.get() + 1));
+
+    let id = crate::new_activity(Loader);
+    let c = counter.clone();
+    id.attach_future(async move {
+        crate::publish_awaiting(Loaded).await_dispatch().await;
+        c.set(c.get() + 10);
+    });
+    assert_eq!(11, counter.get());
+
+    // A future that never completes is dropped with its activity
+    let guard = DropGuard(counter.clone());
+    id.attach_future(async move {
+        let _guard = guard;
+        core::future::pending::<()>().await;
+    });
+    crate::publish(Ping);
+    assert_eq!(11, counter.get());
+    id.set_status(LifecycleStatus::Deleted);
+    assert_eq!(111, counter.get());
+}
+
+#[test]
+fn attached_future_policies() {
+    use crate::{Error, TaskPolicy};
+    use futures::channel::oneshot;
+    struct Owner;
+    struct Observer;
+    let outcomes = Rc::new(std::cell::RefCell::new(vec![]));
+
+    let owner = crate::new_activity(Owner);
+    let (cancelled_tx, cancelled_rx) = oneshot::channel::<()>();
+    let cancelled = owner.attach_future_with(TaskPolicy::Cancel, async move {
+        let _ = cancelled_rx.await;
+    });
+    let (detached_tx, detached_rx) = oneshot::channel::<()>();
+    let detached = owner.attach_future_with(TaskPolicy::Detach, async move {
+        let _ = detached_rx.await;
+    });
+
+    let observer = crate::new_activity(Observer);
+    let o = outcomes.clone();
+    observer.attach_future(async move {
+        let outcome = cancelled.await;
+        o.borrow_mut().push(outcome);
+        let outcome = detached.await;
+        o.borrow_mut().push(outcome);
+    });
+
+    owner.set_status(LifecycleStatus::Deleted);
+    assert_eq!(vec![Err(Error::TaskCancelled)], *outcomes.borrow());
+    assert!(
+        cancelled_tx.send(()).is_err(),
+        "cancelled future has been dropped"
+    );
+
+    detached_tx
+        .send(())
+        .expect("detached future is still alive");
+    crate::poll_tasks();
+    assert_eq!(vec![Err(Error::TaskCancelled), Ok(())], *outcomes.borrow());
+}
+
+#[test]
+fn control_messages() {
+    use crate::control::*;
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity, msg: &TestMessage| activity.inc(msg.0));
+
+    crate::publish(PauseQueue);
+    crate::publish(TestMessage(1));
+    assert!(publish_command("dump"));
+    assert_eq!(0, counter.get());
+    assert!(dump_state().contains("queue paused: yes, 1 events held back"));
+
+    assert!(publish_command("resume"));
+    assert_eq!(1, counter.get());
+    assert!(dump_state().contains("queue paused: no"));
+
+    assert!(!publish_command("log loud"));
+    assert!(!publish_command("reboot"));
+}
+
+#[test]
+fn process_held_events_in_chunks() {
+    use crate::control::*;
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity, msg: &TestMessage| {
+        activity.inc(1);
+        if msg.0 > 0 {
+            crate::publish(TestMessage(0));
+        }
+    });
+
+    assert_eq!(0, crate::process_at_most(10));
+    crate::publish(PauseQueue);
+    crate::publish(TestMessage(1));
+    crate::publish(TestMessage(1));
+    // The second message and the follow-up of the first one remain
+    assert_eq!(2, crate::process_at_most(1));
+    assert_eq!(1, counter.get());
+    assert_eq!(2, crate::process_at_most(1));
+    assert_eq!(2, counter.get());
+    assert_eq!(0, crate::process_at_most(10));
+    assert_eq!(4, counter.get());
+    crate::publish(ResumeQueue);
+    crate::publish(TestMessage(0));
+    assert_eq!(5, counter.get());
+}
+
+#[test]
+fn process_limited_drain_in_chunks() {
+    crate::configure(crate::ConfigBuilder::new().drain_limit(2))
+        .expect("configured before the first publish");
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity, msg: &TestMessage| {
+        activity.inc(1);
+        for _ in 0..msg.0 {
+            crate::publish(TestMessage(0));
+        }
+    });
+
+    crate::publish(TestMessage(5));
+    assert_eq!(2, counter.get());
+    assert_eq!(2, crate::process_at_most(2));
+    assert_eq!(4, counter.get());
+    // The next drain continues with the waiting events
+    crate::publish(TestMessage(0));
+    assert_eq!(6, counter.get());
+    assert_eq!(1, crate::process_at_most(0));
+    assert_eq!(0, crate::process_at_most(10));
+    assert_eq!(7, counter.get());
+}
+
+#[test]
+fn limited_drain_ends_urgent_priority() {
+    struct N(u32);
+    struct U;
+    crate::configure(crate::ConfigBuilder::new().drain_limit(2))
+        .expect("configured before the first publish");
+    let order = Rc::new(std::cell::RefCell::new(vec![]));
+    let o = order.clone();
+    crate::subscribe(move |n: &N| {
+        o.borrow_mut().push(n.0);
+        if n.0 == 0 {
+            crate::publish(N(1));
+            std::mem::drop(crate::publish_awaiting_response_urgent(U));
+        }
+    });
+    crate::subscribe(|_: &U| {});
+    // The drain stops right after the urgent message
+    crate::publish(N(0));
+    crate::publish(N(2));
+    while crate::process_at_most(10) > 0 {}
+    assert_eq!(vec![0, 1, 2], *order.borrow());
+}
+
+#[test]
+fn aliased_message_types() {
+    #[derive(Clone, Copy)]
+    struct OldMsg(u32);
+    struct NewMsg(u32);
+    struct NewestMsg(u32);
+    let counter = Rc::new(Cell::new(0));
+    let c = counter.clone();
+    crate::subscribe(move |msg: &NewestMsg| c.set(c.get() + msg.0));
+    let c = counter.clone();
+    crate::subscribe(move |_: &OldMsg| c.set(c.get() + 1000));
+
+    crate::alias(|old: OldMsg| NewMsg(old.0));
+    crate::alias(|new: NewMsg| NewestMsg(new.0 * 10));
+    crate::publish(OldMsg(1));
+    crate::publish_copy(OldMsg(2));
+    crate::publish(NewMsg(3));
+    assert_eq!(60, counter.get());
+
+    // Publishes of NewMsg include the converted OldMsg publishes
+    let mut publishes: Vec<u64> = crate::alias_report().iter().map(|r| r.publishes).collect();
+    publishes.sort_unstable();
+    assert_eq!(vec![2, 3], publishes);
+}
+
+#[test]
+fn mapped_message_types() {
+    #[derive(Clone, Copy)]
+    struct OldMsg(u32);
+    #[derive(Clone, Copy)]
+    struct NewMsg(u32);
+    let counter = Rc::new(Cell::new(0));
+    let c = counter.clone();
+    crate::subscribe(move |msg: &NewMsg| c.set(c.get() + msg.0));
+
+    crate::map_messages(|msg: &mut NewMsg| msg.0 *= 10);
+    crate::publish(NewMsg(1));
+    crate::publish_copy(NewMsg(2));
+    assert_eq!(30, counter.get());
+
+    // Aliased messages are mapped as the new type, a second mapper replaces the first one
+    crate::alias(|old: OldMsg| NewMsg(old.0));
+    crate::map_messages(|msg: &mut NewMsg| msg.0 += 100);
+    crate::publish(OldMsg(3));
+    assert_eq!(133, counter.get());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn find_unused_subscriptions() {
+    struct Used;
+    struct Unused;
+    let a = TestActivity::new();
+    let id = crate::new_activity(a);
+    id.subscribe(|_, _: &Used| {});
+    id.subscribe(|_, _: &Unused| {});
+    crate::publish(Used);
+    crate::publish(Used);
+
+    let unused = crate::unused_subscriptions(2);
+    assert_eq!(1, unused.len());
+    assert!(unused[0].topic.ends_with("Unused"));
+    assert_eq!(2, unused[0].broadcasts);
+    assert!(crate::unused_subscriptions(3).is_empty());
+}
+
+#[test]
+fn double_buffered_state() {
+    struct Update(u32);
+    struct Draw;
+    let seen = Rc::new(Cell::new(0));
+    let (writer, reader) = crate::double_buffer::<Vec<u32>>();
+    let r = reader.clone();
+    let s = seen.clone();
+    crate::subscribe(move |msg: &Update| {
+        writer.write(|state| state.push(msg.0));
+        // Not visible before the broadcast has been handled
+        s.set(r.read().len());
+        crate::publish(Draw);
+    });
+    let r = reader.clone();
+    let s = seen.clone();
+    crate::subscribe(move |_: &Draw| s.set(s.get() * 10 + r.read().len()));
+
+    crate::publish(Update(7));
+    assert_eq!(1, seen.get());
+    crate::publish(Update(8));
+    assert_eq!(12, seen.get());
+    assert_eq!(vec![7, 8], *reader.read());
+}
+
+#[test]
+fn count_subscribers() {
+    struct Diagnostics;
+    assert_eq!(0, crate::subscriber_count::<Diagnostics>());
+    crate::subscribe(|_: &Diagnostics| {});
+    let id = crate::new_activity(TestActivity::new());
+    id.subscribe(|_, _: &Diagnostics| {});
+    id.subscribe(|_, _: &TestMessage| {
+        assert_eq!(2, crate::subscriber_count::<Diagnostics>());
+        crate::subscribe(|_: &Diagnostics| {});
+        assert_eq!(2, crate::subscriber_count::<Diagnostics>());
+    });
+    crate::publish(TestMessage(0));
+    assert_eq!(3, crate::subscriber_count::<Diagnostics>());
+}
+
+#[test]
+fn publish_group_adjacent() {
+    use std::cell::RefCell;
+    struct EntityCreated;
+    struct TransformSet;
+    struct Reaction;
+    let log = Rc::new(RefCell::new(vec![]));
+    let id = crate::new_activity(());
+    let l = log.clone();
+    id.subscribe(move |_, _: &EntityCreated| {
+        l.borrow_mut().push("created");
+        crate::publish(Reaction);
+    });
+    let l = log.clone();
+    id.subscribe(move |_, _: &TransformSet| l.borrow_mut().push("transform"));
+    let l = log.clone();
+    id.subscribe(move |_, _: &Reaction| l.borrow_mut().push("reaction"));
+
+    crate::publish_group()
+        .with(EntityCreated)
+        .with(TransformSet)
+        .publish();
+    assert_eq!(*log.borrow(), ["created", "transform", "reaction"]);
+}
+
+#[test]
+#[cfg(feature = "shared-audit")]
+fn shared_payload_audit() {
+    use std::cell::RefCell;
+    struct Handle<T>(T);
+    crate::publish(TestMessage(1));
+    crate::publish(Rc::new(RefCell::new(TestMessage(1))));
+    crate::publish(Handle(Rc::new(1u32)));
+    crate::publish(Handle(Rc::new(1u32)));
+    assert_eq!(2, crate::shared_payload_warnings().len());
+
+    crate::allow_shared::<Handle<Rc<u32>>>();
+    crate::publish(Handle(Rc::new(1u32)));
+    assert_eq!(1, crate::shared_payload_warnings().len());
+}
+
+#[test]
+fn run_frame_delivers_per_phase() {
+    use crate::Phase;
+    use std::cell::RefCell;
+    struct Key;
+    struct Moved;
+    let log = Rc::new(RefCell::new(vec![]));
+
+    let l = log.clone();
+    let plain = crate::new_activity(());
+    plain.subscribe(move |_, _: &Key| l.borrow_mut().push("plain key"));
+    let render = crate::new_activity(1u8);
+    render.set_phase(Phase::Render);
+    let l = log.clone();
+    render.subscribe(move |_, _: &Key| l.borrow_mut().push("render key"));
+    let l = log.clone();
+    render.subscribe(move |_, _: &Moved| l.borrow_mut().push("render moved"));
+    let input = crate::new_activity(2u8);
+    input.set_phase(Phase::Input);
+    let l = log.clone();
+    input.subscribe(move |_, _: &Key| l.borrow_mut().push("input key"));
+    let l = log.clone();
+    input.subscribe(move |_, _: &Moved| l.borrow_mut().push("input moved"));
+    let update = crate::new_activity(3u8);
+    update.set_phase(Phase::Update);
+    let l = log.clone();
+    update.subscribe(move |_, _: &Key| {
+        l.borrow_mut().push("update key");
+        crate::publish(Moved);
+    });
+
+    crate::publish(Key);
+    assert_eq!(vec!["plain key"], *log.borrow());
+    log.borrow_mut().clear();
+
+    crate::run_frame();
+    assert_eq!(
+        vec!["input key", "update key", "render key", "render moved"],
+        *log.borrow()
+    );
+    log.borrow_mut().clear();
+
+    crate::run_frame();
+    assert_eq!(
+        vec!["input moved"],
+        *log.borrow(),
+        "earlier phases receive messages in the next frame"
+    );
+    log.borrow_mut().clear();
+
+    crate::run_frame();
+    assert!(log.borrow().is_empty());
+}
+
+#[test]
+fn subscribe_dyn_receives_family_members() {
+    trait Weighted {
+        fn weight(&self) -> u32;
+    }
+    struct Light;
+    struct Heavy(u32);
+    struct Unrelated;
+    impl Weighted for Light {
+        fn weight(&self) -> u32 {
+            1
+        }
+    }
+    impl Weighted for Heavy {
+        fn weight(&self) -> u32 {
+            self.0
+        }
+    }
+
+    crate::register_dyn::<dyn Weighted, Light>(|m| m);
+    let id = crate::new_activity(0u32);
+    id.subscribe_dyn::<dyn Weighted>(|total, msg| *total += msg.weight());
+    // Registered after the subscription, and twice
+    crate::register_dyn::<dyn Weighted, Heavy>(|m| m);
+    crate::register_dyn::<dyn Weighted, Heavy>(|m| m);
+
+    crate::publish(Light);
+    crate::publish(Heavy(10));
+    crate::publish(Unrelated);
+    let total = crate::nut::read_activity(id, |total| *total);
+    assert_eq!(Some(11), total);
+}
+
+#[test]
+fn configure_before_first_publish() {
+    use crate::{ConfigBuilder, DispatchOrder, Error};
+    use std::cell::RefCell;
+    assert_eq!(
+        Err(Error::InvalidConfig("the queue limit must be at least 1")),
+        crate::configure(ConfigBuilder::new().queue_limit(0))
+    );
+    crate::configure(ConfigBuilder::new().dispatch_order(DispatchOrder::Creation))
+        .expect("configured before publish");
+
+    let order = Rc::new(RefCell::new(vec![]));
+    for i in 0..8 {
+        let o = order.clone();
+        crate::new_activity(i).subscribe(move |i, _: &TestMessage| o.borrow_mut().push(*i));
+    }
+    crate::publish(TestMessage(0));
+    assert_eq!((0..8).collect::<Vec<_>>(), *order.borrow());
+    assert_eq!(
+        Err(Error::ConfiguredAfterPublish),
+        crate::configure(ConfigBuilder::new())
+    );
+}
+
+#[test]
+fn caught_handler_panic_continues() {
+    use crate::{ConfigBuilder, DispatchOrder, PanicPolicy};
+    crate::configure(
+        ConfigBuilder::new()
+            .panic_policy(PanicPolicy::CatchAndContinue)
+            .dispatch_order(DispatchOrder::Creation),
+    )
+    .expect("configured before publish");
+    let calls = Rc::new(Cell::new(0));
+    crate::new_activity(()).subscribe(|_, _: &TestMessage| panic!("handler failed"));
+    let c = calls.clone();
+    crate::new_activity(()).subscribe(move |_, msg: &TestMessage| c.set(c.get() + msg.0));
+    crate::publish(TestMessage(1));
+    crate::publish(TestMessage(2));
+    assert_eq!(3, calls.get());
+}
+
+#[test]
+#[should_panic(expected = "queue limit")]
+fn queue_limit_stops_endless_cascade() {
+    struct Ping;
+    crate::configure(crate::ConfigBuilder::new().queue_limit(2)).expect("valid limit");
+    crate::subscribe(|_: &Ping| {
+        crate::publish(Ping);
+        crate::publish(Ping);
+    });
+    crate::publish(Ping);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn subscription_table_as_json() {
+    struct Ping;
+    let id = crate::new_activity(TestActivity::new());
+    id.subscribe(|_, _: &TestMessage| {});
+    id.subscribe_mut(|_, _: &mut TestMessage| {});
+    id.private_channel(|_, _: Ping| {});
+    crate::subscribe(|_: &TestMessage| {});
+
+    let json = crate::subscription_table().to_json();
+    let expected = format!(
+        "[\n  {{\"topic\": \"{ping}\", \"private\": true, \"activity\": \"{act}\", \"handlers\": 1}},\n  {{\"topic\": \"{msg}\", \"private\": false, \"activity\": null, \"handlers\": 1}},\n  {{\"topic\": \"{msg}\", \"private\": false, \"activity\": \"{act}\", \"handlers\": 2}}\n]\n",
+        msg = std::any::type_name::<TestMessage>(),
+        act = std::any::type_name::<TestActivity>(),
+        ping = std::any::type_name::<Ping>(),
+    );
+    assert_eq!(expected, json);
+}
+
+#[test]
+fn ask_activity_for_reply() {
+    struct Counter(u32);
+    struct Add(u32);
+    struct Total;
+    let counter = crate::new_activity(Counter(0));
+    counter.subscribe_request(|c, Add(n)| {
+        c.0 += n;
+        c.0
+    });
+    counter.subscribe_request(|c, _: Total| c.0);
+    // The first future is not polled before the second request is handled
+    let first = counter.ask::<_, u32>(Add(2));
+    let second = counter.ask::<_, u32>(Add(3));
+    assert_eq!(Ok(5), futures::executor::block_on(second));
+    assert_eq!(Ok(2), futures::executor::block_on(first));
+    assert_eq!(
+        Ok(5),
+        futures::executor::block_on(counter.ask::<_, u32>(Total))
+    );
+}
+
+#[test]
+fn ask_without_request_handler() {
+    struct Question;
+    let id = crate::new_activity(());
+    assert_eq!(
+        Err(crate::Error::NoReply),
+        futures::executor::block_on(id.ask::<_, u32>(Question))
+    );
+    // A deleted activity does not reply either
+    id.subscribe_request(|_, _: Question| 1u32);
+    id.set_status(crate::LifecycleStatus::Deleted);
+    assert_eq!(
+        Err(crate::Error::NoReply),
+        futures::executor::block_on(id.ask::<_, u32>(Question))
+    );
+}
+
+#[test]
+fn request_from_single_responder() {
+    struct Primary;
+    struct Fallback;
+    struct Lookup(u32);
+    let primary = crate::new_activity(Primary);
+    let fallback = crate::new_activity(Fallback);
+    let old = primary.respond(|_, Lookup(n)| n * 2);
+    assert_eq!(
+        Ok(4),
+        futures::executor::block_on(crate::request::<_, u32>(Lookup(2)))
+    );
+
+    // A new responder replaces the old one, which is unsubscribed
+    fallback.respond(|_, Lookup(n)| n * 10);
+    assert_eq!(None, old.stats());
+    assert_eq!(
+        Ok(20),
+        futures::executor::block_on(crate::request::<_, u32>(Lookup(2)))
+    );
+    // Responders are registered per pair of request and response type
+    fallback.respond(|_, Lookup(n)| format!("#{}", n));
+    assert_eq!(
+        Ok(String::from("#3")),
+        futures::executor::block_on(crate::request::<_, String>(Lookup(3)))
+    );
+    assert_eq!(
+        Ok(30),
+        futures::executor::block_on(crate::request::<_, u32>(Lookup(3)))
+    );
+}
+
+#[test]
+#[should_panic(expected = "No responder")]
+fn request_without_responder() {
+    struct Question;
+    let _reply = crate::request::<_, u32>(Question);
+}
+
+#[test]
+fn request_to_deleted_responder() {
+    struct Lookup(u32);
+    struct Trigger;
+    let responder = crate::new_activity(());
+    responder.respond(|_, Lookup(n)| n);
+    let reply = Rc::new(std::cell::RefCell::new(None));
+    let r = reply.clone();
+    crate::subscribe(move |_: &Trigger| {
+        responder.set_status(LifecycleStatus::Deleted);
+        *r.borrow_mut() = Some(crate::request::<_, u32>(Lookup(1)));
+    });
+    crate::publish(Trigger);
+    let reply = reply.borrow_mut().take().expect("request sent");
+    assert_eq!(
+        Err(crate::Error::NoReply),
+        futures::executor::block_on(reply)
+    );
+}
+
+#[test]
+#[should_panic(expected = "No responder")]
+fn request_after_responder_has_been_deleted() {
+    struct Question;
+    let responder = crate::new_activity(());
+    responder.respond(|_, _: Question| 1u32);
+    responder.set_status(LifecycleStatus::Deleted);
+    let _reply = crate::request::<_, u32>(Question);
+}
+
+#[test]
+fn simulation_steps_are_reproducible() {
+    use crate::sim::SimTick;
+    let draws = Rc::new(std::cell::RefCell::new(vec![]));
+    let d = draws.clone();
+    crate::subscribe(move |tick: &SimTick| {
+        // Messages published in reaction to a tick are processed within the step
+        crate::publish(TestMessage(tick.rng().below(1000) as u32));
+    });
+    crate::subscribe(move |msg: &TestMessage| d.borrow_mut().push(msg.0));
+
+    crate::sim::run(5, 7);
+    assert_eq!(5, draws.borrow().len());
+    let first = draws.replace(vec![]);
+    crate::sim::reset_index();
+    crate::sim::run(5, 7);
+    assert_eq!(first, *draws.borrow());
+    assert_eq!(5, crate::sim::step(0).index);
+}
+
+#[test]
+#[should_panic(expected = "queue is paused")]
+fn simulation_step_on_paused_queue() {
+    crate::publish(crate::control::PauseQueue);
+    crate::sim::step(1);
+}
+
+#[test]
+fn keyed_messages_keep_order_per_key() {
+    struct Ping;
+    let a = crate::new_activity(Vec::<(u8, u32)>::new());
+    a.subscribe_keyed(|log, key: &u8, msg: &TestMessage| log.push((*key, msg.0)));
+    // Reactions are queued behind the messages published before them with the same key
+    a.subscribe_keyed(|_, key: &u8, msg: &TestMessage| {
+        if msg.0 == 1 {
+            crate::publish_keyed(*key, TestMessage(3));
+        }
+    });
+    a.subscribe(|_, _: &Ping| {
+        crate::publish_keyed(1u8, TestMessage(1));
+        crate::publish_keyed(2u8, TestMessage(1));
+        crate::publish_keyed(1u8, TestMessage(2));
+        // Other key types are separate topics
+        crate::publish_keyed(1u16, TestMessage(9));
+    });
+    crate::publish(Ping);
+
+    let log = crate::nut::read_activity(a, |log| log.clone()).expect("activity is alive");
+    let per_key = |k: u8| -> Vec<u32> {
+        log.iter()
+            .filter(|(key, _)| *key == k)
+            .map(|(_, v)| *v)
+            .collect()
+    };
+    assert_eq!(vec![1, 2, 3], per_key(1));
+    assert_eq!(vec![1, 3], per_key(2));
+    assert_eq!(5, log.len());
+}
+
+#[test]
+fn digest_by_count_and_time() {
+    use std::time::Duration;
+    #[derive(Clone)]
+    struct Sample(u32);
+    let counted = Rc::new(std::cell::RefCell::new(vec![]));
+    let timed = Rc::new(std::cell::RefCell::new(vec![]));
+    let id = crate::new_activity(());
+    let c = counted.clone();
+    id.digest(3)
+        .subscribe(move |_, batch: Vec<Sample>| c.borrow_mut().push(batch.len()));
+    let t = timed.clone();
+    id.digest(Duration::from_millis(100))
+        .subscribe(move |_, batch: Vec<Sample>| {
+            t.borrow_mut()
+                .push(batch.iter().map(|msg| msg.0).collect::<Vec<_>>())
+        });
+
+    for i in 0..7 {
+        crate::publish(Sample(i));
+    }
+    assert_eq!(vec![3, 3], *counted.borrow());
+    assert!(timed.borrow().is_empty());
+
+    crate::advance_time(Duration::from_millis(100));
+    assert_eq!(vec![vec![0, 1, 2, 3, 4, 5, 6]], *timed.borrow());
+    // Nothing collected, nothing delivered
+    crate::advance_time(Duration::from_millis(100));
+    assert_eq!(1, timed.borrow().len());
+
+    crate::publish(Sample(7));
+    id.set_status(LifecycleStatus::Deleted);
+    crate::advance_time(Duration::from_millis(100));
+    assert_eq!(1, timed.borrow().len());
+}
+
+#[test]
+fn subscription_with_private_state() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe_with_state(0u32, |activity, calls: &mut u32, msg: &TestMessage| {
+        *calls += 1;
+        activity.inc(*calls * msg.0);
+    });
+    // Each subscription has its own state
+    id.subscribe_with_state(100u32, |activity, calls: &mut u32, _: &TestMessage| {
+        *calls += 1;
+        activity.inc(*calls);
+    });
+
+    crate::publish(TestMessage(1));
+    crate::publish(TestMessage(10));
+    assert_eq!(1 + 20 + 101 + 102, counter.get());
+}
+
+#[test]
+fn consumed_message_skips_remaining_handlers() {
+    use crate::MessageControl;
+    crate::configure(crate::ConfigBuilder::new().dispatch_order(crate::DispatchOrder::Creation))
+        .expect("not published yet");
+    let calls = Rc::new(std::cell::RefCell::new(vec![]));
+    for i in 0..3u32 {
+        let c = calls.clone();
+        let id = crate::new_activity(i);
+        id.subscribe_with(
+            move |i: &mut u32, msg: &TestMessage, control: MessageControl| {
+                c.borrow_mut().push((*i, control.remaining_subscribers()));
+                if msg.0 == *i {
+                    control.consume();
+                }
+            },
+        );
+    }
+    // Readers are called first and always see the message
+    let c = calls.clone();
+    let reader = crate::new_activity(());
+    reader.subscribe(move |_, _: &TestMessage| c.borrow_mut().push((99, 0)));
+
+    crate::publish(TestMessage(1));
+    assert_eq!(vec![(99, 0), (0, 2), (1, 1)], calls.replace(vec![]));
+    crate::publish(TestMessage(5));
+    assert_eq!(vec![(99, 0), (0, 2), (1, 1), (2, 0)], calls.replace(vec![]));
+}
+
+#[test]
+fn deferred_closure_runs_without_borrows() {
+    let log = Rc::new(std::cell::RefCell::new(vec![]));
+    let l = log.clone();
+    let id = crate::new_activity(());
+    id.subscribe(move |_, _: &TestMessage| {
+        let l2 = l.clone();
+        crate::defer(move || {
+            // Activities can be read again
+            l2.borrow_mut()
+                .push(format!("deferred, valid: {}", id.is_valid()));
+        });
+        l.borrow_mut().push("handler".to_owned());
+    });
+    crate::publish(TestMessage(0));
+    assert_eq!(vec!["handler", "deferred, valid: true"], *log.borrow());
+
+    let l = log.clone();
+    crate::defer(move || l.borrow_mut().push("immediately".to_owned()));
+    assert_eq!("immediately", log.borrow()[2]);
+}
+
+#[test]
+fn restricted_message_published_with_token() {
+    let token = crate::restrict_publish::<TestMessage>().expect("not restricted yet");
+    assert_eq!(
+        Err(crate::Error::PublishTokenTaken),
+        crate::restrict_publish::<TestMessage>().map(|_| ())
+    );
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity, msg: &TestMessage| activity.inc(msg.0));
+    token.publish(TestMessage(3));
+    // Other types are not affected
+    crate::publish(TestUpdateMsg);
+    assert_eq!(3, counter.get());
+}
+
+#[test]
+#[should_panic(expected = "can only be published with its PublishToken")]
+fn restricted_message_published_without_token() {
+    let _token = crate::restrict_publish::<TestMessage>().expect("not restricted yet");
+    crate::publish(TestMessage(3));
+}
+
+#[test]
+fn scratch_is_shared_within_cascade_and_cleared_after() {
+    struct Start;
+    struct Collect;
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|_activity: &mut TestActivity, _msg: &Start| {
+        assert_eq!(None, crate::scratch().insert(3u32));
+        crate::publish(Collect);
+    });
+    id.subscribe(|activity: &mut TestActivity, _msg: &Collect| {
+        let value: u32 = crate::scratch()
+            .get()
+            .expect("stored by the previous handler");
+        activity.inc(value);
+    });
+    crate::publish(Start);
+    assert_eq!(3, counter.get());
+    assert!(!crate::scratch().contains::<u32>());
+
+    // values stored outside of handlers survive until the next cascade has been processed
+    crate::scratch().insert(5u32);
+    assert_eq!(Some(5), crate::scratch().get::<u32>());
+    crate::publish(Collect);
+    assert_eq!(8, counter.get());
+    assert!(!crate::scratch().contains::<u32>());
+}
+
+#[test]
+fn timed_subscription_receives_elapsed_time() {
+    use std::time::Duration;
+    let clock = crate::MockClock::new();
+    crate::set_clock(clock.clone());
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe_timed(|activity: &mut TestActivity, _msg: &TestUpdateMsg, dt| {
+        activity.inc(dt.as_millis() as u32);
+    });
+
+    crate::publish(TestUpdateMsg);
+    assert_eq!(0, counter.get(), "no time elapsed before the first call");
+    clock.advance(Duration::from_millis(16));
+    crate::publish(TestUpdateMsg);
+    assert_eq!(16, counter.get());
+
+    // skipped calls while inactive do not reset the time of the last call
+    id.set_status(LifecycleStatus::Inactive);
+    clock.advance(Duration::from_millis(10));
+    crate::publish(TestUpdateMsg);
+    id.set_status(LifecycleStatus::Active);
+    clock.advance(Duration::from_millis(5));
+    crate::publish(TestUpdateMsg);
+    assert_eq!(31, counter.get());
+}
+
+#[test]
+fn codec_round_trip_publishes_decoded_message() {
+    #[derive(Default)]
+    struct ByteCodec;
+    impl crate::MessageCodec<TestMessage> for ByteCodec {
+        fn encode(&self, msg: &TestMessage) -> Vec<u8> {
+            vec![msg.0 as u8]
+        }
+        fn decode(&self, bytes: &[u8]) -> Option<TestMessage> {
+            match bytes {
+                [byte] => Some(TestMessage(u32::from(*byte))),
+                _ => None,
+            }
+        }
+    }
+
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity: &mut TestActivity, msg: &TestMessage| {
+        activity.inc(msg.0);
+    });
+
+    assert_eq!(
+        Err(crate::Error::NoCodec),
+        crate::encode_message(&TestMessage(3))
+    );
+    crate::register_codec::<TestMessage, ByteCodec>();
+    let encoded = crate::encode_message(&TestMessage(3)).expect("codec registered");
+    assert_eq!(vec![3], encoded.bytes);
+    assert_eq!(0, counter.get(), "encoding does not publish");
+
+    let received = crate::EncodedMessage::from_parts(encoded.type_name, encoded.bytes)
+        .expect("type name registered");
+    crate::publish_encoded(&received).expect("valid bytes");
+    assert_eq!(3, counter.get());
+
+    let malformed = crate::EncodedMessage::from_parts(encoded.type_name, vec![1, 2])
+        .expect("type name registered");
+    assert_eq!(
+        Err(crate::Error::MalformedMessage),
+        crate::publish_encoded(&malformed)
+    );
+    assert_eq!(3, counter.get());
+    assert_eq!(
+        Some(crate::Error::NoCodec),
+        crate::EncodedMessage::from_parts("unknown::Message", vec![]).err()
+    );
+}
+
+#[test]
+fn parallel_safe_handlers_are_called_first() {
+    use std::sync::{Arc, Mutex};
+    struct Reader;
+    impl crate::ParallelSafe for Reader {}
+    let order = Arc::new(Mutex::new(vec![]));
+
+    let id = crate::new_activity(TestActivity::new());
+    let o = order.clone();
+    id.subscribe_mut(move |_, _: &mut TestUpdateMsg| {
+        o.lock().expect("lock poisoned").push("mutating")
+    });
+    let o = order.clone();
+    id.subscribe(move |_, _: &TestUpdateMsg| o.lock().expect("lock poisoned").push("shared"));
+    let reader = crate::new_activity(Reader);
+    let o = order.clone();
+    reader.subscribe_parallel(move |_, _: &TestUpdateMsg| {
+        o.lock().expect("lock poisoned").push("parallel")
+    });
+
+    crate::publish(TestUpdateMsg);
+    assert_eq!(
+        vec!["parallel", "shared", "mutating"],
+        *order.lock().expect("lock poisoned")
+    );
+}
+
+#[test]
+fn async_factory_holds_private_messages() {
+    use futures::channel::oneshot;
+    struct Loader;
+    let (tx, rx) = oneshot::channel::<TestActivity>();
+    crate::new_activity(Loader).attach_future(async move {
+        let factory = async move { rx.await.expect("activity sent") };
+        let id = crate::new_activity_async(factory).await;
+        id.private_channel(|activity: &mut TestActivity, msg: TestMessage| activity.inc(msg.0));
+    });
+
+    crate::send_to::<TestActivity, _>(TestMessage(2));
+    crate::send_to::<TestActivity, _>(TestMessage(3));
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    tx.send(a).ok().expect("loader waiting");
+    crate::poll_tasks();
+    assert_eq!(0, counter.get(), "held until the next publish");
+    crate::publish(TestUpdateMsg);
+    assert_eq!(5, counter.get(), "held messages delivered after creation");
+
+    crate::send_to::<TestActivity, _>(TestMessage(4));
+    assert_eq!(9, counter.get());
+}
+
+#[test]
+fn try_setup_rolls_back_on_error_and_panic() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity: &mut TestActivity, _: &TestUpdateMsg| activity.inc(1));
+
+    let result: Result<(), &str> = id.try_setup(|id| {
+        id.subscribe(|activity: &mut TestActivity, _: &TestUpdateMsg| activity.inc(10));
+        id.subscribe(|activity: &mut TestActivity, msg: &TestMessage| activity.inc(msg.0));
+        id.on_leave(|activity| activity.inc(100));
+        Err("failed")
+    });
+    assert_eq!(Err("failed"), result);
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _: Result<(), ()> = id.try_setup(|id| {
+            id.private_channel(|activity: &mut TestActivity, msg: TestForInt| {
+                activity.inc(msg.0 as u32)
+            });
+            panic!("setup failed");
+        });
+    }));
+    assert!(panicked.is_err());
+
+    crate::publish(TestUpdateMsg);
+    crate::publish(TestMessage(1000));
+    crate::send_to::<TestActivity, _>(TestForInt(1000));
+    id.set_status(LifecycleStatus::Inactive);
+    assert_eq!(
+        1,
+        counter.get(),
+        "only the handler registered before the setup remains"
+    );
+    id.set_status(LifecycleStatus::Active);
+
+    let value = id.try_setup(|id| {
+        id.subscribe(|activity: &mut TestActivity, msg: &TestMessage| activity.inc(msg.0));
+        Ok::<_, ()>(7)
+    });
+    assert_eq!(Ok(7), value);
+    crate::publish(TestMessage(2));
+    assert_eq!(3, counter.get(), "successful setup is kept");
+}
+
+#[test]
+fn scoped_messages_stay_in_their_scope() {
+    struct PluginA;
+    impl crate::ScopeToken for PluginA {}
+    struct PluginB;
+    impl crate::ScopeToken for PluginB {}
+
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity: &mut TestActivity, msg: &TestMessage| activity.inc(msg.0));
+    id.subscribe_scoped::<PluginA, _, _>(|activity: &mut TestActivity, msg: &TestMessage| {
+        activity.inc(10 * msg.0)
+    });
+
+    crate::publish_scoped::<PluginA, _>(TestMessage(1));
+    assert_eq!(10, counter.get());
+    crate::publish_scoped::<PluginB, _>(TestMessage(1));
+    assert_eq!(10, counter.get(), "other scope not received");
+    crate::publish(TestMessage(1));
+    assert_eq!(
+        11,
+        counter.get(),
+        "unscoped message only reaches unscoped handler"
+    );
+}
+
+#[test]
+fn dispatch_weights_order_activities() {
+    struct Background;
+    struct Foreground;
+    struct Overlay;
+    let order = Rc::new(std::cell::RefCell::new(vec![]));
+
+    let overlay = crate::new_activity(Overlay);
+    let foreground = crate::new_activity(Foreground);
+    let background = crate::new_activity(Background);
+    let o = order.clone();
+    overlay.subscribe(move |_, _: &TestUpdateMsg| o.borrow_mut().push("overlay"));
+    let o = order.clone();
+    foreground.subscribe(move |_, _: &TestUpdateMsg| o.borrow_mut().push("foreground"));
+    let o = order.clone();
+    background.subscribe(move |_, _: &TestUpdateMsg| o.borrow_mut().push("background"));
+    overlay.set_dispatch_weight::<TestUpdateMsg>(10);
+    background.set_dispatch_weight::<TestUpdateMsg>(-10);
+
+    crate::publish(TestUpdateMsg);
+    assert_eq!(
+        vec!["background", "foreground", "overlay"],
+        order.replace(vec![])
+    );
+
+    background.set_dispatch_weight::<TestUpdateMsg>(20);
+    crate::publish(TestUpdateMsg);
+    assert_eq!(
+        vec!["foreground", "overlay", "background"],
+        order.replace(vec![])
+    );
+}
+
+#[test]
+fn protected_messages_reject_mutable_handlers() {
+    struct Input(u32);
+    impl crate::ImmutableMessage for Input {}
+    crate::protect_message::<Input>();
+
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity: &mut TestActivity, msg: &Input| activity.inc(msg.0));
+    assert_eq!(
+        Err(crate::Error::ImmutableMessage(
+            std::any::type_name::<Input>()
+        )),
+        id.try_subscribe_mut(|_: &mut TestActivity, msg: &mut Input| msg.0 = 0)
+    );
+    let owned = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        id.private_channel(|_: &mut TestActivity, _msg: Input| {});
+    }));
+    assert!(owned.is_err(), "private channel takes ownership");
+
+    crate::publish(Input(2));
+    assert_eq!(2, counter.get());
+}
+
+#[test]
+#[should_panic(expected = "already handled mutably")]
+fn protecting_mutably_handled_message_panics() {
+    struct Input;
+    impl crate::ImmutableMessage for Input {}
+    let id = crate::new_activity(TestActivity::new());
+    id.subscribe_mut(|_, _: &mut Input| {});
+    crate::protect_message::<Input>();
+}
+
+#[test]
+fn stats_are_published_periodically() {
+    use std::time::Duration;
+    let clock = crate::MockClock::new();
+    crate::set_clock(clock.clone());
+    crate::publish_stats(3);
+
+    let reports = Rc::new(std::cell::RefCell::new(vec![]));
+    let id = crate::new_activity(TestActivity::new());
+    let c = clock.clone();
+    id.subscribe(move |_, _: &TestUpdateMsg| c.advance(Duration::from_millis(2)));
+    id.subscribe(|_, _: &TestUpdateMsg| {});
+    let r = reports.clone();
+    id.subscribe(move |_, stats: &crate::NutsStats| r.borrow_mut().push(stats.clone()));
+
+    crate::publish(TestUpdateMsg);
+    crate::publish(TestUpdateMsg);
+    assert!(reports.borrow().is_empty());
+    crate::publish(TestUpdateMsg);
+    assert_eq!(
+        vec![crate::NutsStats {
+            broadcasts: 3,
+            handlers_run: 6,
+            time_spent: Duration::from_millis(6),
+            queue_depth: 0,
+            activities: 1,
+        }],
+        *reports.borrow()
+    );
+
+    crate::publish_stats(0);
+    for _ in 0..3 {
+        crate::publish(TestUpdateMsg);
+    }
+    assert_eq!(1, reports.borrow().len());
+}
+
+#[test]
+fn unsubscribe_removes_only_that_subscription() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    let ones = id.subscribe(|a, _: &TestUpdateMsg| a.inc(1));
+    id.subscribe(|a, _: &TestUpdateMsg| a.inc(10));
+    crate::publish(TestUpdateMsg);
+    assert_eq!(11, counter.get());
+
+    crate::unsubscribe(ones);
+    assert_eq!(1, crate::subscriber_count::<TestUpdateMsg>());
+    crate::publish(TestUpdateMsg);
+    assert_eq!(21, counter.get());
+    // Removing it twice does nothing
+    crate::unsubscribe(ones);
+
+    // A handler can remove itself, it is not called for later messages
+    let own_id = Rc::new(std::cell::Cell::new(None));
+    let o = own_id.clone();
+    let once = id.subscribe(move |a, _: &TestMessage| {
+        a.inc(100);
+        crate::unsubscribe(o.get().expect("subscription ID"));
+    });
+    own_id.set(Some(once));
+    crate::publish(TestMessage(0));
+    crate::publish(TestMessage(0));
+    assert_eq!(121, counter.get());
+}
+
+#[test]
+fn subscribe_once_is_removed_after_first_call() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_domained_activity(a, &TestDomains::DomainA);
+    crate::store_to_domain(&TestDomains::DomainA, 10u32);
+    id.subscribe_once(|a, msg: &TestMessage| a.inc(msg.0));
+    id.subscribe_once_domained(|a, domain, _: &TestUpdateMsg| a.inc(*domain.get::<u32>()));
+
+    // Skipped while inactive, it still waits for its first call
+    id.set_status(LifecycleStatus::Inactive);
+    crate::publish(TestMessage(100));
+    id.set_status(LifecycleStatus::Active);
+
+    crate::publish(TestMessage(1));
+    crate::publish(TestMessage(2));
+    assert_eq!(1, counter.get());
+    assert_eq!(0, crate::subscriber_count::<TestMessage>());
+
+    crate::publish(TestUpdateMsg);
+    crate::publish(TestUpdateMsg);
+    assert_eq!(11, counter.get());
+}
+
+#[test]
+fn shared_payload_is_never_copied() {
+    struct Frame;
+    struct Audio;
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe_shared::<Frame, Rc<[u8]>, _>(|a, bytes: &[u8]| a.inc(bytes.len() as u32));
+    let kept = Rc::new(std::cell::RefCell::new(vec![]));
+    let k = kept.clone();
+    id.subscribe_shared_owned::<Frame, Rc<[u8]>, _>(move |_, frame| k.borrow_mut().push(frame));
+
+    let frame: Rc<[u8]> = vec![7u8; 4].into();
+    crate::publish_shared::<Frame, _>(frame.clone());
+    // Same pointer type under another tag is another topic
+    crate::publish_shared::<Audio, _>(frame.clone());
+    assert_eq!(4, counter.get());
+    assert_eq!(1, kept.borrow().len());
+    assert!(Rc::ptr_eq(&frame, &kept.borrow()[0]));
+    // The message has been dropped, only the test and the owned handler hold the payload
+    assert_eq!(2, Rc::strong_count(&frame));
+}
+
+#[test]
+fn subscription_priority_orders_activities() {
+    let order = Rc::new(std::cell::RefCell::new(vec![]));
+    let logic = crate::new_activity(TestActivity::new());
+    let observer = crate::new_activity(TestActivity::new());
+    let sanitizer = crate::new_activity(TestActivity::new());
+    let o = order.clone();
+    observer.subscribe_with_priority(SubscriptionPriority::Low, move |_, _: &TestUpdateMsg| {
+        o.borrow_mut().push("observer")
+    });
+    let o = order.clone();
+    logic.subscribe(move |_, _: &TestUpdateMsg| o.borrow_mut().push("logic"));
+    let o = order.clone();
+    sanitizer.subscribe_with_priority(SubscriptionPriority::High, move |_, _: &TestUpdateMsg| {
+        o.borrow_mut().push("sanitizer")
+    });
+
+    crate::publish(TestUpdateMsg);
+    assert_eq!(
+        vec!["sanitizer", "logic", "observer"],
+        order.replace(vec![])
+    );
+
+    // Priorities stay outside of the range of dispatch weights
+    logic.set_dispatch_weight::<TestUpdateMsg>(i32::MIN);
+    crate::publish(TestUpdateMsg);
+    assert_eq!(
+        vec!["sanitizer", "logic", "observer"],
+        order.replace(vec![])
+    );
+    // A dispatch weight replaces the priority
+    sanitizer.set_dispatch_weight::<TestUpdateMsg>(i32::MAX);
+    crate::publish(TestUpdateMsg);
+    assert_eq!(
+        vec!["logic", "sanitizer", "observer"],
+        order.replace(vec![])
+    );
+}
+
+#[test]
+fn subscription_priority_orders_mutating_handlers() {
+    let order = Rc::new(std::cell::RefCell::new(vec![]));
+    let logic = crate::new_activity(TestActivity::new());
+    let sanitizer = crate::new_activity(TestActivity::new());
+    let o = order.clone();
+    logic.subscribe_mut(move |_, msg: &mut TestMessage| o.borrow_mut().push(msg.0));
+    let o = order.clone();
+    logic.subscribe(move |_, _: &TestMessage| o.borrow_mut().push(0));
+    sanitizer.subscribe_mut_with_priority(
+        SubscriptionPriority::High,
+        move |_, msg: &mut TestMessage| msg.0 = msg.0.min(10),
+    );
+
+    crate::publish(TestMessage(99));
+    // Readers first, then the sanitizer before the other mutating handler
+    assert_eq!(vec![0, 10], *order.borrow());
+}
+
+#[test]
+fn subscription_stats() {
+    use std::time::Duration;
+    #[derive(Clone)]
+    struct Sample(u32);
+    let clock = crate::MockClock::new();
+    crate::set_clock(clock.clone());
+    crate::configure(crate::ConfigBuilder::new().record_call_times(true))
+        .expect("configured before the first publish");
+    let id = crate::new_activity(TestActivity::new());
+    let updates = id.subscribe(|a, _: &TestUpdateMsg| a.inc(1));
+    let messages = id.subscribe(|a, msg: &TestMessage| a.inc(msg.0));
+    assert_eq!(Some(SubscriptionStats::default()), updates.stats());
+
+    clock.advance(Duration::from_secs(1));
+    crate::publish(TestUpdateMsg);
+    clock.advance(Duration::from_secs(1));
+    crate::publish(TestUpdateMsg);
+    let stats = updates.stats().expect("subscribed");
+    assert_eq!(2, stats.calls);
+    assert_eq!(Some(Duration::from_secs(2)), stats.last_call);
+    assert_eq!(0, messages.stats().expect("subscribed").calls);
+
+    // Deliveries to inactive activities count, buffered subscriptions add up their handlers
+    let buffered =
+        id.subscribe_buffered(InactivePolicy::BufferLatest, |a, msg: &Sample| a.inc(msg.0));
+    id.set_status(LifecycleStatus::Inactive);
+    crate::publish(TestMessage(10));
+    crate::publish(Sample(10));
+    assert_eq!(1, messages.stats().expect("subscribed").calls);
+    id.set_status(LifecycleStatus::Active);
+    assert_eq!(2, buffered.stats().expect("subscribed").calls);
+
+    crate::unsubscribe(updates);
+    assert_eq!(None, updates.stats());
+}
+
+// With `perf` or `log`, the clock is read for every handler anyway.
+#[cfg(not(any(feature = "perf", feature = "log")))]
+#[test]
+fn subscription_stats_without_call_times() {
+    struct PanickingClock;
+    impl crate::Clock for PanickingClock {
+        fn now(&self) -> std::time::Duration {
+            panic!("clock must not be read")
+        }
+    }
+    crate::set_clock(PanickingClock);
+    let id = crate::new_activity(TestActivity::new());
+    let updates = id.subscribe(|a, _: &TestUpdateMsg| a.inc(1));
+    crate::publish(TestUpdateMsg);
+    let stats = updates.stats().expect("subscribed");
+    assert_eq!(1, stats.calls);
+    assert_eq!(None, stats.last_call);
+}
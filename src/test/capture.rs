@@ -0,0 +1,75 @@
+use crate::{Activity, ActivityId};
+use std::fmt::Debug;
+
+/// A copy of an activity's state, taken with [`capture`](fn.capture.html).
+pub struct SnapshotHandle<A> {
+    id: ActivityId<A>,
+    before: A,
+}
+
+/// Takes a snapshot of the activity's current state.
+///
+/// Publish some messages afterwards and then use the returned handle to check how the state of the activity changed.
+///
+/// # Panics
+/// Panics if the activity has been deleted or if called from inside a subscription handler.
+///
+/// # Example
+/// ```rust
+/// #[derive(Clone, PartialEq, Debug)]
+/// struct Counter { n: usize }
+/// struct Increment;
+///
+/// let id = nuts::new_activity(Counter { n: 0 });
+/// id.subscribe(|counter, _: &Increment| counter.n += 1);
+///
+/// let snapshot = nuts::test::capture(id);
+/// nuts::publish(Increment);
+/// snapshot.assert_changed(|before, after| assert_eq!(before.n + 1, after.n));
+/// ```
+pub fn capture<A>(id: ActivityId<A>) -> SnapshotHandle<A>
+where
+    A: Activity + Clone + PartialEq + Debug,
+{
+    SnapshotHandle {
+        id,
+        before: read_state(id),
+    }
+}
+
+impl<A> SnapshotHandle<A>
+where
+    A: Activity + Clone + PartialEq + Debug,
+{
+    /// Asserts that the activity state differs from the snapshot and then calls `f` with the old and the new state for further checks.
+    ///
+    /// # Panics
+    /// Panics if the state is unchanged or if the activity has been deleted in the meantime.
+    pub fn assert_changed(self, f: impl FnOnce(&A, &A)) {
+        let after = read_state(self.id);
+        assert_ne!(
+            self.before,
+            after,
+            "State of activity {} did not change.",
+            std::any::type_name::<A>()
+        );
+        f(&self.before, &after);
+    }
+    /// Asserts that the activity state is still equal to the snapshot.
+    ///
+    /// # Panics
+    /// Panics if the state has changed or if the activity has been deleted in the meantime.
+    pub fn assert_unchanged(self) {
+        let after = read_state(self.id);
+        assert_eq!(
+            self.before,
+            after,
+            "State of activity {} changed unexpectedly.",
+            std::any::type_name::<A>()
+        );
+    }
+}
+
+fn read_state<A: Activity + Clone>(id: ActivityId<A>) -> A {
+    crate::nut::read_activity(id, A::clone).expect("Cannot capture state of a deleted activity.")
+}
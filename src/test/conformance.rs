@@ -0,0 +1,112 @@
+use crate::{Activity, ActivityId, LifecycleStatus};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Allows an activity to check its own invariants during [`verify_activity`](fn.verify_activity.html).
+pub trait LifecycleConformance: Activity {
+    /// Called after each status change performed by `verify_activity`, with the new status.
+    /// Panic to report a violation.
+    ///
+    /// The default implementation accepts every state.
+    fn check_status(&self, _status: LifecycleStatus) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LifecycleEvent {
+    Enter,
+    Leave,
+}
+
+/// The sequence of status changes `verify_activity` goes through
+const TRANSITIONS: [LifecycleStatus; 7] = [
+    LifecycleStatus::Inactive,
+    LifecycleStatus::Inactive,
+    LifecycleStatus::Active,
+    LifecycleStatus::Active,
+    LifecycleStatus::Inactive,
+    LifecycleStatus::Active,
+    LifecycleStatus::Deleted,
+];
+
+/// Checks that an activity behaves according to the lifecycle contract of Nuts.
+///
+/// The setup function must create the activity and register all its handlers, like it would be done in the application.
+/// Then, the activity is moved through a sequence of status changes, ending with its deletion.
+/// After each change, it is checked that exactly the documented `on_enter` / `on_leave` events have been fired.
+/// Additionally, [`LifecycleConformance::check_status`](trait.LifecycleConformance.html#method.check_status) is called to let the activity check its own invariants.
+///
+/// # Panics
+/// Panics if any check fails or if called from inside a subscription handler.
+///
+/// # Example
+/// ```rust
+/// use nuts::{LifecycleConformance, LifecycleStatus};
+///
+/// struct Music { playing: bool }
+/// impl LifecycleConformance for Music {
+///     fn check_status(&self, status: LifecycleStatus) {
+///         assert_eq!(self.playing, status.is_active());
+///     }
+/// }
+///
+/// nuts::verify_activity(|| {
+///     let id = nuts::new_activity(Music { playing: true });
+///     id.on_enter(|music| music.playing = true);
+///     id.on_leave(|music| music.playing = false);
+///     id
+/// });
+/// ```
+pub fn verify_activity<A, F>(setup: F)
+where
+    A: LifecycleConformance,
+    F: FnOnce() -> ActivityId<A>,
+{
+    let id = setup();
+    let name = std::any::type_name::<A>();
+    let mut status = crate::nut::activity_status(id.into());
+    assert_eq!(
+        status,
+        Some(LifecycleStatus::Active),
+        "Activity {} must be active after creation.",
+        name
+    );
+
+    let log: Rc<RefCell<Vec<LifecycleEvent>>> = Default::default();
+    let enter_log = log.clone();
+    id.on_enter(move |_| enter_log.borrow_mut().push(LifecycleEvent::Enter));
+    let leave_log = log.clone();
+    id.on_leave(move |_| leave_log.borrow_mut().push(LifecycleEvent::Leave));
+
+    for next in TRANSITIONS.iter().copied() {
+        let before = status.expect("Activity deleted too early.");
+        let expected = match (before.is_active(), next.is_active()) {
+            (true, false) => vec![LifecycleEvent::Leave],
+            (false, true) => vec![LifecycleEvent::Enter],
+            _ => vec![],
+        };
+        id.set_status(next);
+        let fired = std::mem::take(&mut *log.borrow_mut());
+        assert_eq!(
+            fired, expected,
+            "Wrong lifecycle events for activity {} when changing status from {:?} to {:?}.",
+            name, before, next
+        );
+
+        status = crate::nut::activity_status(id.into());
+        if next == LifecycleStatus::Deleted {
+            assert!(
+                crate::nut::read_activity(id, |_| ()).is_none(),
+                "Activity {} has not been removed after deletion.",
+                name
+            );
+        } else {
+            assert_eq!(
+                status,
+                Some(next),
+                "Status of activity {} not updated.",
+                name
+            );
+            crate::nut::read_activity(id, |a| a.check_status(next));
+        }
+    }
+}
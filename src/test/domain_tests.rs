@@ -34,3 +34,431 @@ fn store_to_domain_inside_activity() {
     // Check update has been completed
     crate::publish(TestForInt(9));
 }
+
+struct Writer;
+struct Reader;
+
+#[test]
+fn store_to_domain_ordering() {
+    let d = TestDomains::DomainA;
+    crate::store_to_domain(&d, 0usize);
+    // Subscriptions of the same activity are called in the order they have been registered
+    let id = crate::new_domained_activity(Writer, &d);
+    id.subscribe(|_, msg: &TestForInt| {
+        crate::store_to_domain(&TestDomains::DomainA, msg.0);
+    });
+    id.subscribe_domained(|_, domain, msg: &TestForInt| {
+        // The deferred store is not visible to subscribers of the same message
+        let x: usize = *domain.get();
+        assert_eq!(msg.0 - 1, x);
+    });
+    crate::publish(TestForInt(1));
+    crate::publish(TestForInt(2));
+}
+
+#[test]
+fn store_to_domain_ordered_is_visible_to_next_subscriber() {
+    let d = TestDomains::DomainA;
+    crate::store_to_domain(&d, 0usize);
+    // Subscriptions of the same activity are called in the order they have been registered
+    let id = crate::new_domained_activity(Writer, &d);
+    id.subscribe(|_, msg: &TestForInt| {
+        crate::store_to_domain_ordered(&TestDomains::DomainA, msg.0);
+    });
+    id.subscribe_domained(|_, domain, msg: &TestForInt| {
+        let x: usize = *domain.get();
+        assert_eq!(msg.0, x);
+    });
+    crate::publish(TestForInt(1));
+    crate::publish(TestForInt(2));
+}
+
+#[test]
+fn store_to_domain_ordered_in_on_delete() {
+    let d = TestDomains::DomainA;
+    crate::store_to_domain(&d, 0usize);
+    let writer = crate::new_domained_activity(Writer, &d);
+    writer.on_delete_domained(|_, _| {
+        crate::store_to_domain_ordered(&TestDomains::DomainA, 5usize);
+    });
+    writer.set_status(LifecycleStatus::Deleted);
+    let reader = crate::new_domained_activity(Reader, &d);
+    reader.subscribe_domained(|_, domain, _: &TestUpdateMsg| {
+        let x: usize = *domain.get();
+        assert_eq!(5, x);
+    });
+    crate::publish(TestUpdateMsg);
+}
+
+#[test]
+fn store_to_domain_awaitable_resolves_after_store() {
+    use futures::FutureExt;
+    let d = TestDomains::DomainA;
+    crate::store_to_domain(&d, 0usize);
+    // Outside of activities, the store is applied right away
+    assert!(crate::store_to_domain_awaitable(&d, 1usize)
+        .now_or_never()
+        .is_some());
+    let write = std::rc::Rc::new(std::cell::RefCell::new(None));
+    let w = write.clone();
+    let id = crate::new_domained_activity(Writer, &d);
+    id.subscribe_domained(move |_, domain, msg: &TestForInt| {
+        let mut stored =
+            crate::store_to_domain_awaitable(&TestDomains::DomainA, msg.0).boxed_local();
+        assert!(stored.as_mut().now_or_never().is_none());
+        assert_eq!(1, *domain.get::<usize>());
+        *w.borrow_mut() = Some(stored);
+    });
+    crate::publish(TestForInt(2));
+    let stored = write.borrow_mut().take().expect("write future");
+    assert!(stored.now_or_never().is_some());
+    let reader = crate::new_domained_activity(Reader, &d);
+    reader.subscribe_domained(|_, domain, _: &TestUpdateMsg| {
+        assert_eq!(2, *domain.get::<usize>());
+    });
+    crate::publish(TestUpdateMsg);
+}
+
+#[test]
+fn observe_domain_once_per_broadcast() {
+    let d = TestDomains::DomainA;
+    let seen = Rc::new(std::cell::RefCell::new(Vec::new()));
+    crate::store_to_domain(&d, 0usize);
+    let s = seen.clone();
+    crate::observe_domain(&d, move |x: &usize| s.borrow_mut().push(*x));
+
+    let id = crate::new_domained_activity(TestActivity::new(), &d);
+    id.subscribe_domained(|_activity, domain, msg: &TestForInt| {
+        *domain.get_mut::<usize>() += msg.0;
+        *domain.get_mut::<usize>() += msg.0;
+    });
+    id.subscribe(|_activity, msg: &TestMessage| {
+        crate::store_to_domain(&TestDomains::DomainA, msg.0 as usize);
+    });
+    crate::publish(TestForInt(1));
+    crate::publish(TestUpdateMsg);
+    crate::publish(TestForInt(2));
+    crate::publish(TestMessage(10));
+    assert_eq!(vec![2, 6, 10], *seen.borrow());
+}
+
+#[test]
+fn on_domain_change_skips_equal_values() {
+    let d = TestDomains::DomainA;
+    let seen = Rc::new(std::cell::RefCell::new(Vec::new()));
+    crate::store_to_domain(&d, 0usize);
+    let s = seen.clone();
+    crate::on_domain_change(&d, move |x: &usize| s.borrow_mut().push(*x));
+
+    let id = crate::new_domained_activity(TestActivity::new(), &d);
+    id.subscribe_domained(|_activity, domain, msg: &TestForInt| {
+        *domain.get_mut::<usize>() = msg.0;
+    });
+    crate::publish(TestForInt(1));
+    crate::publish(TestForInt(1));
+    crate::publish(TestForInt(3));
+    crate::publish(TestForInt(3));
+    assert_eq!(vec![1, 3], *seen.borrow());
+}
+
+crate::domain_state!(TypedView {
+    counter: usize,
+    label: String
+});
+
+#[test]
+fn typed_domain_view() {
+    let d = TestDomains::DomainA;
+    crate::store_to_domain(&d, 1usize);
+    crate::store_to_domain(&d, "a".to_owned());
+    let id = crate::new_domained_activity(TestActivity::new(), &d);
+    id.subscribe_domained(|_, domain, msg: &TestForInt| {
+        let view = TypedView::from_domain(domain);
+        *view.counter += msg.0;
+        view.label.push_str(&view.counter.to_string());
+    });
+    id.subscribe_domained(|activity, domain, _: &TestUpdateMsg| {
+        assert_eq!("a3", domain.get::<String>());
+        activity.inc(1);
+    });
+    crate::publish(TestForInt(2));
+    crate::publish(TestUpdateMsg);
+}
+
+#[test]
+fn subscribe_with_injected_params() {
+    let d = TestDomains::DomainA;
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_domained_activity(a, &d);
+    crate::store_to_domain(&d, 0usize);
+    id.subscribe_with(
+        |domain: &mut DomainState, msg: &TestForInt, activity: &mut TestActivity| {
+            *domain.get_mut::<usize>() += msg.0;
+            activity.inc(msg.0 as u32);
+        },
+    );
+    id.subscribe_with(move |msg: &mut TestForInt, me: ActivityId<TestActivity>| {
+        assert_eq!(me.id.as_usize(), id.id.as_usize());
+        msg.0 = 100;
+    });
+    id.subscribe_with::<TestForInt, _, _>(|domain: &mut DomainState| {
+        assert_eq!(*domain.get::<usize>(), 3);
+    });
+    crate::publish(TestForInt(3));
+    assert_eq!(counter.get(), 3);
+}
+
+#[test]
+#[should_panic(expected = "Activity has no domain")]
+fn subscribe_with_domain_requires_domain() {
+    let id = crate::new_activity(TestActivity::new());
+    id.subscribe_with::<TestUpdateMsg, _, _>(|_: &mut DomainState| {});
+    crate::publish(TestUpdateMsg);
+}
+
+#[test]
+fn keyed_domain_objects() {
+    #[derive(Debug, PartialEq)]
+    struct Inventory(u32);
+    let d = TestDomains::DomainA;
+    crate::store_to_domain(&d, Inventory(100));
+    let id = crate::new_domained_activity(Writer, &d);
+    id.subscribe_domained(|_, domain, msg: &TestForInt| {
+        *domain
+            .keyed_entry::<Inventory, _>(msg.0)
+            .or_insert(Inventory(0)) = Inventory(msg.0 as u32 * 10);
+    });
+    id.subscribe_domained(|_, domain, _: &TestUpdateMsg| {
+        assert_eq!(Some(&Inventory(10)), domain.get_keyed(&1usize));
+        assert_eq!(Some(&Inventory(20)), domain.get_keyed(&2usize));
+        assert_eq!(None, domain.get_keyed::<Inventory, _>(&3usize));
+        assert_eq!(
+            None,
+            domain.get_keyed::<Inventory, _>(&1u32),
+            "keys of other types are separate"
+        );
+        assert_eq!(Some(Inventory(10)), domain.remove_keyed(&1usize));
+        assert_eq!(1, domain.iter_keyed::<Inventory, usize>().count());
+        // unkeyed value is not affected
+        assert_eq!(&Inventory(100), domain.get::<Inventory>());
+    });
+    crate::publish(TestForInt(1));
+    crate::publish(TestForInt(2));
+    crate::publish(TestUpdateMsg);
+}
+
+#[cfg(feature = "ecs")]
+#[test]
+fn entities_in_domain() {
+    use crate::{ComponentInserted, ComponentRemoved, EntityDespawned};
+    use std::cell::RefCell;
+    #[derive(Debug, PartialEq)]
+    struct Pos(i32);
+    struct Hp;
+    let events = Rc::new(RefCell::new(vec![]));
+    let e = events.clone();
+    crate::subscribe(move |msg: &ComponentInserted<Pos>| e.borrow_mut().push(("pos+", msg.entity)));
+    let e = events.clone();
+    crate::subscribe(move |msg: &ComponentRemoved<Hp>| e.borrow_mut().push(("hp-", msg.entity)));
+    let e = events.clone();
+    crate::subscribe(move |msg: &EntityDespawned| e.borrow_mut().push(("despawn", msg.entity)));
+
+    let d = TestDomains::DomainA;
+    let id = crate::new_domained_activity(Writer, &d);
+    let spawned = Rc::new(RefCell::new(vec![]));
+    let s = spawned.clone();
+    id.subscribe_domained(move |_, domain, msg: &TestForInt| {
+        let entity = domain
+            .entities()
+            .spawn()
+            .insert(Pos(msg.0 as i32))
+            .insert(Hp)
+            .id();
+        s.borrow_mut().push(entity);
+    });
+    let s = spawned.clone();
+    id.subscribe_domained(move |_, domain, _: &TestUpdateMsg| {
+        let entities = domain.entities();
+        for (_, pos) in entities.iter_mut::<Pos>() {
+            pos.0 *= 10;
+        }
+        let first = s.borrow()[0];
+        assert!(entities.despawn(first));
+        assert!(!entities.despawn(first));
+        assert!(entities.entity(first).is_none());
+        assert_eq!(1, entities.len());
+        assert_eq!(Some(&Pos(20)), entities.get(s.borrow()[1]));
+        assert_eq!(vec![s.borrow()[1]], entities.with::<Hp>());
+    });
+    crate::publish(TestForInt(1));
+    crate::publish(TestForInt(2));
+    crate::publish(TestUpdateMsg);
+
+    let (a, b) = (spawned.borrow()[0], spawned.borrow()[1]);
+    assert_eq!(
+        vec![("pos+", a), ("pos+", b), ("hp-", a), ("despawn", a)],
+        *events.borrow()
+    );
+}
+
+#[test]
+fn validate_domains_finds_missing_domain() {
+    struct NoDomain;
+    let id = crate::new_activity(NoDomain);
+    id.subscribe_domained(|_, _, _: &TestForInt| {});
+    assert!(matches!(
+        crate::validate_domains(),
+        Err(crate::Error::MissingDomain(_))
+    ));
+    assert_eq!(0, crate::skipped_domained_calls());
+}
+
+#[test]
+#[should_panic(expected = "after domains have been validated")]
+fn validated_domains_reject_new_subscriptions_without_domain() {
+    let d = TestDomains::DomainA;
+    let id = crate::new_domained_activity(Writer, &d);
+    id.subscribe_domained(|_, _, _: &TestForInt| {});
+    assert_eq!(Ok(()), crate::validate_domains());
+
+    let id = crate::new_activity(TestActivity::new());
+    id.subscribe_domained(|_, _, _: &TestForInt| {});
+}
+
+#[test]
+fn domain_migrations_are_chained_lazily() {
+    struct V1(u8);
+    struct V2(u16);
+    struct V3(u32);
+    let d = TestDomains::DomainA;
+    crate::store_to_domain(&d, V1(1));
+    // Both types already stored, nothing to migrate
+    crate::store_to_domain(&d, 5u8);
+    crate::store_to_domain(&d, 6u16);
+    crate::register_domain_migration(|v: V2| V3(v.0 as u32 * 10));
+    crate::register_domain_migration(|v: V1| V2(v.0 as u16 + 1));
+    crate::register_domain_migration(|v: u8| v as u16);
+
+    let id = crate::new_domained_activity(Writer, &d);
+    id.subscribe_domained(|_, domain, _: &TestUpdateMsg| {
+        assert_eq!(20, domain.get::<V3>().0);
+        assert!(domain.try_get::<V1>().is_none());
+        assert!(domain.try_get::<V2>().is_none());
+        assert_eq!(Some(&5u8), domain.try_get::<u8>());
+        assert_eq!(Some(&6u16), domain.try_get::<u16>());
+    });
+    crate::publish(TestUpdateMsg);
+}
+
+#[test]
+fn enumerate_domains() {
+    crate::store_to_domain(&TestDomains::_DomainB, 7u32);
+    crate::store_to_domain(&TestDomains::_DomainB, "text");
+    let deleted = crate::new_domained_activity(TestActivity::new(), &TestDomains::_DomainB);
+    crate::new_domained_activity((), &TestDomains::_DomainB);
+    deleted.set_status(LifecycleStatus::Deleted);
+
+    let domains = crate::domains();
+    assert_eq!(2, domains.len());
+    assert_eq!(0, domains[0].activity_count);
+    assert!(domains[0].stored_type_names.is_empty());
+    assert_eq!(1, domains[1].id);
+    assert_eq!(1, domains[1].activity_count);
+    assert_eq!(vec!["u32", "&str"], domains[1].stored_type_names);
+}
+
+#[test]
+fn domain_view_reaches_other_domains() {
+    let views = Rc::new(std::cell::RefCell::new(vec![]));
+    let v = views.clone();
+    // An activity without domain
+    let reader = crate::new_activity(());
+    reader
+        .subscribe(move |_, view: &DomainView<u32>| v.borrow_mut().push((view.domain, view.data)));
+
+    crate::store_to_domain(&TestDomains::DomainA, 1u32);
+    crate::publish_domain_view::<_, u32>(&TestDomains::DomainA);
+
+    // Inside a handler, stores issued before are included
+    let writer = crate::new_domained_activity((), &TestDomains::_DomainB);
+    writer.subscribe(|_, msg: &TestForInt| {
+        crate::store_to_domain(&TestDomains::_DomainB, msg.0 as u32);
+        crate::publish_domain_view::<_, u32>(&TestDomains::_DomainB);
+    });
+    crate::publish(TestForInt(5));
+
+    assert_eq!(vec![(0, 1), (1, 5)], *views.borrow());
+}
+
+#[test]
+fn merge_domains_inside_handler() {
+    use crate::ConflictPolicy;
+    #[derive(Clone, Copy)]
+    enum World {
+        Main,
+        Staging,
+    }
+    domain_enum!(World);
+    struct Level(u32);
+    struct Chunk(u32);
+    struct Merge;
+
+    crate::store_to_domain(&World::Main, Level(1));
+    crate::store_to_domain(&World::Main, Chunk(1));
+    crate::store_to_domain(&World::Staging, Level(2));
+    crate::store_to_domain(&World::Staging, 5usize);
+
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let main = crate::new_domained_activity(TestActivity::new(), &World::Main);
+    let staged = crate::new_domained_activity(a, &World::Staging);
+    main.subscribe_domained(|_activity, _domain, _msg: &Merge| {
+        crate::merge_domains(&World::Staging, &World::Main, ConflictPolicy::KeepSource);
+    });
+    staged.subscribe_domained(|activity, domain, _msg: &TestUpdateMsg| {
+        activity.inc(domain.get::<Level>().0 + domain.get::<Chunk>().0);
+    });
+
+    crate::publish(Merge);
+    crate::publish(TestUpdateMsg);
+    assert_eq!(
+        3,
+        counter.get(),
+        "source object replaces the destination object"
+    );
+
+    // stores to the merged domain end up in the destination
+    crate::store_to_domain(&World::Staging, Chunk(10));
+    crate::publish(TestUpdateMsg);
+    assert_eq!(15, counter.get());
+
+    let domains = crate::domains();
+    assert_eq!(2, domains[World::Main as usize].activity_count);
+    assert_eq!(0, domains[World::Staging as usize].activity_count);
+    assert!(domains[World::Staging as usize]
+        .stored_type_names
+        .is_empty());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn capture_of_activity_in_other_domain_is_reported() {
+    let writer = crate::new_domained_activity(Writer, &TestDomains::DomainA);
+    let reader = crate::new_domained_activity(Reader, &TestDomains::DomainA);
+    let remote = crate::new_domained_activity(TestActivity::new(), &TestDomains::_DomainB);
+    crate::store_to_domain(&TestDomains::DomainA, 3usize);
+
+    writer.subscribe_domained_capturing(reader, |_, domain, _reader, _: &TestUpdateMsg| {
+        assert_eq!(3, *domain.get::<usize>());
+    });
+    assert!(crate::capture_warnings().is_empty());
+
+    writer.subscribe_domained_capturing(remote, |_, _, _remote, _: &TestForInt| {});
+    let warnings = crate::capture_warnings();
+    assert_eq!(1, warnings.len());
+    assert!(warnings[0].activity.ends_with("Writer"));
+    assert!(warnings[0].captured.ends_with("TestActivity"));
+    assert!(warnings[0].topic.ends_with("TestForInt"));
+    crate::publish(TestUpdateMsg);
+}
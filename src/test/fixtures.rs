@@ -0,0 +1,38 @@
+//! Shared activities, domains, and messages used by the internal test suites.
+
+pub(super) use crate::*;
+pub(super) use std::cell::Cell;
+pub(super) use std::rc::Rc;
+
+#[derive(Clone)]
+pub(super) struct TestActivity {
+    pub(super) counter: Rc<Cell<u32>>,
+}
+
+impl TestActivity {
+    pub(super) fn new() -> Self {
+        let shared_counter = Rc::new(Cell::new(0));
+        Self {
+            counter: shared_counter,
+        }
+    }
+    pub(super) fn shared_counter_ref(&self) -> Rc<Cell<u32>> {
+        self.counter.clone()
+    }
+    pub(super) fn inc(&self, add: u32) {
+        let i = self.counter.get();
+        self.counter.as_ref().set(i + add)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(super) enum TestDomains {
+    DomainA,
+    _DomainB,
+}
+domain_enum!(TestDomains);
+
+pub(super) struct TestUpdateMsg;
+pub(super) struct TestForInt(pub(super) usize);
+pub(super) struct TestMessage(pub(super) u32);
+pub(super) struct TestMessageNoClone;
@@ -1,4 +1,5 @@
 //! Testing creation and managing of activities while a broadcast is inflight.
+#![allow(clippy::unwrap_used)]
 use super::*;
 
 struct Main;
@@ -327,11 +328,11 @@ fn create_new_after_delete() {
 }
 
 #[test]
-/// Create a (normal) activity A and register on_delete
-/// Delete A, in A.on_delete:
+/// Create a (normal) activity A and register `on_delete`
+/// Delete A, in `A.on_delete`:
 ///     Create (inchoate) activity B
-///     Register on_delete in B
-///     Delete B, in B.on_delete:
+///     Register `on_delete` in B
+///     Delete B, in `B.on_delete`:
 ///         Create C and ensure nothing funky happened to the IDs
 #[allow(non_snake_case)]
 fn complex_scenario_0() {
@@ -358,3 +359,62 @@ fn complex_scenario_0() {
 
     id_a.set_status(LifecycleStatus::Deleted);
 }
+
+/// In a handler: create an activity, subscribe it, then publish an urgent message it subscribed to.
+/// Returns how often the new activity has been called.
+fn urgent_message_after_creation() -> u32 {
+    let main = crate::new_activity(());
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    main.subscribe(move |_, _: &Main| {
+        let id = crate::new_activity(a.clone());
+        id.subscribe(|activity: &mut TestActivity, _: &TestUpdateMsg| activity.inc(1));
+        drop(crate::publish_awaiting_response_urgent(TestUpdateMsg));
+    });
+    crate::publish(Main);
+    counter.get()
+}
+
+#[test]
+fn fifo_order_lets_urgent_message_overtake_creation() {
+    assert_eq!(0, urgent_message_after_creation());
+}
+
+#[test]
+fn bookkeeping_first_prepares_new_activity() {
+    crate::configure(crate::ConfigBuilder::new().queue_order(crate::QueueOrder::BookkeepingFirst))
+        .unwrap();
+    assert_eq!(1, urgent_message_after_creation());
+}
+
+#[test]
+fn inchoate_activity_becomes_live() {
+    let main = crate::new_activity(());
+    assert!(main.is_live());
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let aid_slot: Rc<Cell<Option<ActivityId<TestActivity>>>> = Default::default();
+    let aid_slot_clone = aid_slot.clone();
+    main.subscribe(move |_, _: &Main| {
+        let id = crate::new_activity(a.clone());
+        assert!(!id.is_live());
+        id.on_leave(|activity| activity.inc(1));
+        // Applied after the activity has been added
+        id.set_status(LifecycleStatus::Inactive);
+        assert!(!id.is_live());
+        aid_slot.set(Some(id));
+    });
+
+    crate::publish(Main);
+
+    let id = aid_slot_clone.get().unwrap();
+    assert!(id.is_live());
+    assert_eq!(counter.get(), 1);
+    assert_eq!(
+        Some(LifecycleStatus::Inactive),
+        crate::nut::activity_status(id.into())
+    );
+    id.set_status(LifecycleStatus::Deleted);
+    assert!(id.is_live());
+    assert!(!id.is_valid());
+}
@@ -176,6 +176,37 @@ fn subscribe_after_delete() {
     assert_eq!(0, counter.get());
 }
 
+#[test]
+fn try_subscribe_after_delete() {
+    let d = TestDomains::DomainA;
+    let id = crate::new_domained_activity(TestActivity::new(), &d);
+    assert!(id.try_subscribe(|a, _msg: &TestMessage| a.inc(1)).is_ok());
+    id.set_status(LifecycleStatus::Deleted);
+
+    let deleted = Err(crate::Error::ActivityDeleted);
+    assert_eq!(deleted, id.try_subscribe(|a, _msg: &TestMessage| a.inc(1)));
+    assert_eq!(
+        deleted,
+        id.try_subscribe_mut(|a, _msg: &mut TestMessage| a.inc(1))
+    );
+    assert_eq!(
+        deleted,
+        id.try_subscribe_domained(|a, _domain, _msg: &TestMessage| a.inc(1))
+    );
+}
+
+#[test]
+fn try_set_status_after_delete() {
+    let id = crate::new_activity(());
+    assert_eq!(Ok(()), id.try_set_status(LifecycleStatus::Inactive));
+    id.set_status(LifecycleStatus::Deleted);
+
+    let deleted = Err(crate::Error::ActivityDeleted);
+    assert_eq!(deleted, id.try_set_status(LifecycleStatus::Active));
+    let unchecked: UncheckedActivityId = id.into();
+    assert_eq!(deleted, unchecked.try_set_status(LifecycleStatus::Active));
+}
+
 #[test]
 fn delete_with_on_leave() {
     let a = TestActivity::new();
@@ -203,3 +234,613 @@ fn create_new_after_delete() {
 
     assert_ne!(num_a, num_b);
 }
+
+#[derive(Clone)]
+struct BufferedMsg(u32);
+
+#[test]
+fn inactive_policy_buffer_latest() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe_buffered(InactivePolicy::BufferLatest, |a, msg: &BufferedMsg| {
+        a.inc(msg.0)
+    });
+    id.set_status(LifecycleStatus::Inactive);
+    crate::publish(BufferedMsg(1));
+    crate::publish(BufferedMsg(10));
+    assert_eq!(0, counter.get());
+    id.set_status(LifecycleStatus::Active);
+    assert_eq!(10, counter.get());
+    crate::publish(BufferedMsg(100));
+    assert_eq!(110, counter.get());
+}
+
+#[test]
+fn inactive_policy_buffer_all() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe_buffered(InactivePolicy::BufferAll(2), |a, msg: &BufferedMsg| {
+        a.inc(msg.0)
+    });
+    id.set_status(LifecycleStatus::Inactive);
+    crate::publish(BufferedMsg(1));
+    crate::publish(BufferedMsg(10));
+    crate::publish(BufferedMsg(100));
+    id.set_status(LifecycleStatus::Active);
+    // Oldest message has been dropped
+    assert_eq!(110, counter.get());
+    // Buffer is empty after delivery
+    id.set_status(LifecycleStatus::Inactive);
+    id.set_status(LifecycleStatus::Active);
+    assert_eq!(110, counter.get());
+}
+
+#[test]
+fn inactive_policy_drop() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe_buffered(InactivePolicy::Drop, |a, msg: &BufferedMsg| a.inc(msg.0));
+    id.set_status(LifecycleStatus::Inactive);
+    crate::publish(BufferedMsg(1));
+    id.set_status(LifecycleStatus::Active);
+    assert_eq!(0, counter.get());
+}
+
+#[test]
+fn visit_activities() {
+    struct Other(u32);
+    let a = crate::new_domained_activity(TestActivity::new(), &TestDomains::_DomainB);
+    let deleted = crate::new_activity(TestMessageNoClone);
+    let other = crate::new_activity(Other(7));
+    deleted.set_status(LifecycleStatus::Deleted);
+    other.set_status(LifecycleStatus::Inactive);
+
+    let mut visited = vec![];
+    crate::visit_activities(|meta, activity| {
+        if let Some(other) = activity.downcast_ref::<Other>() {
+            assert_eq!(other.0, 7);
+        }
+        #[cfg(debug_assertions)]
+        assert!(meta.type_name.ends_with("Other") || meta.type_name.ends_with("TestActivity"));
+        visited.push((meta.id, meta.domain, meta.status));
+    });
+    assert_eq!(
+        visited,
+        vec![
+            (a.into(), Some(1), LifecycleStatus::Active),
+            (other.into(), None, LifecycleStatus::Inactive),
+        ]
+    );
+}
+
+#[test]
+fn no_enter_for_initially_active_activity() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.on_enter(|activity| activity.inc(1));
+    crate::publish(TestUpdateMsg);
+    assert_eq!(counter.get(), 0);
+}
+
+#[test]
+fn enter_on_creation() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity_with_enter(a);
+    id.on_enter(|activity| activity.inc(1));
+    id.subscribe(|activity, _: &TestUpdateMsg| {
+        assert_eq!(
+            activity.counter.get() % 10,
+            1,
+            "on_enter must be called before first message"
+        );
+        activity.inc(10);
+    });
+    crate::publish(TestUpdateMsg);
+    assert_eq!(counter.get(), 11);
+    crate::publish(TestUpdateMsg);
+    assert_eq!(counter.get(), 21);
+}
+
+#[test]
+fn enter_on_creation_inside_handler() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let cell = std::cell::RefCell::new(Some(a));
+    crate::subscribe(move |_: &TestUpdateMsg| {
+        if let Some(a) = cell.borrow_mut().take() {
+            let id = crate::new_activity_with_enter(a);
+            id.on_enter(|activity| activity.inc(1));
+        }
+    });
+    crate::publish(TestUpdateMsg);
+    assert_eq!(counter.get(), 1);
+}
+
+#[test]
+fn no_enter_on_creation_when_deactivated() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity_with_enter(a);
+    id.on_enter(|activity| activity.inc(1));
+    id.set_status(LifecycleStatus::Inactive);
+    crate::publish(TestUpdateMsg);
+    assert_eq!(counter.get(), 0);
+}
+
+#[test]
+fn status_batch_is_atomic() {
+    use std::cell::RefCell;
+    struct Old;
+    struct New;
+    struct Swap;
+    struct Probe;
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let old = crate::new_activity(Old);
+    let new = crate::new_activity(New);
+    new.set_status(LifecycleStatus::Inactive);
+
+    let l = log.clone();
+    old.on_leave(move |_| l.borrow_mut().push("old leave"));
+    let l = log.clone();
+    new.on_enter(move |_| l.borrow_mut().push("new enter"));
+    let l = log.clone();
+    old.subscribe(move |_, _: &Probe| l.borrow_mut().push("old probe"));
+    let l = log.clone();
+    new.subscribe(move |_, _: &Probe| l.borrow_mut().push("new probe"));
+    crate::subscribe(move |_: &Swap| {
+        crate::status_batch()
+            .set(new, LifecycleStatus::Active)
+            .set(old, LifecycleStatus::Inactive)
+            .commit();
+    });
+
+    crate::publish(Swap);
+    crate::publish(Probe);
+    assert_eq!(vec!["old leave", "new enter", "new probe"], *log.borrow());
+}
+
+#[test]
+fn activity_id_is_valid() {
+    struct Other;
+    let id = crate::new_activity(TestActivity::new());
+    let wrong_type = ActivityId::<Other>::new(id.id.index, DomainId::default());
+    let out_of_range = ActivityId::<Other>::new(1000, DomainId::default());
+    assert!(id.is_valid());
+    assert!(!wrong_type.is_valid());
+    assert!(!out_of_range.is_valid());
+    id.set_status(LifecycleStatus::Deleted);
+    assert!(!id.is_valid());
+}
+
+#[test]
+fn stale_generation_is_rejected() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    struct Ping;
+    let id = crate::new_activity(TestActivity::new());
+    let pings = Rc::new(Cell::new(0));
+    let p = pings.clone();
+    id.private_channel(move |_, _: Ping| p.set(p.get() + 1));
+
+    let stale = id.with_generation(id.id.generation + 1);
+    assert!(!stale.is_valid());
+    assert_eq!(
+        Err(crate::Error::ActivityDeleted),
+        stale.try_set_status(LifecycleStatus::Inactive)
+    );
+    assert_eq!(
+        Err(crate::Error::ActivityDeleted),
+        stale.try_subscribe(|_, _: &TestUpdateMsg| {})
+    );
+    stale.private_message(Ping);
+    assert_eq!(0, pings.get());
+    id.private_message(Ping);
+    assert_eq!(1, pings.get());
+    assert!(crate::ActivityId::<TestActivity>::from_token(stale.to_token()).is_none());
+}
+
+#[test]
+fn stale_id_after_slot_reuse() {
+    struct First;
+    struct Second;
+    let first = crate::new_activity(First);
+    first.delete();
+    let second = crate::new_activity(Second);
+    assert_eq!(first.id.index, second.id.index);
+
+    let deleted = Rc::new(Cell::new(false));
+    let d = deleted.clone();
+    second.on_delete(move |_: Second| d.set(true));
+    // Neither variant may replace the callback of the activity that took over the slot
+    first.on_delete(|_: First| panic!("stale on_delete called"));
+    assert_eq!(
+        Err(crate::Error::ActivityDeleted),
+        first.try_on_delete(|_: First| {})
+    );
+    assert!(!first.is_valid());
+    assert!(crate::ActivityId::<First>::from_token(first.to_token()).is_none());
+
+    second.delete();
+    assert!(deleted.get());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Other> used for slot containing")]
+fn activity_id_with_wrong_type() {
+    struct Other;
+    let id = crate::new_activity(TestActivity::new());
+    let wrong_type = ActivityId::<Other>::new(id.id.index, DomainId::default());
+    wrong_type.subscribe(|_, _: &TestUpdateMsg| {});
+    crate::publish(TestUpdateMsg);
+}
+
+#[test]
+fn delete_releases_slot_for_reuse() {
+    struct Other;
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|a, _: &TestUpdateMsg| a.inc(1));
+    let deleted = std::rc::Rc::new(std::cell::Cell::new(false));
+    let d = deleted.clone();
+    id.on_delete(move |_| d.set(true));
+
+    id.delete();
+    assert!(deleted.get());
+    assert!(!id.is_valid());
+    assert_eq!(0, crate::subscriber_count::<TestUpdateMsg>());
+
+    let other = crate::new_activity(Other);
+    assert_eq!(id.id.index, other.id.index);
+    other.subscribe(|_, _: &TestUpdateMsg| {});
+    crate::publish(TestUpdateMsg);
+    assert_eq!(0, counter.get());
+    assert!(other.is_valid());
+    assert!(!id.is_valid());
+
+    // Deleting from inside a handler releases the slot once the deletion has been processed
+    other.subscribe(move |_, _: &TestMessage| other.delete());
+    crate::publish(TestMessage(0));
+    let third = crate::new_activity(TestActivity::new());
+    assert_eq!(id.id.index, third.id.index);
+}
+
+#[test]
+fn teardown_at_thread_exit() {
+    use std::sync::{Arc, Mutex};
+    struct First;
+    struct Second;
+    struct DomainObject(&'static str, Arc<Mutex<Vec<String>>>);
+    impl Drop for DomainObject {
+        fn drop(&mut self) {
+            self.1
+                .lock()
+                .expect("poisoned")
+                .push(format!("drop {}", self.0));
+        }
+    }
+
+    let log = Arc::new(Mutex::new(Vec::<String>::new()));
+    let l = log.clone();
+    std::thread::spawn(move || {
+        let push =
+            |l: &Arc<Mutex<Vec<String>>>, s: &str| l.lock().expect("poisoned").push(s.to_owned());
+        let d = TestDomains::DomainA;
+        crate::store_to_domain(&d, DomainObject("a", l.clone()));
+        crate::store_to_domain(&d, (DomainObject("b", l.clone()),));
+
+        let first = crate::new_domained_activity(First, &d);
+        let second = crate::new_activity(Second);
+        let inactive = crate::new_activity(TestActivity::new());
+        inactive.set_status(LifecycleStatus::Inactive);
+
+        let l1 = l.clone();
+        first.on_leave(move |_| push(&l1, "leave first"));
+        let l1 = l.clone();
+        first.on_delete(move |_| push(&l1, "delete first"));
+        let l1 = l.clone();
+        second.on_leave(move |_| push(&l1, "leave second"));
+        let l1 = l.clone();
+        second.on_delete(move |_| push(&l1, "delete second"));
+        let l1 = l.clone();
+        inactive.on_leave(move |_| push(&l1, "leave inactive"));
+        let l1 = l.clone();
+        crate::set_teardown_hook(move || push(&l1, "hook"));
+    })
+    .join()
+    .expect("thread panicked");
+
+    assert_eq!(
+        vec![
+            "hook",
+            "leave second",
+            "leave first",
+            "delete second",
+            "delete first",
+            "drop b",
+            "drop a"
+        ],
+        *log.lock().expect("poisoned")
+    );
+}
+
+#[test]
+fn lazy_publish_during_teardown() {
+    use std::sync::mpsc;
+    struct Goodbye;
+    struct Noisy(mpsc::Sender<bool>);
+    impl Drop for Noisy {
+        fn drop(&mut self) {
+            let published = crate::lazy_publish(|| Goodbye);
+            self.0.send(published).expect("receiver alive");
+        }
+    }
+
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let counter = Rc::new(Cell::new(0));
+        let c = counter.clone();
+        crate::subscribe(move |_: &Goodbye| c.set(c.get() + 1));
+        assert!(crate::lazy_publish(|| Goodbye));
+        assert_eq!(1, counter.get());
+        crate::new_activity(Noisy(tx));
+    })
+    .join()
+    .expect("thread panicked");
+
+    assert!(
+        !rx.recv().expect("activity dropped"),
+        "cannot publish while nuts is torn down"
+    );
+}
+
+#[test]
+fn remote_publisher_disconnects_with_thread() {
+    struct Computed(u32);
+    let counter = Rc::new(Cell::new(0));
+    let c = counter.clone();
+    crate::subscribe(move |msg: &Computed| c.set(c.get() * 10 + msg.0));
+
+    let publisher = crate::channel();
+    let p = publisher.clone();
+    std::thread::spawn(move || {
+        p.publish(Computed(1)).expect("connected");
+        p.publish(Computed(2)).expect("connected");
+    })
+    .join()
+    .expect("thread panicked");
+    // Nothing is published before the queue is processed
+    assert_eq!(0, counter.get());
+    publisher.publish(Computed(3)).expect("connected");
+    assert_eq!(3, crate::process_remote());
+    assert_eq!(123, counter.get());
+    assert_eq!(0, crate::process_remote());
+
+    let other = std::thread::spawn(crate::channel)
+        .join()
+        .expect("thread panicked");
+    assert_eq!(Err(crate::Error::Disconnected), other.publish(Computed(4)));
+}
+
+#[test]
+fn activity_token_round_trip() {
+    let d = TestDomains::DomainA;
+    let id = crate::new_domained_activity(TestActivity::new(), &d);
+    let other = crate::new_activity(());
+    let token = crate::ActivityToken::from_u64(id.to_token().as_u64());
+    assert_eq!(id.to_token(), token);
+
+    let restored = crate::ActivityId::<TestActivity>::from_token(token).expect("valid token");
+    assert_eq!(id.to_token(), restored.to_token());
+    assert!(crate::ActivityId::<()>::from_token(token).is_none());
+    assert!(crate::ActivityId::<()>::from_token(other.to_token()).is_some());
+
+    id.set_status(LifecycleStatus::Deleted);
+    assert!(crate::ActivityId::<TestActivity>::from_token(token).is_none());
+}
+
+#[test]
+fn request_exit_with_veto() {
+    use std::cell::RefCell;
+    struct Quit;
+    let log = Rc::new(RefCell::new(Vec::<String>::new()));
+    let vetoes = Rc::new(Cell::new(1));
+
+    let id = crate::new_activity(TestActivity::new());
+    let v = vetoes.clone();
+    id.subscribe(move |_, exit: &crate::ExitRequested| {
+        if v.get() > 0 {
+            v.set(v.get() - 1);
+            exit.veto();
+        }
+    });
+    id.subscribe(|_, _: &Quit| crate::request_exit(3));
+    let l = log.clone();
+    id.on_leave(move |_| l.borrow_mut().push("leave".to_owned()));
+    let l = log.clone();
+    id.on_delete(move |_| l.borrow_mut().push("delete".to_owned()));
+    let l = log.clone();
+    crate::set_teardown_hook(move || l.borrow_mut().push("hook".to_owned()));
+    let l = log.clone();
+    crate::set_exit_handler(move |code| l.borrow_mut().push(format!("exit {}", code)));
+
+    crate::request_exit(1);
+    assert!(log.borrow().is_empty(), "exit has been vetoed");
+    crate::publish(Quit);
+    assert_eq!(vec!["hook", "leave", "delete", "exit 3"], *log.borrow());
+}
+
+#[test]
+fn evict_idle_activity() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    struct Asset(Rc<Cell<u32>>);
+    impl Drop for Asset {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+    struct Screen {
+        visits: u32,
+        _asset: Asset,
+    }
+    struct Tick;
+
+    let drops = Rc::new(Cell::new(0));
+    let d = drops.clone();
+    let screen = crate::new_activity(Screen {
+        visits: 0,
+        _asset: Asset(drops.clone()),
+    });
+    screen.evict_when_idle(
+        2,
+        |screen| screen.visits.to_le_bytes().to_vec(),
+        move |bytes| Screen {
+            visits: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            _asset: Asset(d.clone()),
+        },
+    );
+    screen.on_enter(|screen| screen.visits += 1);
+    screen.set_status(LifecycleStatus::Inactive);
+    crate::publish(Tick);
+    assert_eq!(0, drops.get(), "evicted too early");
+    crate::publish(Tick);
+    crate::publish(Tick);
+    assert_eq!(1, drops.get(), "not evicted");
+    assert!(screen.is_valid());
+    crate::visit_activities(|meta, _| assert!(meta.evicted));
+
+    screen.set_status(LifecycleStatus::Active);
+    assert_eq!(
+        Some(1),
+        crate::nut::read_activity(screen, |screen| screen.visits)
+    );
+    crate::visit_activities(|meta, _| assert!(!meta.evicted));
+    crate::publish(Tick);
+    crate::publish(Tick);
+    crate::publish(Tick);
+    assert_eq!(1, drops.get(), "active activities must not be evicted");
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn evict_idle_activity_with_serde() {
+    struct Tick;
+    let inventory = crate::new_activity::<(String, Vec<u32>)>(("bag".to_owned(), vec![1, 2]));
+    inventory.evict_when_idle_serde(1);
+    inventory.set_status(LifecycleStatus::Inactive);
+    crate::publish(Tick);
+    crate::publish(Tick);
+    crate::visit_activities(|meta, _| assert!(meta.evicted));
+
+    inventory.set_status(LifecycleStatus::Active);
+    assert_eq!(
+        Some(("bag".to_owned(), vec![1, 2])),
+        crate::nut::read_activity(inventory, |inventory| inventory.clone())
+    );
+}
+
+struct Trigger;
+
+#[test]
+fn set_status_inside_cascade_is_queued() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity: &mut TestActivity, _msg: &TestUpdateMsg| {
+        activity.inc(1);
+    });
+    id.subscribe(move |_activity: &mut TestActivity, _msg: &Trigger| {
+        crate::publish(TestUpdateMsg);
+        id.set_status(LifecycleStatus::Inactive);
+    });
+    crate::publish(Trigger);
+    assert_eq!(
+        1,
+        counter.get(),
+        "message published before the status change is delivered"
+    );
+}
+
+#[test]
+fn set_status_immediate_inside_cascade() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let id = crate::new_activity(a);
+    id.subscribe(|activity: &mut TestActivity, _msg: &TestUpdateMsg| {
+        activity.inc(1);
+    });
+    id.on_leave(|activity: &mut TestActivity| {
+        activity.inc(10);
+    });
+    id.subscribe(move |_activity: &mut TestActivity, _msg: &Trigger| {
+        crate::publish(TestUpdateMsg);
+        id.set_status_immediate(LifecycleStatus::Inactive);
+    });
+    crate::publish(Trigger);
+    assert_eq!(10, counter.get(), "only on_leave is called");
+
+    id.set_status_immediate(LifecycleStatus::Active);
+    crate::publish(TestUpdateMsg);
+    assert_eq!(11, counter.get());
+}
+
+#[test]
+fn set_status_immediate_on_inchoate_activity() {
+    let counter = Rc::new(Cell::new(0));
+    let parent = crate::new_activity(());
+    let shared = counter.clone();
+    parent.subscribe(move |_: &mut (), _msg: &Trigger| {
+        let child = crate::new_activity(TestActivity {
+            counter: shared.clone(),
+        });
+        assert!(!child.is_live());
+        child.subscribe(|activity: &mut TestActivity, _msg: &TestUpdateMsg| {
+            activity.inc(1);
+        });
+        child.on_leave(|activity: &mut TestActivity| {
+            activity.inc(10);
+        });
+        child.set_status_immediate(LifecycleStatus::Inactive);
+        crate::publish(TestUpdateMsg);
+    });
+    crate::publish(Trigger);
+    assert_eq!(
+        10,
+        counter.get(),
+        "status applied once the activity is live"
+    );
+}
+
+#[test]
+fn compact_drops_handlers_of_deleted_activities() {
+    let a = TestActivity::new();
+    let counter = a.shared_counter_ref();
+    let survivor = crate::new_activity(a);
+    survivor.subscribe(|activity: &mut TestActivity, _msg: &TestUpdateMsg| {
+        activity.inc(1);
+    });
+    for _ in 0..10 {
+        let id = crate::new_activity(TestActivity::new());
+        id.subscribe(|_activity: &mut TestActivity, _msg: &TestUpdateMsg| {});
+        id.on_leave(|_activity: &mut TestActivity| {});
+        id.set_status(LifecycleStatus::Deleted);
+    }
+    let report = crate::compact();
+    assert_eq!(20, report.dropped_handlers);
+    assert_eq!(crate::CompactionReport::default(), crate::compact());
+
+    crate::publish(TestUpdateMsg);
+    assert_eq!(1, counter.get());
+    assert_eq!(
+        Some(LifecycleStatus::Active),
+        crate::nut::activity_status(survivor.into())
+    );
+}
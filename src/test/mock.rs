@@ -0,0 +1,132 @@
+use crate::ActivityId;
+use core::any::Any;
+use core::marker::PhantomData;
+
+/// A message received by a mock activity
+struct Delivery {
+    type_name: &'static str,
+    /// Only available for messages registered with `record_cloned`
+    payload: Option<Box<dyn Any>>,
+}
+
+/// State of a mock activity, which only keeps a log of the messages delivered to it
+struct Mock<A> {
+    log: Vec<Delivery>,
+    _collaborator: PhantomData<fn() -> A>,
+}
+
+impl<A> Mock<A> {
+    fn record(&mut self, type_name: &'static str, payload: Option<Box<dyn Any>>) {
+        self.log.push(Delivery { type_name, payload });
+    }
+}
+
+/// Stand-in for an activity of type `A`, created with [`mock_activity`](fn.mock_activity.html).
+///
+/// A mock receives the message types registered on it with `record`, `record_cloned`, or `respond` and logs each delivery.
+/// Each registration logs separately, register only one of them per message type.
+pub struct MockActivity<A> {
+    id: ActivityId<Mock<A>>,
+}
+
+/// Creates a mock in place of the real activity of type `A`, to test another activity in isolation from its collaborators.
+///
+/// The mock is a new activity of its own, the real activity of type `A` is not created and does not need to exist.
+/// Script the mock with the messages the real collaborator would handle and check afterwards what it received.
+///
+/// # Example
+/// ```rust
+/// struct Storage;
+/// struct Edit(u32);
+/// struct Save(u32);
+/// struct Saved(u32);
+///
+/// // The activity under test, which relies on `Storage` to answer `Save` with `Saved`.
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct Editor { saved: Vec<u32> }
+/// let editor = nuts::new_activity(Editor { saved: vec![] });
+/// editor.subscribe(|_editor, edit: &Edit| nuts::publish(Save(edit.0)));
+/// editor.subscribe(|editor, saved: &Saved| editor.saved.push(saved.0));
+///
+/// let storage = nuts::test::mock_activity::<Storage>();
+/// storage.respond(|save: &Save| Saved(save.0));
+///
+/// let snapshot = nuts::test::capture(editor);
+/// nuts::publish(Edit(3));
+/// assert_eq!(1, storage.count::<Save>());
+/// snapshot.assert_changed(|_before, after| assert_eq!(vec![3], after.saved));
+/// ```
+pub fn mock_activity<A: 'static>() -> MockActivity<A> {
+    MockActivity {
+        id: crate::new_activity(Mock::<A> {
+            log: Vec::new(),
+            _collaborator: PhantomData,
+        }),
+    }
+}
+
+impl<A: 'static> MockActivity<A> {
+    /// Logs all deliveries of `MSG` by its type.
+    pub fn record<MSG: Any>(&self) -> &Self {
+        self.id
+            .subscribe(|mock, _msg: &MSG| mock.record(std::any::type_name::<MSG>(), None));
+        self
+    }
+    /// Logs all deliveries of `MSG` with a copy of the message, which can be read with [`payloads`](#method.payloads).
+    pub fn record_cloned<MSG: Any + Clone>(&self) -> &Self {
+        self.id.subscribe(|mock, msg: &MSG| {
+            mock.record(std::any::type_name::<MSG>(), Some(Box::new(msg.clone())))
+        });
+        self
+    }
+    /// Logs all deliveries of `MSG` by its type and publishes the response created by `f` for each of them.
+    ///
+    /// The response is published like any other message, after the current handler finished.
+    pub fn respond<MSG, R>(&self, f: impl Fn(&MSG) -> R + 'static) -> &Self
+    where
+        MSG: Any,
+        R: Any,
+    {
+        self.id.subscribe(move |mock, msg: &MSG| {
+            mock.record(std::any::type_name::<MSG>(), None);
+            crate::publish(f(msg));
+        });
+        self
+    }
+    /// Type names of all messages received so far, in the order of delivery.
+    ///
+    /// # Panics
+    /// Panics if called from inside a subscription handler.
+    pub fn received(&self) -> Vec<&'static str> {
+        self.read(|mock| mock.log.iter().map(|delivery| delivery.type_name).collect())
+    }
+    /// Number of messages of type `MSG` received so far.
+    ///
+    /// # Panics
+    /// Panics if called from inside a subscription handler.
+    pub fn count<MSG: Any>(&self) -> usize {
+        let type_name = std::any::type_name::<MSG>();
+        self.read(|mock| {
+            mock.log
+                .iter()
+                .filter(|delivery| delivery.type_name == type_name)
+                .count()
+        })
+    }
+    /// Copies of all messages of type `MSG` received so far, only available if `MSG` has been registered with [`record_cloned`](#method.record_cloned).
+    ///
+    /// # Panics
+    /// Panics if called from inside a subscription handler.
+    pub fn payloads<MSG: Any + Clone>(&self) -> Vec<MSG> {
+        self.read(|mock| {
+            mock.log
+                .iter()
+                .filter_map(|delivery| delivery.payload.as_ref()?.downcast_ref::<MSG>())
+                .cloned()
+                .collect()
+        })
+    }
+    fn read<T>(&self, f: impl FnOnce(&Mock<A>) -> T) -> T {
+        crate::nut::read_activity(self.id, f).expect("Mock activity has been deleted.")
+    }
+}
@@ -0,0 +1,62 @@
+use core::any::Any;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Records violations of an ordering assumption, created with [`expect_order`](fn.expect_order.html).
+pub struct OrderExpectation {
+    violations: Rc<Cell<usize>>,
+    first: &'static str,
+    second: &'static str,
+}
+
+/// Starts recording whether messages of type `B` are ever dispatched while a message of type `A` is still queued.
+///
+/// Use this to encode the assumption that all pending `A` messages are handled before the next `B`, for example because `B` is published in reaction to `A`.
+/// Check the result with [`assert_held`](struct.OrderExpectation.html#method.assert_held) at the end of the test.
+///
+/// Recording continues until the end of the thread, so call this once per test.
+///
+/// # Example
+/// ```rust
+/// struct Spawn;
+/// struct Render;
+///
+/// let order = nuts::test::expect_order::<Spawn, Render>();
+/// nuts::publish(Spawn);
+/// nuts::publish(Render);
+/// order.assert_held();
+/// ```
+pub fn expect_order<A: Any, B: Any>() -> OrderExpectation {
+    let violations = Rc::new(Cell::new(0));
+    let counter = violations.clone();
+    crate::subscribe(move |_: &B| {
+        if crate::nut::is_queued::<A>() {
+            counter.set(counter.get() + 1);
+        }
+    });
+    OrderExpectation {
+        violations,
+        first: std::any::type_name::<A>(),
+        second: std::any::type_name::<B>(),
+    }
+}
+
+impl OrderExpectation {
+    /// Returns how often a `B` has been dispatched while an `A` was queued.
+    pub fn violations(&self) -> usize {
+        self.violations.get()
+    }
+    /// Asserts that no `B` has been dispatched while an `A` was queued.
+    ///
+    /// # Panics
+    /// Panics if the ordering assumption has been violated at least once.
+    pub fn assert_held(&self) {
+        assert_eq!(
+            0,
+            self.violations(),
+            "{} was dispatched while {} was still queued.",
+            self.second,
+            self.first,
+        );
+    }
+}
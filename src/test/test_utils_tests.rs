@@ -0,0 +1,131 @@
+//! Tests for the public testing utilities in `nuts::test`.
+use super::*;
+
+#[derive(Clone, PartialEq, Debug)]
+struct Score(u32);
+
+#[test]
+fn capture_assert_changed() {
+    let id = crate::new_activity(Score(0));
+    id.subscribe(|score, msg: &TestMessage| score.0 += msg.0);
+    let snapshot = crate::test::capture(id);
+    crate::publish(TestMessage(5));
+    snapshot.assert_changed(|before, after| {
+        assert_eq!(before.0, 0);
+        assert_eq!(after.0, 5);
+    });
+}
+
+#[test]
+fn capture_assert_unchanged() {
+    let id = crate::new_activity(Score(0));
+    id.subscribe(|score, msg: &TestMessage| score.0 += msg.0);
+    let snapshot = crate::test::capture(id);
+    crate::publish(TestUpdateMsg);
+    snapshot.assert_unchanged();
+}
+
+#[test]
+#[should_panic]
+fn capture_detects_missing_change() {
+    let id = crate::new_activity(Score(0));
+    let snapshot = crate::test::capture(id);
+    crate::publish(TestMessage(5));
+    snapshot.assert_changed(|_, _| {});
+}
+
+struct Music {
+    playing: bool,
+}
+
+impl LifecycleConformance for Music {
+    fn check_status(&self, status: LifecycleStatus) {
+        assert_eq!(self.playing, status.is_active());
+    }
+}
+
+#[test]
+fn verify_conforming_activity() {
+    crate::verify_activity(|| {
+        let id = crate::new_activity(Music { playing: true });
+        id.on_enter(|music| music.playing = true);
+        id.on_leave(|music| music.playing = false);
+        id
+    });
+}
+
+#[test]
+#[should_panic]
+fn verify_activity_detects_invariant_violation() {
+    crate::verify_activity(|| {
+        let id = crate::new_activity(Music { playing: true });
+        id.on_enter(|music| music.playing = true);
+        id
+    });
+}
+
+#[test]
+#[should_panic]
+fn verify_activity_requires_active_start() {
+    crate::verify_activity(|| {
+        let id = crate::new_activity(Music { playing: false });
+        id.set_status(LifecycleStatus::Inactive);
+        id
+    });
+}
+
+#[test]
+fn expect_order_detects_overtaking() {
+    struct Created;
+    struct Moved;
+    let order = crate::test::expect_order::<Created, Moved>();
+    crate::subscribe(|_: &TestMessage| {
+        crate::publish(Created);
+        crate::publish(Moved);
+    });
+    crate::publish(TestMessage(0));
+    order.assert_held();
+
+    crate::subscribe(|_: &TestForInt| {
+        crate::publish(Moved);
+        crate::publish(Created);
+    });
+    crate::publish(TestForInt(0));
+    assert_eq!(1, order.violations());
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Request(u32);
+struct Response(u32);
+
+#[test]
+fn mock_activity_records_and_responds() {
+    struct Server;
+    let client = crate::new_activity(Score(0));
+    client.subscribe(|score, response: &Response| score.0 += response.0);
+
+    let server = crate::test::mock_activity::<Server>();
+    server
+        .record_cloned::<Request>()
+        .record::<TestUpdateMsg>()
+        .respond(|msg: &TestMessage| Response(msg.0 * 2));
+
+    let snapshot = crate::test::capture(client);
+    crate::publish(Request(1));
+    crate::publish(TestMessage(4));
+    crate::publish(TestUpdateMsg);
+    crate::publish(Request(2));
+
+    assert_eq!(
+        vec![
+            std::any::type_name::<Request>(),
+            std::any::type_name::<TestMessage>(),
+            std::any::type_name::<TestUpdateMsg>(),
+            std::any::type_name::<Request>(),
+        ],
+        server.received()
+    );
+    assert_eq!(2, server.count::<Request>());
+    assert_eq!(vec![Request(1), Request(2)], server.payloads::<Request>());
+    snapshot.assert_changed(|_before, after| assert_eq!(8, after.0));
+}
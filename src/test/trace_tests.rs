@@ -0,0 +1,130 @@
+//! Tests for correlating messages across publish cascades.
+
+struct Root;
+struct Child;
+struct GrandChild;
+
+#[test]
+fn no_trace_outside_of_handlers() {
+    assert!(crate::current_trace().is_none());
+    crate::subscribe(|_: &Root| assert!(crate::current_trace().is_some()));
+    crate::publish(Root);
+    assert!(crate::current_trace().is_none());
+}
+
+#[test]
+fn trace_tree_links_causes() {
+    crate::record_traces(10);
+    crate::subscribe(|_: &Root| {
+        crate::publish(Child);
+        crate::publish(Child);
+    });
+    crate::subscribe(|_: &Child| crate::publish(GrandChild));
+    crate::publish(Root);
+
+    let tree = crate::trace_tree();
+    assert_eq!(5, tree.len());
+    let root = &tree[0];
+    assert_eq!(None, root.cause);
+    let children: Vec<_> = tree.iter().filter(|n| n.cause == Some(root.id)).collect();
+    assert_eq!(2, children.len());
+    for child in children {
+        assert_eq!(1, tree.iter().filter(|n| n.cause == Some(child.id)).count());
+    }
+    let chain = crate::trace_chain(tree[4].id);
+    assert_eq!(3, chain.len());
+    assert_eq!(root.id, chain[0].id);
+}
+
+#[test]
+fn trace_recording_capacity() {
+    crate::record_traces(2);
+    crate::publish(Root);
+    crate::publish(Root);
+    crate::publish(Root);
+    assert_eq!(2, crate::trace_tree().len());
+    crate::record_traces(0);
+    crate::publish(Root);
+    assert!(crate::trace_tree().is_empty());
+}
+
+#[cfg(feature = "log")]
+mod usage_warnings {
+    use std::cell::RefCell;
+
+    thread_local!(static WARNINGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) });
+
+    /// Collects warnings of the current thread, tests run in parallel threads
+    struct ThreadLogger;
+    impl log::Log for ThreadLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+        fn log(&self, record: &log::Record) {
+            WARNINGS.with(|w| w.borrow_mut().push(record.args().to_string()));
+        }
+        fn flush(&self) {}
+    }
+    static LOGGER: ThreadLogger = ThreadLogger;
+
+    #[test]
+    fn warn_about_suspicious_usage() {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Warn);
+        crate::set_log_thresholds(crate::LogThresholds {
+            max_cascade_depth: 3,
+            ..Default::default()
+        });
+        struct Unheard;
+        struct Countdown(u32);
+        crate::publish(Unheard);
+        crate::publish(Unheard);
+        crate::subscribe(|n: &Countdown| {
+            if n.0 > 0 {
+                crate::publish(Countdown(n.0 - 1))
+            }
+        });
+        crate::publish(Countdown(2));
+        crate::publish(Countdown(10));
+
+        let warnings = WARNINGS.with(|w| w.borrow().clone());
+        assert_eq!(2, warnings.len(), "{:?}", warnings);
+        assert!(warnings[0].contains("without any subscribers"));
+        assert!(warnings[1].contains("cascade deeper than 3"));
+    }
+}
+
+#[cfg(feature = "mutation-audit")]
+#[test]
+fn mutation_trail_records_modifying_activities() {
+    #[derive(Clone, PartialEq)]
+    struct Compared(u32);
+    struct Marked(u32);
+    struct Changer;
+    struct Keeper;
+    use std::{cell::Cell, rc::Rc};
+    crate::audit_mutations::<Compared>();
+
+    let changer = crate::new_activity(Changer);
+    changer.subscribe_mut(|_, msg: &mut Compared| msg.0 += 1);
+    changer.subscribe_mut(|_, msg: &mut Marked| {
+        msg.0 += 1;
+        crate::mark_changed();
+    });
+    let keeper = crate::new_activity(Keeper);
+    keeper.subscribe_mut(|_, msg: &mut Compared| msg.0 += 0);
+    keeper.subscribe_mut(|_, _: &mut Marked| {});
+    let seen = Rc::new(Cell::new(0));
+    let s = seen.clone();
+    // Same activity, hence called after the modification
+    changer.subscribe_mut(move |_, _: &mut Compared| {
+        s.set(crate::mutation_trail::<Compared>().len());
+    });
+
+    crate::publish(Compared(0));
+    crate::publish(Marked(0));
+    assert_eq!(1, seen.get(), "observer sees earlier modification");
+    let name = std::any::type_name::<Changer>();
+    assert_eq!(vec![name], crate::mutation_trail::<Compared>());
+    assert_eq!(vec![name], crate::mutation_trail::<Marked>());
+}
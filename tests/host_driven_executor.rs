@@ -0,0 +1,100 @@
+//! Futures driven by a host loop through `nuts::poll_futures`, without an executor.
+//!
+//! Only what is available on `wasm32-unknown-unknown` is used here: no threads and no clock.
+//! Besides the native target, these tests run on wasm32 with a runner like `wasmtime` (e.g. `cargo test --target wasm32-wasip1`).
+
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+/// Resolves once the host has fired it, like a callback from the browser
+#[derive(Clone, Default)]
+struct Signal(Rc<RefCell<(bool, Option<Waker>)>>);
+
+impl Signal {
+    fn fire(&self) {
+        let mut state = self.0.borrow_mut();
+        state.0 = true;
+        if let Some(waker) = state.1.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Future for Signal {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.0.borrow_mut();
+        if state.0 {
+            Poll::Ready(())
+        } else {
+            state.1 = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Pending once and wakes itself, hands control back to the host for one step
+struct Yield(bool);
+
+impl Future for Yield {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[test]
+fn one_step_per_frame() {
+    let signal = Signal::default();
+    let steps = Rc::new(Cell::new(0));
+    let (sig, s) = (signal.clone(), steps.clone());
+    let id = nuts::new_activity(());
+    id.attach_future(async move {
+        sig.await;
+        for _ in 0..3 {
+            Yield(false).await;
+            s.set(s.get() + 1);
+        }
+    });
+    assert!(!nuts::poll_futures());
+
+    signal.fire();
+    let mut frames = 0;
+    while nuts::poll_futures() {
+        frames += 1;
+        assert_eq!(frames - 1, steps.get());
+    }
+    assert_eq!(3, frames);
+    assert_eq!(3, steps.get());
+}
+
+#[test]
+fn awaited_publish_resolves_in_host_loop() {
+    struct Start;
+    struct Done;
+    let signal = Signal::default();
+    let finished = Rc::new(Cell::new(false));
+    let (sig, f) = (signal.clone(), finished.clone());
+
+    let id = nuts::new_activity(());
+    id.subscribe(|_, _: &Start| nuts::publish(Done));
+    id.attach_future(async move {
+        sig.await;
+        nuts::publish_awaiting(Start).await_quiescent().await;
+        f.set(true);
+    });
+
+    signal.fire();
+    // The message and its reactions are handled inside the step, nothing is left for the next frame
+    assert!(!nuts::poll_futures());
+    assert!(finished.get());
+}
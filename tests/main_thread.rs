@@ -0,0 +1,59 @@
+//! Restriction of Nuts to one thread with `nuts::claim_main_thread`.
+//!
+//! The claim is global for the process, hence it lives in its own test binary and all checks run in a single test.
+
+use std::any::Any;
+use std::sync::mpsc;
+use std::thread;
+
+struct Job;
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        String::new()
+    }
+}
+
+#[test]
+fn claim_main_thread() {
+    // A thread that uses Nuts before the claim keeps working afterwards
+    let (claimed_tx, claimed_rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let early = thread::spawn(move || {
+        nuts::subscribe(|_: &Job| {});
+        ready_tx.send(()).expect("test thread is alive");
+        claimed_rx.recv().expect("test thread is alive");
+        nuts::publish(Job);
+    });
+    ready_rx.recv().expect("early thread is alive");
+
+    assert_eq!(Ok(()), nuts::claim_main_thread());
+    assert_eq!(Ok(()), nuts::claim_main_thread(), "claiming twice is fine");
+    claimed_tx.send(()).expect("early thread is alive");
+    assert!(early.join().is_ok(), "threads used before the claim work");
+
+    let late = thread::spawn(|| nuts::publish(Job));
+    let msg = panic_message(late.join().expect_err("other threads must panic"));
+    assert!(
+        msg.starts_with("Nuts has been called from the wrong thread."),
+        "unexpected panic message: {}",
+        msg
+    );
+
+    let claim = thread::spawn(nuts::claim_main_thread);
+    assert_eq!(
+        Err(nuts::Error::WrongThread),
+        claim.join().expect("claiming does not panic")
+    );
+
+    // The claiming thread is not affected
+    let count = std::rc::Rc::new(std::cell::Cell::new(0));
+    let c = count.clone();
+    nuts::subscribe(move |_: &Job| c.set(c.get() + 1));
+    nuts::publish(Job);
+    assert_eq!(1, count.get());
+}